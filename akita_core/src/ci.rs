@@ -0,0 +1,130 @@
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::AkitaDataError;
+use crate::{FromValue, ToValue, Value};
+
+/// A string compared without its case, e.g. an email, so `Bob@Mail.com` and `bob@mail.com` are the same user.
+/// The text is kept as it was given.
+///
+/// The column holding it has to compare the same way for its unique index to hold, see `CiString::column_type`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct CiString(String);
+
+impl CiString {
+    pub fn new<S: Into<String>>(s: S) -> Self {
+        CiString(s.into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+
+    /// The type of a column comparing its text without the case, for the platform named as in the urls:
+    /// `CITEXT` on Postgres, a `VARCHAR` with a `_ci` collation on MySQL or `NOCASE` on SQLite.
+    pub fn column_type(platform: &str, length: usize) -> String {
+        match platform {
+            "postgres" | "postgresql" => "CITEXT".to_string(),
+            "sqlite" => format!("VARCHAR({}) COLLATE NOCASE", length),
+            _ => format!("VARCHAR({}) COLLATE utf8mb4_unicode_ci", length),
+        }
+    }
+
+    fn folded(&self) -> impl Iterator<Item = char> + '_ {
+        self.0.chars().flat_map(char::to_lowercase)
+    }
+}
+
+impl PartialEq for CiString {
+    fn eq(&self, other: &Self) -> bool {
+        self.folded().eq(other.folded())
+    }
+}
+
+impl Eq for CiString {}
+
+impl PartialEq<str> for CiString {
+    fn eq(&self, other: &str) -> bool {
+        self.folded().eq(other.chars().flat_map(char::to_lowercase))
+    }
+}
+
+impl PartialEq<&str> for CiString {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
+
+impl Hash for CiString {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for c in self.folded() {
+            c.hash(state);
+        }
+    }
+}
+
+impl PartialOrd for CiString {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for CiString {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.folded().cmp(other.folded())
+    }
+}
+
+impl Deref for CiString {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for CiString {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for CiString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl From<String> for CiString {
+    fn from(s: String) -> Self {
+        CiString(s)
+    }
+}
+
+impl From<&str> for CiString {
+    fn from(s: &str) -> Self {
+        CiString(s.to_string())
+    }
+}
+
+impl ToValue for CiString {
+    fn to_value(&self) -> Value {
+        Value::Text(self.0.to_owned())
+    }
+}
+
+impl FromValue for CiString {
+    fn from_value_opt(v: &Value) -> Result<Self, AkitaDataError> {
+        String::from_value_opt(v).map(CiString)
+    }
+}
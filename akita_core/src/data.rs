@@ -1,5 +1,7 @@
 use std::slice;
+use std::hash::Hash;
 use std::ops::Index;
+use indexmap::IndexMap;
 use crate::{AkitaDataError, from_value, from_value_opt, FromValue};
 use crate::value::Value;
 
@@ -45,6 +47,20 @@ impl Rows {
     pub fn len(&self) -> usize {
         self.data.len()
     }
+
+    /// Collapse the rows with the same key into a group, e.g. the rows of a join by the key of the parent record.
+    /// The groups keep the order they were first met.
+    pub fn group_by_key<K, F>(&self, mut key_fn: F) -> IndexMap<K, Vec<Value>>
+        where
+            K: Eq + Hash,
+            F: FnMut(&Value) -> K,
+    {
+        let mut groups: IndexMap<K, Vec<Value>> = IndexMap::new();
+        for row in self.iter() {
+            groups.entry(key_fn(&row)).or_default().push(row);
+        }
+        groups
+    }
 }
 
 /// An iterator over `Row`s.
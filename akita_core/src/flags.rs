@@ -0,0 +1,69 @@
+use std::collections::HashSet;
+use std::hash::Hash;
+
+use crate::error::AkitaDataError;
+use crate::{FromValue, Value};
+
+/// A flag of a `#[field(bitflags)]` set, e.g. a variant of an enum.
+/// Its bit is its position in `FLAGS`, the same as in the MySQL `SET` definition.
+pub trait Flag: Copy + Eq + Hash + 'static {
+    /// All the flags, the first one being bit 0.
+    const FLAGS: &'static [Self];
+
+    /// The name of the flag in the `SET` definition.
+    fn name(&self) -> &'static str;
+
+    fn bit(&self) -> u64 {
+        Self::FLAGS.iter().position(|flag| flag == self).map(|i| 1 << i).unwrap_or_default()
+    }
+}
+
+/// A set of flags stored as a bitmask in an integer or a MySQL `SET` column, see `#[field(bitflags)]`.
+/// Implemented for `HashSet<F: Flag>`, implement it for the bitflags-style structs.
+pub trait BitFlags: Sized {
+    fn bits(&self) -> u64;
+
+    fn from_bits(bits: u64) -> Self;
+
+    /// The bit of a flag named in a MySQL `SET` value, `None` when the name is unknown.
+    fn bit_of(_name: &str) -> Option<u64> {
+        None
+    }
+}
+
+impl<F: Flag> BitFlags for HashSet<F> {
+    fn bits(&self) -> u64 {
+        self.iter().fold(0, |bits, flag| bits | flag.bit())
+    }
+
+    fn from_bits(bits: u64) -> Self {
+        F::FLAGS.iter().filter(|flag| bits & flag.bit() != 0).copied().collect()
+    }
+
+    fn bit_of(name: &str) -> Option<u64> {
+        F::FLAGS.iter().find(|flag| flag.name().eq_ignore_ascii_case(name)).map(|flag| flag.bit())
+    }
+}
+
+/// The value stored for a `#[field(bitflags)]` field.
+pub fn to_bits_value<T: BitFlags>(flags: &T) -> Value {
+    Value::Bigint(flags.bits() as i64)
+}
+
+/// The `#[field(bitflags)]` field read from an integer column, or from the names of a MySQL `SET` like `read,write`.
+pub fn from_bits_value<T: BitFlags>(v: &Value) -> Result<T, AkitaDataError> {
+    let names = match v {
+        Value::Nil => return Ok(T::from_bits(0)),
+        Value::Text(v) => v.to_owned(),
+        Value::Blob(v) => String::from_utf8_lossy(v).to_string(),
+        v => return u64::from_value_opt(v).map(T::from_bits),
+    };
+    // a SET column read as a number in a text protocol.
+    if let Ok(bits) = names.trim().parse::<u64>() {
+        return Ok(T::from_bits(bits));
+    }
+    names.split(',').map(str::trim).filter(|name| !name.is_empty())
+        .try_fold(0, |bits, name| T::bit_of(name).map(|bit| bits | bit)
+            .ok_or_else(|| AkitaDataError::ObjectValidError(format!("unknown flag {}", name))))
+        .map(T::from_bits)
+}
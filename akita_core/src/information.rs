@@ -10,6 +10,16 @@ use crate::{types::SqlType, comm::keywords_safe, Value};
 pub trait GetTableName {
     /// extract the table name from a struct
     fn table_name() -> TableName;
+
+    /// the named datasource holding the table, `None` for the default one
+    fn datasource() -> Option<&'static str> {
+        None
+    }
+
+    /// the indexes declared with `#[index(name = "idx_user_email", columns = "email", unique)]`
+    fn indexes() -> Vec<IndexDef> {
+        Vec::new()
+    }
 }
 
 pub trait GetFields {
@@ -97,6 +107,32 @@ pub struct FieldName {
     pub select: bool,
     pub fill: Option<Fill>,
     pub field_type: FieldType,
+    /// refers to the parent record of the same table
+    pub parent_id: bool,
+    /// its values are masked in the logs
+    pub sensitive: bool,
+    /// flags the deleted records, 1 when deleted and 0 otherwise
+    pub logic_delete: bool,
+    /// the version of the record for the optimistic locking, increased by each update by id
+    pub version: bool,
+    /// how the column is declared by the `CREATE TABLE` of the entity
+    pub column: ColumnMeta,
+}
+
+/// The declaration of a column, from the type of the field and its
+/// `#[field(column_type, len, not_null, default, comment)]` attributes.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct ColumnMeta {
+    /// the type of the field without its `Option`, e.g. `i64` or `chrono::NaiveDateTime`
+    pub rust_type: String,
+    /// the sql type of the column, inferred from the rust type when none
+    pub column_type: Option<String>,
+    /// the length of the text and binary columns
+    pub len: Option<u64>,
+    pub not_null: bool,
+    /// the sql of the default value, e.g. `0` or `'active'`
+    pub default: Option<String>,
+    pub comment: Option<String>,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -112,6 +148,27 @@ pub enum FieldType {
     TableField
 }
 
+impl FieldType {
+    /// A new id for the rows inserted without one, for the `assign_uuid` and `uuid_v7` id types.
+    pub fn generate_id(&self) -> Option<Value> {
+        match self {
+            FieldType::TableId(id_type) if id_type == "assign_uuid" => Some(Value::Uuid(Uuid::new_v4())),
+            FieldType::TableId(id_type) if id_type == "uuid_v7" => Some(Value::Uuid(new_uuid_v7())),
+            _ => None,
+        }
+    }
+}
+
+/// A version 7 uuid, starting with the unix time in milliseconds so the ids follow the inserts in the indexes.
+pub fn new_uuid_v7() -> Uuid {
+    let millis = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|now| now.as_millis() as u64).unwrap_or_default();
+    let mut bytes = *Uuid::new_v4().as_bytes();
+    bytes[..6].copy_from_slice(&millis.to_be_bytes()[2..]);
+    bytes[6] = (bytes[6] & 0x0f) | 0x70;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    Uuid::from_bytes(bytes)
+}
+
 impl FieldName {
     /// create table with name
     pub fn from(arg: &str) -> Self {
@@ -132,6 +189,11 @@ impl FieldName {
                 select: true,
                 fill: None,
                 field_type: FieldType::TableField,
+                parent_id: false,
+                sensitive: false,
+                logic_delete: false,
+                version: false,
+                column: ColumnMeta::default(),
             }
         } else {
             FieldName {
@@ -142,6 +204,11 @@ impl FieldName {
                 select: true,
                 fill: None,
                 field_type: FieldType::TableField,
+                parent_id: false,
+                sensitive: false,
+                logic_delete: false,
+                version: false,
+                column: ColumnMeta::default(),
             }
         }
     }
@@ -328,6 +395,21 @@ pub struct ForeignKey {
 }
 
 
+/// An index of a table, the primary key included.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct IndexDef {
+    pub name: String,
+    /// the columns in the order of the index
+    pub columns: Vec<String>,
+    pub unique: bool,
+}
+
+impl std::fmt::Display for IndexDef {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}({})", if self.unique { "unique " } else { "" }, self.columns.join(", "))
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum TableKey {
     PrimaryKey(Key),
@@ -7,6 +7,9 @@ mod value;
 mod param;
 mod macros;
 mod types;
+mod flags;
+mod net;
+mod ci;
 pub mod comm;
 
 
@@ -21,5 +24,11 @@ pub use types::*;
 #[doc(inline)]
 pub use param::*;
 #[doc(inline)]
+pub use flags::*;
+#[doc(inline)]
+pub use net::*;
+#[doc(inline)]
+pub use ci::*;
+#[doc(inline)]
 pub use error::*;
 pub use serde;
\ No newline at end of file
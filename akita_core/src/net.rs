@@ -0,0 +1,239 @@
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+use crate::error::{AkitaDataError, ConvertError};
+use crate::{FromValue, ToValue, Value};
+
+/// A network, e.g. `10.1.0.0/16`, stored as a `cidr` on Postgres and as text elsewhere.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    /// The network of the address, the bits after the prefix are cleared.
+    pub fn new(addr: IpAddr, prefix: u8) -> Result<Self, AkitaDataError> {
+        if prefix > max_prefix(&addr) {
+            return Err(AkitaDataError::ObjectValidError(format!("the prefix of {}/{} is too long", addr, prefix)));
+        }
+        let addr = from_bits(&addr, to_bits(&addr) & mask(&addr, prefix));
+        Ok(Cidr { addr, prefix })
+    }
+
+    /// The first address of the network.
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    pub fn prefix(&self) -> u8 {
+        self.prefix
+    }
+
+    /// The last address of the network, the broadcast one for IPv4.
+    pub fn last(&self) -> IpAddr {
+        from_bits(&self.addr, to_bits(&self.addr) | (!mask(&self.addr, self.prefix) & full(&self.addr)))
+    }
+
+    pub fn contains(&self, addr: &IpAddr) -> bool {
+        addr.is_ipv4() == self.addr.is_ipv4() && to_bits(addr) & mask(addr, self.prefix) == to_bits(&self.addr)
+    }
+}
+
+impl Default for Cidr {
+    /// `0.0.0.0/0`, all the IPv4 addresses.
+    fn default() -> Self {
+        Cidr { addr: IpAddr::V4(Ipv4Addr::UNSPECIFIED), prefix: 0 }
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = AkitaDataError;
+
+    /// `10.1.0.0/16`, or a single address.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix) = s.trim().split_once('/').map(|(addr, prefix)| (addr, Some(prefix))).unwrap_or((s.trim(), None));
+        let addr = IpAddr::from_str(addr).map_err(|_| not_supported(s, "Cidr"))?;
+        let prefix = match prefix {
+            Some(prefix) => prefix.parse::<u8>().map_err(|_| not_supported(s, "Cidr"))?,
+            None => max_prefix(&addr),
+        };
+        Cidr::new(addr, prefix)
+    }
+}
+
+/// A MAC address, stored as a `macaddr` on Postgres and as text like `08:00:2b:01:02:03` elsewhere.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct MacAddr(pub [u8; 6]);
+
+impl fmt::Display for MacAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [a, b, c, d, e, g] = self.0;
+        write!(f, "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}", a, b, c, d, e, g)
+    }
+}
+
+impl FromStr for MacAddr {
+    type Err = AkitaDataError;
+
+    /// Six hex bytes separated by `:` or `-`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = s.trim().split(|c| c == ':' || c == '-').map(|byte| u8::from_str_radix(byte, 16)).collect::<Result<Vec<_>, _>>();
+        match bytes {
+            Ok(bytes) if bytes.len() == 6 => Ok(MacAddr([bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5]])),
+            _ => Err(not_supported(s, "MacAddr")),
+        }
+    }
+}
+
+impl ToValue for IpAddr {
+    fn to_value(&self) -> Value {
+        Value::Inet(*self)
+    }
+}
+
+impl ToValue for Ipv4Addr {
+    fn to_value(&self) -> Value {
+        Value::Inet(IpAddr::V4(*self))
+    }
+}
+
+impl ToValue for Ipv6Addr {
+    fn to_value(&self) -> Value {
+        Value::Inet(IpAddr::V6(*self))
+    }
+}
+
+impl ToValue for Cidr {
+    fn to_value(&self) -> Value {
+        Value::Cidr(*self)
+    }
+}
+
+impl ToValue for MacAddr {
+    fn to_value(&self) -> Value {
+        Value::MacAddr(*self)
+    }
+}
+
+impl FromValue for IpAddr {
+    fn from_value_opt(v: &Value) -> Result<Self, AkitaDataError> {
+        match v {
+            Value::Inet(v) => Ok(*v),
+            // a single host network, as the inet of Postgres prints them.
+            Value::Cidr(v) if v.prefix == max_prefix(&v.addr) => Ok(v.addr),
+            v => IpAddr::from_str(text(v, "IpAddr")?.split('/').next().unwrap_or_default()).map_err(|_| not_supported(v, "IpAddr")),
+        }
+    }
+}
+
+impl FromValue for Cidr {
+    fn from_value_opt(v: &Value) -> Result<Self, AkitaDataError> {
+        match v {
+            Value::Cidr(v) => Ok(*v),
+            Value::Inet(v) => Cidr::new(*v, max_prefix(v)),
+            v => Cidr::from_str(&text(v, "Cidr")?),
+        }
+    }
+}
+
+impl FromValue for MacAddr {
+    fn from_value_opt(v: &Value) -> Result<Self, AkitaDataError> {
+        match v {
+            Value::MacAddr(v) => Ok(*v),
+            Value::Blob(bytes) if bytes.len() == 6 => Ok(MacAddr([bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5]])),
+            v => MacAddr::from_str(&text(v, "MacAddr")?),
+        }
+    }
+}
+
+fn text(v: &Value, ty: &str) -> Result<String, AkitaDataError> {
+    match v {
+        Value::Text(v) => Ok(v.to_owned()),
+        Value::Blob(v) => String::from_utf8(v.to_owned()).map_err(|_| not_supported(v, ty)),
+        v => Err(not_supported(v, ty)),
+    }
+}
+
+fn not_supported<V: fmt::Debug>(v: V, ty: &str) -> AkitaDataError {
+    AkitaDataError::ConvertError(ConvertError::NotSupported(format!("{:?}", v), ty.to_string()))
+}
+
+fn max_prefix(addr: &IpAddr) -> u8 {
+    if addr.is_ipv4() { 32 } else { 128 }
+}
+
+fn full(addr: &IpAddr) -> u128 {
+    if addr.is_ipv4() { u32::MAX as u128 } else { u128::MAX }
+}
+
+fn mask(addr: &IpAddr, prefix: u8) -> u128 {
+    let bits = max_prefix(addr) as u32;
+    let prefix = (prefix as u32).min(bits);
+    if prefix == 0 { 0 } else { full(addr) & (u128::MAX << (bits - prefix)) }
+}
+
+fn to_bits(addr: &IpAddr) -> u128 {
+    match addr {
+        IpAddr::V4(v) => u32::from(*v) as u128,
+        IpAddr::V6(v) => u128::from(*v),
+    }
+}
+
+fn from_bits(addr: &IpAddr, bits: u128) -> IpAddr {
+    match addr {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::from(bits as u32)),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::from(bits)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::IpAddr;
+    use std::str::FromStr;
+    use super::{Cidr, MacAddr};
+
+    fn ip(s: &str) -> IpAddr {
+        IpAddr::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn cidr() {
+        let all: Cidr = "10.1.2.3/0".parse().unwrap();
+        assert_eq!((all.addr(), all.last()), (ip("0.0.0.0"), ip("255.255.255.255")));
+        assert!(all.contains(&ip("192.168.0.1")));
+        assert!(!all.contains(&ip("::1")));
+        let host: Cidr = "10.1.2.3".parse().unwrap();
+        assert_eq!((host.to_string(), host.last()), ("10.1.2.3/32".to_string(), ip("10.1.2.3")));
+        assert!(host.contains(&ip("10.1.2.3")) && !host.contains(&ip("10.1.2.4")));
+        let net: Cidr = "10.1.2.3/16".parse().unwrap();
+        assert_eq!((net.addr(), net.last()), (ip("10.1.0.0"), ip("10.1.255.255")));
+        assert!(!net.contains(&ip("a01::1")));
+        let v6: Cidr = "fd12:3456::1/32".parse().unwrap();
+        assert_eq!((v6.to_string(), v6.last()), ("fd12:3456::/32".to_string(), ip("fd12:3456:ffff:ffff:ffff:ffff:ffff:ffff")));
+        assert!(v6.contains(&ip("fd12:3456:1::9")) && !v6.contains(&ip("fd12:3457::1")) && !v6.contains(&ip("10.1.0.1")));
+        let host: Cidr = "::1/128".parse().unwrap();
+        assert_eq!((host.addr(), host.last()), (ip("::1"), ip("::1")));
+        let all: Cidr = "::/0".parse().unwrap();
+        assert_eq!(all.last(), ip("ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff"));
+        assert!("10.1.0.0/33".parse::<Cidr>().is_err());
+        assert!("::/129".parse::<Cidr>().is_err());
+        assert!("10.1.0/16".parse::<Cidr>().is_err());
+    }
+
+    #[test]
+    fn mac_addr() {
+        let mac: MacAddr = "08-00-2B-01-02-03".parse().unwrap();
+        assert_eq!(mac, MacAddr([0x08, 0x00, 0x2b, 0x01, 0x02, 0x03]));
+        assert_eq!(mac.to_string(), "08:00:2b:01:02:03");
+        assert!("08:00:2b:01:02".parse::<MacAddr>().is_err());
+        assert!("08:00:2b:01:02:zz".parse::<MacAddr>().is_err());
+    }
+}
@@ -1,4 +1,5 @@
 use crate::value::{Value, ToValue};
+use crate::AkitaDataError;
 
 
 
@@ -12,6 +13,35 @@ pub enum Params {
 //     fn to_param(&self) -> Params;
 // }
 
+impl Params {
+    /// The params of a record, named after its fields: the sql should have `:name` placeholders,
+    /// the `?` ones would be bound in the order the fields are declared.
+    pub fn from_record(sql: &str, record: Value) -> Result<Params, AkitaDataError> {
+        match record {
+            Value::Object(map) if has_named_placeholders(sql) => Ok(Params::Custom(map.into_iter().collect())),
+            Value::Object(_) => Err(AkitaDataError::ObjectValidError(format!("the params of a record are bound by name, use `:name` placeholders in: {}", sql))),
+            record => Ok(Params::from(record)),
+        }
+    }
+
+    /// The params with each value mapped, keeping their names.
+    pub fn map_values<F: FnMut(Value) -> Value>(self, mut f: F) -> Params {
+        match self {
+            Params::Nil => Params::Nil,
+            Params::Vector(values) => Params::Vector(values.into_iter().map(f).collect()),
+            Params::Custom(values) => Params::Custom(values.into_iter().map(|(name, value)| (name, f(value))).collect()),
+        }
+    }
+}
+
+/// whether the sql has `:name` placeholders, the `::` casts are not.
+fn has_named_placeholders(sql: &str) -> bool {
+    let bytes = sql.as_bytes();
+    bytes.iter().enumerate().any(|(i, c)| *c == b':'
+        && (i == 0 || bytes[i - 1] != b':')
+        && bytes.get(i + 1).map(|next| next.is_ascii_alphabetic() || *next == b'_').unwrap_or(false))
+}
+
 impl From<Vec<Value>> for Params {
     fn from(x: Vec<Value>) -> Params {
         Params::Vector(x)
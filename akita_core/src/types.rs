@@ -38,6 +38,7 @@ pub enum SqlType {
     Interval,
 
     IpAddress,
+    MacAddress,
 
     Point,
 
@@ -206,6 +207,8 @@ impl HasType for Value {
             Value::Text(_) => Some(SqlType::Text),
             Value::Json(_) => Some(SqlType::Json),
             Value::Uuid(_) => Some(SqlType::Uuid),
+            Value::Inet(_) | Value::Cidr(_) => Some(SqlType::IpAddress),
+            Value::MacAddr(_) => Some(SqlType::MacAddress),
             Value::Date(_) => Some(SqlType::Date),
             Value::Time(_) => Some(SqlType::Time),
             Value::DateTime(_) => Some(SqlType::Timestamp),
@@ -1,13 +1,13 @@
-use std::{any::type_name, fmt, mem};
+use std::{any::type_name, cell::Cell, fmt, mem, net::IpAddr};
 use bigdecimal::{BigDecimal, ToPrimitive};
 use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
-use serde::{Serialize, Deserialize};
+use serde::{Serialize, Deserialize, de::DeserializeOwned};
 use serde_json::Map;
 use uuid::Uuid;
 use indexmap::{IndexMap};
 
 use crate::error::{ConvertError, AkitaDataError};
-use crate::{Row};
+use crate::{Cidr, MacAddr, Row};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Value {
@@ -29,6 +29,9 @@ pub enum Value {
     Json(serde_json::Value),
 
     Uuid(Uuid),
+    Inet(IpAddr),
+    Cidr(Cidr),
+    MacAddr(MacAddr),
     Date(NaiveDate),
     Time(NaiveTime),
     DateTime(NaiveDateTime),
@@ -274,6 +277,9 @@ impl fmt::Display for Value {
             Value::Text(v) => write!(f, "{}", v),
             Value::Json(v) => write!(f, "{}", serde_json::to_string(v).unwrap_or_default()),
             Value::Uuid(v) => write!(f, "{}", v),
+            Value::Inet(v) => write!(f, "{}", v),
+            Value::Cidr(v) => write!(f, "{}", v),
+            Value::MacAddr(v) => write!(f, "{}", v),
             Value::Date(v) => write!(f, "{}", v),
             Value::Time(v) => write!(f, "{}", v),
             Value::DateTime(v) => write!(f, "{}", v.format("%Y-%m-%d %H:%M:%S").to_string()),
@@ -372,6 +378,33 @@ pub trait ToValue {
     fn to_value(&self) -> Value;
 }
 
+/// The key of a record for the `*_by_id` methods: a value for a single `#[table_id]` field,
+/// or a tuple with a value per id field, in their order, for a composite key.
+pub trait ToKey {
+    fn to_key(&self) -> Vec<Value>;
+}
+
+impl<T: ToValue> ToKey for T {
+    fn to_key(&self) -> Vec<Value> {
+        vec![self.to_value()]
+    }
+}
+
+macro_rules! impl_tuple_to_key {
+    ($($T:ident: $index:tt),+) => {
+        impl<$($T: ToValue),+> ToKey for ($($T,)+) {
+            fn to_key(&self) -> Vec<Value> {
+                vec![$(self.$index.to_value()),+]
+            }
+        }
+    };
+}
+
+impl_tuple_to_key!(A: 0, B: 1);
+impl_tuple_to_key!(A: 0, B: 1, C: 2);
+impl_tuple_to_key!(A: 0, B: 1, C: 2, D: 3);
+impl_tuple_to_key!(A: 0, B: 1, C: 2, D: 3, E: 4);
+
 macro_rules! impl_to_value {
     ($ty:ty, $variant:ident) => {
         impl ToValue for $ty {
@@ -548,6 +581,68 @@ where
 // }
 
 
+thread_local! {
+    static STRICT_MAPPING: Cell<bool> = Cell::new(false);
+}
+
+/// Whether the derived `FromValue` fail on the columns without a field and on the missing fields,
+/// instead of defaulting them. Set on the thread by the statements run with `AkitaConfig::set_strict_mapping`.
+pub fn strict_mapping() -> bool {
+    STRICT_MAPPING.with(|strict| strict.get())
+}
+
+pub fn set_strict_mapping(strict: bool) {
+    STRICT_MAPPING.with(|cell| cell.set(strict));
+}
+
+/// Fail on the first column of the object without a field.
+pub fn check_unmapped_columns(data: &Value, fields: &[&str]) -> Result<(), AkitaDataError> {
+    check_unmapped_columns_with(data, fields, &[])
+}
+
+/// Fail on the first column of the object without a field, the columns of the `#[nested]` fields' prefixes mapped.
+pub fn check_unmapped_columns_with(data: &Value, fields: &[&str], prefixes: &[&str]) -> Result<(), AkitaDataError> {
+    if let Value::Object(data) = data {
+        if let Some(column) = data.keys().find(|column| !fields.contains(&column.as_str()) && !prefixes.iter().any(|prefix| column.starts_with(prefix))) {
+            return Err(AkitaDataError::ObjectValidError(format!("the column {} has no field", column)));
+        }
+    }
+    Ok(())
+}
+
+/// The columns of a `#[nested(prefix = "d_")]` field with the prefix taken off, e.g. `d_name` as `name`,
+/// none when they are all null, as for the rows a left join matched nothing to.
+pub fn nested_value(data: &Value, prefix: &str) -> Option<Value> {
+    let mut nested = Value::new_object();
+    let mut found = false;
+    if let Value::Object(data) = data {
+        for (column, value) in data.iter().filter(|(column, _)| column.starts_with(prefix)) {
+            found |= *value != Value::Nil;
+            nested.insert_obj_value(&column[prefix.len()..], value);
+        }
+    }
+    if found { Some(nested) } else { None }
+}
+
+/// The value stored for a `#[field(json)]` field, `NULL` for `None`.
+pub fn to_json_value<T: Serialize>(v: &T) -> Value {
+    match serde_json::to_value(v) {
+        Ok(serde_json::Value::Null) | Err(_) => Value::Nil,
+        Ok(v) => Value::Json(v),
+    }
+}
+
+/// The `#[field(json)]` field read from a json or a text column.
+pub fn from_json_value<T: DeserializeOwned>(v: &Value) -> Result<T, AkitaDataError> {
+    let json = match v {
+        Value::Nil => serde_json::Value::Null,
+        Value::Text(v) => serde_json::from_str(v)?,
+        Value::Blob(v) => serde_json::from_slice(v)?,
+        v => serde_json::Value::from_value_opt(v)?,
+    };
+    serde_json::from_value(json).map_err(AkitaDataError::from)
+}
+
 pub trait FromValue: Sized {
     fn from_value(v: &Value) -> Self {
         match Self::from_value_opt(v) {
@@ -599,7 +694,6 @@ macro_rules! impl_from_value_numeric {
 
 impl_from_value!(Vec<u8>, "Vec<u8>", Blob);
 impl_from_value!(char, "char", Char);
-impl_from_value!(Uuid, "Uuid", Uuid);
 impl_from_value!(NaiveDate, "NaiveDate", Date);
 
 impl_from_value_numeric!(i8, to_i8, "i8", Tinyint);
@@ -639,6 +733,9 @@ impl FromValue for String {
             Value::BigDecimal(ref v) => Ok(v.to_string()),
             Value::Json(ref v) => Ok(serde_json::to_string(v).unwrap_or_default()),
             Value::Uuid(ref v) => Ok(v.to_string()),
+            Value::Inet(ref v) => Ok(v.to_string()),
+            Value::Cidr(ref v) => Ok(v.to_string()),
+            Value::MacAddr(ref v) => Ok(v.to_string()),
             Value::Date(ref v) => Ok(v.to_string()),
             Value::Time(ref v) => Ok(v.to_string()),
             Value::DateTime(ref v) => Ok(v.to_string()),
@@ -718,6 +815,20 @@ impl FromValue for bool {
     }
 }
 
+/// Read from a uuid, its 16 bytes or its text.
+impl FromValue for Uuid {
+    fn from_value_opt(v: &Value) -> Result<Self, AkitaDataError> {
+        let uuid = match *v {
+            Value::Uuid(ref v) => return Ok(*v),
+            Value::Blob(ref v) if v.len() == 16 => Uuid::from_slice(v).ok(),
+            Value::Blob(ref v) => std::str::from_utf8(v).ok().and_then(|v| Uuid::parse_str(v.trim()).ok()),
+            Value::Text(ref v) => Uuid::parse_str(v.trim()).ok(),
+            _ => None,
+        };
+        uuid.ok_or_else(|| AkitaDataError::ConvertError(ConvertError::NotSupported(format!("{:?}", v), "Uuid".into())))
+    }
+}
+
 impl FromValue for serde_json::Value {
     fn from_value_opt(v: &Value) -> Result<Self, AkitaDataError> {
         match v.clone() {
@@ -734,6 +845,9 @@ impl FromValue for serde_json::Value {
             Value::Text(v) => serde_json::to_value(v).map_err(AkitaDataError::from),
             Value::Json(v) => Ok(v.clone()), //serde_json::to_value(v).map_err(AkitaDataError::from),
             Value::Uuid(v) => serde_json::to_value(v).map_err(AkitaDataError::from),
+            Value::Inet(v) => serde_json::to_value(v).map_err(AkitaDataError::from),
+            Value::Cidr(v) => Ok(serde_json::Value::String(v.to_string())),
+            Value::MacAddr(v) => Ok(serde_json::Value::String(v.to_string())),
             Value::Date(v) => serde_json::to_value(v).map_err(AkitaDataError::from),
             Value::Time(v) => serde_json::to_value(v).map_err(AkitaDataError::from),
             Value::DateTime(v) => serde_json::to_value(v).map_err(AkitaDataError::from),
@@ -28,6 +28,9 @@ pub static CUSTOM_ARG_ALLOWED_COPY_TYPES: [&str; 14] = [
     "f64",
 ];
 
+/// the fill functions provided by akita, evaluated when the statement is built.
+pub static FILL_FUNCTIONS: [&str; 3] = ["now", "today", "timestamp"];
+
 pub static NUMBER_TYPES: [&str; 38] = [
     "usize",
     "u8",
@@ -96,16 +99,51 @@ pub enum FieldExtra {
     Name(String),
     IdType(String),
     Table(String),
+    Datasource(String),
+    /// `#[table(rename_all = "camelCase")]`, the naming of the columns of the fields
+    RenameAll(String),
     Select(bool),
     Exist(bool),
     Fill {
         /// This is the name of the function that should be cacalledlled
         function: String,
+        /// the key of the `AkitaContext` value filled instead of a function's
+        context: Option<String>,
         mode: Option<String>,
         /// This is the argument type that can be passed in with a macro
         argument: Option<CustomArgument>,
     },
     NumericScale(ValueOrPath<u64>),
+    ParentId,
+    /// the codec compressing the value
+    Compress(String),
+    /// stored as a json document with serde
+    Json,
+    /// stored as a bitmask, or read from the names of a MySQL `SET`
+    BitFlags,
+    Sensitive,
+    LogicDelete,
+    Version,
+    /// read from the columns with the prefix of a joined query
+    Nested(String),
+    /// the sql type of the column in the ddl
+    ColumnType(String),
+    Len(u64),
+    NotNull,
+    /// the sql of the default value of the column
+    Default(String),
+    Comment(String),
+    /// `#[index(name = "idx_user_email", columns = "email", unique)]` on the struct
+    Index {
+        name: Option<String>,
+        columns: Vec<String>,
+        unique: bool,
+    },
+    /// `has_many` or `belongs_to`, loaded by `list_with` rather than from a column
+    Relation {
+        kind: String,
+        foreign_key: String,
+    },
 }
 
 /// This struct stores information about defined custom arguments that will be passed in
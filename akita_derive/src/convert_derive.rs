@@ -2,7 +2,7 @@ use proc_macro::{TokenStream};
 use quote::quote;
 use syn::{self, DeriveInput};
 
-use crate::{util::{get_field_default_value, collect_field_info}, comm::FieldInformation};
+use crate::{util::{get_field_default_value, collect_field_info, is_option_type}, comm::{FieldExtra, FieldInformation}};
 
 pub fn impl_from_akita(input: TokenStream) -> TokenStream {
     let ast = syn::parse::<DeriveInput>(input).unwrap();
@@ -20,14 +20,78 @@ pub fn build_from_akita(name: &syn::Ident, _generics: &syn::Generics, fields: &V
             let field_name = &field.name;
             let field_info = field.field.ident.as_ref().unwrap();
             let default_value = get_field_default_value(&field.field.ty, field.field.ident.as_ref().unwrap());
-            quote!( #field_info: match data.get_obj(#field_name) { Ok(v) => v, Err(_) => { #default_value } },)
+            // in strict mode only the options and the fields left out of the selects may be missing.
+            let required = !is_option_type(&field.field.ty) && !field.extra.iter().any(|extra| matches!(extra, FieldExtra::Exist(false) | FieldExtra::Select(false)));
+            let missing_error = quote!(akita::core::AkitaDataError::ObjectValidError(format!("the field {} has no column", #field_name)));
+            let missing = if required {
+                quote!(Err(akita::core::AkitaDataError::NoSuchValueError(_)) if strict => return Err(#missing_error),)
+            } else {
+                quote!(Err(akita::core::AkitaDataError::NoSuchValueError(_)) => { #default_value })
+            };
+            let missing_value = if required { quote!(None if strict => return Err(#missing_error),) } else { quote!() };
+            if is_relation(field) {
+                // loaded by `list_with`.
+                return quote!( #field_info: Default::default(),);
+            }
+            if let Some(prefix) = nested_prefix(field) {
+                return if is_option_type(&field.field.ty) {
+                    quote!( #field_info: match akita::core::nested_value(data, #prefix) {
+                        Some(v) => Some(akita::core::FromValue::from_value_opt(&v)?),
+                        None => None,
+                    },)
+                } else {
+                    quote!( #field_info: akita::core::FromValue::from_value_opt(&akita::core::nested_value(data, #prefix).unwrap_or_else(akita::core::Value::new_object))?,)
+                };
+            }
+            if is_json(field) {
+                // a document not matching the type can't be defaulted, it always fails.
+                return quote!( #field_info: match data.get_obj_value(#field_name) {
+                    Some(v) => akita::core::from_json_value(v)?,
+                    #missing_value
+                    None => akita::core::from_json_value(&akita::core::Value::Nil)?,
+                },);
+            }
+            if is_bitflags(field) {
+                let ty = &field.field.ty;
+                return quote!( #field_info: match data.get_obj_value(#field_name).map(akita::core::from_bits_value) {
+                    Some(Ok(v)) => v,
+                    Some(Err(e)) if strict => return Err(e),
+                    #missing_value
+                    _ => <#ty as akita::core::BitFlags>::from_bits(0),
+                },);
+            }
+            if compression(field).is_some() {
+                return quote!( #field_info: match data.get_obj_value(#field_name).map(|v| akita::core::FromValue::from_value_opt(&akita::decompress_value(v))) {
+                    Some(Ok(v)) => v,
+                    Some(Err(e)) if strict => return Err(e),
+                    #missing_value
+                    _ => { #default_value }
+                },);
+            }
+            quote!( #field_info: match data.get_obj(#field_name) {
+                Ok(v) => v,
+                #missing
+                Err(e) if strict => return Err(e),
+                Err(_) => { #default_value }
+            },)
         })
         .collect();
+    let columns = fields.iter().map(|field| field.name.replace("r#", "")).collect::<Vec<_>>();
+    let prefixes = fields.iter().filter_map(nested_prefix).collect::<Vec<_>>();
+    let check_unmapped = if prefixes.is_empty() {
+        quote!(akita::core::check_unmapped_columns(data, &[#(#columns),*])?;)
+    } else {
+        quote!(akita::core::check_unmapped_columns_with(data, &[#(#columns),*], &[#(#prefixes),*])?;)
+    };
 
     quote!(
         impl akita::core::FromValue for #name {
 
             fn from_value_opt(data: &akita::core::Value) -> Result<Self, akita::core::AkitaDataError> {
+                let strict = akita::core::strict_mapping();
+                if strict {
+                    #check_unmapped
+                }
                 Ok(#name {
                     #(#from_fields)*
                 })
@@ -51,6 +115,18 @@ pub fn build_to_akita(name: &syn::Ident, generics: &syn::Generics, fields: &Vec<
         .map(|field| {
             let field_name = &field.name;
             let field_info = field.field.ident.as_ref().unwrap();
+            if is_relation(field) || nested_prefix(field).is_some() {
+                return quote!();
+            }
+            if is_json(field) {
+                return quote!( data.insert_obj_value(#field_name, &akita::core::to_json_value(&self.#field_info));)
+            }
+            if is_bitflags(field) {
+                return quote!( data.insert_obj_value(#field_name, &akita::core::to_bits_value(&self.#field_info));)
+            }
+            if let Some(compression) = compression(field) {
+                return quote!( data.insert_obj_value(#field_name, &akita::compress_value(#compression, akita::core::ToValue::to_value(&self.#field_info)));)
+            }
             quote!( data.insert_obj(#field_name, &self.#field_info );)
         })
         .collect();
@@ -67,3 +143,33 @@ pub fn build_to_akita(name: &syn::Ident, generics: &syn::Generics, fields: &Vec<
     res
 }
 
+/// the codec of `#[field(compress = "...")]`.
+fn compression(field: &FieldInformation) -> Option<proc_macro2::TokenStream> {
+    field.extra.iter().find_map(|extra| match extra {
+        FieldExtra::Compress(codec) if codec == "zstd" => Some(quote!(akita::Compression::Zstd)),
+        _ => None,
+    })
+}
+
+/// whether the field is `#[field(json)]`.
+fn is_json(field: &FieldInformation) -> bool {
+    field.extra.iter().any(|extra| matches!(extra, FieldExtra::Json))
+}
+
+/// the column prefix of `#[nested]`.
+fn nested_prefix(field: &FieldInformation) -> Option<String> {
+    field.extra.iter().find_map(|extra| match extra {
+        FieldExtra::Nested(prefix) => Some(prefix.clone()),
+        _ => None,
+    })
+}
+
+/// whether the field is `#[has_many]` or `#[belongs_to]`.
+fn is_relation(field: &FieldInformation) -> bool {
+    field.extra.iter().any(|extra| matches!(extra, FieldExtra::Relation { .. }))
+}
+
+/// whether the field is `#[field(bitflags)]`.
+fn is_bitflags(field: &FieldInformation) -> bool {
+    field.extra.iter().any(|extra| matches!(extra, FieldExtra::BitFlags))
+}
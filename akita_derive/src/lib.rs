@@ -10,13 +10,14 @@ mod table_derive;
 #[macro_use]
 mod convert_derive;
 mod sql_derive;
+mod transactional_derive;
 #[allow(unused)]
 mod util;
 mod comm;
 
 
 /// Generate table info data
-#[proc_macro_derive(FromValue)]
+#[proc_macro_derive(FromValue, attributes(field, nested))]
 pub fn from_akita(input: TokenStream) -> TokenStream {
     convert_derive::impl_from_akita(input)
 }
@@ -29,7 +30,7 @@ pub fn to_akita(input: TokenStream) -> TokenStream {
 
 /// Generate table info
 /// ```rust
-/// /// Annotion Support: Table、table_id、field (name, exist)
+/// /// Annotion Support: Table (name, datasource)、table_id、field (name, exist, parent_id, compress, json, bitflags, sensitive)、has_many、belongs_to、nested
 /// #[derive(Debug, FromValue, ToValue, AkitaTable, Clone)]
 /// #[table(name="t_system_user")]
 /// struct SystemUser {
@@ -42,7 +43,7 @@ pub fn to_akita(input: TokenStream) -> TokenStream {
 /// }
 /// ```
 /// 
-#[proc_macro_derive(AkitaTable, attributes(field, table, table_id, fill))]
+#[proc_macro_derive(AkitaTable, attributes(field, table, table_id, index, fill, has_many, belongs_to, nested))]
 #[proc_macro_error]
 pub fn to_table(input: TokenStream) -> TokenStream {
     table_derive::impl_get_table(input)
@@ -61,6 +62,14 @@ pub fn to_table(input: TokenStream) -> TokenStream {
 /// or:
 ///    #[sql(AK,"select * from mch_info where mch_no = ?")]
 ///     fn select(ak: &AKita, name: &str) -> Vec<MchInfo> { todo!() }
+///
+/// or, with the result cached for the ttl (ms, s, m, h), keyed by the args or only the sql:
+///    #[sql(AK,"select * from mch_info where mch_no = ?", cache = "ttl=30s, key=args")]
+///     fn select(name: &str) -> Vec<MchInfo> { todo!() }
+///
+/// or, once per record of the slice with the params bound by the names of its fields, returning the affected rows:
+///    #[sql(AK,"update mch_info set mch_name = :mch_name where mch_no = :mch_no")]
+///     fn rename_all(rows: &[MchRename]) -> Result<u64, AkitaError> { todo!() }
 /// </pre>
 #[proc_macro_attribute]
 pub fn sql(args: TokenStream, func: TokenStream) -> TokenStream {
@@ -68,4 +77,40 @@ pub fn sql(args: TokenStream, func: TokenStream) -> TokenStream {
     let target_fn: ItemFn = syn::parse(func).unwrap();
     let stream = sql_derive::impl_sql(&target_fn, &args);
     stream
-}
\ No newline at end of file
+}
+
+/// run the function in a transaction, committed when it returns `Ok` and rolled back on `Err`.
+/// the function must return a `Result` whose error converts from `AkitaError`, it can be async.
+/// <pre>
+/// for example:
+///     #[transactional]
+///     fn transfer(ak: &Akita, from: &Account, to: &Account) -> Result<(), AkitaError> {
+///         ak.update_by_id(from)?;
+///         ak.update_by_id(to)?;
+///         Ok(())
+///     }
+///
+/// or, with the transaction akita bound as `akita`:
+///     #[transactional(AK)]
+///     fn transfer(from: &Account, to: &Account) -> Result<(), AkitaError> {
+///         akita.update_by_id(from)?;
+///         akita.update_by_id(to)?;
+///         Ok(())
+///     }
+///
+/// or, with the isolation level, the access mode, and the retries on deadlock
+/// (the body runs again so it can only borrow the args, the error must implement `Retryable`):
+///     #[transactional(isolation = "read_committed", read_only = false, retry = 3)]
+///     fn transfer(ak: &Akita, from: &Account, to: &Account) -> Result<(), AkitaError> {
+///         ak.update_by_id(from)?;
+///         ak.update_by_id(to)?;
+///         Ok(())
+///     }
+/// </pre>
+#[proc_macro_attribute]
+pub fn transactional(args: TokenStream, func: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(args as AttributeArgs);
+    let target_fn: ItemFn = syn::parse(func).unwrap();
+    let stream = transactional_derive::impl_transactional(&target_fn, &args);
+    stream
+}
@@ -1,9 +1,9 @@
 use proc_macro::{TokenStream};
 use quote::ToTokens;
 use quote::quote;
-use syn::{self, ItemFn, AttributeArgs, FnArg, Pat};
+use syn::{self, ItemFn, AttributeArgs, FnArg, Pat, NestedMeta, Meta, Lit};
 use proc_macro2::{Ident, Span};
-use crate::util::{find_return_type, is_akita_ref, find_fn_body, get_page_req_ident, is_fetch, get_fn_args, is_fetch_array};
+use crate::util::{find_return_type, is_akita_ref, find_fn_body, get_page_req_ident, is_fetch, get_fn_args, get_slice_args, is_fetch_array};
 
 #[allow(unused)]
 pub fn impl_sql(
@@ -28,6 +28,19 @@ pub fn impl_sql(
         }
     }
 
+    // the named `cache = "ttl=30s, key=args"` is taken apart from the positional args.
+    let mut cache = None;
+    let args = args.iter().filter(|arg| match arg {
+        NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("cache") => {
+            match &nv.lit {
+                Lit::Str(value) => cache = parse_cache(&value.value(), &func_name_ident.to_string()).into(),
+                _ => panic!("[Akita] cache of '{}()' should be a string like \"ttl=30s, key=args\"!", func_name_ident),
+            }
+            false
+        }
+        _ => true,
+    }).collect::<Vec<_>>();
+
     let sql_ident;
     if args.len() == 1 {
         if akita_name.is_empty() {
@@ -56,6 +69,31 @@ pub fn impl_sql(
         akita_ident = Ident::new(&akita_ident.to_string().trim_start_matches("mut "), Span::call_site()).to_token_stream();
     }
 
+    // a slice of records runs the statement once per record, with the params taken from its fields.
+    let slice_args = get_slice_args(target_fn);
+    if let Some(records) = slice_args.first() {
+        let other_args = get_fn_args(target_fn).iter()
+            .filter(|arg| arg.to_token_stream().to_string().trim_start_matches("mut ").trim() != akita_name)
+            .count();
+        if other_args > 1 || cache.is_some() {
+            panic!("[Akita] the batch '{}()' should only take the akita ref and the slice of records!", func_name_ident);
+        }
+        let attrs = &target_fn.attrs;
+        let vis = &target_fn.vis;
+        let sig = &target_fn.sig;
+        return quote! {
+           #(#attrs)*
+           #vis #sig {
+               #fn_body
+               let akita_sql: String = (#sql_ident).into();
+               let akita_batch = #records.iter()
+                   .map(|record| akita::Params::from_record(&akita_sql, record.to_value()).map_err(akita::AkitaError::from))
+                   .collect::<Result<Vec<_>, _>>()?;
+               return #akita_ident.exec_batch(akita_sql, akita_batch);
+           }
+        }.into();
+    }
+
     let mut call_method = quote! {};
     let is_fetch = is_fetch(&return_ty.to_string());
     if is_fetch {
@@ -80,6 +118,16 @@ pub fn impl_sql(
         call_method = quote! {fetch_page};
     }
 
+    let mut cache_policy = quote! {};
+    if let Some((ttl_millis, key_args)) = cache {
+        call_method = match call_method.to_string().as_str() {
+            "exec_raw" => quote! {exec_raw_cached},
+            "exec_first" => quote! {exec_first_cached},
+            _ => panic!("[Akita] cache only works on the select of '{}()', not on writes or pages!", func_name_ident),
+        };
+        cache_policy = quote! {,&akita::CachePolicy::new(std::time::Duration::from_millis(#ttl_millis)).set_key_args(#key_args)};
+    }
+
     //append all args
     let sql_args_gen = filter_args_context_id(&akita_name, &get_fn_args(target_fn), &[page_req_str]);
     //gen rust code templete
@@ -88,13 +136,46 @@ pub fn impl_sql(
            let mut akita_args =vec![];
            #sql_args_gen
            #fn_body
-           return #akita_ident.#call_method(#sql_ident,akita_args #page_req);
+           return #akita_ident.#call_method(#sql_ident,akita_args #page_req #cache_policy);
        }
     };
     return gen_token_temple.into();
 }
 
-
+/// parse `ttl=30s, key=args` into the ttl in millis and whether the args are part of the key.
+fn parse_cache(cache: &str, func_name: &str) -> (u64, bool) {
+    let mut ttl = None;
+    let mut key_args = true;
+    for item in cache.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+        let (name, value) = item.split_once('=').map(|(name, value)| (name.trim(), value.trim()))
+            .unwrap_or_else(|| panic!("[Akita] cache option '{}' of '{}()' should be like name=value!", item, func_name));
+        match name {
+            "ttl" => {
+                let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+                let amount: u64 = value[..split].parse()
+                    .unwrap_or_else(|_| panic!("[Akita] cache ttl '{}' of '{}()' should be like 30s!", value, func_name));
+                let millis = match &value[split..] {
+                    "ms" => amount,
+                    "" | "s" => amount * 1000,
+                    "m" => amount * 60 * 1000,
+                    "h" => amount * 60 * 60 * 1000,
+                    unit => panic!("[Akita] unknown cache ttl unit '{}' of '{}()', use ms, s, m or h!", unit, func_name),
+                };
+                ttl = Some(millis);
+            }
+            "key" => {
+                key_args = match value {
+                    "args" => true,
+                    "sql" => false,
+                    _ => panic!("[Akita] cache key '{}' of '{}()' should be args or sql!", value, func_name),
+                };
+            }
+            _ => panic!("[Akita] unknown cache option '{}' of '{}()'!", name, func_name),
+        }
+    }
+    let ttl = ttl.unwrap_or_else(|| panic!("[Akita] cache of '{}()' miss the ttl!", func_name));
+    (ttl, key_args)
+}
 
 fn filter_args_context_id(
     akita_name: &str,
@@ -1,7 +1,7 @@
 use proc_macro::TokenStream;
 use quote::quote;
-use syn::{DeriveInput};
-use crate::{convert_derive::{build_to_akita, build_from_akita}, comm::{ FieldExtra},util::{ find_struct_annotions, collect_field_info, to_snake_name}};
+use syn::{DeriveInput, spanned::Spanned};
+use crate::{convert_derive::{build_to_akita, build_from_akita}, comm::{ FieldExtra, FILL_FUNCTIONS},util::{ is_option_type, find_struct_annotions, find_index_annotions, collect_field_info, inner_type, option_to_tokens, to_snake_name}};
 
 pub fn impl_get_table(input: TokenStream) -> TokenStream {
     let derive_input = syn::parse::<DeriveInput>(input).unwrap();
@@ -22,6 +22,26 @@ fn parse_table(ast: &syn::DeriveInput) -> TokenStream {
    if table_name.is_empty() {
        table_name = to_snake_name(struct_name);
    }
+    let datasource = structs.iter().find_map(|st| match st { FieldExtra::Datasource(name) => Some(name.clone()), _ => None })
+        .map(|name| quote!(
+            fn datasource() -> Option<&'static str> {
+                Some(#name)
+            }
+        ));
+    let indexes = find_index_annotions(&ast.attrs).into_iter().filter_map(|extra| match extra {
+        FieldExtra::Index { name, columns, unique } => {
+            let name = name.unwrap_or_else(|| format!("{}_{}_{}", if unique { "uk" } else { "idx" }, table_name.replace('.', "_"), columns.join("_")));
+            Some(quote!(akita::core::IndexDef { name: #name.to_string(), columns: vec![#(#columns.to_string()),*], unique: #unique }))
+        }
+        _ => None,
+    }).collect::<Vec<_>>();
+    let indexes = if indexes.is_empty() { None } else {
+        Some(quote!(
+            fn indexes() -> Vec<akita::core::IndexDef> {
+                vec![#(#indexes),*]
+            }
+        ))
+    };
     let from_fields: Vec<proc_macro2::TokenStream> = fields
         .iter()
         .map(|field| {
@@ -29,13 +49,25 @@ fn parse_table(ast: &syn::DeriveInput) -> TokenStream {
             let mut exist = true;
             let mut select = true;
             let mut identify = false;
+            let mut parent_id = false;
+            let mut sensitive = false;
+            let mut logic_delete = false;
+            let mut version = false;
             let mut fill_function = String::default();
+            let mut fill_context = None;
             let mut fill_mode = None;
+            let mut id_type = String::from("none");
+            let mut column_type = None;
+            let mut len = None;
+            let mut not_null = false;
+            let mut default = None;
+            let mut comment = None;
 
             for extra in field.extra.iter() {
                 match extra {
-                    FieldExtra::Fill {ref function, ref mode, .. } => {
+                    FieldExtra::Fill {ref function, ref context, ref mode, .. } => {
                         fill_function = function.clone();
+                        fill_context = context.clone();
                         fill_mode = mode.clone();
                     }
                     FieldExtra::Name(v) => {
@@ -51,14 +83,59 @@ fn parse_table(ast: &syn::DeriveInput) -> TokenStream {
                     FieldExtra::TableId(_) => {
                         identify = true;
                     }
+                    FieldExtra::IdType(v) => {
+                        id_type = v.clone();
+                    }
+                    FieldExtra::ParentId => {
+                        parent_id = true;
+                    }
+                    FieldExtra::Sensitive => {
+                        sensitive = true;
+                    }
+                    FieldExtra::LogicDelete => {
+                        logic_delete = true;
+                    }
+                    FieldExtra::Version => {
+                        version = true;
+                    }
+                    FieldExtra::ColumnType(v) => {
+                        column_type = Some(v.clone());
+                    }
+                    FieldExtra::Len(v) => {
+                        len = Some(*v);
+                    }
+                    FieldExtra::NotNull => {
+                        not_null = true;
+                    }
+                    FieldExtra::Default(v) => {
+                        default = Some(v.clone());
+                    }
+                    FieldExtra::Comment(v) => {
+                        comment = Some(v.clone());
+                    }
                     _ => { }
                 }
             }
 
-            let field_type = if identify { quote!(akita::FieldType::TableId("none".to_string())) } else { quote!(akita::FieldType::TableField) };
+            let rust_type = field.field_type.strip_prefix("Option<").and_then(|ty| ty.strip_suffix('>')).unwrap_or(&field.field_type).to_string();
+            let column_type = option_to_tokens(&column_type.map(|ty| quote!(#ty.to_string())));
+            let len = option_to_tokens(&len);
+            let default = option_to_tokens(&default.map(|default| quote!(#default.to_string())));
+            let comment = option_to_tokens(&comment.map(|comment| quote!(#comment.to_string())));
+            let field_type = if identify { quote!(akita::FieldType::TableId(#id_type.to_string())) } else { quote!(akita::FieldType::TableField) };
             let fill_mode = fill_mode.unwrap_or(String::from("default")).to_lowercase();
-            let fill = if fill_function.is_empty() { quote! (None) } else { let fn_ident: syn::Path = syn::parse_str(&fill_function).unwrap(); quote! (akita::core::Fill {
-                        value: Some(#fn_ident().to_value()),
+            let fill = if fill_function.is_empty() && fill_context.is_none() { quote! (None) } else {
+                // the built-in fill functions are evaluated by akita, the other ones are paths to the application's.
+                let fill_value = if let Some(key) = &fill_context {
+                    quote!(akita::AkitaContext::get(#key))
+                } else if FILL_FUNCTIONS.contains(&fill_function.as_str()) {
+                    quote!(Some(akita::fill_function(#fill_function)))
+                } else {
+                    let fn_ident: syn::Path = syn::parse_str(&fill_function).unwrap();
+                    quote!(Some(#fn_ident().to_value()))
+                };
+                quote! (akita::core::Fill {
+                        value: #fill_value,
                         mode: #fill_mode.to_string()
                     }.into()) };
 
@@ -71,6 +148,18 @@ fn parse_table(ast: &syn::DeriveInput) -> TokenStream {
                     fill: #fill,
                     select: #select,
                     exist: #exist,
+                    parent_id: #parent_id,
+                    sensitive: #sensitive,
+                    logic_delete: #logic_delete,
+                    version: #version,
+                    column: akita::core::ColumnMeta {
+                        rust_type: #rust_type.to_string(),
+                        column_type: #column_type,
+                        len: #len,
+                        not_null: #not_null,
+                        default: #default,
+                        comment: #comment,
+                    },
                 },
             )
         }).collect();
@@ -93,9 +182,16 @@ fn parse_table(ast: &syn::DeriveInput) -> TokenStream {
                 }
             }
             if exist {
+                // e.g. `User::USER_NAME`, for the wrappers.
+                let constant = syn::Ident::new(&to_snake_name(&field_name.to_string().trim_start_matches("r#").to_string()).to_uppercase(), field_name.span());
+                // the column of an `Option<T>` field holds the `T`.
+                let ty = &field.field.ty;
+                let ty = if is_option_type(ty) { inner_type(ty).unwrap_or(ty) } else { ty };
                 quote!(
-                    pub fn #field_name() -> String {
-                        #name.to_string()
+                    pub const #constant: &'static str = #name;
+
+                    pub fn #field_name() -> akita::Column<#ty> {
+                        akita::Column::new(#name)
                     }
                 )
             } else {
@@ -103,6 +199,35 @@ fn parse_table(ast: &syn::DeriveInput) -> TokenStream {
                 )
             }
         }).collect();
+    let relations: Vec<proc_macro2::TokenStream> = fields
+        .iter()
+        .filter_map(|field| {
+            let (kind, foreign_key) = field.extra.iter().find_map(|extra| match extra {
+                FieldExtra::Relation { kind, foreign_key } => Some((kind.as_str(), foreign_key)),
+                _ => None,
+            })?;
+            let field_name = field.field.ident.as_ref().unwrap();
+            let related = match inner_type(&field.field.ty) {
+                Some(related) => related,
+                None => proc_macro_error::abort!(field.field.ty.span(), "`{}` on field `{}` needs a `Vec<T>` or `Option<T>`", kind, field_name),
+            };
+            let (relation, attach) = if kind == "has_many" {
+                (quote!(akita::Relation::HasMany { foreign_key: #foreign_key }), quote!(self.#field_name = related;))
+            } else {
+                (quote!(akita::Relation::BelongsTo { foreign_key: #foreign_key }), quote!(self.#field_name = related.into_iter().next();))
+            };
+            Some(quote!(
+                impl akita::Related<#related> for #struct_info {
+                    fn relation() -> akita::Relation {
+                        #relation
+                    }
+
+                    fn attach(&mut self, related: Vec<#related>) {
+                        #attach
+                    }
+                }
+            ))
+        }).collect();
     let impl_mapper = impl_table_mapper(struct_info);
     let impl_to_akita = build_to_akita(struct_info, generics, &fields);
     let impl_from_akita = build_from_akita(struct_info, generics, &fields);
@@ -122,6 +247,10 @@ fn parse_table(ast: &syn::DeriveInput) -> TokenStream {
                     alias: #struct_name.to_lowercase().into(),
                 }
             }
+
+            #datasource
+
+            #indexes
         }
 
         impl #generics akita::core::GetFields for #struct_info #generics {
@@ -138,6 +267,8 @@ fn parse_table(ast: &syn::DeriveInput) -> TokenStream {
 
         }
 
+        #(#relations)*
+
     ).into()
 }
 
@@ -171,7 +302,7 @@ fn impl_table_mapper(name: &syn::Ident) -> proc_macro2::TokenStream {
                 entity_manager.remove::<Self::Item>(wrapper)
             }
 
-            fn delete_by_id<I: akita::core::ToValue, M: akita::AkitaMapper>(&self, entity_manager: &M, id: I) -> Result<u64, akita::AkitaError> where Self::Item : akita::core::GetFields + akita::core::GetTableName + akita::core::ToValue {
+            fn delete_by_id<I: akita::core::ToKey, M: akita::AkitaMapper>(&self, entity_manager: &M, id: I) -> Result<u64, akita::AkitaError> where Self::Item : akita::core::GetFields + akita::core::GetTableName + akita::core::ToValue {
                 entity_manager.remove_by_id::<Self::Item, I>(id)
             }
 
@@ -188,7 +319,7 @@ fn impl_table_mapper(name: &syn::Ident) -> proc_macro2::TokenStream {
             }
 
             /// Find Data With Table's Ident.
-            fn find_by_id<I: akita::core::ToValue, M: akita::AkitaMapper>(&self, entity_manager: &M, id: I) -> Result<Option<Self::Item>, akita::AkitaError> where Self::Item : akita::core::GetTableName + akita::core::GetFields + akita::core::FromValue {
+            fn find_by_id<I: akita::core::ToKey, M: akita::AkitaMapper>(&self, entity_manager: &M, id: I) -> Result<Option<Self::Item>, akita::AkitaError> where Self::Item : akita::core::GetTableName + akita::core::GetFields + akita::core::FromValue {
                 entity_manager.select_by_id(id)
             }
         }
@@ -0,0 +1,149 @@
+use proc_macro::{TokenStream};
+use quote::ToTokens;
+use quote::quote;
+use syn::{self, ItemFn, AttributeArgs, FnArg, ReturnType, NestedMeta, Meta, Lit};
+use proc_macro2::{Ident, Span};
+use crate::util::{is_akita_ref};
+
+pub fn impl_transactional(
+    target_fn: &ItemFn,
+    args: &AttributeArgs
+) -> TokenStream {
+    let attrs = &target_fn.attrs;
+    let vis = &target_fn.vis;
+    let sig = &target_fn.sig;
+    let block = &target_fn.block;
+    let return_ty = match &sig.output {
+        ReturnType::Type(_, ty) => ty.to_token_stream(),
+        ReturnType::Default => panic!("[Akita] #[transactional] fn '{}()' must return a Result!", sig.ident),
+    };
+
+    let mut akita_param = None;
+    for x in &sig.inputs {
+        match x {
+            FnArg::Receiver(_) => {}
+            FnArg::Typed(t) => {
+                let ty_stream = t.ty.to_token_stream().to_string();
+                if is_akita_ref(&ty_stream) {
+                    let name = t.pat.to_token_stream().to_string().trim_start_matches("mut ").to_string();
+                    akita_param = Ident::new(&name, Span::call_site()).into();
+                    break;
+                }
+            }
+        }
+    }
+
+    // the named options are taken apart from the positional akita arg.
+    let mut isolation = None;
+    let mut read_only = None;
+    let mut retry = 0usize;
+    let mut positional = Vec::new();
+    for arg in args {
+        match arg {
+            NestedMeta::Meta(Meta::NameValue(nv)) => {
+                let name = nv.path.to_token_stream().to_string();
+                match (name.as_str(), &nv.lit) {
+                    ("isolation", Lit::Str(value)) => {
+                        isolation = match value.value().to_lowercase().replace(' ', "_").as_str() {
+                            "read_uncommitted" => quote! {ReadUncommitted},
+                            "read_committed" => quote! {ReadCommitted},
+                            "repeatable_read" => quote! {RepeatableRead},
+                            "serializable" => quote! {Serializable},
+                            level => panic!("[Akita] unknown isolation level '{}' on '{}()'!", level, sig.ident),
+                        }.into();
+                    }
+                    ("read_only", Lit::Bool(value)) => read_only = value.value.into(),
+                    ("retry", Lit::Int(value)) => retry = value.base10_parse().expect("[Akita] retry should be a number of attempts!"),
+                    _ => panic!("[Akita] unknown transactional option '{}' on '{}()', use isolation, read_only or retry!", name, sig.ident),
+                }
+            }
+            _ => positional.push(arg),
+        }
+    }
+
+    // the body runs against the akita with the transaction connection pinned,
+    // shadowing the akita param, or bound as `akita` when given in the macro.
+    let (akita_source, akita_ident) = if positional.len() == 1 {
+        (positional.get(0).expect("[Akita] miss akita ident param!").to_token_stream(), quote! {akita})
+    } else if positional.is_empty() {
+        match akita_param {
+            Some(ident) => (ident.to_token_stream(), ident.to_token_stream()),
+            None => panic!("[Akita] you should add akita ref param  akita: &Akita  on '{}()'!", sig.ident),
+        }
+    } else {
+        panic!("[Akita] Incorrect macro parameter length!");
+    };
+
+    let start = if isolation.is_none() && read_only.is_none() {
+        quote! { #akita_source.start_transaction() }
+    } else {
+        let isolation = isolation.map(|level| quote! { .set_isolation(akita::IsolationLevel::#level) });
+        let read_only = read_only.map(|read_only| quote! { .set_read_only(#read_only) });
+        quote! { #akita_source.start_transaction_with(akita::TransactionOptions::new() #isolation #read_only) }
+    };
+
+    if retry == 0 {
+        let run = if sig.asyncness.is_some() {
+            quote! { async move #block.await }
+        } else {
+            quote! { (move || -> #return_ty #block)() }
+        };
+        let gen_token_temple = quote! {
+            #(#attrs)*
+            #vis #sig {
+                let akita_transaction = #start?;
+                let result: #return_ty = {
+                    let #akita_ident = akita_transaction.akita();
+                    #run
+                };
+                match result {
+                    Ok(value) => {
+                        akita_transaction.commit()?;
+                        Ok(value)
+                    }
+                    Err(err) => {
+                        let _ = akita_transaction.rollback();
+                        Err(err)
+                    }
+                }
+            }
+        };
+        return gen_token_temple.into();
+    }
+
+    // the body is run again on a deadlock, so it only borrows the args.
+    let run = if sig.asyncness.is_some() {
+        quote! { async #block.await }
+    } else {
+        quote! { (|| -> #return_ty #block)() }
+    };
+    let gen_token_temple = quote! {
+        #(#attrs)*
+        #vis #sig {
+            let mut akita_attempts = 0usize;
+            loop {
+                let akita_transaction = #start?;
+                let result: #return_ty = {
+                    let #akita_ident = akita_transaction.akita();
+                    #run
+                };
+                match result {
+                    Ok(value) => match akita_transaction.commit() {
+                        Ok(()) => return Ok(value),
+                        Err(err) if akita_attempts < #retry && err.is_deadlock() => akita_attempts += 1,
+                        Err(err) => return Err(err.into()),
+                    },
+                    Err(err) => {
+                        let _ = akita_transaction.rollback();
+                        if akita_attempts < #retry && akita::Retryable::is_retryable(&err) {
+                            akita_attempts += 1;
+                            continue;
+                        }
+                        return Err(err);
+                    }
+                }
+            }
+        }
+    };
+    gen_token_temple.into()
+}
@@ -48,15 +48,19 @@ pub fn get_field_type(ty: &syn::Type) -> Option<String> {
 }
 
 
+/// whether the type is an `Option`, which a missing column leaves to `None`.
+pub fn is_option_type(ty: &Type) -> bool {
+    match ty {
+        Type::Path(r#path) => r#path.path.segments[0].ident == "Option",
+        _ => false,
+    }
+}
+
 #[allow(unused)]
 pub fn get_field_default_value(ty: &Type, ident: &Ident) -> proc_macro2::TokenStream {
     let ident_name = ident.to_string();
     let ori_ty = get_field_type(ty).unwrap_or_default();
-    let mut ft = String::default();
-    if let Type::Path(r#path) = ty {
-        ft = r#path.path.segments[0].ident.to_string();
-    }
-    if ft.eq("Option") {
+    if is_option_type(ty) {
         quote!(None)
     } else {
         match ori_ty.as_str() {
@@ -69,6 +73,8 @@ pub fn get_field_default_value(ty: &Type, ident: &Ident) -> proc_macro2::TokenSt
             "NaiveDateTime" => quote!(Local::now().naive_local()),
             "Vec" => quote!(Vec::new()),
             "Value" => quote!(serde_json::Value::default()),
+            "IpAddr" => quote!(std::net::IpAddr::V4(std::net::Ipv4Addr::UNSPECIFIED)),
+            "Cidr" | "MacAddr" | "CiString" => quote!(Default::default()),
             _ => quote!(None)
         }
     }
@@ -121,6 +127,19 @@ pub fn find_struct_annotion(attr: &syn::Attribute) -> Vec<FieldExtra> {
                                         None => error(lit.span(), "invalid argument for `name` annotion: only strings are allowed"),
                                     };
                                 }
+                                "datasource" => {
+                                    match lit_to_string(lit) {
+                                        Some(s) => extras.push(FieldExtra::Datasource(s)),
+                                        None => error(lit.span(), "invalid argument for `datasource` annotion: only strings are allowed"),
+                                    };
+                                }
+                                "rename_all" => {
+                                    match lit_to_string(lit) {
+                                        Some(s) if RENAME_RULES.contains(&s.as_str()) => extras.push(FieldExtra::RenameAll(s)),
+                                        Some(_) => error(lit.span(), "invalid argument for `rename_all` annotion: only `snake_case` `camelCase` `PascalCase` `SCREAMING_SNAKE` are allowed"),
+                                        None => error(lit.span(), "invalid argument for `rename_all` annotion: only strings are allowed"),
+                                    };
+                                }
                                 v => abort!(path.span(),"unexpected name value annotion: {:?}",v),
                             };
                         }
@@ -154,13 +173,48 @@ pub fn find_struct_annotion(attr: &syn::Attribute) -> Vec<FieldExtra> {
     extras
 }
 
+/// The `#[index(name = "idx_user_email", columns = "tenant_id, email", unique)]` of the struct, the name is optional.
+pub fn find_index_annotions(struct_attrs: &[syn::Attribute]) -> Vec<FieldExtra> {
+    struct_attrs.iter().filter(|attribute| attribute.path == parse_quote!(index)).map(|attr| {
+        let error = |span: Span, msg: &str| -> ! {
+            abort!(span, "Invalid index annotion: {}", msg);
+        };
+        let (mut name, mut columns, mut unique) = (None, Vec::new(), false);
+        match attr.parse_meta() {
+            Ok(syn::Meta::List(syn::MetaList { ref nested, .. })) => {
+                for meta_item in nested.iter() {
+                    match meta_item {
+                        syn::NestedMeta::Meta(syn::Meta::Path(ref path)) if path.is_ident("unique") => unique = true,
+                        syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue { ref path, ref lit, .. })) => {
+                            let value = lit_to_string(lit).unwrap_or_else(|| error(lit.span(), "only strings are allowed"));
+                            match path.get_ident().map(|ident| ident.to_string()).unwrap_or_default().as_ref() {
+                                "name" => name = Some(value),
+                                "columns" => columns = value.split(',').map(|column| column.trim().to_string()).filter(|column| !column.is_empty()).collect(),
+                                v => error(path.span(), &format!("unexpected argument `{}`, only `name`, `columns` and `unique` are allowed", v)),
+                            }
+                        }
+                        _ => error(meta_item.span(), "only `name`, `columns` and `unique` are allowed"),
+                    }
+                }
+            }
+            _ => error(attr.span(), "expected `#[index(columns = \"..\")]`"),
+        }
+        if columns.is_empty() {
+            error(attr.span(), "missing the `columns` of the index");
+        }
+        FieldExtra::Index { name, columns, unique }
+    }).collect()
+}
+
 
 pub fn collect_field_info(ast: &syn::DeriveInput) -> Vec<FieldInformation> {
     let mut fields = collect_fields(ast);
     let field_types = find_fields_type(&fields);
+    let rename_all = find_struct_annotions(&ast.attrs).into_iter().find_map(|extra| match extra { FieldExtra::RenameAll(rule) => Some(rule), _ => None });
     fields.drain(..).fold(vec![], |mut acc, field| {
         let key = field.ident.clone().unwrap().to_string();
         let (name, extra) = find_extra_for_field(&field, &field_types);
+        let name = rename_all.as_ref().map(|rule| rename_field(&name, rule)).unwrap_or(name);
         acc.push(FieldInformation::new(
             field,
             field_types.get(&key).unwrap().clone(),
@@ -252,6 +306,17 @@ pub fn find_extra_for_field(
     let mut has_field = false;
 
     for attr in &field.attrs {
+        if attr.path == parse_quote!(has_many) || attr.path == parse_quote!(belongs_to) {
+            extras.push(extract_relation(attr, &field_ident));
+            // the related records are not a column of the table.
+            extras.push(FieldExtra::Exist(false));
+            continue;
+        }
+        if attr.path == parse_quote!(nested) {
+            extras.push(extract_nested(attr, &field_ident));
+            extras.push(FieldExtra::Exist(false));
+            continue;
+        }
         if attr.path != parse_quote!(field) && attr.path != parse_quote!(table_id) {
             continue;
         }
@@ -260,6 +325,9 @@ pub fn find_extra_for_field(
         }
         match attr.parse_meta() {
             Ok(syn::Meta::List(syn::MetaList { ref nested, .. })) => {
+                if attr.path == parse_quote!(table_id) {
+                    extras.push(FieldExtra::TableId(String::from("none")));
+                }
                 let meta_items = nested.iter().collect::<Vec<_>>();
                 // only field from there on
                 for meta_item in meta_items {
@@ -267,10 +335,17 @@ pub fn find_extra_for_field(
                         syn::NestedMeta::Meta(ref item) => match *item {
                             // name, exist, fill, select
                             syn::Meta::Path(ref name) => {
-                                match name.get_ident().unwrap().to_string() {
+                                match name.get_ident().unwrap().to_string().as_ref() {
                                     // "fill" => {
                                     //     extras.push(FieldExtra::Name());
                                     // }
+                                    "parent_id" => extras.push(FieldExtra::ParentId),
+                                    "json" => extras.push(FieldExtra::Json),
+                                    "bitflags" => extras.push(FieldExtra::BitFlags),
+                                    "sensitive" => extras.push(FieldExtra::Sensitive),
+                                    "logic_delete" => extras.push(FieldExtra::LogicDelete),
+                                    "version" => extras.push(FieldExtra::Version),
+                                    "not_null" => extras.push(FieldExtra::NotNull),
                                     _ => {
                                         let mut ident = proc_macro2::TokenStream::new();
                                         name.to_tokens(&mut ident);
@@ -288,6 +363,7 @@ pub fn find_extra_for_field(
                                         match lit_to_string(lit) {
                                             Some(s) => extras.push(FieldExtra::Fill{
                                                 function: s,
+                                                context: None,
                                                 mode: None,
                                                 argument: None,
                                             }),
@@ -303,8 +379,8 @@ pub fn find_extra_for_field(
                                     "id_type" => {
                                         match lit_to_string(lit) {
                                             Some(s) => match s.to_lowercase().as_ref() {
-                                                "auto" | "none" | "input" | "assign_id" | "assign_uuid" => extras.push(FieldExtra::IdType(s)),
-                                                _=> error(lit.span(), "invalid argument for `id_type` annotion: only `auto` `none` `input` `assign_id` `assign_uuid` are allowed")
+                                                "auto" | "none" | "input" | "assign_id" | "assign_uuid" | "uuid_v7" => extras.push(FieldExtra::IdType(s.to_lowercase())),
+                                                _=> error(lit.span(), "invalid argument for `id_type` annotion: only `auto` `none` `input` `assign_id` `assign_uuid` `uuid_v7` are allowed")
                                             },
                                             None => error(lit.span(), "invalid argument for `name` annotion: only strings are allowed"),
                                         };
@@ -330,6 +406,31 @@ pub fn find_extra_for_field(
                                             None => error(lit.span(), "invalid argument for `numberic_scale` annotion: only strings are allowed"),
                                         };
                                     }
+                                    "column_type" | "default" | "comment" => {
+                                        match lit_to_string(lit) {
+                                            Some(s) => extras.push(match ident.to_string().as_ref() {
+                                                "column_type" => FieldExtra::ColumnType(s),
+                                                "default" => FieldExtra::Default(s),
+                                                _ => FieldExtra::Comment(s),
+                                            }),
+                                            None => error(lit.span(), &format!("invalid argument for `{}` annotion: only strings are allowed", ident)),
+                                        };
+                                    }
+                                    "len" => {
+                                        match lit_to_int(lit) {
+                                            Some(len) => extras.push(FieldExtra::Len(len)),
+                                            None => error(lit.span(), "invalid argument for `len` annotion: only integers are allowed"),
+                                        };
+                                    }
+                                    "compress" => {
+                                        match lit_to_string(lit) {
+                                            Some(s) => match s.to_lowercase().as_ref() {
+                                                "zstd" => extras.push(FieldExtra::Compress(s.to_lowercase())),
+                                                _ => error(lit.span(), "invalid argument for `compress` annotion: only `zstd` is allowed"),
+                                            },
+                                            None => error(lit.span(), "invalid argument for `compress` annotion: only strings are allowed"),
+                                        };
+                                    }
                                     v => abort!(
                                         path.span(),
                                         "unexpected name value annotion: {:?}",
@@ -383,6 +484,7 @@ pub fn find_extra_for_field(
                         match lit_to_string(lit) {
                             Some(s) => extras.push(FieldExtra::Fill{
                                 function: s,
+                                context: None,
                                 mode: None,
                                 argument: None,
                             }),
@@ -399,8 +501,8 @@ pub fn find_extra_for_field(
                         match lit_to_string(lit) {
                             Some(s) => {
                                 match s.to_lowercase().as_ref() {
-                                    "auto" | "none" | "input" | "assign_id" | "assign_uuid" => extras.push(FieldExtra::IdType(s)),
-                                    _=> error(lit.span(), "invalid argument for `id_type` annotion: only `auto` `none` `input` `assign_id` `assign_uuid` are allowed")
+                                    "auto" | "none" | "input" | "assign_id" | "assign_uuid" | "uuid_v7" => extras.push(FieldExtra::IdType(s.to_lowercase())),
+                                    _=> error(lit.span(), "invalid argument for `id_type` annotion: only `auto` `none` `input` `assign_id` `assign_uuid` `uuid_v7` are allowed")
                                 }
 
                             },
@@ -459,6 +561,66 @@ pub fn find_extra_for_field(
     (field_ident, extras)
 }
 
+/// For has_many and belongs_to, e.g. `#[has_many(foreign_key = "user_id")]`
+pub fn extract_relation(attr: &syn::Attribute, field: &str) -> FieldExtra {
+    let kind = attr.path.get_ident().unwrap().to_string();
+    let mut foreign_key = None;
+    if let Ok(syn::Meta::List(syn::MetaList { ref nested, .. })) = attr.parse_meta() {
+        for meta_item in nested.iter() {
+            match *meta_item {
+                syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue { ref path, ref lit, .. })) if path.is_ident("foreign_key") => {
+                    match lit_to_string(lit) {
+                        Some(s) => foreign_key = Some(s),
+                        None => abort!(lit.span(), "invalid argument for `foreign_key` of `{}` on field `{}`: only strings are allowed", kind, field),
+                    }
+                }
+                _ => abort!(meta_item.span(), "unexpected argument for `{}` on field `{}`, only `foreign_key` is allowed", kind, field),
+            }
+        }
+    }
+    match foreign_key {
+        Some(foreign_key) => FieldExtra::Relation { kind, foreign_key },
+        None => abort!(attr.span(), "`{}` on field `{}` needs the `foreign_key` column, e.g. #[{}(foreign_key = \"user_id\")]", kind, field, kind),
+    }
+}
+
+/// For nested, the prefix of the columns, e.g. `#[nested(prefix = "d_")]`, the field name and `_` by default
+pub fn extract_nested(attr: &syn::Attribute, field: &str) -> FieldExtra {
+    let mut prefix = format!("{}_", field);
+    match attr.parse_meta() {
+        Ok(syn::Meta::Path(_)) => {}
+        Ok(syn::Meta::List(syn::MetaList { ref nested, .. })) => {
+            for meta_item in nested.iter() {
+                match *meta_item {
+                    syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue { ref path, ref lit, .. })) if path.is_ident("prefix") => {
+                        match lit_to_string(lit) {
+                            Some(s) => prefix = s,
+                            None => abort!(lit.span(), "invalid argument for `prefix` of `nested` on field `{}`: only strings are allowed", field),
+                        }
+                    }
+                    _ => abort!(meta_item.span(), "unexpected argument for `nested` on field `{}`, only `prefix` is allowed", field),
+                }
+            }
+        }
+        _ => abort!(attr.span(), "`nested` on field `{}` seems to be misformed, e.g. #[nested(prefix = \"d_\")]", field),
+    }
+    FieldExtra::Nested(prefix)
+}
+
+/// the type of the items of a `Vec<T>` or `Option<T>`.
+pub fn inner_type(ty: &Type) -> Option<&Type> {
+    match ty {
+        Type::Path(r#path) => match &r#path.path.segments.last()?.arguments {
+            syn::PathArguments::AngleBracketed(path_arg) => path_arg.args.iter().find_map(|arg| match arg {
+                syn::GenericArgument::Type(arg_type) => Some(arg_type),
+                _ => None,
+            }),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
 /// For fill, name, exist, select, numberic_scale
 pub fn extract_one_arg_annotion(
     val_name: &str,
@@ -513,7 +675,7 @@ pub fn extract_one_arg_annotion(
     }
 
     let extra = match name.as_ref() {
-        "fill" => FieldExtra::Fill { function: value.unwrap(), argument: None, mode: None },
+        "fill" => FieldExtra::Fill { function: value.unwrap(), context: None, argument: None, mode: None },
         "id_type" => FieldExtra::IdType(value.unwrap()),
         "select" => FieldExtra::Select(value.unwrap().parse::<bool>().unwrap_or(true)),
         "exist" => FieldExtra::Exist(value.unwrap().parse::<bool>().unwrap_or(true)),
@@ -530,6 +692,7 @@ pub fn extract_fill_custom(
     meta_items: &[syn::NestedMeta],
 ) -> FieldExtra {
     let mut function = None;
+    let mut context = None;
     let mut argument = None;
     let mut mode = None;
 
@@ -549,6 +712,12 @@ pub fn extract_fill_custom(
                                 None => error(lit.span(), "invalid argument type for `function` of `fill` annotion: expected a string")
                             };
                         }
+                        "context" => {
+                            context = match lit_to_string(lit) {
+                                Some(s) => Some(s),
+                                None => error(lit.span(), "invalid argument type for `context` of `fill` annotion: expected a string")
+                            };
+                        }
                         "mode" => {
                             mode = match lit_to_string(lit) {
                                 Some(s) => match s.as_ref() {
@@ -582,7 +751,7 @@ pub fn extract_fill_custom(
                             };
                         }
                         v => error(path.span(), &format!(
-                            "unknown argument `{}` for annotion `fill` (it only has `function`, `arg`)",
+                            "unknown argument `{}` for annotion `fill` (it only has `function`, `context`, `mode`, `arg`)",
                             v
                         )),
                     }
@@ -597,10 +766,10 @@ pub fn extract_fill_custom(
         }
     }
 
-    if function.is_none() {
-        error(attr.span(), "The annotion `custom` requires the `function` parameter.");
+    if function.is_none() == context.is_none() {
+        error(attr.span(), "The annotion `fill` requires either the `function` or the `context` parameter.");
     }
-    let extra = FieldExtra::Fill { function: function.unwrap(), argument, mode };
+    let extra = FieldExtra::Fill { function: function.unwrap_or_default(), context, argument, mode };
     extra
 }
 
@@ -774,6 +943,25 @@ pub(crate) fn is_fetch_array(return_source: &str) -> bool {
     return is_array;
 }
 
+/// The naming rules of `#[table(rename_all)]`.
+pub const RENAME_RULES: [&str; 4] = ["snake_case", "camelCase", "PascalCase", "SCREAMING_SNAKE"];
+
+/// The column of the field named by the rule, e.g. `created_at` is `createdAt` in `camelCase`.
+pub fn rename_field(name: &str, rule: &str) -> String {
+    let name = name.strip_prefix("r#").unwrap_or(name);
+    let capitalize = |word: &str| {
+        let mut chars = word.chars();
+        chars.next().map(|first| first.to_uppercase().chain(chars).collect::<String>()).unwrap_or_default()
+    };
+    match rule {
+        "camelCase" => name.split('_').filter(|word| !word.is_empty()).enumerate()
+            .map(|(i, word)| if i == 0 { word.to_string() } else { capitalize(word) }).collect(),
+        "PascalCase" => name.split('_').map(capitalize).collect(),
+        "SCREAMING_SNAKE" => to_snake_name(&name.to_string()).to_uppercase(),
+        _ => to_snake_name(&name.to_string()),
+    }
+}
+
 pub fn to_snake_name(name: &String) -> String {
     let chs = name.chars();
     let mut new_name = String::new();
@@ -824,6 +1012,17 @@ pub(crate) fn get_fn_args(target_fn: &ItemFn) -> Vec<Box<Pat>> {
     fn_arg_name_vec
 }
 
+/// the args taken as slices, like `rows: &[Row]`.
+pub(crate) fn get_slice_args(target_fn: &ItemFn) -> Vec<Box<Pat>> {
+    target_fn.sig.inputs.iter().filter_map(|arg| match arg {
+        FnArg::Typed(t) => match t.ty.as_ref() {
+            syn::Type::Reference(r) if matches!(r.elem.as_ref(), syn::Type::Slice(_)) => Some(t.pat.clone()),
+            _ => None,
+        },
+        _ => None,
+    }).collect()
+}
+
 pub(crate) fn filter_fn_args(
     target_fn: &ItemFn,
     arg_name: &str,
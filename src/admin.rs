@@ -0,0 +1,134 @@
+//!
+//! An admin console of a running akita, served by the http server of the application.
+//!
+
+use std::sync::Arc;
+use serde_json::json;
+
+use crate::{Akita, SensitiveColumns, SlowQueryInterceptor};
+use crate::bench::explain;
+
+/// A request to the admin console, as read by the http server of the application.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AdminRequest {
+    pub method: String,
+    pub path: String,
+}
+
+impl AdminRequest {
+    pub fn new<S: Into<String>>(method: S, path: S) -> Self {
+        AdminRequest { method: method.into(), path: path.into() }
+    }
+}
+
+/// The response of the admin console, a json body.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AdminResponse {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+impl AdminResponse {
+    fn json(status: u16, body: serde_json::Value) -> Self {
+        AdminResponse { status, content_type: "application/json", body: body.to_string() }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        Self::json(status, json!({ "error": message }))
+    }
+}
+
+/// The operational window of an akita, handling the requests routed to it by any http framework:
+///
+/// * `GET /pool` the connections of the pool and the counters of their acquisitions
+/// * `GET /slow-queries` the slow statements counted by fingerprint, with `set_slow_queries`
+/// * `GET /interceptors` the interceptors of the config, in the order they run
+/// * `POST /slow-queries/{id}/explain` the plan of the last slow statement of the fingerprint
+///
+/// Only the statements captured can be explained, no sql is taken from the requests.
+/// The console does no authentication, route to it behind the one of the application.
+pub struct AdminConsole {
+    akita: Akita,
+    slow_queries: Option<Arc<SlowQueryInterceptor>>,
+    base_path: String,
+}
+
+impl AdminConsole {
+    pub fn new(akita: &Akita) -> Self {
+        AdminConsole { akita: akita.duplicate(), slow_queries: None, base_path: String::new() }
+    }
+
+    /// The interceptor the slow statements are read from, the one added to the config.
+    pub fn set_slow_queries(mut self, slow_queries: Arc<SlowQueryInterceptor>) -> Self {
+        self.slow_queries = slow_queries.into();
+        self
+    }
+
+    /// The path the console is mounted on, e.g. `/admin/akita`, taken off the paths of the requests.
+    pub fn set_base_path<S: Into<String>>(mut self, base_path: S) -> Self {
+        self.base_path = base_path.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn handle(&self, request: &AdminRequest) -> AdminResponse {
+        let path = match request.path.split('?').next().unwrap_or_default().strip_prefix(self.base_path.as_str()) {
+            Some(path) => path.trim_matches('/'),
+            None => return AdminResponse::error(404, "not found"),
+        };
+        let segments = path.split('/').collect::<Vec<_>>();
+        let method = request.method.to_uppercase();
+        match (segments.as_slice(), method.as_str()) {
+            (["pool"], "GET") => self.pool(),
+            (["slow-queries"], "GET") => self.slow_queries(),
+            (["interceptors"], "GET") => AdminResponse::json(200, json!(self.akita.config().interceptors().names())),
+            (["slow-queries", id, "explain"], "POST") => self.explain(id),
+            (["pool"], _) | (["slow-queries"], _) | (["interceptors"], _) | (["slow-queries", _, "explain"], _) => AdminResponse::error(405, "method not allowed"),
+            _ => AdminResponse::error(404, "not found"),
+        }
+    }
+
+    fn pool(&self) -> AdminResponse {
+        match self.akita.pool_status() {
+            Ok(status) => AdminResponse::json(200, json!({
+                "size": status.size,
+                "max_size": status.max_size,
+                "idle": status.idle,
+                "in_use": status.in_use(),
+                "waiters": status.waiters,
+                "acquisitions": status.acquisitions,
+                "acquisition_failures": status.acquisition_failures,
+                "wait_histogram": status.wait_histogram.iter().map(|(bound, count)| json!({
+                    "le_micros": if *bound == std::time::Duration::MAX { None } else { Some(bound.as_micros() as u64) },
+                    "count": count,
+                })).collect::<Vec<_>>(),
+                "oldest_connection_age_secs": status.oldest_connection_age.map(|age| age.as_secs()),
+            })),
+            Err(err) => AdminResponse::error(500, &err.to_string()),
+        }
+    }
+
+    fn slow_queries(&self) -> AdminResponse {
+        let stats = self.slow_queries.as_ref().map(|slow_queries| slow_queries.stats()).unwrap_or_default();
+        AdminResponse::json(200, json!(stats.iter().map(|stats| json!({
+            "id": stats.id,
+            "fingerprint": stats.fingerprint,
+            "count": stats.count,
+            "total_micros": stats.total.as_micros() as u64,
+            "max_micros": stats.max.as_micros() as u64,
+            "sql": SensitiveColumns::global().mask_sql(&stats.sql),
+        })).collect::<Vec<_>>()))
+    }
+
+    fn explain(&self, id: &str) -> AdminResponse {
+        let stats = match self.slow_queries.as_ref().and_then(|slow_queries| slow_queries.stats_of(id)) {
+            Some(stats) => stats,
+            None => return AdminResponse::error(404, "no slow query with this id"),
+        };
+        match explain(&self.akita, &stats.sql, &stats.params) {
+            Some(plan) => AdminResponse::json(200, json!({ "id": stats.id, "fingerprint": stats.fingerprint, "plan": plan })),
+            None => AdminResponse::error(422, "the statement could not be explained"),
+        }
+    }
+}
+
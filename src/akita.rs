@@ -3,14 +3,28 @@
 //!
 
 use akita_core::{FieldType, GetTableName};
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use once_cell::sync::OnceCell;
 
 use crate::segment::ISegment;
-use crate::{AkitaError, AkitaMapper, IPage, Pool, Wrapper, database::DatabasePlatform, AkitaConfig};
-use crate::{cfg_if, Params, Rows, FromValue, Value, ToValue, GetFields};
-use crate::database::Platform;
-use crate::manager::{AkitaTransaction, build_insert_clause, build_update_clause};
-use crate::pool::{PlatformPool, PooledConnection};
+use crate::{AkitaError, AkitaMapper, ChunkProgress, ExecuteResult, IPage, TableChecksum, UpsertResult, Pool, Wrapper, database::DatabasePlatform, AkitaConfig, Idempotent};
+use crate::{cfg_if, Params, Rows, FromValue, Value, ToKey, ToValue, GetFields};
+use crate::database::{DatabaseConnection, Platform};
+use crate::mapper::refresh_all_with;
+use crate::pagination::PaginationOptions;
+use crate::logic_delete::{delete_clause, exclude_deleted, not_deleted_sql};
+use crate::set_operation::apply_unions;
+use crate::cte::with_clause;
+use crate::key::key_condition;
+use crate::fill::fill_fields;
+use crate::listener::{after_insert, after_remove, after_update, before_insert, before_remove, before_update, load};
+use crate::manager::{AkitaTransaction, TransactionOptions, begin_transaction_with, build_insert_clause, build_select_clause, build_update_clause, descendants_with, exec_batch_with, remove_by_ids_chunked_with, table_checksum_with, update_batch_by_id_with, update_by_id_with, upsert_returning, upsert_with};
+use crate::pool::{PlatformPool, PoolStatus, ReadConsistency, ReplicaRouter};
+#[cfg(any(feature = "akita-mysql", feature = "akita-sqlite"))]
+use crate::pool::{PoolMetrics, PooledConnection};
 
 cfg_if! {if #[cfg(feature = "akita-mysql")]{
     use crate::platform::{mysql::{self, MysqlDatabase}};
@@ -25,6 +39,25 @@ pub struct Akita{
     /// the connection pool
     pool: OnceCell<PlatformPool>,
     cfg: AkitaConfig,
+    /// the connection pinned by a transaction, all the statements will be sent on it
+    conn: Option<Arc<Mutex<DatabasePlatform>>>,
+    read_only: bool,
+    /// the nesting depth of the transactions on the pinned connection
+    depth: usize,
+    /// the replicas serving the reads out of a transaction
+    replicas: Option<Arc<ReplicaRouter>>,
+    /// whether the reads are sent to the primary too
+    force_master: bool,
+    /// the pools of the named datasources
+    datasources: Arc<HashMap<String, (PlatformPool, AkitaConfig)>>,
+    /// the name of the datasource of this akita, `None` for the default one
+    datasource: Option<String>,
+    /// the time of the last write of the session, shared by the akitas of the session
+    session: Option<Arc<Mutex<Option<Instant>>>>,
+    /// how the reads of the session follow its writes
+    consistency: ReadConsistency,
+    /// the session variables set on the pinned connection, reset when its transaction ends
+    variables: Option<Arc<Mutex<Vec<String>>>>,
 }
 
 #[allow(unused)]
@@ -32,20 +65,68 @@ impl Akita {
     
     pub fn new(cfg: AkitaConfig) -> Result<Self, AkitaError> {
         let platform = Self::init_pool(&cfg)?;
-        Ok(Self {
+        let replicas = if cfg.replicas().is_empty() {
+            None
+        } else {
+            let replica_cfg = match cfg.replica_policy() {
+                Some(policy) => cfg.clone().set_statement_policy(policy.clone()),
+                None => cfg.clone(),
+            };
+            let pools = cfg.replicas().iter()
+                .map(|url| Self::init_pool(&replica_cfg.clone().set_url(url.to_string())))
+                .collect::<Result<Vec<_>, _>>()?;
+            Some(Arc::new(ReplicaRouter::new(pools)))
+        };
+        let mut datasources = HashMap::new();
+        for (name, url) in cfg.datasources() {
+            let mut ds_cfg = cfg.clone().set_url(url.to_string()).set_replicas(Vec::new());
+            if let Some(policy) = cfg.datasource_policy(name) {
+                ds_cfg = ds_cfg.set_statement_policy(policy.clone());
+            }
+            datasources.insert(name.to_string(), (Self::init_pool(&ds_cfg)?, ds_cfg));
+        }
+        let akita = Self {
             pool: OnceCell::from(platform),
-            cfg
-        })
+            cfg,
+            conn: None,
+            read_only: false,
+            depth: 0,
+            replicas,
+            force_master: false,
+            datasources: Arc::new(datasources),
+            datasource: None,
+            session: None,
+            consistency: ReadConsistency::Eventual,
+            variables: None,
+        };
+        akita.sync_schema(akita.cfg.schema_sync())?;
+        Ok(akita)
     }
 
     pub fn from_pool(pool: &Pool) -> Result<Self, AkitaError> {
         let platform = pool.get_pool()?;
         Ok(Self {
             pool: OnceCell::from(platform),
-            cfg: pool.config().clone()
+            cfg: pool.config().clone(),
+            conn: None,
+            read_only: false,
+            depth: 0,
+            replicas: None,
+            force_master: false,
+            datasources: Arc::new(HashMap::new()),
+            datasource: None,
+            session: None,
+            consistency: ReadConsistency::Eventual,
+            variables: None,
         })
     }
 
+    /// Use the r2d2 pool the application already manages, built with `MysqlConnectionManager`
+    /// or `SqliteConnectionManager`, instead of creating a second one.
+    pub fn from_r2d2_pool<P: Into<PlatformPool>>(pool: P, cfg: AkitaConfig) -> Result<Self, AkitaError> {
+        Self::from_pool(&Pool::from_r2d2(pool, cfg))
+    }
+
     #[cfg(feature = "akita-fuse")]
     pub fn fuse(&self) -> crate::fuse::Fuse {
         crate::fuse::Fuse::new(self)
@@ -56,30 +137,164 @@ impl Akita {
         match cfg.platform() {
             #[cfg(feature = "akita-mysql")]
             Platform::Mysql => {
-                let pool_mysql = mysql::init_pool(&cfg)?;
-                Ok(PlatformPool::MysqlPool(pool_mysql))
+                let metrics = Arc::new(PoolMetrics::default());
+                let pool_mysql = mysql::init_pool(&cfg, &metrics)?;
+                Ok(PlatformPool::MysqlPool(pool_mysql, metrics))
             }
             #[cfg(feature = "akita-sqlite")]
             Platform::Sqlite(ref path) => {
                 let mut cfg = cfg.clone();
                 cfg = cfg.set_url(path.to_string());
-                let pool_sqlite = sqlite::init_pool(&cfg)?;
-                Ok(PlatformPool::SqlitePool(pool_sqlite))
+                let metrics = Arc::new(PoolMetrics::default());
+                let pool_sqlite = sqlite::init_pool(&cfg, &metrics)?;
+                Ok(PlatformPool::SqlitePool(pool_sqlite, metrics))
             }
             Platform::Unsupported(scheme) => Err(AkitaError::UnknownDatabase(scheme))
         }
     }
 
-    pub fn start_transaction(&self) -> Result<AkitaTransaction, AkitaError> {
-        let mut conn = self.acquire()?;
-        conn.start_transaction()?;
+    /// Start a transaction on a pinned connection, a transaction started inside another one
+    /// is emulated with a savepoint, so it can be rolled back alone.
+    pub fn start_transaction(&self) -> Result<AkitaTransaction<'_>, AkitaError> {
+        self.start_transaction_with(TransactionOptions::default())
+    }
+
+    /// Start a transaction with the isolation level and access mode,
+    /// they can only be set on the outermost transaction.
+    pub fn start_transaction_with(&self, options: TransactionOptions) -> Result<AkitaTransaction<'_>, AkitaError> {
+        let mut conn = self.pin()?;
+        conn.depth += 1;
+        let savepoint = if self.in_transaction() {
+            if options != TransactionOptions::default() {
+                return Err(AkitaError::UnsupportedOperation("the transaction options can not be changed within a transaction".to_string()));
+            }
+            let name = format!("akita_savepoint_{}", conn.depth);
+            conn.acquire()?.savepoint(&name)?;
+            Some(name)
+        } else {
+            begin_transaction_with(&mut *conn.acquire()?, &options)?;
+            conn.read_only = options.read_only;
+            None
+        };
         Ok(AkitaTransaction {
-            conn: &self,
+            conn,
+            savepoint,
+            options,
             committed: false,
             rolled_back: false,
+            _origin: PhantomData,
         })
     }
 
+    /// Run the closure with the MySQL driver connection, the one of the transaction if any,
+    /// to reach the driver APIs akita does not cover.
+    #[cfg(feature = "akita-mysql")]
+    pub fn with_raw_mysql<F, R>(&self, f: F) -> Result<R, AkitaError>
+        where
+            F: FnOnce(&mut ::mysql::Conn) -> Result<R, AkitaError>,
+    {
+        let mut conn = self.acquire()?;
+        #[allow(unreachable_patterns)]
+        match &mut *conn {
+            DatabasePlatform::Mysql(mysql) => f(mysql.raw()),
+            _ => Err(AkitaError::UnsupportedOperation("the datasource is not mysql".to_string())),
+        }
+    }
+
+    /// Run the closure with the SQLite driver connection, the one of the transaction if any,
+    /// to reach the driver APIs akita does not cover.
+    #[cfg(feature = "akita-sqlite")]
+    pub fn with_raw_sqlite<F, R>(&self, f: F) -> Result<R, AkitaError>
+        where
+            F: FnOnce(&mut rusqlite::Connection) -> Result<R, AkitaError>,
+    {
+        let mut conn = self.acquire()?;
+        #[allow(unreachable_patterns)]
+        match &mut *conn {
+            DatabasePlatform::Sqlite(sqlite) => f(sqlite.raw()),
+            _ => Err(AkitaError::UnsupportedOperation("the datasource is not sqlite".to_string())),
+        }
+    }
+
+    /// Guard the following write with an idempotency key, a retried write with the same key is skipped.
+    pub fn idempotent<S: Into<String>>(&self, key: S) -> Idempotent<'_> {
+        Idempotent::new(self, key)
+    }
+
+    /// Run the closure in a read-only transaction, any write statement inside will be refused by the database.
+    /// Nested calls reuse the read-only transaction already opened.
+    pub fn read_only<F, R>(&self, f: F) -> Result<R, AkitaError>
+        where
+            F: FnOnce(&Akita) -> Result<R, AkitaError>,
+    {
+        if self.read_only {
+            return f(self);
+        }
+        let in_transaction = self.in_transaction();
+        let mut ak = self.pin()?;
+        ak.read_only = true;
+        ak.depth += 1;
+        {
+            let mut conn = ak.acquire()?;
+            #[allow(unreachable_patterns)]
+            match *conn {
+                #[cfg(feature = "akita-mysql")]
+                DatabasePlatform::Mysql(_) if in_transaction => return Err(AkitaError::UnsupportedOperation("mysql can not turn a read-write transaction into read-only".to_string())),
+                #[cfg(feature = "akita-mysql")]
+                DatabasePlatform::Mysql(_) => conn.execute_drop("START TRANSACTION READ ONLY", Params::Nil)?,
+                _ => {
+                    conn.execute_drop("PRAGMA query_only = 1", Params::Nil)?;
+                    if !in_transaction {
+                        conn.start_transaction()?;
+                    }
+                }
+            }
+        }
+        let result = f(&ak);
+        let mut conn = ak.acquire()?;
+        #[allow(unreachable_patterns)]
+        match *conn {
+            #[cfg(feature = "akita-mysql")]
+            DatabasePlatform::Mysql(_) => conn.commit_transaction()?,
+            _ => {
+                if !in_transaction {
+                    conn.commit_transaction()?;
+                }
+                conn.execute_drop("PRAGMA query_only = 0", Params::Nil)?;
+            }
+        }
+        result
+    }
+
+    /// get an Akita with a connection pinned, or this one's if already pinned.
+    pub(crate) fn pin(&self) -> Result<Akita, AkitaError> {
+        if self.conn.is_some() {
+            return Ok(self.duplicate());
+        }
+        let conn = Arc::new(Mutex::new(self.acquire_pooled()?));
+        Ok(Akita { conn: Some(conn), variables: Some(Arc::new(Mutex::new(Vec::new()))), ..self.duplicate() })
+    }
+
+    /// whether this akita runs within a transaction, and not only on a pinned connection
+    /// as the one of a temporary table.
+    pub(crate) fn in_transaction(&self) -> bool {
+        self.depth > 0
+    }
+
+    /// the names of the session variables set on the pinned connection.
+    pub(crate) fn session_variables(&self) -> Option<&Arc<Mutex<Vec<String>>>> {
+        self.variables.as_ref()
+    }
+
+    pub fn config(&self) -> &AkitaConfig {
+        &self.cfg
+    }
+
+    /// The connections of the primary pool and the counters of its acquisitions.
+    pub fn pool_status(&self) -> Result<PoolStatus, AkitaError> {
+        Ok(self.get_pool()?.status())
+    }
+
     /// get conn pool
     pub fn get_pool(&self) -> Result<&PlatformPool, AkitaError> {
         let p = self.pool.get();
@@ -90,7 +305,167 @@ impl Akita {
     }
 
     /// get an DataBase Connection used for the next step
-    pub fn acquire(&self) -> Result<DatabasePlatform, AkitaError> {
+    pub fn acquire(&self) -> Result<DatabaseConnection<'_>, AkitaError> {
+        if let Some(session) = &self.session {
+            if let Ok(mut last_write) = session.lock() {
+                *last_write = Some(Instant::now());
+            }
+        }
+        self.acquire_primary()
+    }
+
+    /// get the pinned connection or one of the primary pool, without recording a write of the session.
+    fn acquire_primary(&self) -> Result<DatabaseConnection<'_>, AkitaError> {
+        match &self.conn {
+            Some(conn) => conn.try_lock()
+                .map(DatabaseConnection::Pinned)
+                .map_err(|_| AkitaError::R2D2Error("[akita] the transaction connection is already in use!".to_string())),
+            None => self.acquire_pooled().map(DatabaseConnection::Pooled),
+        }
+    }
+
+    /// get a connection for a read, from a replica unless pinned by a transaction or forced to the primary.
+    fn acquire_read(&self) -> Result<DatabaseConnection<'_>, AkitaError> {
+        if self.conn.is_none() && !self.force_master {
+            if let Some(pool) = self.replicas.as_ref().and_then(|replicas| replicas.pick()) {
+                if let Some(conn) = self.acquire_replica(pool)? {
+                    return Ok(DatabaseConnection::Pooled(conn));
+                }
+            }
+        }
+        self.acquire_primary()
+    }
+
+    /// get a connection on the replica, `None` when the read has to see a write of the session not applied there yet.
+    fn acquire_replica(&self, pool: &PlatformPool) -> Result<Option<DatabasePlatform>, AkitaError> {
+        let last_write = self.session.as_ref().and_then(|session| session.lock().ok().and_then(|last_write| *last_write));
+        let last_write = match last_write {
+            Some(last_write) => last_write,
+            None => return pool.database(&self.cfg).map(Some),
+        };
+        match self.consistency {
+            ReadConsistency::Eventual => pool.database(&self.cfg).map(Some),
+            ReadConsistency::ReadYourWrites(window) => {
+                if last_write.elapsed() < window {
+                    return Ok(None);
+                }
+                pool.database(&self.cfg).map(Some)
+            }
+            ReadConsistency::WaitReplica(timeout) => {
+                let mut replica = pool.database(&self.cfg)?;
+                if !self.wait_replica(&mut replica, last_write, timeout)? {
+                    return Ok(None);
+                }
+                if let Some(session) = &self.session {
+                    if let Ok(mut session_write) = session.lock() {
+                        if *session_write == Some(last_write) {
+                            *session_write = None;
+                        }
+                    }
+                }
+                Ok(Some(replica))
+            }
+        }
+    }
+
+    /// wait for the replica to apply the transactions executed on the primary.
+    fn wait_replica(&self, replica: &mut DatabasePlatform, last_write: Instant, timeout: Duration) -> Result<bool, AkitaError> {
+        #[allow(unreachable_patterns)]
+        match replica {
+            #[cfg(feature = "akita-mysql")]
+            DatabasePlatform::Mysql(_) => {
+                let executed = self.acquire_primary()?.execute_result("SELECT @@GLOBAL.gtid_executed", Params::Nil)?;
+                let gtid = executed.iter().next().map(|data| String::from_value(&data)).unwrap_or_default();
+                if gtid.is_empty() {
+                    return Ok(false);
+                }
+                let waited = replica.execute_result("SELECT WAIT_FOR_EXECUTED_GTID_SET(?, ?)", Params::Vector(vec![gtid.to_value(), timeout.as_secs().max(1).to_value()]))?;
+                Ok(waited.iter().next().map(|data| i64::from_value(&data)) == Some(0))
+            }
+            _ => Ok(last_write.elapsed() >= timeout),
+        }
+    }
+
+    /// get a connection for the sql, only plain selects are sent to the replicas.
+    fn acquire_for(&self, sql: &str) -> Result<DatabaseConnection<'_>, AkitaError> {
+        let lower = sql.trim_start().to_lowercase();
+        if lower.starts_with("select") && !lower.contains(" for update") && !lower.contains(" lock in share mode") && !lower.contains(" for share") {
+            self.acquire_read()
+        } else {
+            self.acquire()
+        }
+    }
+
+    /// An akita starting a session, its reads follow its own writes as the consistency tells
+    /// when they are routed to the replicas.
+    pub fn session(&self, consistency: ReadConsistency) -> Akita {
+        Akita { session: Some(Arc::new(Mutex::new(None))), consistency, ..self.duplicate() }
+    }
+
+    /// The same session with another consistency, for the following calls.
+    pub fn with_consistency(&self, consistency: ReadConsistency) -> Akita {
+        Akita { consistency, ..self.duplicate() }
+    }
+
+    /// An akita sending the reads to the primary as well, for the reads following a write.
+    pub fn force_master(&self) -> Akita {
+        Akita { force_master: true, ..self.duplicate() }
+    }
+
+    /// a copy of this akita, sharing its pools and pinned connection.
+    pub(crate) fn duplicate(&self) -> Akita {
+        Akita {
+            pool: self.pool.clone(),
+            cfg: self.cfg.clone(),
+            conn: self.conn.clone(),
+            read_only: self.read_only,
+            depth: self.depth,
+            replicas: self.replicas.clone(),
+            force_master: self.force_master,
+            datasources: self.datasources.clone(),
+            datasource: self.datasource.clone(),
+            session: self.session.clone(),
+            consistency: self.consistency,
+            variables: self.variables.clone(),
+        }
+    }
+
+    /// An akita sending its statements to the named datasource added with `AkitaConfig::add_datasource`.
+    pub fn use_ds(&self, name: &str) -> Result<Akita, AkitaError> {
+        if self.datasource.as_deref() == Some(name) {
+            return Ok(self.duplicate());
+        }
+        if self.conn.is_some() {
+            return Err(AkitaError::UnsupportedOperation(format!("can not switch to the datasource {} within a transaction", name)));
+        }
+        let (pool, cfg) = self.datasources.get(name)
+            .ok_or_else(|| AkitaError::DataError(format!("unknown datasource: {}", name)))?;
+        Ok(Akita {
+            pool: OnceCell::from(pool.clone()),
+            cfg: cfg.clone(),
+            conn: None,
+            read_only: false,
+            depth: 0,
+            replicas: None,
+            force_master: false,
+            datasources: self.datasources.clone(),
+            datasource: Some(name.to_string()),
+            session: self.session.clone(),
+            consistency: self.consistency,
+            variables: None,
+        })
+    }
+
+    /// the akita of the entity datasource when it is not this one.
+    pub(crate) fn entity_ds<T: GetTableName>(&self) -> Result<Option<Akita>, AkitaError> {
+        match T::datasource() {
+            Some(name) if self.datasource.as_deref() != Some(name) => self.use_ds(name).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// get a new DataBase Connection from the pool
+    fn acquire_pooled(&self) -> Result<DatabasePlatform, AkitaError> {
         let pool = self.get_pool()?;
         let conn = pool.acquire()?;
         match conn {
@@ -119,6 +494,9 @@ impl AkitaMapper for Akita {
             T: GetTableName + GetFields + FromValue,
 
     {
+        if let Some(akita) = self.entity_ds::<T>()? {
+            return akita.list::<T>(wrapper);
+        }
         let table = T::table_name();
         if table.complete_name().is_empty() {
             return Err(AkitaError::MissingTable("Find Error, Missing Table Name !".to_string()))
@@ -135,14 +513,17 @@ impl AkitaMapper for Akita {
         } else {
             select_fields
         };
+        exclude_deleted::<T>(&mut wrapper);
+        apply_unions::<T>(&mut wrapper, &enumerated_columns);
+        let with = with_clause(&mut wrapper)?;
         let where_condition = wrapper.get_sql_segment();
         let where_condition = if where_condition.trim().is_empty() { String::default() } else { format!("WHERE {}",where_condition) };
-        let sql = format!("SELECT {} FROM {} {}", &enumerated_columns, &table.complete_name(),where_condition);
-        let mut conn = self.acquire()?;
+        let sql = format!("{}SELECT {} FROM {} {}", with, &enumerated_columns, &table.complete_name(),where_condition);
+        let mut conn = self.acquire_read()?;
         let rows = conn.execute_result(&sql, Params::Nil)?;
         let mut entities = vec![];
         for data in rows.iter() {
-            let entity = T::from_value(&data);
+            let entity = load::<T>(data)?;
             entities.push(entity)
         }
         Ok(entities)
@@ -153,6 +534,9 @@ impl AkitaMapper for Akita {
         where
             T: GetTableName + GetFields + FromValue,
     {
+        if let Some(akita) = self.entity_ds::<T>()? {
+            return akita.select_one::<T>(wrapper);
+        }
         let table = T::table_name();
         if table.complete_name().is_empty() {
             return Err(AkitaError::MissingTable("Find Error, Missing Table Name !".to_string()))
@@ -169,20 +553,37 @@ impl AkitaMapper for Akita {
         } else {
             select_fields
         };
+        exclude_deleted::<T>(&mut wrapper);
+        apply_unions::<T>(&mut wrapper, &enumerated_columns);
+        let with = with_clause(&mut wrapper)?;
         let where_condition = wrapper.get_sql_segment();
         let where_condition = if where_condition.trim().is_empty() { String::default() } else { format!("WHERE {}",where_condition) };
-        let sql = format!("SELECT {} FROM {} {}", &enumerated_columns, &table.complete_name(), where_condition);
-        let mut conn = self.acquire()?;
+        let sql = format!("{}SELECT {} FROM {} {}", with, &enumerated_columns, &table.complete_name(), where_condition);
+        let mut conn = self.acquire_read()?;
         let rows = conn.execute_result(&sql, Params::Nil)?;
-        Ok(rows.iter().next().map(|data| T::from_value(&data)))
+        rows.iter().next().map(load::<T>).transpose()
     }
 
     /// Get one the table of records by id
+    fn refresh_all<T>(&self, entities: &mut [T]) -> Result<(), AkitaError>
+        where
+            T: GetTableName + GetFields + FromValue + ToValue,
+    {
+        if let Some(akita) = self.entity_ds::<T>()? {
+            return akita.refresh_all(entities);
+        }
+        // the rows were likely just written, the replicas may lag behind.
+        refresh_all_with(&self.force_master(), entities)
+    }
+
     fn select_by_id<T, I>(&self, id: I) -> Result<Option<T>, AkitaError>
         where
             T: GetTableName + GetFields + FromValue,
-            I: ToValue
+            I: ToKey
     {
+        if let Some(akita) = self.entity_ds::<T>()? {
+            return akita.select_by_id::<T, I>(id);
+        }
         let table = T::table_name();
         if table.complete_name().is_empty() {
             return Err(AkitaError::MissingTable("Find Error, Missing Table Name !".to_string()))
@@ -194,24 +595,11 @@ impl AkitaMapper for Akita {
             .map(|c| format!("`{}`", c.name))
             .collect::<Vec<_>>()
             .join(", ");
-        let mut conn = self.acquire()?;
-        if let Some(field) = columns.iter().find(| field| match field.field_type {
-            FieldType::TableId(_) => true,
-            FieldType::TableField => false,
-        }) {
-            let sql = match conn {
-                #[cfg(feature = "akita-mysql")]
-                DatabasePlatform::Mysql(_) => format!("SELECT {} FROM {} WHERE `{}` = ? limit 1", &enumerated_columns, &table.complete_name(), &field.name),
-                #[cfg(feature = "akita-sqlite")]
-                DatabasePlatform::Sqlite(_) => format!("SELECT {} FROM {} WHERE `{}` = ${} limit 1", &enumerated_columns, &table.complete_name(), &field.name, col_len + 1),
-                _ => format!("SELECT {} FROM {} WHERE `{}` = ${} limit 1", &enumerated_columns, &table.complete_name(), &field.name, col_len + 1),
-            };
-
-            let rows = conn.execute_result(&sql, (id.to_value(),).into())?;
-            Ok(rows.iter().next().map(|data| T::from_value(&data)))
-        } else {
-            Err(AkitaError::MissingIdent(format!("Table({}) Missing Ident...", &table.name)))
-        }
+        let mut conn = self.acquire_read()?;
+        let (condition, values) = key_condition::<T, I>(&conn, &id, col_len)?;
+        let sql = format!("SELECT {} FROM {} WHERE {}{} limit 1", &enumerated_columns, &table.complete_name(), condition, not_deleted_sql::<T>());
+        let rows = conn.execute_result(&sql, values.into())?;
+        rows.iter().next().map(load::<T>).transpose()
     }
 
     /// Get table of records with page
@@ -220,6 +608,9 @@ impl AkitaMapper for Akita {
             T: GetTableName + GetFields + FromValue,
 
     {
+        if let Some(akita) = self.entity_ds::<T>()? {
+            return akita.page::<T>(page, size, wrapper);
+        }
         let table = T::table_name();
         if table.complete_name().is_empty() {
             return Err(AkitaError::MissingTable("Find Error, Missing Table Name !".to_string()))
@@ -236,19 +627,22 @@ impl AkitaMapper for Akita {
         } else {
             select_fields
         };
+        exclude_deleted::<T>(&mut wrapper);
+        apply_unions::<T>(&mut wrapper, &enumerated_columns);
+        let with = with_clause(&mut wrapper)?;
         let where_condition = wrapper.get_sql_segment();
         let where_condition = if where_condition.trim().is_empty() { String::default() } else { format!("WHERE {}",where_condition) };
-        let mut sql = format!("SELECT {} FROM {} {}", &enumerated_columns, &table.complete_name(), where_condition);
-        let count_sql = format!("select count(*) from ({}) TOTAL", &sql);
+        let sql = format!("{}SELECT {} FROM {} {}", with, &enumerated_columns, &table.complete_name(), where_condition);
+        let count_sql = PaginationOptions::default().count_sql(&sql);
         let count: i64 = self.exec_first(&count_sql, ())?;
         let mut page = IPage::new(page, size ,count as usize, vec![]);
         if page.total > 0 {
-            let sql = format!("SELECT {} FROM {} {} limit {}, {}", &enumerated_columns, &table.complete_name(), where_condition,page.offset(),  page.size);
-            let mut conn = self.acquire()?;
+            let sql = format!("{}SELECT {} FROM {} {} limit {}, {}", with, &enumerated_columns, &table.complete_name(), where_condition,page.offset(),  page.size);
+            let mut conn = self.acquire_read()?;
             let rows = conn.execute_result(&sql, Params::Nil)?;
             let mut entities = vec![];
             for dao in rows.iter() {
-                let entity = T::from_value(&dao);
+                let entity = load::<T>(dao)?;
                 entities.push(entity)
             }
             page.records = entities;
@@ -261,14 +655,24 @@ impl AkitaMapper for Akita {
         where
             T: GetTableName + GetFields,
     {
+        if let Some(akita) = self.entity_ds::<T>()? {
+            return akita.count::<T>(wrapper);
+        }
         let table = T::table_name();
         if table.complete_name().is_empty() {
             return Err(AkitaError::MissingTable("Find Error, Missing Table Name !".to_string()))
         }
+        if wrapper.has_unions() {
+            let sql = format!("SELECT COUNT(1) AS count FROM ({}) TOTAL", build_select_clause::<T>(&mut wrapper)?);
+            return self.exec_first(&sql, ());
+        }
+        exclude_deleted::<T>(&mut wrapper);
+        let with = with_clause(&mut wrapper)?;
         let where_condition = wrapper.get_sql_segment();
         let where_condition = if where_condition.trim().is_empty() { String::default() } else { format!("WHERE {}",where_condition) };
         let sql = format!(
-            "SELECT COUNT(1) AS count FROM {} {}",
+            "{}SELECT COUNT(1) AS count FROM {} {}",
+            with,
             table.complete_name(),
             where_condition
         );
@@ -280,13 +684,17 @@ impl AkitaMapper for Akita {
         where
             T: GetTableName + GetFields,
     {
+        if let Some(akita) = self.entity_ds::<T>()? {
+            return akita.remove::<T>(wrapper);
+        }
         let table = T::table_name();
         if table.complete_name().is_empty() {
             return Err(AkitaError::MissingTable("Find Error, Missing Table Name !".to_string()))
         }
+        exclude_deleted::<T>(&mut wrapper);
         let where_condition = wrapper.get_sql_segment();
         let where_condition = if where_condition.trim().is_empty() { String::default() } else { format!("WHERE {}",where_condition) };
-        let sql = format!("delete from {} {}", &table.complete_name(), where_condition);
+        let sql = format!("{} {}", delete_clause::<T>(&table.complete_name()), where_condition);
         let mut conn = self.acquire()?;
         let _rows = conn.execute_result(&sql, Params::Nil)?;
         Ok(conn.affected_rows())
@@ -295,8 +703,11 @@ impl AkitaMapper for Akita {
     /// Remove the records by id.
     fn remove_by_id<T, I>(&self, id: I) -> Result<u64, AkitaError>
         where
-            I: ToValue,
+            I: ToKey,
             T: GetTableName + GetFields {
+        if let Some(akita) = self.entity_ds::<T>()? {
+            return akita.remove_by_id::<T, I>(id);
+        }
         let table = T::table_name();
         if table.complete_name().is_empty() {
             return Err(AkitaError::MissingTable("Find Error, Missing Table Name !".to_string()))
@@ -304,22 +715,12 @@ impl AkitaMapper for Akita {
         let cols = T::fields();
         let mut conn = self.acquire()?;
         let col_len = cols.len();
-        if let Some(field) = cols.iter().find(| field| match field.field_type {
-            FieldType::TableId(_) => true,
-            FieldType::TableField => false,
-        }) {
-            let sql = match conn {
-                #[cfg(feature = "akita-mysql")]
-                DatabasePlatform::Mysql(_) => format!("delete from {} where `{}` = ?", &table.name, &field.name),
-                #[cfg(feature = "akita-sqlite")]
-                DatabasePlatform::Sqlite(_) => format!("delete from {} where `{}` = ${}", &table.name, &field.name, col_len + 1),
-                _ => format!("delete from {} where `{}` = ${}", &table.name, &field.name, col_len + 1),
-            };
-            let _rows = conn.execute_result(&sql, (id.to_value(),).into())?;
-            Ok(conn.affected_rows())
-        } else {
-            Err(AkitaError::MissingIdent(format!("Table({}) Missing Ident...", &table.name)))
-        }
+        let (condition, values) = key_condition::<T, I>(&conn, &id, col_len)?;
+        before_remove::<T>(&values)?;
+        let sql = format!("{} where {}", delete_clause::<T>(&table.name), condition);
+        let _rows = conn.execute_result(&sql, values.clone().into())?;
+        after_remove::<T>(&values);
+        Ok(conn.affected_rows())
     }
 
 
@@ -328,6 +729,9 @@ impl AkitaMapper for Akita {
         where
             I: ToValue,
             T: GetTableName + GetFields {
+        if let Some(akita) = self.entity_ds::<T>()? {
+            return akita.remove_by_ids::<T, I>(ids);
+        }
         let table = T::table_name();
         if table.complete_name().is_empty() {
             return Err(AkitaError::MissingTable("Find Error, Missing Table Name !".to_string()))
@@ -339,12 +743,12 @@ impl AkitaMapper for Akita {
             FieldType::TableId(_) => true,
             FieldType::TableField => false,
         }) {
-            let sql = match conn {
+            let sql = match *conn {
                 #[cfg(feature = "akita-mysql")]
-                DatabasePlatform::Mysql(_) => format!("delete from {} where `{}` in (?)", &table.name, &field.name),
+                DatabasePlatform::Mysql(_) => format!("{} where `{}` in (?)", delete_clause::<T>(&table.name), &field.name),
                 #[cfg(feature = "akita-sqlite")]
-                DatabasePlatform::Sqlite(_) => format!("delete from {} where `{}` in (${})", &table.name, &field.name, col_len + 1),
-                _ => format!("delete from {} where `{}` = ${}", &table.name, &field.name, col_len + 1),
+                DatabasePlatform::Sqlite(_) => format!("{} where `{}` in (${})", delete_clause::<T>(&table.name), &field.name, col_len + 1),
+                _ => format!("{} where `{}` = ${}", delete_clause::<T>(&table.name), &field.name, col_len + 1),
             };
             let ids = ids.iter().map(|v| v.to_value().to_string()).collect::<Vec<String>>().join(",");
             let _rows = conn.execute_result(&sql, (ids,).into())?;
@@ -359,12 +763,15 @@ impl AkitaMapper for Akita {
     fn update<T>(&self, entity: &T, mut wrapper: Wrapper) -> Result<u64, AkitaError>
         where
             T: GetTableName + GetFields + ToValue {
+        if let Some(akita) = self.entity_ds::<T>()? {
+            return akita.update::<T>(entity, wrapper);
+        }
         let table = T::table_name();
         if table.complete_name().is_empty() {
             return Err(AkitaError::MissingTable("Find Error, Missing Table Name !".to_string()))
         }
         let mut conn = self.acquire()?;
-        let columns = T::fields();
+        let columns = fill_fields::<T>(conn.config());
         let mut sql = build_update_clause(&conn, entity, &mut wrapper);
         let update_fields = wrapper.fields_set.to_owned();
         let is_set = wrapper.get_set_sql().is_none();
@@ -373,7 +780,8 @@ impl AkitaMapper for Akita {
         }
         let _bvalues: Vec<&Value> = Vec::new();
         if update_fields.is_empty() && is_set {
-            let data = entity.to_value();
+            let mut data = entity.to_value();
+            before_update::<T>(&mut data)?;
             let mut values: Vec<Value> = Vec::with_capacity(columns.len());
             for col in columns.iter() {
                 if !col.exist || col.field_type.ne(&FieldType::TableField) {
@@ -399,6 +807,7 @@ impl AkitaMapper for Akita {
             }
 
             let _rows = conn.execute_result(&sql, values.into())?;
+            after_update::<T>(&data);
         } else {
             let _rows = conn.execute_result(&sql, Params::Nil)?;
         }
@@ -409,76 +818,60 @@ impl AkitaMapper for Akita {
     fn update_by_id<T>(&self, entity: &T) -> Result<u64, AkitaError>
         where
             T: GetTableName + GetFields + ToValue {
-        let table = T::table_name();
-        if table.complete_name().is_empty() {
-            return Err(AkitaError::MissingTable("Find Error, Missing Table Name !".to_string()))
+        if let Some(akita) = self.entity_ds::<T>()? {
+            return akita.update_by_id::<T>(entity);
         }
-        let data = entity.to_value();
-        let columns = T::fields();
-        let col_len = columns.len();
         let mut conn = self.acquire()?;
-        if let Some(field) = T::fields().iter().find(| field| match field.field_type {
-            FieldType::TableId(_) => true,
-            FieldType::TableField => false,
-        }) {
-            let set_fields = columns
-                .iter().filter(|col| col.exist && col.field_type == FieldType::TableField)
-                .enumerate()
-                .map(|(x, col)| {
-                    #[allow(unreachable_patterns)]
-                    match conn {
-                        #[cfg(feature = "akita-mysql")]
-                        DatabasePlatform::Mysql(_) => format!("`{}` = ?", &col.name),
-                        #[cfg(feature = "akita-sqlite")]
-                        DatabasePlatform::Sqlite(_) => format!("`{}` = ${}",&col.name, x + 1),
-                        _ => format!("`{}` = ${}", &col.name, x + 1),
-                    }
-                })
-                .collect::<Vec<_>>()
-                .join(", ");
-            let sql = match conn {
-                #[cfg(feature = "akita-mysql")]
-                DatabasePlatform::Mysql(_) => format!("update {} set {} where `{}` = ?", &table.name, &set_fields, &field.name),
-                #[cfg(feature = "akita-sqlite")]
-                DatabasePlatform::Sqlite(_) => format!("update {} set {} where `{}` = ${}", &table.name, &set_fields, &field.name, col_len + 1),
-                _ => format!("update {} set {} where `{}` = ${}", &table.name, &set_fields, &field.name, col_len + 1),
-            };
-            let mut values: Vec<Value> = Vec::with_capacity(columns.len());
-            let id = data.get_obj_value(&field.name);
-            for col in columns.iter() {
-                if !col.exist || col.field_type.ne(&FieldType::TableField) {
-                    continue;
-                }
-                let col_name = &col.name;
-                let mut value = data.get_obj_value(col_name);
-                match &col.fill {
-                    None => {}
-                    Some(v) => {
-                        match v.mode.as_ref() {
-                            "update" | "default" => {
-                                value = v.value.as_ref();
-                            }
-                            _=> {}
-                        }
-                    }
-                }
-                match value {
-                    Some(value) => values.push(value.clone()),
-                    None => values.push(Value::Nil),
-                }
-            }
-            match id {
-                Some(id) => values.push(id.clone()),
-                None => {
-                    return Err(AkitaError::MissingIdent(format!("Table({}) Missing Ident value...", &table.name)));
-                }
-            }
-            let _ = conn.execute_result(&sql, values.into())?;
-            Ok(conn.affected_rows())
-        } else {
-            Err(AkitaError::MissingIdent(format!("Table({}) Missing Ident...", &table.name)))
+        update_by_id_with(&mut conn, entity)
+    }
+
+    /// Update the records by id, all in one transaction.
+    fn update_batch_by_id<T>(&self, entities: &[&T]) -> Result<u64, AkitaError>
+        where
+            T: GetTableName + GetFields + ToValue {
+        if let Some(akita) = self.entity_ds::<T>()? {
+            return akita.update_batch_by_id::<T>(entities);
         }
+        let mut conn = self.acquire()?;
+        let in_transaction = self.in_transaction();
+        update_batch_by_id_with(&mut conn, in_transaction, entities)
+    }
 
+    /// Remove the records by ids in chunks, each chunk in its own transaction.
+    fn remove_by_ids_chunked<T, I, F>(&self, ids: Vec<I>, chunk_size: usize, pause: Option<Duration>, on_progress: F) -> Result<u64, AkitaError>
+        where
+            I: ToValue,
+            T: GetTableName + GetFields,
+            F: FnMut(&ChunkProgress) {
+        if let Some(akita) = self.entity_ds::<T>()? {
+            return akita.remove_by_ids_chunked::<T, I, F>(ids, chunk_size, pause, on_progress);
+        }
+        let mut conn = self.acquire()?;
+        let in_transaction = self.in_transaction();
+        remove_by_ids_chunked_with::<T, I, F>(&mut conn, in_transaction, ids, chunk_size, pause, on_progress)
+    }
+
+    /// Get all the descendants of the record.
+    fn descendants_of<T, I>(&self, id: I) -> Result<Vec<T>, AkitaError>
+        where
+            T: GetTableName + GetFields + FromValue,
+            I: ToValue {
+        if let Some(akita) = self.entity_ds::<T>()? {
+            return akita.descendants_of::<T, I>(id);
+        }
+        let mut conn = self.acquire_read()?;
+        descendants_with(&mut conn, id.to_value())
+    }
+
+    /// Get the checksum of the records.
+    fn table_checksum<T>(&self, mut wrapper: Wrapper) -> Result<TableChecksum, AkitaError>
+        where
+            T: GetTableName + GetFields {
+        if let Some(akita) = self.entity_ds::<T>()? {
+            return akita.table_checksum::<T>(wrapper);
+        }
+        let mut conn = self.acquire_read()?;
+        table_checksum_with::<T>(&mut conn, &mut wrapper)
     }
 
     #[allow(unused_variables)]
@@ -486,14 +879,22 @@ impl AkitaMapper for Akita {
         where
             T: GetTableName + GetFields + ToValue
     {
-        let columns = T::fields();
+        if let Some(akita) = self.entity_ds::<T>()? {
+            return akita.save_batch::<T>(entities);
+        }
         let mut conn = self.acquire()?;
+        let columns = fill_fields::<T>(conn.config());
         let sql = build_insert_clause(&conn, entities);
 
         let mut values: Vec<Value> = Vec::with_capacity(entities.len() * columns.len());
+        let mut records = Vec::with_capacity(entities.len());
         for entity in entities.iter() {
+            let mut data = entity.to_value();
+            before_insert::<T>(&mut data)?;
             for col in columns.iter() {
-                let data = entity.to_value();
+                if !col.exist {
+                    continue;
+                }
                 let mut value = data.get_obj_value(&col.name);
                 match &col.fill {
                     None => {}
@@ -507,13 +908,15 @@ impl AkitaMapper for Akita {
                     }
                 }
                 match value {
-                    Some(value) => values.push(value.clone()),
-                    None => values.push(Value::Nil),
+                    Some(value) if *value != Value::Nil => values.push(value.clone()),
+                    _ => values.push(col.field_type.generate_id().unwrap_or(Value::Nil)),
                 }
             }
+            records.push(data);
         }
         let bvalues: Vec<&Value> = values.iter().collect();
         conn.execute_result(&sql,values.into())?;
+        records.iter().for_each(after_insert::<T>);
         Ok(())
     }
 
@@ -523,12 +926,20 @@ impl AkitaMapper for Akita {
             T: GetTableName + GetFields + ToValue,
             I: FromValue,
     {
-        let columns = T::fields();
+        if let Some(akita) = self.entity_ds::<T>()? {
+            return akita.save::<T, I>(entity);
+        }
         let mut conn = self.acquire()?;
+        let columns = fill_fields::<T>(conn.config());
         let sql = build_insert_clause(&conn, &[entity]);
-        let data = entity.to_value();
+        let mut data = entity.to_value();
+        before_insert::<T>(&mut data)?;
         let mut values: Vec<Value> = Vec::with_capacity(columns.len());
+        let mut generated_id = None;
         for col in columns.iter() {
+            if !col.exist {
+                continue;
+            }
             let mut value = data.get_obj_value(&col.name);
             match &col.fill {
                 None => {}
@@ -542,14 +953,24 @@ impl AkitaMapper for Akita {
                 }
             }
             match value {
-                Some(value) => values.push(value.clone()),
-                None => values.push(Value::Nil),
+                Some(value) if *value != Value::Nil => values.push(value.clone()),
+                _ => {
+                    let id = col.field_type.generate_id();
+                    if id.is_some() {
+                        generated_id = id.clone();
+                    }
+                    values.push(id.unwrap_or(Value::Nil));
+                }
             }
         }
         let _bvalues: Vec<&Value> = values.iter().collect();
 
         conn.execute_result(&sql,values.into())?;
-        let _rows: Rows = match conn {
+        after_insert::<T>(&data);
+        if let Some(id) = generated_id {
+            return Ok(Some(I::from_value_opt(&id)?));
+        }
+        let _rows: Rows = match *conn {
             #[cfg(feature = "akita-mysql")]
             DatabasePlatform::Mysql(_) => conn.execute_result("SELECT LAST_INSERT_ID();", Params::Nil)?,
             #[cfg(feature = "akita-sqlite")]
@@ -565,6 +986,9 @@ impl AkitaMapper for Akita {
         where
             T: GetTableName + GetFields + ToValue,
             I: FromValue {
+        if let Some(akita) = self.entity_ds::<T>()? {
+            return akita.save_or_update::<T, I>(entity);
+        }
         let data = entity.to_value();
         let id = if let Some(field) = T::fields().iter().find(| field| match field.field_type {
             FieldType::TableId(_) => true,
@@ -583,12 +1007,64 @@ impl AkitaMapper for Akita {
         }
     }
 
-    fn exec_iter<S: Into<String>, P: Into<Params>>(&self, sql: S, params: P) -> Result<Rows, AkitaError> {
+    /// insert or update on the table key
+    fn upsert<T>(&self, entity: &T) -> Result<u64, AkitaError>
+        where
+            T: GetTableName + GetFields + ToValue {
+        if let Some(akita) = self.entity_ds::<T>()? {
+            return akita.upsert::<T>(entity);
+        }
+        let mut conn = self.acquire()?;
+        upsert_with(&mut conn, entity)
+    }
+
+    fn save_or_update_returning<T>(&self, entity: &T) -> Result<UpsertResult<T>, AkitaError>
+        where
+            T: GetTableName + GetFields + ToValue + FromValue {
+        if let Some(akita) = self.entity_ds::<T>()? {
+            return akita.save_or_update_returning::<T>(entity);
+        }
         let mut conn = self.acquire()?;
-        let rows = conn.execute_result(&sql.into(), params.into())?;
+        let in_transaction = self.in_transaction();
+        upsert_returning(&mut conn, in_transaction, entity)
+    }
+
+    fn exec_iter<S: Into<String>, P: Into<Params>>(&self, sql: S, params: P) -> Result<Rows, AkitaError> {
+        let sql: String = sql.into();
+        let mut conn = self.acquire_for(&sql)?;
+        let rows = conn.execute_result(&sql, params.into())?;
         Ok(rows)
     }
 
+    fn exec_batch<S: Into<String>, P: Into<Params>>(&self, sql: S, params: Vec<P>) -> Result<u64, AkitaError> {
+        let sql: String = sql.into();
+        let mut conn = self.acquire()?;
+        let in_transaction = self.in_transaction();
+        let affected = exec_batch_with(&mut conn, in_transaction, &sql, params.into_iter().map(Into::into).collect())?;
+        Ok(affected)
+    }
+
+    fn exec_result<S: Into<String>, P: Into<Params>>(&self, sql: S, params: P) -> Result<ExecuteResult, AkitaError> {
+        let sql: String = sql.into();
+        let mut conn = self.acquire()?;
+        conn.execute_drop(&sql, params.into())?;
+        ExecuteResult::of(&mut conn)
+    }
+
+    fn exec_stream<R, S: Into<String>, P: Into<Params>, F>(&self, sql: S, params: P, mut f: F) -> Result<(), AkitaError>
+        where
+            R: FromValue,
+            F: FnMut(R) -> Result<(), AkitaError>,
+    {
+        let sql: String = sql.into();
+        let mut conn = self.acquire_for(&sql)?;
+        conn.execute_stream(&sql, params.into(), &mut |row| f(load::<R>(row.to_value())?))
+    }
+
+    fn fetch_size(&self) -> usize {
+        self.cfg.fetch_size()
+    }
+
 }
 
 #[allow(unused)]
@@ -0,0 +1,264 @@
+//!
+//! Audit log of the statements, written to a table in the background.
+//!
+
+use std::collections::HashMap;
+use std::hash::Hasher;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::time::{Duration, Instant};
+use chrono::NaiveDateTime;
+use once_cell::sync::OnceCell;
+
+use crate::{Akita, AkitaError, AkitaMapper, Local, Params, ToValue, Value};
+use crate::cache::{read_tables, write_tables};
+use crate::interceptor::{ExecutedStatement, Interceptor, OperationType};
+
+/// The table the audit records are written to unless set with `AuditInterceptor::set_table`.
+pub const AUDIT_TABLE: &str = "akita_audit_log";
+
+type UserProvider = Box<dyn Fn() -> Option<String> + Send + Sync>;
+
+/// An audited statement.
+#[derive(Clone, Debug)]
+pub struct AuditRecord {
+    pub operation: OperationType,
+    pub table: String,
+    /// the hash of the sql, the same for all the runs of a statement whatever its params
+    pub sql_digest: String,
+    pub affected_rows: Option<u64>,
+    pub user: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+/// Records who changed what and when in the `akita_audit_log` table, created on the first write.
+/// Add it with `AkitaConfig::add_interceptor`.
+///
+/// The records are written by a background thread through the akita given, in batches,
+/// the statements only wait for them to be queued. The records of a transaction are queued once it is committed,
+/// and dropped on a rollback. The statements on the audit table itself are never audited.
+pub struct AuditInterceptor {
+    akita: Mutex<Option<Akita>>,
+    table: String,
+    operations: Vec<OperationType>,
+    user: Option<UserProvider>,
+    batch_size: usize,
+    flush_interval: Duration,
+    capacity: usize,
+    sender: OnceCell<SyncSender<AuditRecord>>,
+    /// the records of the transactions in progress
+    pending: Mutex<HashMap<u64, Vec<AuditRecord>>>,
+    dropped: AtomicU64,
+}
+
+impl AuditInterceptor {
+    /// Write the records with the akita, built from a config without this interceptor.
+    /// The inserts, updates and deletes are audited, in batches of 100 records or every second.
+    pub fn new(akita: Akita) -> Self {
+        AuditInterceptor {
+            akita: Mutex::new(Some(akita)),
+            table: AUDIT_TABLE.to_string(),
+            operations: vec![OperationType::Insert, OperationType::Update, OperationType::Delete],
+            user: None,
+            batch_size: 100,
+            flush_interval: Duration::from_secs(1),
+            capacity: 10_000,
+            sender: OnceCell::new(),
+            pending: Mutex::new(HashMap::new()),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    pub fn set_table<S: Into<String>>(mut self, table: S) -> Self {
+        self.table = table.into();
+        self
+    }
+
+    /// The audited operations.
+    pub fn set_operations(mut self, operations: Vec<OperationType>) -> Self {
+        self.operations = operations;
+        self
+    }
+
+    /// The user of the application running the statement, e.g. read from the request context.
+    pub fn set_user_provider<F>(mut self, user: F) -> Self
+        where
+            F: Fn() -> Option<String> + Send + Sync + 'static,
+    {
+        self.user = Some(Box::new(user));
+        self
+    }
+
+    /// The records written at once, and the longest a record waits for its batch.
+    pub fn set_batch(mut self, batch_size: usize, flush_interval: Duration) -> Self {
+        self.batch_size = batch_size.max(1);
+        self.flush_interval = flush_interval;
+        self
+    }
+
+    /// The records waiting to be written, the next ones are dropped when the database can't keep up. 10000 by default.
+    pub fn set_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// The records dropped so far because the queue was full.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    fn queue(&self, records: Vec<AuditRecord>) {
+        let sender = self.sender.get_or_init(|| {
+            let (sender, receiver) = mpsc::sync_channel(self.capacity);
+            if let Some(akita) = self.akita.lock().ok().and_then(|mut akita| akita.take()) {
+                let writer = AuditWriter { akita, table: self.table.to_owned(), batch_size: self.batch_size, flush_interval: self.flush_interval, created: false };
+                std::thread::spawn(move || writer.run(receiver));
+            }
+            sender
+        });
+        for record in records {
+            if let Err(TrySendError::Full(_) | TrySendError::Disconnected(_)) = sender.try_send(record) {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn is_audit_table(&self, table: &str) -> bool {
+        let table = table.rsplit('.').next().unwrap_or_default();
+        table.eq_ignore_ascii_case(&self.table)
+    }
+}
+
+impl Interceptor for AuditInterceptor {
+    fn after_execute(&self, statement: &ExecutedStatement) {
+        let operation = statement.operation();
+        if statement.error.is_some() || !self.operations.contains(&operation) {
+            return;
+        }
+        let tables = write_tables(statement.sql).into_iter().chain(read_tables(statement.sql)).collect::<Vec<_>>();
+        if tables.iter().any(|table| self.is_audit_table(table)) {
+            return;
+        }
+        let record = AuditRecord {
+            operation,
+            table: tables.into_iter().next().unwrap_or_default(),
+            sql_digest: sql_digest(statement.sql),
+            affected_rows: statement.affected_rows,
+            user: self.user.as_ref().and_then(|user| user()),
+            created_at: Local::now().naive_local(),
+        };
+        match statement.transaction_id {
+            Some(transaction_id) => {
+                if let Ok(mut pending) = self.pending.lock() {
+                    pending.entry(transaction_id).or_default().push(record);
+                }
+            }
+            None => self.queue(vec![record]),
+        }
+    }
+
+    fn on_transaction_commit(&self, transaction_id: u64) {
+        let records = self.pending.lock().ok().and_then(|mut pending| pending.remove(&transaction_id));
+        if let Some(records) = records {
+            self.queue(records);
+        }
+    }
+
+    fn on_transaction_rollback(&self, transaction_id: u64) {
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.remove(&transaction_id);
+        }
+    }
+}
+
+struct AuditWriter {
+    akita: Akita,
+    table: String,
+    batch_size: usize,
+    flush_interval: Duration,
+    created: bool,
+}
+
+impl AuditWriter {
+    /// write the records as they come until the interceptor is dropped.
+    fn run(mut self, receiver: Receiver<AuditRecord>) {
+        let mut batch = Vec::with_capacity(self.batch_size);
+        let mut deadline = Instant::now() + self.flush_interval;
+        loop {
+            match receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(record) => {
+                    batch.push(record);
+                    if batch.len() < self.batch_size {
+                        continue;
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    let _ = self.write(&batch);
+                    return;
+                }
+            }
+            // the records are dropped on an error rather than piling up.
+            let _ = self.write(&batch);
+            batch.clear();
+            deadline = Instant::now() + self.flush_interval;
+        }
+    }
+
+    fn write(&mut self, records: &[AuditRecord]) -> Result<(), AkitaError> {
+        if records.is_empty() {
+            return Ok(());
+        }
+        if !self.created {
+            self.create_table()?;
+            self.created = true;
+        }
+        let values = records.iter().map(|_| "(?, ?, ?, ?, ?, ?)").collect::<Vec<_>>().join(", ");
+        let params = records.iter().flat_map(|record| vec![
+            Value::Text(format!("{:?}", record.operation).to_uppercase()),
+            record.table.to_value(),
+            record.sql_digest.to_value(),
+            record.affected_rows.to_value(),
+            record.user.to_value(),
+            record.created_at.to_value(),
+        ]).collect::<Vec<_>>();
+        self.akita.exec_drop(format!("INSERT INTO {} (operation, table_name, sql_digest, affected_rows, user_name, created_at) VALUES {}", &self.table, values), Params::Vector(params))
+    }
+
+    fn create_table(&self) -> Result<(), AkitaError> {
+        let conn = self.akita.acquire()?;
+        #[allow(unreachable_patterns)]
+        let id = match *conn {
+            #[cfg(feature = "akita-mysql")]
+            crate::database::DatabasePlatform::Mysql(_) => "BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY",
+            _ => "INTEGER PRIMARY KEY AUTOINCREMENT",
+        };
+        drop(conn);
+        self.akita.exec_drop(format!("CREATE TABLE IF NOT EXISTS {} (id {}, operation VARCHAR(16) NOT NULL, table_name VARCHAR(255) NOT NULL, sql_digest CHAR(16) NOT NULL, affected_rows BIGINT, user_name VARCHAR(255), created_at DATETIME NOT NULL)", &self.table, id), Params::Nil)
+    }
+}
+
+/// the hash of the sql with its whitespace collapsed.
+pub(crate) fn sql_digest(sql: &str) -> String {
+    let mut hasher = twox_hash::XxHash64::with_seed(0);
+    for (i, token) in sql.split_whitespace().enumerate() {
+        if i > 0 {
+            hasher.write(b" ");
+        }
+        hasher.write(token.as_bytes());
+    }
+    format!("{:016x}", hasher.finish())
+}
+
+#[cfg(test)]
+mod test {
+    use super::sql_digest;
+
+    #[test]
+    fn digest_ignores_whitespace() {
+        assert_eq!(sql_digest("UPDATE t_user SET name = ?  WHERE id = ?"), sql_digest("UPDATE t_user\n  SET name = ? WHERE id = ?"));
+        assert_ne!(sql_digest("DELETE FROM t_user"), sql_digest("DELETE FROM t_order"));
+        assert_eq!(sql_digest("SELECT 1").len(), 16);
+    }
+}
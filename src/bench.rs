@@ -0,0 +1,177 @@
+//!
+//! Query benchmarks, with the latency percentiles and the stability of the plans.
+//!
+
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+
+use crate::{Akita, AkitaError, AkitaMapper, GetFields, GetTableName, Params, Rows, Wrapper};
+use crate::manager::build_select_clause;
+
+/// The columns of the plans changing with the table statistics rather than with the plan itself.
+const PLAN_ESTIMATES: [&str; 3] = ["rows", "filtered", "cost"];
+
+struct BenchQuery {
+    name: String,
+    sql: String,
+    params: Params,
+}
+
+/// A set of queries run against a database to measure them, the report is meant for the CI.
+pub struct Bench {
+    queries: Vec<BenchQuery>,
+    iterations: usize,
+    warmup: usize,
+}
+
+/// The measures of a query, in microseconds.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct QueryReport {
+    pub name: String,
+    pub sql: String,
+    pub iterations: usize,
+    pub errors: usize,
+    pub min_micros: u64,
+    pub mean_micros: u64,
+    pub p50_micros: u64,
+    pub p90_micros: u64,
+    pub p99_micros: u64,
+    pub max_micros: u64,
+    /// the plan explained before the runs, none when the database couldn't explain it
+    pub plan: Option<String>,
+    /// whether the plan explained after the runs is the same
+    pub plan_stable: bool,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct BenchReport {
+    pub queries: Vec<QueryReport>,
+}
+
+impl BenchReport {
+    pub fn to_json(&self) -> Result<String, AkitaError> {
+        serde_json::to_string_pretty(self).map_err(|e| AkitaError::DataError(e.to_string()))
+    }
+}
+
+impl Default for Bench {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Bench {
+    pub fn new() -> Self {
+        Bench { queries: Vec::new(), iterations: 100, warmup: 5 }
+    }
+
+    /// The measured runs of each query, 100 by default.
+    pub fn set_iterations(mut self, iterations: usize) -> Self {
+        self.iterations = iterations.max(1);
+        self
+    }
+
+    /// The runs of each query before the measured ones, 5 by default.
+    pub fn set_warmup(mut self, warmup: usize) -> Self {
+        self.warmup = warmup;
+        self
+    }
+
+    /// Add a raw query.
+    pub fn add<S: Into<String>, P: Into<Params>>(mut self, name: S, sql: S, params: P) -> Self {
+        self.queries.push(BenchQuery { name: name.into(), sql: sql.into(), params: params.into() });
+        self
+    }
+
+    /// Add the select built for the entity with the wrapper, as `list` would send it.
+    pub fn add_wrapper<T, S: Into<String>>(mut self, name: S, mut wrapper: Wrapper) -> Result<Self, AkitaError>
+        where
+            T: GetTableName + GetFields,
+    {
+        let sql = build_select_clause::<T>(&mut wrapper)?;
+        self.queries.push(BenchQuery { name: name.into(), sql, params: Params::Nil });
+        Ok(self)
+    }
+
+    /// Run the queries one after the other, a failing run is counted and left out of the latencies.
+    pub fn run(&self, akita: &Akita) -> Result<BenchReport, AkitaError> {
+        let mut report = BenchReport::default();
+        for query in self.queries.iter() {
+            let plan = explain(akita, &query.sql, &query.params);
+            for _ in 0..self.warmup {
+                let _ = akita.exec_iter(&query.sql, query.params.clone());
+            }
+            let mut latencies = Vec::with_capacity(self.iterations);
+            let mut errors = 0;
+            for _ in 0..self.iterations {
+                let start = Instant::now();
+                match akita.exec_iter(&query.sql, query.params.clone()) {
+                    Ok(_) => latencies.push(start.elapsed()),
+                    Err(_) => errors += 1,
+                }
+            }
+            latencies.sort();
+            let micros = |latency: Option<&Duration>| latency.map(|latency| latency.as_micros() as u64).unwrap_or_default();
+            let mean = if latencies.is_empty() { Duration::default() } else { latencies.iter().sum::<Duration>() / latencies.len() as u32 };
+            let plan_stable = plan.is_some() && plan == explain(akita, &query.sql, &query.params);
+            report.queries.push(QueryReport {
+                name: query.name.to_owned(),
+                sql: query.sql.to_owned(),
+                iterations: self.iterations,
+                errors,
+                min_micros: micros(latencies.first()),
+                mean_micros: micros(Some(&mean)),
+                p50_micros: micros(percentile(&latencies, 50)),
+                p90_micros: micros(percentile(&latencies, 90)),
+                p99_micros: micros(percentile(&latencies, 99)),
+                max_micros: micros(latencies.last()),
+                plan,
+                plan_stable,
+            });
+        }
+        Ok(report)
+    }
+}
+
+/// the nearest-rank percentile of the sorted latencies.
+fn percentile(sorted: &[Duration], percent: usize) -> Option<&Duration> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = (percent * sorted.len()).div_ceil(100);
+    sorted.get(rank.clamp(1, sorted.len()) - 1)
+}
+
+/// the plan of the query, one line per step without the estimates.
+pub(crate) fn explain(akita: &Akita, sql: &str, params: &Params) -> Option<String> {
+    let mut conn = akita.acquire().ok()?;
+    #[allow(unreachable_patterns)]
+    let explain = match *conn {
+        #[cfg(feature = "akita-mysql")]
+        crate::database::DatabasePlatform::Mysql(_) => format!("EXPLAIN {}", sql),
+        _ => format!("EXPLAIN QUERY PLAN {}", sql),
+    };
+    let rows: Rows = conn.execute_result(&explain, params.clone()).ok()?;
+    Some(rows.data.iter().map(|row| row.columns.iter().zip(row.data.iter())
+        .filter(|(column, _)| !PLAN_ESTIMATES.contains(&column.to_lowercase().as_str()))
+        .map(|(column, value)| format!("{}={}", column, value))
+        .collect::<Vec<_>>()
+        .join(" "))
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+    use super::percentile;
+
+    #[test]
+    fn nearest_rank() {
+        let latencies = (1..=10).map(Duration::from_millis).collect::<Vec<_>>();
+        assert_eq!(percentile(&latencies, 50), Some(&Duration::from_millis(5)));
+        assert_eq!(percentile(&latencies, 90), Some(&Duration::from_millis(9)));
+        assert_eq!(percentile(&latencies, 99), Some(&Duration::from_millis(10)));
+        assert_eq!(percentile(&[], 50), None);
+    }
+}
@@ -0,0 +1,186 @@
+//!
+//! Circuit breaker around the database.
+//!
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::{AkitaError, Params};
+use crate::interceptor::{ExecutedStatement, Interceptor};
+
+/// The state of a circuit breaker.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CircuitState {
+    /// the statements go through
+    Closed,
+    /// the statements are refused with `AkitaError::CircuitOpen` until the cool-down is over
+    Open,
+    /// a few statements go through to probe the database, the circuit closes once they all succeed
+    HalfOpen,
+}
+
+struct Circuit {
+    state: CircuitState,
+    /// whether the last statements failed, the oldest first
+    outcomes: VecDeque<bool>,
+    opened_at: Option<Instant>,
+    probes: u32,
+    succeeded_probes: u32,
+}
+
+/// Opens once too many of the last statements failed or were too slow, refusing the next ones
+/// right away instead of piling them on a database that doesn't keep up. Add it with `AkitaConfig::add_interceptor`,
+/// wrapped in an `Arc` to keep an eye on its state.
+pub struct CircuitBreakerInterceptor {
+    failure_rate: f64,
+    slow_call: Option<Duration>,
+    window: usize,
+    cool_down: Duration,
+    half_open_calls: u32,
+    circuit: Mutex<Circuit>,
+}
+
+impl Default for CircuitBreakerInterceptor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CircuitBreakerInterceptor {
+    /// Opens when half of the last 20 statements failed, for 30 seconds, then lets 3 probes through.
+    pub fn new() -> Self {
+        CircuitBreakerInterceptor {
+            failure_rate: 0.5,
+            slow_call: None,
+            window: 20,
+            cool_down: Duration::from_secs(30),
+            half_open_calls: 3,
+            circuit: Mutex::new(Circuit {
+                state: CircuitState::Closed,
+                outcomes: VecDeque::new(),
+                opened_at: None,
+                probes: 0,
+                succeeded_probes: 0,
+            }),
+        }
+    }
+
+    /// The rate of failed statements in the window opening the circuit, from 0 to 1.
+    pub fn set_failure_rate(mut self, failure_rate: f64) -> Self {
+        self.failure_rate = failure_rate.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Count the statements slower than this as failed.
+    pub fn set_slow_call(mut self, slow_call: Duration) -> Self {
+        self.slow_call = Some(slow_call);
+        self
+    }
+
+    /// The number of last statements the rate is computed on, the circuit can't open before as many ran.
+    pub fn set_window(mut self, window: usize) -> Self {
+        self.window = window.max(1);
+        self
+    }
+
+    /// How long the circuit stays open before probing the database.
+    pub fn set_cool_down(mut self, cool_down: Duration) -> Self {
+        self.cool_down = cool_down;
+        self
+    }
+
+    /// The probes let through once the cool-down is over.
+    pub fn set_half_open_calls(mut self, half_open_calls: u32) -> Self {
+        self.half_open_calls = half_open_calls.max(1);
+        self
+    }
+
+    pub fn state(&self) -> CircuitState {
+        self.circuit.lock().map(|circuit| circuit.state).unwrap_or(CircuitState::Closed)
+    }
+
+    fn open(&self, circuit: &mut Circuit) {
+        circuit.state = CircuitState::Open;
+        circuit.opened_at = Some(Instant::now());
+        circuit.outcomes.clear();
+    }
+}
+
+impl Interceptor for CircuitBreakerInterceptor {
+    fn before_execute(&self, _sql: &mut String, _params: &mut Params) -> Result<(), AkitaError> {
+        let mut circuit = match self.circuit.lock() {
+            Ok(circuit) => circuit,
+            Err(_) => return Ok(()),
+        };
+        if circuit.state == CircuitState::Open {
+            let open_for = circuit.opened_at.map(|opened_at| opened_at.elapsed()).unwrap_or_default();
+            if open_for < self.cool_down {
+                return Err(AkitaError::CircuitOpen(format!("the database circuit is open for {:?} more", self.cool_down - open_for)));
+            }
+            circuit.state = CircuitState::HalfOpen;
+            circuit.probes = 0;
+            circuit.succeeded_probes = 0;
+        }
+        if circuit.state == CircuitState::HalfOpen {
+            if circuit.probes >= self.half_open_calls {
+                return Err(AkitaError::CircuitOpen("the database circuit is half-open, waiting for the probes".to_string()));
+            }
+            circuit.probes += 1;
+        }
+        Ok(())
+    }
+
+    fn after_execute(&self, statement: &ExecutedStatement) {
+        let failed = statement.error.is_some() || self.slow_call.map(|slow_call| statement.elapsed > slow_call).unwrap_or_default();
+        let mut circuit = match self.circuit.lock() {
+            Ok(circuit) => circuit,
+            Err(_) => return,
+        };
+        match circuit.state {
+            CircuitState::HalfOpen if failed => self.open(&mut circuit),
+            CircuitState::HalfOpen => {
+                circuit.succeeded_probes += 1;
+                if circuit.succeeded_probes >= self.half_open_calls {
+                    circuit.state = CircuitState::Closed;
+                    circuit.opened_at = None;
+                }
+            }
+            CircuitState::Closed => {
+                circuit.outcomes.push_back(failed);
+                if circuit.outcomes.len() > self.window {
+                    circuit.outcomes.pop_front();
+                }
+                let failures = circuit.outcomes.iter().filter(|failed| **failed).count();
+                if circuit.outcomes.len() >= self.window && failures as f64 >= self.failure_rate * self.window as f64 {
+                    self.open(&mut circuit);
+                }
+            }
+            CircuitState::Open => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::time::Duration;
+    use crate::{AkitaError, Interceptors, Params};
+    use super::{CircuitBreakerInterceptor, CircuitState};
+
+    #[test]
+    fn open_and_half_open() {
+        let breaker = Arc::new(CircuitBreakerInterceptor::new().set_window(4).set_cool_down(Duration::from_millis(20)).set_half_open_calls(1));
+        let mut interceptors = Interceptors::default();
+        interceptors.push(breaker.clone());
+        let run = |fail: bool| interceptors.intercept("SELECT 1", Params::Nil, None, |_, _| if fail { Err(AkitaError::MySQLError("Lost connection".to_string())) } else { Ok(()) });
+        for fail in [true, false, true, true] {
+            let _ = run(fail);
+        }
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(matches!(run(false), Err(AkitaError::CircuitOpen(_))));
+        std::thread::sleep(Duration::from_millis(25));
+        assert!(run(false).is_ok());
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+}
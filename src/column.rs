@@ -0,0 +1,80 @@
+//!
+//! The columns of the entities with the type of their field, for the typed conditions of the wrappers.
+//!
+
+use std::fmt;
+use std::marker::PhantomData;
+
+use crate::segment::ToSegment;
+
+/// A column of an entity holding the values of `T`, given by the derive, e.g. `User::age()` for `age: Option<u8>`
+/// is a `Column<u8>`. `Wrapper::eq_t(User::age(), 18)` only builds with a `u8`, the untyped conditions take it as a name.
+pub struct Column<T> {
+    name: &'static str,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Column<T> {
+    pub const fn new(name: &'static str) -> Self {
+        Column { name, marker: PhantomData }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl<T> Clone for Column<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Column<T> {}
+
+impl<T> fmt::Debug for Column<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Column({})", self.name)
+    }
+}
+
+impl<T> fmt::Display for Column<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name)
+    }
+}
+
+impl<T> PartialEq<&str> for Column<T> {
+    fn eq(&self, other: &&str) -> bool {
+        self.name == *other
+    }
+}
+
+impl<T> From<Column<T>> for String {
+    fn from(column: Column<T>) -> String {
+        column.name.to_string()
+    }
+}
+
+/// A value comparable with a `Column<T>`: a `T`, or a `&str` for the text columns.
+pub trait ColumnValue<T>: ToSegment {}
+
+impl<T: ToSegment> ColumnValue<T> for T {}
+
+impl ColumnValue<String> for &str {}
+
+#[cfg(test)]
+mod test {
+    use crate::{ISegment, Wrapper};
+    use super::Column;
+
+    const AGE: Column<u8> = Column::new("age");
+    const NAME: Column<String> = Column::new("name");
+
+    #[test]
+    fn typed_conditions() {
+        let mut wrapper = Wrapper::new().eq_t(NAME, "Jack").ge_t(AGE, 18).inside_t(AGE, vec![20, 30]);
+        assert_eq!(wrapper.get_sql_segment().trim(), "(name = 'Jack' and age >= 18 and age in (20,30))");
+        assert_eq!(String::from(AGE), "age");
+    }
+}
@@ -0,0 +1,111 @@
+//!
+//! Compressed column values.
+//!
+
+use crate::Value;
+
+/// The header of a compressed value, followed by the codec and the kind of the value.
+pub const COMPRESSED_MAGIC: &[u8; 3] = b"AKZ";
+
+/// The values shorter than this are stored as is.
+const MIN_COMPRESSED_LEN: usize = 64;
+
+const KIND_BLOB: u8 = 0;
+const KIND_TEXT: u8 = 1;
+const KIND_JSON: u8 = 2;
+
+/// The codecs of `#[field(compress = "...")]`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    #[cfg(feature = "akita-zstd")]
+    Zstd,
+}
+
+impl Compression {
+    fn id(&self) -> u8 {
+        match *self {
+            #[cfg(feature = "akita-zstd")]
+            Compression::Zstd => 1,
+        }
+    }
+}
+
+/// Compress the text, blob or json value into a blob starting with `COMPRESSED_MAGIC`,
+/// the other values and the short ones are kept as is.
+pub fn compress_value(compression: Compression, value: Value) -> Value {
+    let (kind, bytes) = match value {
+        Value::Blob(ref v) if v.len() >= MIN_COMPRESSED_LEN => (KIND_BLOB, v.to_owned()),
+        Value::Text(ref v) if v.len() >= MIN_COMPRESSED_LEN => (KIND_TEXT, v.as_bytes().to_vec()),
+        Value::Json(ref v) => match serde_json::to_vec(v) {
+            Ok(json) if json.len() >= MIN_COMPRESSED_LEN => (KIND_JSON, json),
+            _ => return value,
+        },
+        _ => return value,
+    };
+    match encode(compression, &bytes) {
+        Some(compressed) => {
+            let mut data = Vec::with_capacity(COMPRESSED_MAGIC.len() + 2 + compressed.len());
+            data.extend_from_slice(COMPRESSED_MAGIC);
+            data.push(compression.id());
+            data.push(kind);
+            data.extend_from_slice(&compressed);
+            Value::Blob(data)
+        }
+        None => value,
+    }
+}
+
+/// Restore a value compressed by `compress_value`, the values stored before the compression was on are kept as is.
+pub fn decompress_value(value: &Value) -> Value {
+    if let Value::Blob(data) = value {
+        let header = COMPRESSED_MAGIC.len() + 2;
+        if data.len() >= header && data.starts_with(COMPRESSED_MAGIC) {
+            let (codec, kind) = (data[header - 2], data[header - 1]);
+            if let Some(bytes) = decode(codec, &data[header..]) {
+                let restored = match kind {
+                    KIND_TEXT => String::from_utf8(bytes).ok().map(Value::Text),
+                    KIND_JSON => serde_json::from_slice(&bytes).ok().map(Value::Json),
+                    _ => Some(Value::Blob(bytes)),
+                };
+                if let Some(restored) = restored {
+                    return restored;
+                }
+            }
+        }
+    }
+    value.to_owned()
+}
+
+#[allow(unused)]
+fn encode(compression: Compression, bytes: &[u8]) -> Option<Vec<u8>> {
+    match compression {
+        #[cfg(feature = "akita-zstd")]
+        Compression::Zstd => zstd::encode_all(bytes, 0).ok(),
+    }
+}
+
+#[allow(unused)]
+fn decode(codec: u8, bytes: &[u8]) -> Option<Vec<u8>> {
+    match codec {
+        #[cfg(feature = "akita-zstd")]
+        1 => zstd::decode_all(bytes).ok(),
+        _ => None,
+    }
+}
+
+#[cfg(all(test, feature = "akita-zstd"))]
+mod test {
+    use crate::Value;
+    use super::{compress_value, decompress_value, Compression};
+
+    #[test]
+    fn compress_round_trip() {
+        let text = Value::Text("akita ".repeat(100));
+        let compressed = compress_value(Compression::Zstd, text.clone());
+        assert!(matches!(&compressed, Value::Blob(data) if data.starts_with(b"AKZ") && data.len() < 600));
+        assert_eq!(decompress_value(&compressed), text);
+        let short = Value::Text("akita".to_string());
+        assert_eq!(compress_value(Compression::Zstd, short.clone()), short);
+        assert_eq!(decompress_value(&Value::Blob(vec![1, 2, 3])), Value::Blob(vec![1, 2, 3]));
+    }
+}
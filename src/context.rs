@@ -0,0 +1,77 @@
+//!
+//! Values of the current request, read by the fills of the entities.
+//!
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use crate::{ToValue, Value};
+
+thread_local! {
+    static CONTEXT: RefCell<HashMap<String, Value>> = RefCell::new(HashMap::new());
+}
+
+/// The values of the request run by the current thread, e.g. the authenticated user set by a middleware,
+/// filled in the fields with `#[field(fill(context = "user_id", mode = "insert"))]` by the saves and updates.
+/// A field keeps the entity's value while the key is not set.
+///
+/// The values are thread-local: set them in the thread running the statements, with `scope` to have them
+/// cleared afterwards, as the threads of a pool serve other requests next.
+pub struct AkitaContext;
+
+impl AkitaContext {
+    pub fn set<S: Into<String>, V: ToValue>(key: S, value: V) {
+        CONTEXT.with(|context| context.borrow_mut().insert(key.into(), value.to_value()));
+    }
+
+    pub fn get(key: &str) -> Option<Value> {
+        CONTEXT.with(|context| context.borrow().get(key).cloned())
+    }
+
+    pub fn remove(key: &str) -> Option<Value> {
+        CONTEXT.with(|context| context.borrow_mut().remove(key))
+    }
+
+    pub fn clear() {
+        CONTEXT.with(|context| context.borrow_mut().clear());
+    }
+
+    /// Run the closure with the values set, the previous ones are back once it returns.
+    pub fn scope<S: Into<String>, R, F: FnOnce() -> R>(values: Vec<(S, Value)>, f: F) -> R {
+        let previous = CONTEXT.with(|context| {
+            let mut context = context.borrow_mut();
+            let previous = context.clone();
+            context.extend(values.into_iter().map(|(key, value)| (key.into(), value)));
+            previous
+        });
+        // restored on a panic of the closure too.
+        let _restore = Restore(Some(previous));
+        f()
+    }
+}
+
+struct Restore(Option<HashMap<String, Value>>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        if let Some(previous) = self.0.take() {
+            CONTEXT.with(|context| *context.borrow_mut() = previous);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{ToValue, Value};
+    use super::AkitaContext;
+
+    #[test]
+    fn context_scope() {
+        AkitaContext::set("tenant_id", 7);
+        let user = AkitaContext::scope(vec![("user_id", "alice".to_value())], || AkitaContext::get("user_id"));
+        assert_eq!(user, Some(Value::Text("alice".to_string())));
+        assert_eq!((AkitaContext::get("user_id"), AkitaContext::get("tenant_id")), (None, Some(Value::Int(7))));
+        AkitaContext::clear();
+        assert_eq!(AkitaContext::get("tenant_id"), None);
+    }
+}
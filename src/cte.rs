@@ -0,0 +1,61 @@
+//!
+//! The common table expressions of the wrappers, as `WITH`.
+//!
+
+use crate::{AkitaError, ISegment, Wrapper};
+use crate::segment::MergeSegments;
+use crate::wrapper::SetOperation;
+
+/// the `WITH` clause of the common table expressions of the wrapper, to put before its query, empty without any.
+/// `RECURSIVE` applies to all of them, it is given once any is recursive.
+pub(crate) fn with_clause(wrapper: &mut Wrapper) -> Result<String, AkitaError> {
+    if wrapper.ctes.is_empty() {
+        return Ok(String::new());
+    }
+    let ctes = std::mem::take(&mut wrapper.ctes);
+    let recursive = ctes.iter().any(|cte| cte.recursive);
+    let mut expressions = Vec::with_capacity(ctes.len());
+    for cte in ctes {
+        let columns = if cte.columns.is_empty() { String::new() } else { format!(" ({})", cte.columns.join(", ")) };
+        expressions.push(format!("{}{} AS ({})", cte.name, columns, query_sql(cte.query)?));
+    }
+    Ok(format!("WITH {}{} ", if recursive { "RECURSIVE " } else { "" }, expressions.join(", ")))
+}
+
+/// the select of the wrapper from its table, with its unions before its order and limit.
+fn query_sql(mut wrapper: Wrapper) -> Result<String, AkitaError> {
+    let table = wrapper.table.take().ok_or_else(|| AkitaError::MissingTable("Find Error, Missing Table Name of the common table expression !".to_string()))?;
+    let mut unions = String::new();
+    for (operation, mut other) in std::mem::take(&mut wrapper.set_operations) {
+        let keyword = match operation {
+            SetOperation::Union => "UNION",
+            SetOperation::UnionAll => "UNION ALL",
+            // they compare the records of an entity by its key.
+            SetOperation::Intersect | SetOperation::Except => return Err(AkitaError::InvalidSQL("a common table expression takes no intersect or except".to_string())),
+        };
+        other.last_sql = None;
+        other.expression.order_by = MergeSegments::default().order_by;
+        unions.push_str(&format!(" {} {}", keyword, query_sql(other)?));
+    }
+    let mut order_by = std::mem::replace(&mut wrapper.expression.order_by, MergeSegments::default().order_by);
+    wrapper.last_sql = format!("{}{} {}", unions, order_by.get_sql_segment(), wrapper.last_sql.take().unwrap_or_default()).into();
+    Ok(format!("SELECT {} FROM {} WHERE {}", wrapper.get_select_sql(), table, wrapper.get_sql_segment().trim()))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::Wrapper;
+    use super::with_clause;
+
+    #[test]
+    fn recursive() {
+        let columns = vec!["id".to_string(), "parent_id".to_string()];
+        let mut wrapper = Wrapper::new().with_cte("roots", |w| w.table("t_org").select(columns.clone()).is_null("parent_id"))
+            .with_recursive_cte("org_tree", columns.clone(), |w| w.table("roots").select(columns.clone())
+                .union_all(Wrapper::new().table("t_org o, org_tree t").select(vec!["o.id".to_string(), "o.parent_id".to_string()]).eq_column("o.parent_id", "t.id")));
+        assert_eq!(with_clause(&mut wrapper).unwrap().split_whitespace().collect::<Vec<_>>().join(" "), "WITH RECURSIVE roots AS (SELECT id,parent_id FROM t_org WHERE (parent_id is null)), \
+            org_tree (id, parent_id) AS (SELECT id,parent_id FROM roots WHERE (1 = 1) UNION ALL SELECT o.id,o.parent_id FROM t_org o, org_tree t WHERE (o.parent_id = t.id))");
+        assert!(wrapper.ctes.is_empty());
+        assert!(with_clause(&mut Wrapper::new().with_cte("missing", |w| w.eq("id", 1))).is_err());
+    }
+}
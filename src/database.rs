@@ -1,6 +1,6 @@
-use std::{convert::TryFrom, ops::Deref};
+use std::{convert::TryFrom, ops::{Deref, DerefMut}, sync::MutexGuard};
 
-use crate::{cfg_if, Params, TableName, DatabaseName, SchemaContent, TableDef, Rows};
+use crate::{cfg_if, Params, TableName, DatabaseName, SchemaContent, TableDef, Rows, Row};
 use url::Url;
 
 cfg_if! {if #[cfg(feature = "akita-sqlite")]{
@@ -14,7 +14,7 @@ cfg_if! {if #[cfg(feature = "akita-auth")]{
     use crate::auth::{GrantUserPrivilege, Role, UserInfo, DataBaseUser};
 }}
 
-use crate::{AkitaError};
+use crate::{AkitaConfig, AkitaError, SqlWarning};
 
 
 pub trait Database {
@@ -24,10 +24,20 @@ pub trait Database {
 
     fn rollback_transaction(&mut self) -> Result<(), AkitaError>;
 
+    fn savepoint(&mut self, name: &str) -> Result<(), AkitaError>;
+
+    fn release_savepoint(&mut self, name: &str) -> Result<(), AkitaError>;
+
+    fn rollback_to_savepoint(&mut self, name: &str) -> Result<(), AkitaError>;
+
     fn execute_result(&mut self, sql: &str, param: Params) -> Result<Rows, AkitaError>;
 
     fn execute_drop(&mut self, sql: &str, param: Params) -> Result<(), AkitaError>;
 
+    /// Execute the sql and hand the rows to the consumer one by one as they are read,
+    /// instead of collecting the whole result set into `Rows`.
+    fn execute_stream(&mut self, sql: &str, param: Params, consumer: &mut dyn FnMut(Row) -> Result<(), AkitaError>) -> Result<(), AkitaError>;
+
     fn get_table(&mut self, table_name: &TableName) -> Result<Option<TableDef>, AkitaError>;
 
     fn exist_table(&mut self, table_name: &TableName) -> Result<bool, AkitaError>;
@@ -53,6 +63,15 @@ pub trait Database {
 
     fn last_insert_id(&self) -> u64;
 
+    /// The warnings raised by the last statement on the connection.
+    fn warnings(&mut self) -> Result<Vec<SqlWarning>, AkitaError>;
+
+    /// The id of the transaction in progress on the connection.
+    fn transaction_id(&self) -> Option<u64>;
+
+    /// The config the connection was opened with.
+    fn config(&self) -> &AkitaConfig;
+
     fn get_database_name(&mut self) -> Result<Option<DatabaseName>, AkitaError>;
 
     fn create_database(&mut self, database: &str) -> Result<(), AkitaError>;
@@ -131,6 +150,32 @@ impl std::ops::DerefMut for DatabasePlatform {
 }
 
 
+/// A connection handed out by `Akita`, either fresh from the pool
+/// or the one pinned to a transaction or a temporary table.
+pub enum DatabaseConnection<'a> {
+    Pooled(DatabasePlatform),
+    Pinned(MutexGuard<'a, DatabasePlatform>),
+}
+
+impl Deref for DatabaseConnection<'_> {
+    type Target = DatabasePlatform;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            DatabaseConnection::Pooled(conn) => conn,
+            DatabaseConnection::Pinned(conn) => conn,
+        }
+    }
+}
+
+impl DerefMut for DatabaseConnection<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        match self {
+            DatabaseConnection::Pooled(conn) => conn,
+            DatabaseConnection::Pinned(conn) => conn,
+        }
+    }
+}
 #[derive(Debug, Clone)]
 pub enum Platform {
     #[cfg(feature = "akita-mysql")]
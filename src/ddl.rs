@@ -0,0 +1,239 @@
+//!
+//! The `CREATE TABLE` of the entities, from the metadata of their fields.
+//!
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{Akita, AkitaError, FieldName, FieldType, GetFields, GetTableName, IndexDef, Platform};
+
+/// The `CREATE TABLE IF NOT EXISTS` of an entity in the sql of the platform, for the tests and the small tools
+/// creating their tables: the columns of the fields with their `#[field(column_type, len, not_null, default, comment)]`,
+/// and the primary key of the `#[table_id]` fields. Implemented for all the entities.
+///
+/// The type of a column is inferred from the type of its field, the other types are stored as text.
+/// The `#[field(json)]` fields are text columns too, set their `column_type = "json"` on MySQL.
+/// The key columns are `NOT NULL`, and a single `id_type = "auto"` key is auto incremented.
+/// The logic delete and version columns default to `0`, the conditions on them don't match a `NULL`.
+/// The `#[index]` of the entity are keys of the table on MySQL, `CREATE INDEX` statements after it on SQLite.
+pub trait TableDdl {
+    fn ddl(platform: &Platform) -> Result<String, AkitaError>;
+}
+
+impl<T: GetTableName + GetFields> TableDdl for T {
+    fn ddl(platform: &Platform) -> Result<String, AkitaError> {
+        let table = T::table_name().complete_name();
+        if table.is_empty() {
+            return Err(AkitaError::MissingTable("Create Error, Missing Table Name !".to_string()))
+        }
+        let fields = T::fields().into_iter().filter(|field| field.exist).collect::<Vec<_>>();
+        if fields.is_empty() {
+            return Err(AkitaError::MissingField(format!("Table({}) has no column", &table)))
+        }
+        let keys = fields.iter().filter(|field| matches!(field.field_type, FieldType::TableId(_))).collect::<Vec<_>>();
+        let auto_increment = match keys.as_slice() {
+            [key] => matches!(&key.field_type, FieldType::TableId(id_type) if id_type == "auto"),
+            _ => false,
+        };
+        let mut lines = fields.iter().map(|field| column(platform, field, auto_increment)).collect::<Vec<_>>();
+        let inline_key = auto_increment && is_sqlite(platform);
+        if !keys.is_empty() && !inline_key {
+            lines.push(format!("PRIMARY KEY ({})", keys.iter().map(|key| format!("`{}`", key.name)).collect::<Vec<_>>().join(", ")));
+        }
+        let indexes = T::indexes();
+        if !is_sqlite(platform) {
+            lines.extend(indexes.iter().map(|index| format!("{}KEY `{}` ({})", if index.unique { "UNIQUE " } else { "" }, index.name, index_columns(index))));
+        }
+        let mut sql = format!("CREATE TABLE IF NOT EXISTS {} (\n    {}\n)", table, lines.join(",\n    "));
+        if is_sqlite(platform) {
+            for index in indexes.iter() {
+                sql.push_str(";\n");
+                sql.push_str(&create_index(platform, &table, index));
+            }
+        }
+        Ok(sql)
+    }
+}
+
+impl Akita {
+    /// Create the table of the entity and its indexes when it does not exist, see `TableDdl`.
+    pub fn create_table<T: GetTableName + GetFields>(&self) -> Result<(), AkitaError> {
+        let sql = T::ddl(&self.config().platform())?;
+        self.run_script(&sql)?;
+        Ok(())
+    }
+}
+
+#[allow(unreachable_patterns)]
+pub(crate) fn is_sqlite(platform: &Platform) -> bool {
+    match platform {
+        #[cfg(feature = "akita-sqlite")]
+        Platform::Sqlite(_) => true,
+        _ => false,
+    }
+}
+
+/// the milliseconds since the epoch, the times kept by the tables akita creates for its stores.
+pub(crate) fn now_millis() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|now| now.as_millis() as i64).unwrap_or_default()
+}
+
+/// the names of the tables, views or variables given by the application are sent as is, only dotted identifiers are allowed.
+pub(crate) fn check_identifier(kind: &str, name: &str) -> Result<(), AkitaError> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.') {
+        return Err(AkitaError::InvalidSQL(format!("invalid {}: {}", kind, name)));
+    }
+    Ok(())
+}
+
+/// the statement adding the index to the existing table.
+pub(crate) fn create_index(platform: &Platform, table: &str, index: &IndexDef) -> String {
+    format!("CREATE {}INDEX {}`{}` ON {} ({})", if index.unique { "UNIQUE " } else { "" },
+        if is_sqlite(platform) { "IF NOT EXISTS " } else { "" }, index.name, table, index_columns(index))
+}
+
+fn index_columns(index: &IndexDef) -> String {
+    index.columns.iter().map(|column| format!("`{}`", column)).collect::<Vec<_>>().join(", ")
+}
+
+/// the declaration of the column of the field.
+pub(crate) fn column(platform: &Platform, field: &FieldName, auto_increment: bool) -> String {
+    let column = &field.column;
+    let key = matches!(field.field_type, FieldType::TableId(_));
+    let sql_type = match &column.column_type {
+        Some(column_type) if column_type.contains('(') => column_type.to_string(),
+        Some(column_type) => column.len.map(|len| format!("{}({})", column_type, len)).unwrap_or_else(|| column_type.to_string()),
+        None => column_type(platform, &column.rust_type, column.len),
+    };
+    let mut sql = format!("`{}` {}", field.name, sql_type);
+    if key && auto_increment && is_sqlite(platform) {
+        // only an `INTEGER PRIMARY KEY` column is auto incremented by SQLite.
+        return format!("`{}` INTEGER PRIMARY KEY AUTOINCREMENT", field.name);
+    }
+    if key || column.not_null || field.logic_delete || field.version {
+        sql.push_str(" NOT NULL");
+    }
+    if key && auto_increment {
+        sql.push_str(" AUTO_INCREMENT");
+    }
+    match &column.default {
+        Some(default) => sql.push_str(&format!(" DEFAULT {}", default)),
+        None if field.logic_delete || field.version => sql.push_str(" DEFAULT 0"),
+        None => {}
+    }
+    if let Some(comment) = &column.comment {
+        if !is_sqlite(platform) {
+            sql.push_str(&format!(" COMMENT '{}'", comment.replace('\\', "\\\\").replace('\'', "''")));
+        }
+    }
+    sql
+}
+
+/// the sql type of the rust type, with the length of the text and binary columns.
+fn column_type(platform: &Platform, rust_type: &str, len: Option<u64>) -> String {
+    let mut rust_type = rust_type.replace(' ', "");
+    while let Some(inner) = rust_type.strip_prefix("Option<").and_then(|ty| ty.strip_suffix('>')) {
+        rust_type = inner.to_string();
+    }
+    // the last segment of the path, e.g. `NaiveDateTime` for `chrono::NaiveDateTime`.
+    let name = rust_type.split('<').next().unwrap_or_default().rsplit("::").next().unwrap_or_default();
+    let binary = rust_type.ends_with("Vec<u8>");
+    if is_sqlite(platform) {
+        return match name {
+            "i8" | "i16" | "i32" | "i64" | "isize" | "u8" | "u16" | "u32" | "u64" | "usize" | "bool" => "INTEGER",
+            "f32" | "f64" => "REAL",
+            "BigDecimal" => "NUMERIC",
+            _ if binary => "BLOB",
+            _ => "TEXT",
+        }.to_string();
+    }
+    match name {
+        "i8" => "TINYINT".to_string(),
+        "i16" => "SMALLINT".to_string(),
+        "i32" => "INT".to_string(),
+        "i64" | "isize" => "BIGINT".to_string(),
+        "u8" => "TINYINT UNSIGNED".to_string(),
+        "u16" => "SMALLINT UNSIGNED".to_string(),
+        "u32" => "INT UNSIGNED".to_string(),
+        "u64" | "usize" => "BIGINT UNSIGNED".to_string(),
+        "bool" => "TINYINT(1)".to_string(),
+        "f32" => "FLOAT".to_string(),
+        "f64" => "DOUBLE".to_string(),
+        "BigDecimal" => "DECIMAL(20, 6)".to_string(),
+        "char" => "CHAR(1)".to_string(),
+        "String" | "str" => format!("VARCHAR({})", len.unwrap_or(255)),
+        "NaiveDate" => "DATE".to_string(),
+        "NaiveTime" => "TIME".to_string(),
+        "NaiveDateTime" => "DATETIME".to_string(),
+        "DateTime" => "TIMESTAMP".to_string(),
+        "Uuid" => "CHAR(36)".to_string(),
+        "IpAddr" | "Ipv4Addr" | "Ipv6Addr" => "VARCHAR(45)".to_string(),
+        _ if binary => len.map(|len| format!("VARBINARY({})", len)).unwrap_or_else(|| "BLOB".to_string()),
+        _ => "TEXT".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{FieldName, FieldType, GetFields, GetTableName, IndexDef, Platform, TableName};
+    use super::{check_identifier, TableDdl};
+
+    struct Account;
+
+    impl GetTableName for Account {
+        fn table_name() -> TableName {
+            TableName::from("t_account")
+        }
+
+        fn indexes() -> Vec<IndexDef> {
+            vec![IndexDef { name: "uk_account_email".to_string(), columns: vec!["email".to_string()], unique: true },
+                 IndexDef { name: "idx_account_state".to_string(), columns: vec!["state".to_string(), "created_at".to_string()], unique: false }]
+        }
+    }
+
+    impl GetFields for Account {
+        fn fields() -> Vec<FieldName> {
+            let field = |name: &str, rust_type: &str| {
+                let mut field = FieldName::from(name);
+                field.column.rust_type = rust_type.to_string();
+                field
+            };
+            let mut id = field("id", "i64");
+            id.field_type = FieldType::TableId("auto".to_string());
+            let mut email = field("email", "String");
+            email.column.len = Some(128);
+            email.column.not_null = true;
+            email.column.comment = Some("the login's email".to_string());
+            let mut state = field("state", "i8");
+            state.column.default = Some("1".to_string());
+            let mut deleted = field("deleted", "bool");
+            deleted.logic_delete = true;
+            let mut extra = field("extra", "String");
+            extra.column.column_type = Some("json".to_string());
+            vec![id, email, state, field("created_at", "chrono::NaiveDateTime"), field("avatar", "Vec<u8>"), deleted, extra]
+        }
+    }
+
+    #[test]
+    fn create_table() {
+        let ddl = Account::ddl(&Platform::Unsupported("mysql".to_string())).unwrap();
+        assert_eq!(ddl, "CREATE TABLE IF NOT EXISTS t_account (\n    \
+            `id` BIGINT NOT NULL AUTO_INCREMENT,\n    \
+            `email` VARCHAR(128) NOT NULL COMMENT 'the login''s email',\n    \
+            `state` TINYINT DEFAULT 1,\n    \
+            `created_at` DATETIME,\n    \
+            `avatar` BLOB,\n    \
+            `deleted` TINYINT(1) NOT NULL DEFAULT 0,\n    \
+            `extra` json,\n    \
+            PRIMARY KEY (`id`),\n    \
+            UNIQUE KEY `uk_account_email` (`email`),\n    \
+            KEY `idx_account_state` (`state`, `created_at`)\n)");
+    }
+
+    #[test]
+    fn identifier() {
+        assert!(check_identifier("table name", "app_settings").is_ok());
+        assert!(check_identifier("table name", "config.app_settings").is_ok());
+        assert!(check_identifier("table name", "").is_err());
+        assert!(check_identifier("table name", "t; DROP TABLE t_user").is_err());
+    }
+}
@@ -0,0 +1,89 @@
+//!
+//! Changes between two states of an entity, for the audit payloads and the history of the records.
+//!
+
+use serde::{Serialize, Serializer};
+
+use crate::{FromValue, GetFields, SensitiveColumns, ToValue, Value, MASK};
+
+/// A column whose value changed, serialized with the values as json, e.g. in an audit payload.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct FieldChange {
+    pub column: String,
+    #[serde(serialize_with = "serialize_value")]
+    pub old: Value,
+    #[serde(serialize_with = "serialize_value")]
+    pub new: Value,
+}
+
+/// The columns whose value differ between the two states of the entity, in the order of its fields.
+/// The changes of the sensitive columns are listed with their values masked as `***`, see `SensitiveColumns`.
+pub fn entity_diff<T: GetFields + ToValue>(old: &T, new: &T) -> Vec<FieldChange> {
+    let (old, new) = (old.to_value(), new.to_value());
+    let sensitive = SensitiveColumns::global();
+    T::fields().into_iter().filter(|field| field.exist).filter_map(|field| {
+        let old = old.get_obj_value(&field.name).cloned().unwrap_or(Value::Nil);
+        let new = new.get_obj_value(&field.name).cloned().unwrap_or(Value::Nil);
+        if old == new {
+            return None;
+        }
+        if field.sensitive || sensitive.is_sensitive(&field.name) {
+            return Some(FieldChange { column: field.name, old: mask(old), new: mask(new) });
+        }
+        Some(FieldChange { column: field.name, old, new })
+    }).collect()
+}
+
+fn serialize_value<S: Serializer>(value: &Value, serializer: S) -> Result<S::Ok, S::Error> {
+    serde_json::Value::from_value_opt(value).unwrap_or_else(|_| serde_json::Value::String(value.to_string())).serialize(serializer)
+}
+
+/// a null value tells nothing, it is kept.
+fn mask(value: Value) -> Value {
+    match value {
+        Value::Nil => Value::Nil,
+        _ => Value::Text(MASK.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{FieldName, GetFields, ToValue, Value, MASK};
+    use super::{entity_diff, FieldChange};
+
+    struct User {
+        name: &'static str,
+        password: Option<&'static str>,
+        age: i32,
+    }
+
+    impl GetFields for User {
+        fn fields() -> Vec<FieldName> {
+            let mut password = FieldName::from("password");
+            password.sensitive = true;
+            vec![FieldName::from("name"), password, FieldName::from("age")]
+        }
+    }
+
+    impl ToValue for User {
+        fn to_value(&self) -> Value {
+            let mut value = Value::new_object();
+            value.insert_obj("name", self.name);
+            value.insert_obj("password", self.password);
+            value.insert_obj("age", self.age);
+            value
+        }
+    }
+
+    #[test]
+    fn diff_masks_sensitive() {
+        let old = User { name: "alice", password: None, age: 30 };
+        let new = User { name: "alice", password: Some("secret"), age: 31 };
+        assert_eq!(entity_diff(&old, &new), vec![
+            FieldChange { column: "password".to_string(), old: Value::Nil, new: Value::Text(MASK.to_string()) },
+            FieldChange { column: "age".to_string(), old: Value::Int(30), new: Value::Int(31) },
+        ]);
+        assert!(entity_diff(&old, &old).is_empty());
+        assert_eq!(serde_json::to_string(&entity_diff(&old, &new)[1]).unwrap(), r#"{"column":"age","old":30,"new":31}"#);
+    }
+}
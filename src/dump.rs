@@ -0,0 +1,304 @@
+//!
+//! Logical dumps of the records of an entity, written and restored without an external dump tool.
+//!
+
+use std::io::{BufRead, Write};
+
+use crate::{Akita, AkitaError, AkitaMapper, GetFields, GetTableName, OperationType, Params, Platform, Value, Wrapper};
+use crate::cache::write_tables;
+use crate::manager::build_select_clause_with_deleted;
+use crate::script::split_script;
+
+/// The format of a dump.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dump {
+    /// a json object per line, with the columns of the record
+    Jsonl,
+    /// a header line with the columns, then a line per record, a `NULL` is an empty field without quotes
+    Csv,
+    /// an `INSERT` statement per record, with the values inlined
+    SqlInserts,
+}
+
+impl Akita {
+    /// Write the records of the entity matching the wrapper, streamed from the connection,
+    /// the ones flagged by the logic delete field included. Returns the records written.
+    ///
+    /// The dates and the binary columns are written as text in json and csv, the binary ones in base64:
+    /// they are restored as such, dump the tables with binary columns as `Dump::SqlInserts`.
+    pub fn dump<T, W>(&self, mut writer: W, format: Dump, mut wrapper: Wrapper) -> Result<u64, AkitaError>
+        where
+            T: GetTableName + GetFields,
+            W: Write,
+    {
+        let sql = build_select_clause_with_deleted::<T>(&mut wrapper)?;
+        let table = T::table_name().complete_name();
+        let platform = self.config().platform();
+        let mut count = 0;
+        self.exec_stream(sql, (), |row: Value| {
+            let columns = row.as_object().cloned().unwrap_or_default();
+            if count == 0 && format == Dump::Csv {
+                write_line(&mut writer, &columns.keys().map(|column| csv_field(Some(column))).collect::<Vec<_>>().join(","))?;
+            }
+            let line = match format {
+                Dump::Jsonl => serde_json::Value::Object(columns.iter().map(|(column, value)| (column.to_string(), json(value))).collect()).to_string(),
+                Dump::Csv => columns.values().map(|value| csv_field(text(value).as_deref())).collect::<Vec<_>>().join(","),
+                Dump::SqlInserts => format!("INSERT INTO {} ({}) VALUES ({});", &table,
+                    columns.keys().map(|column| format!("`{}`", column)).collect::<Vec<_>>().join(", "),
+                    columns.values().map(|value| literal(&platform, value)).collect::<Vec<_>>().join(", ")),
+            };
+            count += 1;
+            write_line(&mut writer, &line)
+        })?;
+        if count == 0 && format == Dump::Csv {
+            let columns = T::fields().into_iter().filter(|field| field.exist).map(|field| csv_field(Some(&field.name))).collect::<Vec<_>>();
+            write_line(&mut writer, &columns.join(","))?;
+        }
+        writer.flush().map_err(|err| AkitaError::DataError(err.to_string()))?;
+        Ok(count)
+    }
+
+    /// Insert the records of a dump of the entity, in a transaction per `fetch_size` records.
+    /// The records of the chunks committed stay inserted when a later chunk fails. Returns the records inserted.
+    ///
+    /// The columns must be fields of the entity and a sql dump may only insert into its table.
+    /// The records are inserted as read, the listeners of the entity are not run.
+    pub fn load<T, R>(&self, mut reader: R, format: Dump) -> Result<u64, AkitaError>
+        where
+            T: GetTableName + GetFields,
+            R: BufRead,
+    {
+        let table = T::table_name().complete_name();
+        if table.is_empty() {
+            return Err(AkitaError::MissingTable("Load Error, Missing Table Name !".to_string()))
+        }
+        let fields = T::fields().into_iter().filter(|field| field.exist).map(|field| field.name).collect::<Vec<_>>();
+        let check_column = |column: &str| if fields.iter().any(|field| field == column) {
+            Ok(())
+        } else {
+            Err(AkitaError::InvalidField(format!("Table({}) has no field {}", &table, column)))
+        };
+        let fetch_size = self.fetch_size().max(1);
+        let mut count = 0;
+        match format {
+            Dump::SqlInserts => {
+                let mut script = String::new();
+                reader.read_to_string(&mut script).map_err(|err| AkitaError::DataError(err.to_string()))?;
+                let statements = split_script(&script);
+                for statement in statements.iter() {
+                    let tables = write_tables(&statement.sql);
+                    if OperationType::of(&statement.sql) != OperationType::Insert || !tables.first().is_some_and(|written| written.eq_ignore_ascii_case(&table)) {
+                        return Err(AkitaError::StatementDenied(format!("statement at line {} does not insert into {}: {}", statement.line, &table, &statement.sql)));
+                    }
+                }
+                for chunk in statements.chunks(fetch_size) {
+                    let transaction = self.start_transaction()?;
+                    for statement in chunk {
+                        count += transaction.exec_result(&statement.sql, Params::Nil)?.affected_rows;
+                    }
+                    transaction.commit()?;
+                }
+            }
+            Dump::Jsonl => {
+                let mut chunk: Vec<(Vec<String>, Vec<Value>)> = Vec::new();
+                for line in reader.lines() {
+                    let line = line.map_err(|err| AkitaError::DataError(err.to_string()))?;
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    let record = match serde_json::from_str(&line).map_err(|err| AkitaError::DataError(err.to_string()))? {
+                        serde_json::Value::Object(record) => record,
+                        _ => return Err(AkitaError::DataError(format!("a json dump has an object per line: {}", line))),
+                    };
+                    let columns = record.keys().cloned().collect::<Vec<_>>();
+                    columns.iter().try_for_each(|column| check_column(column))?;
+                    chunk.push((columns, record.values().map(from_json).collect()));
+                    if chunk.len() >= fetch_size {
+                        count += self.insert_chunk(&table, std::mem::take(&mut chunk))?;
+                    }
+                }
+                count += self.insert_chunk(&table, chunk)?;
+            }
+            Dump::Csv => {
+                let columns = match read_csv_record(&mut reader)? {
+                    Some(header) => header.into_iter().map(Option::unwrap_or_default).collect::<Vec<_>>(),
+                    None => return Ok(0),
+                };
+                columns.iter().try_for_each(|column| check_column(column))?;
+                let mut chunk = Vec::new();
+                while let Some(record) = read_csv_record(&mut reader)? {
+                    if record.len() != columns.len() {
+                        return Err(AkitaError::DataError(format!("a csv record has {} fields, the header {}", record.len(), columns.len())));
+                    }
+                    chunk.push((columns.clone(), record.into_iter().map(|field| field.map(Value::Text).unwrap_or(Value::Nil)).collect()));
+                    if chunk.len() >= fetch_size {
+                        count += self.insert_chunk(&table, std::mem::take(&mut chunk))?;
+                    }
+                }
+                count += self.insert_chunk(&table, chunk)?;
+            }
+        }
+        Ok(count)
+    }
+
+    /// insert the records in a transaction, a batch for each run of records with the same columns.
+    fn insert_chunk(&self, table: &str, records: Vec<(Vec<String>, Vec<Value>)>) -> Result<u64, AkitaError> {
+        if records.is_empty() {
+            return Ok(0);
+        }
+        let transaction = self.start_transaction()?;
+        let mut count = 0;
+        let mut records = records.into_iter().peekable();
+        while let Some((columns, values)) = records.next() {
+            let mut batch = vec![Params::Vector(values)];
+            while let Some((_, values)) = records.next_if(|(next, _)| next == &columns) {
+                batch.push(Params::Vector(values));
+            }
+            let sql = format!("INSERT INTO {} ({}) VALUES ({})", table,
+                columns.iter().map(|column| format!("`{}`", column)).collect::<Vec<_>>().join(", "),
+                columns.iter().map(|_| "?").collect::<Vec<_>>().join(", "));
+            count += transaction.exec_batch(sql, batch)?;
+        }
+        transaction.commit()?;
+        Ok(count)
+    }
+}
+
+fn write_line<W: Write>(writer: &mut W, line: &str) -> Result<(), AkitaError> {
+    writeln!(writer, "{}", line).map_err(|err| AkitaError::DataError(err.to_string()))
+}
+
+/// the value as the text of a field, none for a null.
+fn text(value: &Value) -> Option<String> {
+    match value {
+        Value::Nil => None,
+        Value::Blob(bytes) => Some(base64::encode(bytes)),
+        Value::DateTime(datetime) => Some(datetime.format("%Y-%m-%d %H:%M:%S%.f").to_string()),
+        Value::Timestamp(timestamp) => Some(timestamp.naive_utc().format("%Y-%m-%d %H:%M:%S%.f").to_string()),
+        value => Some(value.to_string()),
+    }
+}
+
+/// the value in a json dump, the numbers and the booleans kept as such and the rest as text.
+fn json(value: &Value) -> serde_json::Value {
+    match value {
+        Value::Bool(v) => (*v).into(),
+        Value::Tinyint(v) => (*v).into(),
+        Value::Smallint(v) => (*v).into(),
+        Value::Int(v) => (*v).into(),
+        Value::Bigint(v) => (*v).into(),
+        Value::Float(v) => (*v).into(),
+        Value::Double(v) => (*v).into(),
+        value => text(value).map(serde_json::Value::String).unwrap_or(serde_json::Value::Null),
+    }
+}
+
+/// the value of a field of a json dump, the arrays and the objects of a dump edited by hand as their json text.
+fn from_json(value: &serde_json::Value) -> Value {
+    match value {
+        serde_json::Value::Null => Value::Nil,
+        serde_json::Value::Bool(v) => Value::Bool(*v),
+        serde_json::Value::Number(v) => v.as_i64().map(Value::Bigint).unwrap_or_else(|| Value::Double(v.as_f64().unwrap_or_default())),
+        serde_json::Value::String(v) => Value::Text(v.to_string()),
+        value => Value::Text(value.to_string()),
+    }
+}
+
+/// the value inlined in an `INSERT` statement.
+fn literal(platform: &Platform, value: &Value) -> String {
+    match value {
+        Value::Nil => "NULL".to_string(),
+        Value::Bool(v) => (if *v { "1" } else { "0" }).to_string(),
+        Value::Tinyint(_) | Value::Smallint(_) | Value::Int(_) | Value::Bigint(_) | Value::Float(_) | Value::Double(_) | Value::BigDecimal(_) => value.to_string(),
+        Value::Blob(bytes) => format!("X'{}'", bytes.iter().map(|byte| format!("{:02X}", byte)).collect::<String>()),
+        value => quote_literal(platform, &text(value).unwrap_or_default()),
+    }
+}
+
+fn quote_literal(platform: &Platform, text: &str) -> String {
+    #[allow(unreachable_patterns)]
+    match platform {
+        #[cfg(feature = "akita-mysql")]
+        Platform::Mysql => format!("'{}'", text.replace('\\', "\\\\").replace('\'', "''")),
+        _ => format!("'{}'", text.replace('\'', "''")),
+    }
+}
+
+/// the field of a csv record, quoted when it holds a separator, a quote or a line break, or when it is empty:
+/// an empty field without quotes is a null.
+fn csv_field(text: Option<&str>) -> String {
+    match text {
+        None => String::new(),
+        Some(text) if text.is_empty() || text.contains([',', '"', '\n', '\r']) => format!("\"{}\"", text.replace('"', "\"\"")),
+        Some(text) => text.to_string(),
+    }
+}
+
+/// the next record of a csv dump, its quoted fields can span several lines.
+fn read_csv_record<R: BufRead>(reader: &mut R) -> Result<Option<Vec<Option<String>>>, AkitaError> {
+    let mut line = String::new();
+    loop {
+        let read = reader.read_line(&mut line).map_err(|err| AkitaError::DataError(err.to_string()))?;
+        if read == 0 || line.matches('"').count().is_multiple_of(2) {
+            break;
+        }
+    }
+    let record = line.trim_end_matches(['\n', '\r']);
+    if record.is_empty() {
+        return if line.is_empty() { Ok(None) } else { read_csv_record(reader) };
+    }
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut in_quotes = false;
+    let mut chars = record.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => {
+                in_quotes = !in_quotes;
+                quoted = true;
+            }
+            ',' if !in_quotes => {
+                fields.push(if field.is_empty() && !quoted { None } else { Some(std::mem::take(&mut field)) });
+                quoted = false;
+            }
+            c => field.push(c),
+        }
+    }
+    if in_quotes {
+        return Err(AkitaError::DataError(format!("a csv record ends inside a quoted field: {}", record)));
+    }
+    fields.push(if field.is_empty() && !quoted { None } else { Some(field) });
+    Ok(Some(fields))
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use crate::{Platform, Value};
+    use super::{csv_field, literal, read_csv_record};
+
+    #[test]
+    fn csv_records() {
+        let line = [csv_field(Some("a,b")), csv_field(None), csv_field(Some("")), csv_field(Some("say \"hi\"\nbye")), csv_field(Some("7"))].join(",");
+        assert_eq!(line, "\"a,b\",,\"\",\"say \"\"hi\"\"\nbye\",7");
+        let mut reader = Cursor::new(format!("{}\r\n\nx\n", line));
+        assert_eq!(read_csv_record(&mut reader).unwrap(), Some(vec![Some("a,b".to_string()), None, Some(String::new()), Some("say \"hi\"\nbye".to_string()), Some("7".to_string())]));
+        assert_eq!(read_csv_record(&mut reader).unwrap(), Some(vec![Some("x".to_string())]));
+        assert_eq!(read_csv_record(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn sql_literals() {
+        let platform = Platform::Unsupported(String::new());
+        assert_eq!(literal(&platform, &Value::Text("it's".to_string())), "'it''s'");
+        assert_eq!(literal(&platform, &Value::Blob(vec![0, 255])), "X'00FF'");
+        assert_eq!(literal(&platform, &Value::Nil), "NULL");
+        assert_eq!(literal(&platform, &Value::Bigint(-3)), "-3");
+    }
+}
@@ -3,7 +3,7 @@
 //! 
 use std::{fmt, str::Utf8Error, string::ParseError};
 
-use crate::ConvertError;
+use crate::{AkitaDataError, ConvertError};
 
 
 #[derive(Debug)]
@@ -22,6 +22,14 @@ pub enum AkitaError {
     RedundantField(String),
     UnknownDatabase(String),
     UnsupportedOperation(String),
+    /// refused by the circuit breaker while the database is failing
+    CircuitOpen(String),
+    /// cancelled on the server once over the statement timeout
+    QueryTimeout(String),
+    /// the record was changed since it was read, its `#[field(version)]` does not match anymore
+    OptimisticLockFailed(String),
+    /// refused by the `StatementPolicy` of the datasource
+    StatementDenied(String),
     Unknown,
 }
 
@@ -43,10 +51,55 @@ impl fmt::Display for AkitaError {
             AkitaError::MySQLError(ref err) => err.fmt(f),
             AkitaError::SQLiteError(ref err) => err.fmt(f),
             AkitaError::R2D2Error(ref err) => err.fmt(f),
+            AkitaError::CircuitOpen(ref err) => write!(f, "Circuit open: {}", err),
+            AkitaError::QueryTimeout(ref err) => write!(f, "Query timeout: {}", err),
+            AkitaError::OptimisticLockFailed(ref err) => write!(f, "Optimistic lock failed: {}", err),
+            AkitaError::StatementDenied(ref err) => write!(f, "Statement denied: {}", err),
         }
     }
 }
 
+impl AkitaError {
+    /// whether the database gave up on a deadlock or a lock wait, the transaction can be run again.
+    pub fn is_deadlock(&self) -> bool {
+        match self {
+            AkitaError::MySQLError(err) | AkitaError::ExcuteSqlError(err, _) => {
+                err.contains("Deadlock found") || err.contains("Lock wait timeout exceeded")
+            }
+            AkitaError::SQLiteError(err) => err.contains("database is locked") || err.contains("database table is locked"),
+            _ => false,
+        }
+    }
+
+    /// whether the statement failed on a lost connection, a deadlock or a serialization failure,
+    /// so running it again may succeed.
+    pub fn is_transient(&self) -> bool {
+        if self.is_deadlock() {
+            return true;
+        }
+        match self {
+            AkitaError::MySQLError(err) | AkitaError::ExcuteSqlError(err, _) | AkitaError::R2D2Error(err) => {
+                let err = err.to_lowercase();
+                ["connection reset", "broken pipe", "server has gone away", "lost connection", "connection refused", "serialization failure", "could not serialize"]
+                    .iter().any(|cause| err.contains(cause))
+            }
+            AkitaError::SQLiteError(err) => err.contains("database is busy"),
+            _ => false,
+        }
+    }
+}
+
+/// Errors telling whether the failed transaction can be run again, see `#[transactional(retry = 3)]`.
+pub trait Retryable {
+    fn is_retryable(&self) -> bool;
+}
+
+impl Retryable for AkitaError {
+    fn is_retryable(&self) -> bool {
+        self.is_deadlock() || matches!(self, AkitaError::OptimisticLockFailed(_))
+    }
+}
+
 #[allow(deprecated, deprecated_in_future)]
 impl std::error::Error for AkitaError {
     fn description(&self) -> &str {
@@ -66,6 +119,10 @@ impl std::error::Error for AkitaError {
             AkitaError::MySQLError(ref err) => err,
             AkitaError::SQLiteError(ref err) => err,
             AkitaError::R2D2Error(ref err) => err,
+            AkitaError::CircuitOpen(ref err) => err,
+            AkitaError::QueryTimeout(ref err) => err,
+            AkitaError::OptimisticLockFailed(ref err) => err,
+            AkitaError::StatementDenied(ref err) => err,
         }
     }
 }
@@ -93,6 +150,16 @@ impl From<ConvertError> for AkitaError {
     }
 }
 
+impl From<AkitaDataError> for AkitaError {
+    fn from(err: AkitaDataError) -> Self {
+        match err {
+            AkitaDataError::ConvertError(err) => AkitaError::from(err),
+            AkitaDataError::NoSuchValueError(err) => AkitaError::DataError(err),
+            AkitaDataError::ObjectValidError(err) => AkitaError::DataError(err),
+        }
+    }
+}
+
 #[cfg(feature = "akita-mysql")]
 impl From<mysql::Error> for AkitaError {
     fn from(err: mysql::Error) -> Self {
@@ -0,0 +1,77 @@
+//!
+//! Values filled in the columns on insert and update.
+//!
+
+use chrono::{Local, SubsecRound};
+
+use crate::{AkitaConfig, FieldName, FieldType, GetFields, ToValue, Value};
+use crate::core::Fill;
+
+/// the columns filled with the current time on insert.
+const CREATED_COLUMNS: [&str; 2] = ["created_at", "create_time"];
+/// the columns filled with the current time on insert and update.
+const UPDATED_COLUMNS: [&str; 2] = ["updated_at", "update_time"];
+
+/// The value of a built-in function of `#[field(fill(function = ..))]`, read each time a statement is built:
+/// `now` the local date and time to the second, `today` the local date, `timestamp` the unix seconds.
+#[doc(hidden)]
+pub fn fill_function(function: &str) -> Value {
+    // to the second of a `DATETIME` column.
+    let now = Local::now().naive_local().trunc_subsecs(0);
+    match function {
+        "now" => now.to_value(),
+        "today" => now.date().to_value(),
+        "timestamp" => Local::now().timestamp().to_value(),
+        _ => Value::Nil,
+    }
+}
+
+/// the fields of the entity, with the timestamp columns named by convention filled when enabled,
+/// see `AkitaConfig::set_auto_timestamps`. The fields filled from an `AkitaContext` key not set keep their value.
+pub(crate) fn fill_fields<T: GetFields>(cfg: &AkitaConfig) -> Vec<FieldName> {
+    let mut fields = T::fields();
+    for field in fields.iter_mut().filter(|field| matches!(&field.fill, Some(fill) if fill.value.is_none())) {
+        field.fill = None;
+    }
+    if cfg.auto_timestamps() {
+        let now = fill_function("now");
+        for field in fields.iter_mut().filter(|field| field.exist && field.fill.is_none() && field.field_type == FieldType::TableField) {
+            let name = field.name.to_lowercase();
+            let mode = if CREATED_COLUMNS.contains(&name.as_str()) {
+                "insert"
+            } else if UPDATED_COLUMNS.contains(&name.as_str()) {
+                "default"
+            } else {
+                continue;
+            };
+            field.fill = Some(Fill { mode: mode.to_string(), value: Some(now.clone()) });
+        }
+    }
+    fields
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{AkitaConfig, FieldName, GetFields, Value};
+    use crate::core::Fill;
+    use super::{fill_fields, fill_function};
+
+    struct Post;
+
+    impl GetFields for Post {
+        fn fields() -> Vec<FieldName> {
+            // filled from a context key not set.
+            let mut created_by = FieldName::from("created_by");
+            created_by.fill = Some(Fill { mode: "insert".to_string(), value: None });
+            vec![FieldName::from("id"), FieldName::from("created_at"), FieldName::from("update_time"), FieldName::from("title"), created_by]
+        }
+    }
+
+    #[test]
+    fn auto_timestamps() {
+        let modes = |cfg: AkitaConfig| fill_fields::<Post>(&cfg).into_iter().map(|field| field.fill.map(|fill| fill.mode)).collect::<Vec<_>>();
+        assert_eq!(modes(AkitaConfig::default()), vec![None, None, None, None, None]);
+        assert_eq!(modes(AkitaConfig::default().set_auto_timestamps(true)), vec![None, Some("insert".to_string()), Some("default".to_string()), None, None]);
+        assert!(matches!((fill_function("now"), fill_function("today"), fill_function("timestamp")), (Value::DateTime(_), Value::Date(_), Value::Bigint(_))));
+    }
+}
@@ -0,0 +1,127 @@
+//!
+//! The foreign keys of the tables, and the rows referring to missing records.
+//!
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Akita, AkitaError, AkitaMapper, FieldType, FromValue, GetFields, GetTableName, Params, TableName, ToValue};
+use crate::ddl::is_sqlite;
+
+/// A foreign key of a table, the columns referring to the ones of another table.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ForeignKeyDef {
+    /// the name of the constraint, SQLite names none
+    pub name: Option<String>,
+    /// the columns of the table, in the order of the key
+    pub columns: Vec<String>,
+    pub referenced_table: String,
+    /// the columns of the referenced table, matched in order with the columns
+    pub referenced_columns: Vec<String>,
+}
+
+impl Akita {
+    /// The foreign keys of the table, read on the primary.
+    pub fn foreign_keys(&self, table: &str) -> Result<Vec<ForeignKeyDef>, AkitaError> {
+        let akita = self.force_master();
+        let table = TableName::from(table);
+        let mut keys: Vec<(String, ForeignKeyDef)> = Vec::new();
+        if is_sqlite(&akita.config().platform()) {
+            for row in akita.exec_iter(format!("PRAGMA foreign_key_list({})", table.complete_name()), Params::Nil)?.iter() {
+                let id = row.get_obj::<i64>("id")?.to_string();
+                let column = row.get_obj::<String>("from")?;
+                let referenced_table = row.get_obj::<String>("table")?;
+                // no `to` is the primary key of the referenced table.
+                let referenced = row.get_obj::<Option<String>>("to")?;
+                push_column(&mut keys, id, None, column, referenced_table, referenced.unwrap_or_default());
+            }
+            for (_, key) in keys.iter_mut().filter(|(_, key)| key.referenced_columns.iter().any(String::is_empty)) {
+                let mut primary = Vec::new();
+                for row in akita.exec_iter(format!("PRAGMA table_info({})", key.referenced_table), Params::Nil)?.iter() {
+                    let pk = row.get_obj::<i64>("pk")?;
+                    if pk > 0 {
+                        primary.push((pk, row.get_obj::<String>("name")?));
+                    }
+                }
+                primary.sort();
+                key.referenced_columns = primary.into_iter().map(|(_, name)| name).collect();
+            }
+        } else {
+            let sql = "SELECT CONSTRAINT_NAME AS name, COLUMN_NAME AS column_name, REFERENCED_TABLE_NAME AS referenced_table, REFERENCED_COLUMN_NAME AS referenced_column \
+                FROM information_schema.key_column_usage WHERE TABLE_SCHEMA = COALESCE(?, DATABASE()) AND TABLE_NAME = ? AND REFERENCED_TABLE_NAME IS NOT NULL \
+                ORDER BY CONSTRAINT_NAME, ORDINAL_POSITION";
+            for row in akita.exec_iter(sql, Params::Vector(vec![table.schema.to_value(), table.name.to_value()]))?.iter() {
+                let name = row.get_obj::<String>("name")?;
+                push_column(&mut keys, name.to_string(), Some(name), row.get_obj("column_name")?, row.get_obj("referenced_table")?, row.get_obj("referenced_column")?);
+            }
+        }
+        Ok(keys.into_iter().map(|(_, key)| key).collect())
+    }
+
+    /// Hand the records of `Child` whose `fk_column` refers to no `Parent` to the closure, streamed for the data-quality jobs.
+    /// The parent column is the one of the foreign key of the column to the table of the parent, its key without one.
+    /// The records with no reference are not orphans, a logically deleted parent still counts. Returns the orphans found.
+    pub fn check_orphans<Child, Parent, F>(&self, fk_column: &str, mut f: F) -> Result<u64, AkitaError>
+        where
+            Child: GetTableName + GetFields + FromValue,
+            Parent: GetTableName + GetFields,
+            F: FnMut(Child) -> Result<(), AkitaError>,
+    {
+        let child = Child::table_name().complete_name();
+        let parent = Parent::table_name().complete_name();
+        if child.is_empty() || parent.is_empty() {
+            return Err(AkitaError::MissingTable("Find Error, Missing Table Name !".to_string()))
+        }
+        let fields = Child::fields();
+        if !fields.iter().any(|field| field.exist && field.name == fk_column) {
+            return Err(AkitaError::MissingField(format!("Table({}) has no column {}", &child, fk_column)))
+        }
+        let declared = self.foreign_keys(&child)?.into_iter()
+            .find(|key| key.columns.len() == 1 && key.columns[0].eq_ignore_ascii_case(fk_column) && TableName::from(key.referenced_table.as_str()).name.eq_ignore_ascii_case(&Parent::table_name().name))
+            .map(|key| key.referenced_columns[0].to_string());
+        let parent_column = match declared {
+            Some(column) => column,
+            None => {
+                let keys = Parent::fields().into_iter().filter(|field| matches!(field.field_type, FieldType::TableId(_))).collect::<Vec<_>>();
+                match keys.as_slice() {
+                    [key] => key.name.to_string(),
+                    _ => return Err(AkitaError::MissingIdent(format!("Table({}) has no single key for {}", &parent, fk_column))),
+                }
+            }
+        };
+        let columns = fields.iter().filter(|field| field.exist).map(|field| field.name.as_str()).collect::<Vec<_>>();
+        let mut orphans = 0;
+        self.exec_stream(orphans_sql(&child, &columns, fk_column, &parent, &parent_column), Params::Nil, |record| {
+            orphans += 1;
+            f(record)
+        })?;
+        Ok(orphans)
+    }
+}
+
+/// add the column to the key of the id, in the order of the rows.
+fn push_column(keys: &mut Vec<(String, ForeignKeyDef)>, id: String, name: Option<String>, column: String, referenced_table: String, referenced_column: String) {
+    match keys.iter_mut().find(|(key_id, _)| *key_id == id) {
+        Some((_, key)) => {
+            key.columns.push(column);
+            key.referenced_columns.push(referenced_column);
+        }
+        None => keys.push((id, ForeignKeyDef { name, columns: vec![column], referenced_table, referenced_columns: vec![referenced_column] })),
+    }
+}
+
+/// the rows of the child table referring to no row of the parent table.
+fn orphans_sql(child: &str, columns: &[&str], fk_column: &str, parent: &str, parent_column: &str) -> String {
+    format!("SELECT {} FROM {} c WHERE c.`{}` IS NOT NULL AND NOT EXISTS (SELECT 1 FROM {} p WHERE p.`{}` = c.`{}`)",
+        columns.iter().map(|column| format!("c.`{}`", column)).collect::<Vec<_>>().join(", "), child, fk_column, parent, parent_column, fk_column)
+}
+
+#[cfg(test)]
+mod test {
+    use super::orphans_sql;
+
+    #[test]
+    fn orphans() {
+        assert_eq!(orphans_sql("t_order", &["id", "user_id"], "user_id", "t_user", "id"),
+            "SELECT c.`id`, c.`user_id` FROM t_order c WHERE c.`user_id` IS NOT NULL AND NOT EXISTS (SELECT 1 FROM t_user p WHERE p.`id` = c.`user_id`)");
+    }
+}
@@ -5,6 +5,7 @@
 use akita_core::{FromValue, GetFields, Rows, ToValue, Value};
 use crate::{Akita, AkitaError, AkitaMapper, IPage, ISegment, Wrapper};
 use crate::database::DatabasePlatform;
+use crate::listener::load;
 
 pub struct Fuse<'a> {
     akita: &'a Akita,
@@ -43,7 +44,7 @@ impl<'a> Fuse<'a> {
         let rows = self.akita.exec_iter(&sql, ())?;
         let mut entities = vec![];
         for data in rows.iter() {
-            let entity = T::from_value(&data);
+            let entity = load::<T>(data)?;
             entities.push(entity)
         }
         Ok(entities)
@@ -68,7 +69,7 @@ impl<'a> Fuse<'a> {
         let where_condition = if where_condition.trim().is_empty() { String::default() } else { format!("WHERE {}",where_condition) };
         let sql = format!("SELECT {} FROM {} {}", &enumerated_columns, &self.table, where_condition);
         let rows = self.akita.exec_iter(&sql, ())?;
-        Ok(rows.iter().next().map(|data| T::from_value(&data)))
+        rows.iter().next().map(load::<T>).transpose()
     }
 
     /// Get table of records with page
@@ -97,7 +98,7 @@ impl<'a> Fuse<'a> {
             let rows = self.akita.exec_iter(&sql, ())?;
             let mut entities = vec![];
             for dao in rows.iter() {
-                let entity = T::from_value(&dao);
+                let entity = load::<T>(dao)?;
                 entities.push(entity)
             }
             page.records = entities;
@@ -202,7 +203,7 @@ impl<'a> Fuse<'a> {
                 .enumerate()
                 .map(|(x, (col, value))| {
                     #[allow(unreachable_patterns)]
-                    match db {
+                    match *db {
                         #[cfg(feature = "akita-mysql")]
                         DatabasePlatform::Mysql(_) => format!("`{}` = {}", col, value.get_sql_segment()),
                         #[cfg(feature = "akita-sqlite")]
@@ -310,7 +311,7 @@ impl<'a> Fuse<'a> {
                         .enumerate()
                         .map(|(x, _)| {
                             #[allow(unreachable_patterns)]
-                            match db {
+                            match *db {
                                 #[cfg(feature = "with-sqlite")]
                                 DatabasePlatform::Sqlite(_) => format!("${}", y * columns_len + x + 1),
                                 #[cfg(feature = "akita-mysql")]
@@ -358,7 +359,7 @@ impl<'a> Fuse<'a> {
                         .enumerate()
                         .map(|(x, _)| {
                             #[allow(unreachable_patterns)]
-                            match db {
+                            match *db {
                                 #[cfg(feature = "with-sqlite")]
                                 DatabasePlatform::Sqlite(_) => format!("${}", y * columns_len + x + 1),
                                 #[cfg(feature = "akita-mysql")]
@@ -0,0 +1,150 @@
+//!
+//! Idempotent writes.
+//!
+
+use std::collections::HashSet;
+use std::sync::Mutex;
+use once_cell::sync::Lazy;
+
+use crate::{Akita, AkitaError, AkitaMapper, FromValue, GetFields, GetTableName, Params, ToKey, ToValue, Wrapper};
+use crate::manager::AkitaTransaction;
+
+/// The table recording the executed idempotency keys.
+pub const IDEMPOTENCY_TABLE: &str = "akita_idempotency";
+
+/// the urls of the databases where the idempotency table was created.
+static CREATED: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+/// Write operations guarded by an idempotency key.
+///
+/// The key is recorded in `akita_idempotency` within the same transaction as the write,
+/// so a retried request with the same key is skipped and returns `None`.
+/// The table is created on first use out of a transaction, see `Akita::create_idempotency_table` for the others.
+pub struct Idempotent<'a> {
+    akita: &'a Akita,
+    key: String,
+}
+
+impl<'a> Idempotent<'a> {
+    pub fn new<S: Into<String>>(akita: &'a Akita, key: S) -> Self {
+        Self { akita, key: key.into() }
+    }
+
+    /// Run the closure once for the key, returns `None` if the key has already been executed.
+    pub fn run<F, R>(&self, f: F) -> Result<Option<R>, AkitaError>
+        where
+            F: FnOnce(&AkitaTransaction) -> Result<R, AkitaError>,
+    {
+        if self.key.is_empty() {
+            return Err(AkitaError::DataError("idempotency key cannot be empty".to_string()))
+        }
+        // within a transaction the table must already be there, creating it would commit the transaction on MySQL.
+        if !self.akita.in_transaction() && !is_created(self.akita) {
+            self.akita.create_idempotency_table()?;
+        }
+        let transaction = self.akita.start_transaction()?;
+        let recorded = {
+            let mut conn = transaction.conn.acquire()?;
+            #[allow(unreachable_patterns)]
+            let sql = match *conn {
+                #[cfg(feature = "akita-mysql")]
+                crate::database::DatabasePlatform::Mysql(_) => format!("INSERT IGNORE INTO {} (idempotency_key) VALUES (?)", IDEMPOTENCY_TABLE),
+                _ => format!("INSERT OR IGNORE INTO {} (idempotency_key) VALUES ($1)", IDEMPOTENCY_TABLE),
+            };
+            conn.execute_drop(&sql, Params::Vector(vec![self.key.to_value()]))?;
+            conn.affected_rows() > 0
+        };
+        if !recorded {
+            transaction.rollback()?;
+            return Ok(None)
+        }
+        let result = f(&transaction)?;
+        transaction.commit()?;
+        Ok(Some(result))
+    }
+
+    /// Save the record once for the key.
+    pub fn save<T, I>(&self, entity: &T) -> Result<Option<I>, AkitaError>
+        where
+            T: GetTableName + GetFields + ToValue,
+            I: FromValue,
+    {
+        self.run(|tx| tx.save(entity)).map(Option::flatten)
+    }
+
+    /// Save the records once for the key.
+    pub fn save_batch<T>(&self, entities: &[&T]) -> Result<Option<()>, AkitaError>
+        where
+            T: GetTableName + GetFields + ToValue,
+    {
+        self.run(|tx| tx.save_batch(entities))
+    }
+
+    /// Upsert the record once for the key.
+    pub fn upsert<T>(&self, entity: &T) -> Result<Option<u64>, AkitaError>
+        where
+            T: GetTableName + GetFields + ToValue,
+    {
+        self.run(|tx| tx.upsert(entity))
+    }
+
+    /// Update the records by wrapper once for the key.
+    pub fn update<T>(&self, entity: &T, wrapper: Wrapper) -> Result<Option<u64>, AkitaError>
+        where
+            T: GetTableName + GetFields + ToValue,
+    {
+        self.run(|tx| tx.update(entity, wrapper))
+    }
+
+    /// Update the record by id once for the key.
+    pub fn update_by_id<T>(&self, entity: &T) -> Result<Option<u64>, AkitaError>
+        where
+            T: GetTableName + GetFields + ToValue,
+    {
+        self.run(|tx| tx.update_by_id(entity))
+    }
+
+    /// Update the records by id once for the key.
+    pub fn update_batch_by_id<T>(&self, entities: &[&T]) -> Result<Option<u64>, AkitaError>
+        where
+            T: GetTableName + GetFields + ToValue,
+    {
+        self.run(|tx| tx.update_batch_by_id(entities))
+    }
+
+    /// Remove the records by wrapper once for the key.
+    pub fn remove<T>(&self, wrapper: Wrapper) -> Result<Option<u64>, AkitaError>
+        where
+            T: GetTableName + GetFields,
+    {
+        self.run(|tx| tx.remove::<T>(wrapper))
+    }
+
+    /// Remove the record by id once for the key.
+    pub fn remove_by_id<T, I>(&self, id: I) -> Result<Option<u64>, AkitaError>
+        where
+            I: ToKey,
+            T: GetTableName + GetFields,
+    {
+        self.run(|tx| tx.remove_by_id::<T, I>(id))
+    }
+}
+
+impl Akita {
+    /// Create `akita_idempotency` unless it exists, for the idempotent writes only run within a transaction.
+    /// It can't be done within one: the DDL would commit it on MySQL.
+    pub fn create_idempotency_table(&self) -> Result<(), AkitaError> {
+        if self.in_transaction() {
+            return Err(AkitaError::DataError(format!("{} can't be created within a transaction", IDEMPOTENCY_TABLE)))
+        }
+        self.exec_result(format!("CREATE TABLE IF NOT EXISTS {} (idempotency_key VARCHAR(255) NOT NULL PRIMARY KEY, created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP)", IDEMPOTENCY_TABLE), Params::Nil)?;
+        if let Ok(mut created) = CREATED.lock() {
+            created.insert(self.config().url());
+        }
+        Ok(())
+    }
+}
+
+fn is_created(akita: &Akita) -> bool {
+    CREATED.lock().map(|created| created.contains(&akita.config().url())).unwrap_or(false)
+}
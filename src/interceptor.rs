@@ -0,0 +1,362 @@
+//!
+//! Interceptors around the statements and transactions.
+//!
+
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::{AkitaError, Params, Rows};
+#[cfg(feature = "akita-tracing")]
+use crate::SensitiveColumns;
+
+#[allow(unused)]
+static TRANSACTION_ID: AtomicU64 = AtomicU64::new(1);
+
+/// A statement executed by akita, as seen by the interceptors once it is done.
+pub struct ExecutedStatement<'a> {
+    pub sql: &'a str,
+    pub params: &'a Params,
+    pub elapsed: Duration,
+    pub error: Option<&'a AkitaError>,
+    /// the transaction the statement ran in, if any
+    pub transaction_id: Option<u64>,
+    /// the rows changed by the statement or read by the query, none when it failed or the driver didn't tell
+    pub affected_rows: Option<u64>,
+    /// the id of the connection on the server, none on the embedded databases
+    pub connection_id: Option<u64>,
+    /// the id of the tracing span of the statement with the `akita-tracing` feature, entered while the interceptors run.
+    /// The OpenTelemetry trace id is read from `Span::current()`, e.g. with `tracing-opentelemetry`.
+    pub span_id: Option<u64>,
+}
+
+/// Where a statement runs.
+#[allow(unused)]
+pub(crate) struct StatementContext {
+    /// the `db.system` of the OpenTelemetry conventions, e.g. `mysql`
+    pub system: &'static str,
+    /// the server host
+    pub peer: Option<String>,
+    /// the datasource, see `AkitaConfig::datasource_key`
+    pub datasource: String,
+    pub transaction_id: Option<u64>,
+    pub connection_id: Option<u64>,
+}
+
+impl<'a> ExecutedStatement<'a> {
+    /// The kind of the statement, after its first keyword.
+    pub fn operation(&self) -> OperationType {
+        OperationType::of(self.sql)
+    }
+}
+
+/// The kind of a statement.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum OperationType {
+    Select,
+    Insert,
+    Update,
+    Delete,
+    /// `CREATE`, `ALTER`, `DROP`, `TRUNCATE` and `RENAME`
+    Ddl,
+    Other,
+}
+
+impl OperationType {
+    pub fn of(sql: &str) -> Self {
+        match sql.split_whitespace().next().unwrap_or_default().to_lowercase().as_str() {
+            "select" | "with" => OperationType::Select,
+            "insert" | "replace" => OperationType::Insert,
+            "update" => OperationType::Update,
+            "delete" => OperationType::Delete,
+            "create" | "alter" | "drop" | "truncate" | "rename" => OperationType::Ddl,
+            _ => OperationType::Other,
+        }
+    }
+}
+
+/// Hooks around each statement and transaction, added with `AkitaConfig::add_interceptor`.
+/// All the hooks are optional.
+pub trait Interceptor: Send + Sync {
+    /// The name the interceptor is listed by, its type by default.
+    fn name(&self) -> String {
+        std::any::type_name::<Self>().to_string()
+    }
+
+    /// Called before the statement is sent, it can rewrite the sql and params, or refuse it with an error.
+    fn before_execute(&self, _sql: &mut String, _params: &mut Params) -> Result<(), AkitaError> {
+        Ok(())
+    }
+
+    /// Called once the statement is done, failed or not.
+    fn after_execute(&self, _statement: &ExecutedStatement) {}
+
+    /// Called before a query is sent to the datasource, after `before_execute`, the rows returned are used instead of running it.
+    /// Not called for the queries in a transaction. The datasource tells apart the ones sharing the interceptor.
+    fn cached_rows(&self, _datasource: &str, _sql: &str, _params: &Params) -> Option<Rows> {
+        None
+    }
+
+    /// Called with the rows read from the datasource by a query out of a transaction.
+    fn after_rows(&self, _datasource: &str, _sql: &str, _params: &Params, _rows: &Rows) {}
+
+    /// Called once the statement failed out of a transaction, with the number of times it ran,
+    /// returns how long to wait before running it again, or none to give up.
+    /// The statements failing in a transaction are never run again, the whole transaction is to be.
+    fn retry_after(&self, _statement: &ExecutedStatement, _attempt: u32) -> Option<Duration> {
+        None
+    }
+
+    /// Called once a transaction has begun on the connection.
+    fn on_transaction_start(&self, _transaction_id: u64) {}
+
+    /// Called once the transaction is committed, the side effects deferred until then can be released.
+    fn on_transaction_commit(&self, _transaction_id: u64) {}
+
+    /// Called once the transaction is rolled back, the side effects deferred until its commit can be dropped.
+    fn on_transaction_rollback(&self, _transaction_id: u64) {}
+}
+
+/// An interceptor shared with the application, which can keep an eye on it.
+impl<I: Interceptor + ?Sized> Interceptor for Arc<I> {
+    fn name(&self) -> String {
+        (**self).name()
+    }
+
+    fn before_execute(&self, sql: &mut String, params: &mut Params) -> Result<(), AkitaError> {
+        (**self).before_execute(sql, params)
+    }
+
+    fn after_execute(&self, statement: &ExecutedStatement) {
+        (**self).after_execute(statement)
+    }
+
+    fn cached_rows(&self, datasource: &str, sql: &str, params: &Params) -> Option<Rows> {
+        (**self).cached_rows(datasource, sql, params)
+    }
+
+    fn after_rows(&self, datasource: &str, sql: &str, params: &Params, rows: &Rows) {
+        (**self).after_rows(datasource, sql, params, rows)
+    }
+
+    fn retry_after(&self, statement: &ExecutedStatement, attempt: u32) -> Option<Duration> {
+        (**self).retry_after(statement, attempt)
+    }
+
+    fn on_transaction_start(&self, transaction_id: u64) {
+        (**self).on_transaction_start(transaction_id)
+    }
+
+    fn on_transaction_commit(&self, transaction_id: u64) {
+        (**self).on_transaction_commit(transaction_id)
+    }
+
+    fn on_transaction_rollback(&self, transaction_id: u64) {
+        (**self).on_transaction_rollback(transaction_id)
+    }
+}
+
+/// The interceptors of a config, run in the order they were added.
+#[derive(Clone, Default)]
+pub struct Interceptors(Arc<Vec<Arc<dyn Interceptor>>>);
+
+#[allow(unused)]
+impl Interceptors {
+    pub(crate) fn push(&mut self, interceptor: Arc<dyn Interceptor>) {
+        let mut interceptors = self.0.as_ref().clone();
+        interceptors.push(interceptor);
+        self.0 = Arc::new(interceptors);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The names of the interceptors, in the order they run.
+    pub fn names(&self) -> Vec<String> {
+        self.0.iter().map(|interceptor| interceptor.name()).collect()
+    }
+
+    /// run the statement through the interceptors.
+    pub(crate) fn intercept<R, F>(&self, sql: &str, params: Params, transaction_id: Option<u64>, mut f: F) -> Result<R, AkitaError>
+        where
+            F: FnMut(&str, Params) -> Result<R, AkitaError>,
+    {
+        let context = StatementContext { system: "other_sql", peer: None, datasource: String::new(), transaction_id, connection_id: None };
+        self.intercept_counted(sql, params, context, |sql, params| f(sql, params).map(|result| (result, None)))
+    }
+
+    /// run the statement through the interceptors in its span, the closure also returns the rows it affected.
+    pub(crate) fn intercept_counted<R, F>(&self, sql: &str, params: Params, context: StatementContext, mut f: F) -> Result<R, AkitaError>
+        where
+            F: FnMut(&str, Params) -> Result<(R, Option<u64>), AkitaError>,
+    {
+        #[cfg(feature = "akita-tracing")]
+        let span = statement_span(sql, &context);
+        #[cfg(feature = "akita-tracing")]
+        let _entered = span.enter();
+        #[cfg(feature = "akita-tracing")]
+        let span_id = span.id().map(|id| id.into_u64());
+        #[cfg(not(feature = "akita-tracing"))]
+        let span_id = None;
+        if self.is_empty() {
+            let result = f(sql, params);
+            #[cfg(feature = "akita-tracing")]
+            record_outcome(&span, &result);
+            return result.map(|(result, _)| result);
+        }
+        let mut sql = sql.to_string();
+        let mut params = params;
+        for interceptor in self.0.iter() {
+            interceptor.before_execute(&mut sql, &mut params)?;
+        }
+        #[cfg(feature = "akita-tracing")]
+        span.record("db.statement", SensitiveColumns::global().mask_sql(&sql).as_ref());
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let start = Instant::now();
+            let result = f(&sql, params.clone());
+            #[cfg(feature = "akita-tracing")]
+            record_outcome(&span, &result);
+            let statement = ExecutedStatement {
+                sql: &sql,
+                params: &params,
+                elapsed: start.elapsed(),
+                error: result.as_ref().err(),
+                transaction_id: context.transaction_id,
+                affected_rows: result.as_ref().ok().and_then(|(_, affected_rows)| *affected_rows),
+                connection_id: context.connection_id,
+                span_id,
+            };
+            for interceptor in self.0.iter() {
+                interceptor.after_execute(&statement);
+            }
+            let retry = match context.transaction_id {
+                None if result.is_err() => self.0.iter().find_map(|interceptor| interceptor.retry_after(&statement, attempt)),
+                _ => None,
+            };
+            match retry {
+                Some(delay) => std::thread::sleep(delay),
+                None => return result.map(|(result, _)| result),
+            }
+        }
+    }
+
+    /// run the query through the interceptors, its rows may be read from or kept by them out of a transaction.
+    pub(crate) fn intercept_rows<F>(&self, sql: &str, params: Params, context: StatementContext, mut f: F) -> Result<Rows, AkitaError>
+        where
+            F: FnMut(&str, Params) -> Result<(Rows, Option<u64>), AkitaError>,
+    {
+        if self.is_empty() || context.transaction_id.is_some() || OperationType::of(sql) != OperationType::Select {
+            return self.intercept_counted(sql, params, context, f);
+        }
+        let datasource = context.datasource.clone();
+        self.intercept_counted(sql, params, context, |sql, params| {
+            if let Some(rows) = self.0.iter().find_map(|interceptor| interceptor.cached_rows(&datasource, sql, &params)) {
+                let count = rows.data.len() as u64;
+                return Ok((rows, Some(count)));
+            }
+            let (rows, affected_rows) = f(sql, params.clone())?;
+            self.0.iter().for_each(|interceptor| interceptor.after_rows(&datasource, sql, &params, &rows));
+            Ok((rows, affected_rows))
+        })
+    }
+
+    /// a new transaction id, announced to the interceptors.
+    pub(crate) fn transaction_started(&self) -> u64 {
+        let transaction_id = TRANSACTION_ID.fetch_add(1, Ordering::Relaxed);
+        self.0.iter().for_each(|interceptor| interceptor.on_transaction_start(transaction_id));
+        transaction_id
+    }
+
+    pub(crate) fn transaction_committed(&self, transaction_id: Option<u64>) {
+        if let Some(transaction_id) = transaction_id {
+            self.0.iter().for_each(|interceptor| interceptor.on_transaction_commit(transaction_id));
+        }
+    }
+
+    pub(crate) fn transaction_rolled_back(&self, transaction_id: Option<u64>) {
+        if let Some(transaction_id) = transaction_id {
+            self.0.iter().for_each(|interceptor| interceptor.on_transaction_rollback(transaction_id));
+        }
+    }
+}
+
+/// the span of a statement, with the fields of the OpenTelemetry conventions for the databases.
+#[cfg(feature = "akita-tracing")]
+fn statement_span(sql: &str, context: &StatementContext) -> tracing::Span {
+    tracing::info_span!("akita.statement",
+        otel.name = ?OperationType::of(sql),
+        otel.kind = "client",
+        otel.status_code = tracing::field::Empty,
+        otel.status_description = tracing::field::Empty,
+        db.system = context.system,
+        db.statement = SensitiveColumns::global().mask_sql(sql).as_ref(),
+        db.rows_affected = tracing::field::Empty,
+        net.peer.name = context.peer.as_deref(),
+        akita.transaction_id = context.transaction_id,
+        akita.connection_id = context.connection_id,
+    )
+}
+
+#[cfg(feature = "akita-tracing")]
+fn record_outcome<R>(span: &tracing::Span, result: &Result<(R, Option<u64>), AkitaError>) {
+    match result {
+        Ok((_, Some(affected_rows))) => {
+            span.record("db.rows_affected", affected_rows);
+        }
+        Ok(_) => {}
+        Err(error) => {
+            span.record("otel.status_code", "ERROR");
+            span.record("otel.status_description", error.to_string().as_str());
+        }
+    }
+}
+
+impl fmt::Debug for Interceptors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Interceptors({})", self.0.len())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+    use crate::{AkitaError, Params};
+    use super::{ExecutedStatement, Interceptor, Interceptors};
+
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl Interceptor for Recorder {
+        fn before_execute(&self, sql: &mut String, _params: &mut Params) -> Result<(), AkitaError> {
+            sql.push_str(" LIMIT 1");
+            Ok(())
+        }
+
+        fn after_execute(&self, statement: &ExecutedStatement) {
+            self.0.lock().unwrap().push(format!("{:?} {}", statement.transaction_id, statement.sql));
+        }
+
+        fn on_transaction_commit(&self, transaction_id: u64) {
+            self.0.lock().unwrap().push(format!("commit {}", transaction_id));
+        }
+    }
+
+    #[test]
+    fn intercept_statements_and_transactions() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut interceptors = Interceptors::default();
+        interceptors.push(Arc::new(Recorder(events.clone())));
+        let transaction_id = interceptors.transaction_started();
+        let sql = interceptors.intercept("SELECT 1", Params::Nil, Some(transaction_id), |sql, _| Ok(sql.to_string())).unwrap();
+        assert_eq!(sql, "SELECT 1 LIMIT 1");
+        interceptors.transaction_committed(Some(transaction_id));
+        interceptors.transaction_rolled_back(None);
+        assert_eq!(*events.lock().unwrap(), vec![
+            format!("Some({}) SELECT 1 LIMIT 1", transaction_id),
+            format!("commit {}", transaction_id),
+        ]);
+    }
+}
@@ -0,0 +1,90 @@
+//!
+//! Keys of the records, of one `#[table_id]` field or several for a composite key.
+//!
+
+use crate::{AkitaError, FieldName, FieldType, GetFields, GetTableName, ToKey, Value};
+use crate::database::DatabasePlatform;
+
+/// the id fields of the entity, in their order.
+pub(crate) fn key_fields<T: GetTableName + GetFields>() -> Result<Vec<FieldName>, AkitaError> {
+    let keys = T::fields().into_iter().filter(|field| matches!(field.field_type, FieldType::TableId(_))).collect::<Vec<_>>();
+    if keys.is_empty() {
+        return Err(AkitaError::MissingIdent(format!("Table({}) Missing Ident...", &T::table_name().name)));
+    }
+    Ok(keys)
+}
+
+/// the id field of the entity, for the queries working on one key column only.
+pub(crate) fn single_key_field<T: GetTableName + GetFields>() -> Result<FieldName, AkitaError> {
+    let mut keys = key_fields::<T>()?;
+    if keys.len() > 1 {
+        return Err(AkitaError::MissingIdent(format!("Table({}) has a composite key of {} id fields, a single one is needed", &T::table_name().name, keys.len())));
+    }
+    Ok(keys.remove(0))
+}
+
+/// the condition on the key columns, the SQLite parameters numbered from `offset + 1`.
+pub(crate) fn key_clause(platform: &DatabasePlatform, keys: &[FieldName], offset: usize) -> String {
+    keys.iter().enumerate().map(|(x, key)| {
+        #[allow(unreachable_patterns)]
+        match *platform {
+            #[cfg(feature = "akita-mysql")]
+            DatabasePlatform::Mysql(_) => format!("`{}` = ?", &key.name),
+            _ => format!("`{}` = ${}", &key.name, offset + x + 1),
+        }
+    }).collect::<Vec<_>>().join(" and ")
+}
+
+/// the values of the id, one per key column.
+pub(crate) fn key_values<T: GetTableName, I: ToKey>(keys: &[FieldName], id: &I) -> Result<Vec<Value>, AkitaError> {
+    let values = id.to_key();
+    if values.len() != keys.len() {
+        return Err(AkitaError::MissingIdent(format!("Table({}) has {} id fields, {} values given", &T::table_name().name, keys.len(), values.len())));
+    }
+    Ok(values)
+}
+
+/// the condition matching the record with the id, and its values.
+pub(crate) fn key_condition<T, I>(platform: &DatabasePlatform, id: &I, offset: usize) -> Result<(String, Vec<Value>), AkitaError>
+    where
+        T: GetTableName + GetFields,
+        I: ToKey,
+{
+    let keys = key_fields::<T>()?;
+    let values = key_values::<T, I>(&keys, id)?;
+    Ok((key_clause(platform, &keys, offset), values))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{FieldName, FieldType, GetFields, GetTableName, TableName, ToKey, Value};
+    use super::{key_fields, key_values, single_key_field};
+
+    struct Member;
+
+    impl GetTableName for Member {
+        fn table_name() -> TableName {
+            TableName::from("t_member")
+        }
+    }
+
+    impl GetFields for Member {
+        fn fields() -> Vec<FieldName> {
+            let key = |name: &str| {
+                let mut key = FieldName::from(name);
+                key.field_type = FieldType::TableId("none".to_string());
+                key
+            };
+            vec![key("org_id"), key("login"), FieldName::from("role")]
+        }
+    }
+
+    #[test]
+    fn composite_key() {
+        let keys = key_fields::<Member>().unwrap();
+        assert_eq!(keys.iter().map(|key| key.name.as_str()).collect::<Vec<_>>(), vec!["org_id", "login"]);
+        assert_eq!((1, "alice").to_key(), vec![Value::Int(1), Value::Text("alice".to_string())]);
+        assert!(key_values::<Member, _>(&keys, &1).is_err());
+        assert!(single_key_field::<Member>().is_err());
+    }
+}
@@ -0,0 +1,174 @@
+//!
+//! A key-value store in a table.
+//!
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use once_cell::sync::OnceCell;
+
+use crate::{Akita, AkitaError, AkitaMapper, Params, ToValue};
+use crate::ddl::{check_identifier, is_sqlite, now_millis};
+
+/// A cached value, `None` for a missing key.
+struct CachedValue {
+    value: Option<String>,
+    /// when the value expires in the table, in milliseconds since the epoch
+    expires_at: Option<i64>,
+    cached_until: Instant,
+}
+
+/// The text values of a table by key, e.g. the settings of an application, read and written on the primary.
+///
+/// The table is created on first use with the `kv_key`, `kv_value` and `expires_at` columns,
+/// the expiry in milliseconds since the epoch. The expired keys read as missing until `purge_expired` deletes them.
+/// With `set_cache`, the values are kept in memory for a while, the writes of the other processes seen after it.
+pub struct KvStore {
+    akita: Akita,
+    table: String,
+    ttl: Option<Duration>,
+    cache: Option<Duration>,
+    cached: Mutex<HashMap<String, CachedValue>>,
+    created: OnceCell<()>,
+}
+
+impl KvStore {
+    pub fn new<S: Into<String>>(akita: &Akita, table: S) -> Self {
+        KvStore { akita: akita.force_master(), table: table.into(), ttl: None, cache: None, cached: Mutex::new(HashMap::new()), created: OnceCell::new() }
+    }
+
+    /// How long the values set live, forever by default.
+    pub fn set_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl.into();
+        self
+    }
+
+    /// How long the values read stay in memory, none are by default.
+    pub fn set_cache(mut self, cache: Duration) -> Self {
+        self.cache = cache.into();
+        self
+    }
+
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+
+    pub fn get(&self, key: &str) -> Result<Option<String>, AkitaError> {
+        let now = now_millis();
+        if let Some((value, expires_at)) = self.cached.lock().ok().and_then(|cached| cached.get(key).filter(|cached| cached.cached_until > Instant::now()).map(|cached| (cached.value.clone(), cached.expires_at))) {
+            return Ok(value.filter(|_| expires_at.is_none_or(|expires_at| expires_at > now)));
+        }
+        self.create()?;
+        let rows = self.akita.exec_iter(format!("SELECT kv_value, expires_at FROM {} WHERE kv_key = ? AND (expires_at IS NULL OR expires_at > ?)", self.table),
+            Params::Vector(vec![key.to_value(), now.to_value()]))?;
+        let (value, expires_at) = match rows.iter().next() {
+            Some(row) => (row.get_obj::<Option<String>>("kv_value")?, row.get_obj::<Option<i64>>("expires_at")?),
+            None => (None, None),
+        };
+        self.cache(key, value.clone(), expires_at);
+        Ok(value)
+    }
+
+    /// Set the value of the key, living for the ttl of the store.
+    pub fn set(&self, key: &str, value: &str) -> Result<(), AkitaError> {
+        self.set_with_ttl(key, value, self.ttl)
+    }
+
+    /// Set the value of the key living for the ttl, forever without one.
+    pub fn set_with_ttl(&self, key: &str, value: &str, ttl: Option<Duration>) -> Result<(), AkitaError> {
+        self.create()?;
+        let expires_at = ttl.map(|ttl| now_millis() + ttl.as_millis() as i64);
+        let sql = if is_sqlite(&self.akita.config().platform()) {
+            format!("INSERT INTO {} (kv_key, kv_value, expires_at) VALUES (?, ?, ?) ON CONFLICT(kv_key) DO UPDATE SET kv_value = excluded.kv_value, expires_at = excluded.expires_at", self.table)
+        } else {
+            format!("INSERT INTO {} (kv_key, kv_value, expires_at) VALUES (?, ?, ?) ON DUPLICATE KEY UPDATE kv_value = VALUES(kv_value), expires_at = VALUES(expires_at)", self.table)
+        };
+        self.akita.exec_result(sql, Params::Vector(vec![key.to_value(), value.to_value(), expires_at.to_value()]))?;
+        self.cache(key, Some(value.to_string()), expires_at);
+        Ok(())
+    }
+
+    /// Delete the key, returns whether it had a value.
+    pub fn delete(&self, key: &str) -> Result<bool, AkitaError> {
+        self.create()?;
+        let result = self.akita.exec_result(format!("DELETE FROM {} WHERE kv_key = ? AND (expires_at IS NULL OR expires_at > ?)", self.table),
+            Params::Vector(vec![key.to_value(), now_millis().to_value()]))?;
+        if result.affected_rows == 0 {
+            // an expired value goes too.
+            self.akita.exec_result(format!("DELETE FROM {} WHERE kv_key = ?", self.table), Params::Vector(vec![key.to_value()]))?;
+        }
+        self.cache(key, None, None);
+        Ok(result.affected_rows > 0)
+    }
+
+    /// Set the value of the key only if it is still the expected one, `None` for a missing key.
+    /// Returns whether the value was set, the other writers' having won otherwise.
+    pub fn compare_and_swap(&self, key: &str, expected: Option<&str>, value: &str) -> Result<bool, AkitaError> {
+        self.create()?;
+        let now = now_millis();
+        let expires_at = self.ttl.map(|ttl| now + ttl.as_millis() as i64);
+        let swapped = match expected {
+            None => {
+                self.akita.exec_result(format!("DELETE FROM {} WHERE kv_key = ? AND expires_at <= ?", self.table), Params::Vector(vec![key.to_value(), now.to_value()]))?;
+                let sql = if is_sqlite(&self.akita.config().platform()) {
+                    format!("INSERT OR IGNORE INTO {} (kv_key, kv_value, expires_at) VALUES (?, ?, ?)", self.table)
+                } else {
+                    format!("INSERT IGNORE INTO {} (kv_key, kv_value, expires_at) VALUES (?, ?, ?)", self.table)
+                };
+                self.akita.exec_result(sql, Params::Vector(vec![key.to_value(), value.to_value(), expires_at.to_value()]))?.affected_rows > 0
+            }
+            Some(expected) => {
+                let result = self.akita.exec_result(format!("UPDATE {} SET kv_value = ?, expires_at = ? WHERE kv_key = ? AND kv_value = ? AND (expires_at IS NULL OR expires_at > ?)", self.table),
+                    Params::Vector(vec![value.to_value(), expires_at.to_value(), key.to_value(), expected.to_value(), now.to_value()]))?;
+                // MySQL counts the rows changed, none when the value was already the new one.
+                result.affected_rows > 0 || (expected == value && self.get_fresh(key)?.as_deref() == Some(value))
+            }
+        };
+        if swapped {
+            self.cache(key, Some(value.to_string()), expires_at);
+        } else {
+            self.forget(key);
+        }
+        Ok(swapped)
+    }
+
+    /// Delete the expired keys, returns how many.
+    pub fn purge_expired(&self) -> Result<u64, AkitaError> {
+        self.create()?;
+        let result = self.akita.exec_result(format!("DELETE FROM {} WHERE expires_at <= ?", self.table), Params::Vector(vec![now_millis().to_value()]))?;
+        Ok(result.affected_rows)
+    }
+
+    /// the value in the table, not the cached one.
+    fn get_fresh(&self, key: &str) -> Result<Option<String>, AkitaError> {
+        self.forget(key);
+        self.get(key)
+    }
+
+    fn create(&self) -> Result<(), AkitaError> {
+        self.created.get_or_try_init(|| {
+            check_identifier("key-value table name", &self.table)?;
+            self.akita.exec_result(format!("CREATE TABLE IF NOT EXISTS {} (kv_key VARCHAR(255) NOT NULL PRIMARY KEY, kv_value TEXT, expires_at BIGINT)", self.table), Params::Nil)?;
+            Ok(())
+        }).map(|_| ())
+    }
+
+    fn cache(&self, key: &str, value: Option<String>, expires_at: Option<i64>) {
+        if let (Some(cache), Ok(mut cached)) = (self.cache, self.cached.lock()) {
+            cached.insert(key.to_string(), CachedValue { value, expires_at, cached_until: Instant::now() + cache });
+        }
+    }
+
+    fn forget(&self, key: &str) {
+        if let Ok(mut cached) = self.cached.lock() {
+            cached.remove(key);
+        }
+    }
+}
+
+impl Akita {
+    /// The key-value store of the table, see `KvStore`.
+    pub fn kv<S: Into<String>>(&self, table: S) -> KvStore {
+        KvStore::new(self, table)
+    }
+}
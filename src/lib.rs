@@ -189,27 +189,157 @@ mod manager;
 #[cfg(feature = "akita-fuse")]
 mod fuse;
 mod akita;
+mod idempotent;
+mod cache;
+mod result_cache;
+mod script;
+mod seeder;
+mod interceptor;
+mod retry;
+mod breaker;
+mod slow_query;
+mod audit;
+mod tenant;
+mod table_name;
+mod pagination;
+mod logic_delete;
+mod optimistic_lock;
+mod fill;
+mod context;
+mod key;
+mod diff;
+mod relation;
+mod set_operation;
+mod cte;
+mod sql_format;
+mod masking;
+mod session_context;
+mod synchronization;
+mod watchdog;
+mod compress;
+mod partition;
+mod temporary;
+mod bench;
+mod policy;
+mod listener;
+mod dump;
+mod ddl;
+mod schema_sync;
+mod schema;
+mod foreign_key;
+mod profile;
+mod kv;
+mod column;
+mod queue;
+mod materialized_view;
+#[cfg(feature = "akita-admin")]
+mod admin;
 
 
 #[doc(inline)]
-pub use wrapper::Wrapper;
+pub use wrapper::{CommonTableExpression, SetOperation, Wrapper};
 #[doc(inline)]
 pub use database::Platform;
 #[doc(inline)]
-pub use mapper::{BaseMapper, IPage, AkitaMapper};
+pub use mapper::{BaseMapper, IPage, AkitaMapper, ChunkProgress, ExecuteResult, SqlWarning, TableChecksum, TreeNode, UpsertResult};
 #[doc(inline)]
 pub use segment::{Segment, AkitaKeyword, ISegment};
 #[doc(inline)]
-pub use errors::AkitaError;
+pub use errors::{AkitaError, Retryable};
 #[doc(inline)]
-pub use pool::{AkitaConfig, LogLevel, Pool};
+pub use pool::{AkitaConfig, DEFAULT_FETCH_SIZE, FailoverPolicy, LogLevel, Pool, PoolConfig, PoolStatus, ReadConsistency, ReplicaRouter, TlsOptions, TlsVerifyMode};
 #[cfg(feature = "akita-auth")]
 pub use auth::*;
 #[cfg(feature = "akita-fuse")]
 pub use fuse::*;
 pub use akita::*;
 #[doc(inline)]
-pub use manager::{AkitaEntityManager};
+pub use manager::{AkitaEntityManager, AkitaTransaction, IsolationLevel, TransactionOptions, XaTransaction};
+#[doc(inline)]
+pub use cache::{CachePolicy, DEFAULT_QUERY_CACHE_CAPACITY, QueryCache};
+#[doc(inline)]
+pub use result_cache::{CacheBackend, CacheInterceptor, LruCacheBackend};
+#[doc(inline)]
+pub use idempotent::{Idempotent, IDEMPOTENCY_TABLE};
+#[doc(inline)]
+pub use script::{split_script, ScriptStatement};
+#[doc(inline)]
+pub use seeder::{Seeder, SeederRegistry, SEEDER_TABLE};
+#[doc(inline)]
+pub use interceptor::{ExecutedStatement, Interceptor, Interceptors, OperationType};
+#[doc(inline)]
+pub use retry::RetryInterceptor;
+#[doc(inline)]
+pub use breaker::{CircuitBreakerInterceptor, CircuitState};
+#[doc(inline)]
+pub use slow_query::{fingerprint, SlowQueryInterceptor, SlowQueryStats};
+#[doc(inline)]
+pub use audit::{AuditInterceptor, AuditRecord, AUDIT_TABLE};
+#[doc(inline)]
+pub use tenant::TenantLineInterceptor;
+#[doc(inline)]
+pub use table_name::DynamicTableNameInterceptor;
+#[doc(inline)]
+pub use pagination::PaginationOptions;
+#[doc(inline)]
+pub use logic_delete::{LOGIC_DELETED, LOGIC_NOT_DELETED};
+#[doc(inline)]
+pub use sql_format::SqlFormat;
+#[doc(inline)]
+pub use masking::{SensitiveColumns, MASK};
+#[doc(inline)]
+pub use diff::{entity_diff, FieldChange};
+#[doc(inline)]
+pub use relation::{Related, Relation};
+#[doc(inline)]
+pub use synchronization::TransactionSynchronization;
+#[doc(inline)]
+pub use session_context::SESSION_TABLE;
+#[doc(hidden)]
+pub use fill::fill_function;
+#[doc(inline)]
+pub use context::AkitaContext;
+#[doc(inline)]
+pub use compress::{compress_value, decompress_value, Compression, COMPRESSED_MAGIC};
+#[doc(inline)]
+pub use partition::{PartitionInfo, Partitions};
+#[doc(inline)]
+pub use temporary::TempTable;
+#[doc(inline)]
+pub use bench::{Bench, BenchReport, QueryReport};
+#[doc(inline)]
+pub use policy::StatementPolicy;
+#[doc(inline)]
+pub use listener::{EntityListener, EntityListeners};
+#[doc(inline)]
+pub use dump::Dump;
+#[doc(inline)]
+pub use ddl::TableDdl;
+#[doc(inline)]
+pub use schema_sync::{SchemaSync, SchemaSyncMode};
+#[doc(inline)]
+pub use schema::{ColumnSnapshot, SchemaChange, SchemaSnapshot, TableSnapshot, covers_any};
+#[doc(inline)]
+pub use foreign_key::ForeignKeyDef;
+#[doc(inline)]
+pub use profile::ColumnProfile;
+#[doc(inline)]
+pub use kv::KvStore;
+#[doc(inline)]
+pub use column::{Column, ColumnValue};
+#[doc(inline)]
+pub use queue::{DbQueue, DeadLetter, QueueMessage};
+#[doc(inline)]
+pub use materialized_view::{MaterializedView, MATERIALIZED_VIEW_TABLE};
+#[cfg(feature = "akita-admin")]
+#[doc(inline)]
+pub use admin::{AdminConsole, AdminRequest, AdminResponse};
+#[cfg(feature = "akita-mysql")]
+#[doc(inline)]
+pub use platform::mysql::{MysqlConnection, MysqlConnectionManager};
+#[cfg(feature = "akita-sqlite")]
+#[doc(inline)]
+pub use platform::sqlite::SqliteConnectionManager;
 #[doc(inline)]
 pub use chrono::{Local, NaiveDate, NaiveDateTime};
 // Re-export #[derive(AkitaTable)].
@@ -226,7 +356,7 @@ pub use akita_core as core;
 
 pub use akita_core::*;
 
-pub use crate::core::{FieldName, FieldType, GetFields, GetTableName, Table, ToValue, FromValue};
+pub use crate::core::{FieldName, FieldType, GetFields, GetTableName, Table, ToKey, ToValue, FromValue};
 
 pub use akita_core::*;
 
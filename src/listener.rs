@@ -0,0 +1,163 @@
+//!
+//! Hooks run by the mappers around the records of an entity they save, update, remove and load.
+//!
+
+use std::any::type_name;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use once_cell::sync::Lazy;
+
+use crate::{AkitaError, FromValue, Value};
+
+static ENTITY_LISTENERS: Lazy<EntityListeners> = Lazy::new(EntityListeners::new);
+
+/// Hooks around the records of an entity, registered with `EntityListeners::global().register::<User, _>(listener)`.
+/// All the hooks are optional.
+///
+/// The records are seen as the columns read from the entity with `ToValue`, or read from the rows before they are
+/// turned into the entity, so a hook can normalize them or fill derived columns. The `before_` hooks can refuse
+/// the statement with an error. The removes and updates by a wrapper don't know their records, the hooks are not run.
+pub trait EntityListener: Send + Sync {
+    /// Called with the columns of the record about to be inserted by `save`, `save_batch` or the upserts,
+    /// which are seen as inserts even when they update the existing record.
+    fn before_insert(&self, _data: &mut Value) -> Result<(), AkitaError> {
+        Ok(())
+    }
+
+    /// Called once the record is inserted.
+    fn after_insert(&self, _data: &Value) {}
+
+    /// Called with the columns of the record about to be updated by `update` or `update_by_id`.
+    fn before_update(&self, _data: &mut Value) -> Result<(), AkitaError> {
+        Ok(())
+    }
+
+    /// Called once the record is updated.
+    fn after_update(&self, _data: &Value) {}
+
+    /// Called with the key of the record about to be removed by `remove_by_id`.
+    fn before_remove(&self, _id: &[Value]) -> Result<(), AkitaError> {
+        Ok(())
+    }
+
+    /// Called once the record is removed.
+    fn after_remove(&self, _id: &[Value]) {}
+
+    /// Called with each row read before it is turned into the entity.
+    fn after_load(&self, _data: &mut Value) {}
+}
+
+/// The listeners of the entities, by the type of the entity.
+pub struct EntityListeners {
+    listeners: RwLock<HashMap<&'static str, Vec<Arc<dyn EntityListener>>>>,
+}
+
+impl EntityListeners {
+    fn new() -> Self {
+        EntityListeners { listeners: RwLock::new(HashMap::new()) }
+    }
+
+    /// The listeners shared by the whole process.
+    pub fn global() -> &'static EntityListeners {
+        &ENTITY_LISTENERS
+    }
+
+    /// Run the listener around the records of `T`, after the ones already registered.
+    pub fn register<T, L: EntityListener + 'static>(&self, listener: L) {
+        if let Ok(mut listeners) = self.listeners.write() {
+            listeners.entry(type_name::<T>()).or_default().push(Arc::new(listener));
+        }
+    }
+
+    /// Drop the listeners of `T`.
+    pub fn clear<T>(&self) {
+        if let Ok(mut listeners) = self.listeners.write() {
+            listeners.remove(type_name::<T>());
+        }
+    }
+
+    /// the listeners of `T`, out of the lock while they run.
+    fn of<T>(&self) -> Vec<Arc<dyn EntityListener>> {
+        self.listeners.read().ok().and_then(|listeners| listeners.get(type_name::<T>()).cloned()).unwrap_or_default()
+    }
+}
+
+pub(crate) fn before_insert<T>(data: &mut Value) -> Result<(), AkitaError> {
+    EntityListeners::global().of::<T>().iter().try_for_each(|listener| listener.before_insert(data))
+}
+
+pub(crate) fn after_insert<T>(data: &Value) {
+    EntityListeners::global().of::<T>().iter().for_each(|listener| listener.after_insert(data))
+}
+
+pub(crate) fn before_update<T>(data: &mut Value) -> Result<(), AkitaError> {
+    EntityListeners::global().of::<T>().iter().try_for_each(|listener| listener.before_update(data))
+}
+
+pub(crate) fn after_update<T>(data: &Value) {
+    EntityListeners::global().of::<T>().iter().for_each(|listener| listener.after_update(data))
+}
+
+pub(crate) fn before_remove<T>(id: &[Value]) -> Result<(), AkitaError> {
+    EntityListeners::global().of::<T>().iter().try_for_each(|listener| listener.before_remove(id))
+}
+
+pub(crate) fn after_remove<T>(id: &[Value]) {
+    EntityListeners::global().of::<T>().iter().for_each(|listener| listener.after_remove(id))
+}
+
+/// the entity of the row, once seen by the listeners.
+pub(crate) fn load<T: FromValue>(mut data: Value) -> Result<T, AkitaError> {
+    EntityListeners::global().of::<T>().iter().for_each(|listener| listener.after_load(&mut data));
+    Ok(T::from_value_opt(&data)?)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{AkitaDataError, AkitaError, FromValue, Value};
+    use super::{before_insert, load, EntityListener, EntityListeners};
+
+    struct Tag {
+        name: String,
+        slug: String,
+    }
+
+    impl FromValue for Tag {
+        fn from_value_opt(v: &Value) -> Result<Self, AkitaDataError> {
+            Ok(Tag { name: v.get_obj("name")?, slug: v.get_obj("slug")? })
+        }
+    }
+
+    struct Slugs;
+
+    impl EntityListener for Slugs {
+        fn before_insert(&self, data: &mut Value) -> Result<(), AkitaError> {
+            let name = data.get_obj::<String>("name")?;
+            if name.is_empty() {
+                return Err(AkitaError::DataError("the tag has no name".to_string()));
+            }
+            data.insert_obj("name", name.trim());
+            Ok(())
+        }
+
+        fn after_load(&self, data: &mut Value) {
+            let slug = data.get_obj::<String>("name").unwrap_or_default().to_lowercase().replace(' ', "-");
+            data.insert_obj("slug", slug);
+        }
+    }
+
+    #[test]
+    fn listeners() {
+        EntityListeners::global().register::<Tag, _>(Slugs);
+        let mut data = Value::new_object();
+        data.insert_obj("name", " Rust Lang ");
+        before_insert::<Tag>(&mut data).unwrap();
+        let tag: Tag = load(data).unwrap();
+        assert_eq!((tag.name.as_str(), tag.slug.as_str()), ("Rust Lang", "rust-lang"));
+        let mut data = Value::new_object();
+        data.insert_obj("name", "");
+        assert!(before_insert::<Tag>(&mut data).is_err());
+        EntityListeners::global().clear::<Tag>();
+        assert!(before_insert::<Tag>(&mut data).is_ok());
+    }
+}
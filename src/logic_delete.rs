@@ -0,0 +1,88 @@
+//!
+//! Logical delete of the records with a `#[field(logic_delete)]` field.
+//!
+
+use crate::{AkitaError, GetFields, GetTableName, Wrapper};
+use crate::set_operation::apply_set_operations;
+
+/// The value of the logic delete column of the deleted records.
+pub const LOGIC_DELETED: i32 = 1;
+/// The value of the logic delete column of the other records.
+pub const LOGIC_NOT_DELETED: i32 = 0;
+
+/// the logic delete column of the entity, if any.
+pub(crate) fn logic_delete_column<T: GetFields>() -> Option<String> {
+    T::fields().into_iter().find(|field| field.logic_delete && field.exist).map(|field| field.name)
+}
+
+/// the logic delete column of the entity, required by the restores.
+pub(crate) fn require_logic_delete_column<T: GetTableName + GetFields>() -> Result<String, AkitaError> {
+    logic_delete_column::<T>().ok_or_else(|| AkitaError::MissingField(format!("Table({}) Missing logic delete field...", &T::table_name().name)))
+}
+
+/// keep the deleted records only.
+pub(crate) fn only_deleted<T: GetTableName + GetFields>(wrapper: &mut Wrapper) -> Result<(), AkitaError> {
+    let column = require_logic_delete_column::<T>()?;
+    let conditions = std::mem::replace(wrapper, Wrapper::new());
+    *wrapper = conditions.and_eq_nested(format!("`{}`", column), LOGIC_DELETED);
+    Ok(())
+}
+
+/// keep the records not deleted only, the set operations of the wrapper applied first.
+pub(crate) fn exclude_deleted<T: GetTableName + GetFields>(wrapper: &mut Wrapper) {
+    apply_set_operations::<T>(wrapper);
+    if let Some(column) = logic_delete_column::<T>() {
+        let conditions = std::mem::replace(wrapper, Wrapper::new());
+        *wrapper = conditions.and_eq_nested(format!("`{}`", column), LOGIC_NOT_DELETED);
+    }
+}
+
+/// the condition on the logic delete column to append to a where clause, empty without one.
+pub(crate) fn not_deleted_sql<T: GetFields>() -> String {
+    logic_delete_column::<T>().map(|column| format!(" and `{}` = {}", column, LOGIC_NOT_DELETED)).unwrap_or_default()
+}
+
+/// the statement removing the records of the table, flagging them deleted with a logic delete column.
+pub(crate) fn delete_clause<T: GetFields>(table: &str) -> String {
+    match logic_delete_column::<T>() {
+        Some(column) => format!("update {} set `{}` = {}", table, column, LOGIC_DELETED),
+        None => format!("delete from {}", table),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{FieldName, GetFields, GetTableName, ISegment, TableName, Wrapper};
+    use super::{delete_clause, exclude_deleted, not_deleted_sql, only_deleted};
+
+    struct Order;
+
+    impl GetFields for Order {
+        fn fields() -> Vec<FieldName> {
+            let mut deleted = FieldName::from("deleted");
+            deleted.logic_delete = true;
+            vec![FieldName::from("id"), deleted]
+        }
+    }
+
+    impl GetTableName for Order {
+        fn table_name() -> TableName {
+            TableName::from("t_order")
+        }
+    }
+
+    #[test]
+    fn logic_delete_sql() {
+        let mut wrapper = Wrapper::new().eq("state", 1).or_direct().eq("state", 2);
+        exclude_deleted::<Order>(&mut wrapper);
+        assert_eq!(wrapper.get_sql_segment().split_whitespace().collect::<Vec<_>>().join(" "), "( (state = 1 or state = 2) and `deleted` = 0)");
+        let mut wrapper = Wrapper::new();
+        exclude_deleted::<Order>(&mut wrapper);
+        assert_eq!(wrapper.get_sql_segment().trim(), "(`deleted` = 0)");
+        assert_eq!(not_deleted_sql::<Order>(), " and `deleted` = 0");
+        assert_eq!(delete_clause::<Order>("t_order"), "update t_order set `deleted` = 1");
+        let mut wrapper = Wrapper::new().eq("state", 1);
+        only_deleted::<Order>(&mut wrapper).unwrap();
+        assert_eq!(wrapper.get_sql_segment().split_whitespace().collect::<Vec<_>>().join(" "), "( (state = 1) and `deleted` = 1)");
+    }
+}
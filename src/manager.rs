@@ -1,5 +1,19 @@
-use crate::{AkitaError, IPage, Wrapper, database::{DatabasePlatform}, mapper::AkitaMapper, GetFields, GetTableName, FromValue, ToValue, Rows, TableName, DatabaseName, FieldName, Params, Value, FieldType, TableDef, segment::ISegment, AkitaConfig, Akita};
+use std::collections::HashSet;
+use std::hash::Hasher;
+use std::marker::PhantomData;
+use std::time::Duration;
+use crate::{AkitaError, ChunkProgress, ExecuteResult, IPage, TableChecksum, UpsertResult, Wrapper, database::{DatabasePlatform}, mapper::AkitaMapper, GetFields, GetTableName, FromValue, ToKey, ToValue, Rows, TableName, DatabaseName, FieldName, Params, Value, FieldType, TableDef, segment::ISegment, AkitaConfig, Akita};
 use crate::pool::PlatformPool;
+use crate::key::{key_clause, key_condition, key_fields, single_key_field};
+use crate::set_operation::{apply_set_operations, apply_unions};
+use crate::cte::with_clause;
+use crate::pagination::PaginationOptions;
+use crate::masking::SensitiveColumns;
+use crate::logic_delete::{delete_clause, exclude_deleted, not_deleted_sql};
+use crate::session_context::reset_session_with;
+use crate::optimistic_lock::{version_column, version_increment};
+use crate::fill::fill_fields;
+use crate::listener::{after_insert, after_remove, after_update, before_insert, before_remove, before_update, load};
 
 /// an interface executing sql statement and getting the results as generic Akita values
 /// without any further conversion.
@@ -7,41 +21,334 @@ use crate::pool::PlatformPool;
 #[allow(unused)]
 pub struct AkitaEntityManager(PlatformPool, AkitaConfig);
 
+/// The isolation level of a transaction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IsolationLevel {
+    ReadUncommitted,
+    ReadCommitted,
+    RepeatableRead,
+    Serializable,
+}
+
+impl IsolationLevel {
+    pub fn as_sql(&self) -> &'static str {
+        match self {
+            IsolationLevel::ReadUncommitted => "READ UNCOMMITTED",
+            IsolationLevel::ReadCommitted => "READ COMMITTED",
+            IsolationLevel::RepeatableRead => "REPEATABLE READ",
+            IsolationLevel::Serializable => "SERIALIZABLE",
+        }
+    }
+}
+
+/// The options a transaction is started with, the database defaults are used when not set.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TransactionOptions {
+    pub isolation: Option<IsolationLevel>,
+    pub read_only: bool,
+}
+
+impl TransactionOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_isolation(mut self, isolation: IsolationLevel) -> Self {
+        self.isolation = isolation.into();
+        self
+    }
+
+    pub fn set_read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+}
+
+/// begin a transaction with the options.
+/// SQLite transactions are always serializable, only `READ UNCOMMITTED` can loosen it in shared-cache mode.
+pub(crate) fn begin_transaction_with(conn: &mut DatabasePlatform, options: &TransactionOptions) -> Result<(), AkitaError> {
+    #[allow(unreachable_patterns)]
+    match conn {
+        #[cfg(feature = "akita-mysql")]
+        DatabasePlatform::Mysql(_) => {
+            let mut characteristics = Vec::new();
+            if let Some(isolation) = options.isolation {
+                characteristics.push(format!("ISOLATION LEVEL {}", isolation.as_sql()));
+            }
+            if options.read_only {
+                characteristics.push("READ ONLY".to_string());
+            }
+            if !characteristics.is_empty() {
+                conn.execute_drop(&format!("SET TRANSACTION {}", characteristics.join(", ")), Params::Nil)?;
+            }
+        }
+        _ => {
+            if options.isolation == Some(IsolationLevel::ReadUncommitted) {
+                conn.execute_drop("PRAGMA read_uncommitted = 1", Params::Nil)?;
+            }
+            if options.read_only {
+                conn.execute_drop("PRAGMA query_only = 1", Params::Nil)?;
+            }
+        }
+    }
+    conn.start_transaction()
+}
+
+/// restore the connection settings changed for the transaction once it ended.
+pub(crate) fn end_transaction_with(conn: &mut DatabasePlatform, options: &TransactionOptions) -> Result<(), AkitaError> {
+    #[allow(unreachable_patterns)]
+    match conn {
+        // `SET TRANSACTION` only applies to the next transaction.
+        #[cfg(feature = "akita-mysql")]
+        DatabasePlatform::Mysql(_) => Ok(()),
+        _ => {
+            if options.isolation == Some(IsolationLevel::ReadUncommitted) {
+                conn.execute_drop("PRAGMA read_uncommitted = 0", Params::Nil)?;
+            }
+            if options.read_only {
+                conn.execute_drop("PRAGMA query_only = 0", Params::Nil)?;
+            }
+            Ok(())
+        }
+    }
+}
+
 pub struct AkitaTransaction<'a> {
-    pub(crate) conn: &'a Akita,
+    /// the akita with the transaction connection pinned
+    pub(crate) conn: Akita,
+    /// the savepoint emulating this transaction when nested in another one
+    pub(crate) savepoint: Option<String>,
+    pub(crate) options: TransactionOptions,
     pub committed: bool,
     pub rolled_back: bool,
+    pub(crate) _origin: PhantomData<&'a Akita>,
 }
 
 #[allow(unused)]
 impl AkitaTransaction <'_> {
     pub fn commit(mut self) -> Result<(), AkitaError> {
         let mut conn = self.conn.acquire()?;
-        conn.commit_transaction()?;
+        match &self.savepoint {
+            Some(name) => conn.release_savepoint(name)?,
+            None => {
+                conn.commit_transaction()?;
+                end_transaction_with(&mut conn, &self.options)?;
+                reset_session_with(&self.conn, &mut conn)?;
+            }
+        }
         self.committed = true;
         Ok(())
     }
 
     pub fn rollback(mut self) -> Result<(), AkitaError> {
         let mut conn = self.conn.acquire()?;
-        conn.rollback_transaction()?;
+        match &self.savepoint {
+            Some(name) => {
+                conn.rollback_to_savepoint(name)?;
+                conn.release_savepoint(name)?;
+            }
+            None => {
+                conn.rollback_transaction()?;
+                end_transaction_with(&mut conn, &self.options)?;
+                reset_session_with(&self.conn, &mut conn)?;
+            }
+        }
         self.rolled_back = true;
         Ok(())
     }
+
+    /// The akita sending its statements within this transaction.
+    pub fn akita(&self) -> &Akita {
+        &self.conn
+    }
+
+    /// Run the callback once the outermost transaction commits, it is dropped on rollback.
+    pub fn after_commit<F: FnOnce() + Send + 'static>(&self, f: F) -> Result<(), AkitaError> {
+        self.conn.after_commit(f)
+    }
+
+    /// Start a nested transaction, backed by a savepoint of this one.
+    pub fn start_transaction(&self) -> Result<AkitaTransaction<'_>, AkitaError> {
+        self.conn.start_transaction()
+    }
+
+    /// Start a nested transaction with the options, only the default ones are allowed there.
+    pub fn start_transaction_with(&self, options: TransactionOptions) -> Result<AkitaTransaction<'_>, AkitaError> {
+        self.conn.start_transaction_with(options)
+    }
+
+    /// Set a savepoint with the name.
+    pub fn savepoint(&self, name: &str) -> Result<(), AkitaError> {
+        check_savepoint_name(name)?;
+        self.conn.acquire()?.savepoint(name)
+    }
+
+    /// Release the savepoint, the changes made after it are kept.
+    pub fn release_savepoint(&self, name: &str) -> Result<(), AkitaError> {
+        check_savepoint_name(name)?;
+        self.conn.acquire()?.release_savepoint(name)
+    }
+
+    /// Undo the changes made after the savepoint, the savepoint is kept.
+    pub fn rollback_to(&self, name: &str) -> Result<(), AkitaError> {
+        check_savepoint_name(name)?;
+        self.conn.acquire()?.rollback_to_savepoint(name)
+    }
+
+    /// Run the closure in read-only mode within this transaction.
+    pub fn read_only<F, R>(&self, f: F) -> Result<R, AkitaError>
+        where
+            F: FnOnce(&Akita) -> Result<R, AkitaError>,
+    {
+        self.conn.read_only(f)
+    }
+}
+
+/// savepoint names are sent as is, only plain identifiers are allowed.
+fn check_savepoint_name(name: &str) -> Result<(), AkitaError> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') || name.starts_with(|c: char| c.is_ascii_digit()) {
+        return Err(AkitaError::InvalidSQL(format!("invalid savepoint name: {}", name)));
+    }
+    Ok(())
 }
 
 impl<'a> Drop for AkitaTransaction<'a> {
     /// Will rollback transaction.
     fn drop(&mut self) {
         if !self.committed && !self.rolled_back {
-            // match self.conn.acquire() {
-            //     Ok(mut conn) => {
-            //         let _ = conn.rollback_transaction().unwrap_or_default();
-            //     }
-            //     Err(_err) => {
-            //         // todo: Error to rollback
-            //     }
-            // }
+            if let Ok(mut conn) = self.conn.acquire() {
+                match &self.savepoint {
+                    Some(name) => {
+                        let _ = conn.rollback_to_savepoint(name);
+                        let _ = conn.release_savepoint(name);
+                    }
+                    None => {
+                        let _ = conn.rollback_transaction();
+                        let _ = end_transaction_with(&mut conn, &self.options);
+                        let _ = reset_session_with(&self.conn, &mut conn);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A distributed transaction spanning several datasources with the two-phase commit,
+/// each datasource runs an XA branch of the same global transaction.
+///
+/// All the branches are prepared before any of them is committed, if one fails to prepare
+/// they are all rolled back. Only MySQL supports XA transactions.
+pub struct XaTransaction<'a> {
+    gtrid: String,
+    /// the akitas with the branch connections pinned
+    branches: Vec<Akita>,
+    /// the number of branches still active, ended ones are idle or prepared
+    active: usize,
+    finished: bool,
+    _origin: PhantomData<&'a Akita>,
+}
+
+#[allow(unused)]
+impl<'a> XaTransaction<'a> {
+    /// Start the global transaction `gtrid` with a branch on each akita.
+    pub fn begin<S: Into<String>>(gtrid: S, akitas: &[&'a Akita]) -> Result<XaTransaction<'a>, AkitaError> {
+        let gtrid = gtrid.into();
+        if gtrid.is_empty() || gtrid.len() > 64 || !gtrid.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            return Err(AkitaError::InvalidSQL(format!("invalid xa transaction id: {}", gtrid)));
+        }
+        let mut xa = XaTransaction { gtrid, branches: Vec::with_capacity(akitas.len()), active: 0, finished: false, _origin: PhantomData };
+        for akita in akitas {
+            if akita.in_transaction() {
+                return Err(AkitaError::UnsupportedOperation("an xa transaction can not be started within a transaction".to_string()));
+            }
+            let branch = akita.pin()?;
+            let xid = xa.xid(xa.branches.len());
+            {
+                let mut conn = branch.acquire()?;
+                #[allow(unreachable_patterns)]
+                match *conn {
+                    #[cfg(feature = "akita-mysql")]
+                    DatabasePlatform::Mysql(_) => conn.execute_drop(&format!("XA START {}", xid), Params::Nil)?,
+                    _ => return Err(AkitaError::UnsupportedOperation("xa transactions are only supported on mysql".to_string())),
+                }
+            }
+            xa.branches.push(branch);
+            xa.active += 1;
+        }
+        Ok(xa)
+    }
+
+    /// The akita sending its statements within the branch at the index, in the order given to `begin`.
+    pub fn branch(&self, index: usize) -> Option<&Akita> {
+        self.branches.get(index)
+    }
+
+    /// The global transaction id.
+    pub fn gtrid(&self) -> &str {
+        &self.gtrid
+    }
+
+    /// Prepare all the branches then commit them, all of them are rolled back if one fails to prepare.
+    /// A failure while committing leaves the remaining branches prepared, to be recovered with `XA RECOVER`.
+    pub fn commit(mut self) -> Result<(), AkitaError> {
+        self.end_all()?;
+        for index in 0..self.branches.len() {
+            let prepared = self.execute(index, "XA PREPARE");
+            if let Err(err) = prepared {
+                self.rollback_all();
+                return Err(err);
+            }
+        }
+        self.finished = true;
+        for index in 0..self.branches.len() {
+            self.execute(index, "XA COMMIT")
+                .map_err(|err| AkitaError::DataError(format!("xa transaction '{}' partially committed, branch {} failed: {}", self.gtrid, index, err)))?;
+        }
+        Ok(())
+    }
+
+    /// Roll back all the branches.
+    pub fn rollback(mut self) -> Result<(), AkitaError> {
+        self.end_all()?;
+        self.finished = true;
+        for index in 0..self.branches.len() {
+            self.execute(index, "XA ROLLBACK")?;
+        }
+        Ok(())
+    }
+
+    /// the xid of the branch, `'gtrid','branch_n'`.
+    fn xid(&self, index: usize) -> String {
+        format!("'{}','branch_{}'", self.gtrid, index)
+    }
+
+    fn execute(&self, index: usize, statement: &str) -> Result<(), AkitaError> {
+        self.branches[index].acquire()?.execute_drop(&format!("{} {}", statement, self.xid(index)), Params::Nil)
+    }
+
+    /// end the branches still active, required before preparing or rolling back.
+    fn end_all(&mut self) -> Result<(), AkitaError> {
+        while self.active > 0 {
+            self.execute(self.branches.len() - self.active, "XA END")?;
+            self.active -= 1;
+        }
+        Ok(())
+    }
+
+    fn rollback_all(&mut self) {
+        let _ = self.end_all();
+        self.finished = true;
+        for index in 0..self.branches.len() {
+            let _ = self.execute(index, "XA ROLLBACK");
+        }
+    }
+}
+
+impl Drop for XaTransaction<'_> {
+    /// Will rollback all the branches.
+    fn drop(&mut self) {
+        if !self.finished {
+            self.rollback_all();
         }
     }
 }
@@ -71,7 +378,7 @@ impl AkitaMapper for AkitaTransaction <'_> {
     fn select_by_id<T, I>(&self, id: I) -> Result<Option<T>, AkitaError>
     where
         T: GetTableName + GetFields + FromValue,
-        I: ToValue
+        I: ToKey
     {
         self.conn.select_by_id(id)
     }
@@ -105,10 +412,19 @@ impl AkitaMapper for AkitaTransaction <'_> {
         self.conn.remove_by_ids::<T,I>(ids)
     }
 
+    /// Remove the records by ids in chunks, all in this transaction.
+    fn remove_by_ids_chunked<T, I, F>(&self, ids: Vec<I>, chunk_size: usize, pause: Option<Duration>, on_progress: F) -> Result<u64, AkitaError>
+    where
+        I: ToValue,
+        T: GetTableName + GetFields,
+        F: FnMut(&ChunkProgress) {
+        self.conn.remove_by_ids_chunked::<T, I, F>(ids, chunk_size, pause, on_progress)
+    }
+
     /// Remove the records by id.
     fn remove_by_id<T, I>(&self, id: I) -> Result<u64, AkitaError>
     where
-        I: ToValue,
+        I: ToKey,
         T: GetTableName + GetFields {
             self.conn.remove_by_id::<T, I>(id)
         
@@ -129,6 +445,28 @@ impl AkitaMapper for AkitaTransaction <'_> {
         
     }
 
+    /// Update the records by id, all in one transaction.
+    fn update_batch_by_id<T>(&self, entities: &[&T]) -> Result<u64, AkitaError>
+    where
+        T: GetTableName + GetFields + ToValue {
+        self.conn.update_batch_by_id(entities)
+    }
+
+    /// Get all the descendants of the record.
+    fn descendants_of<T, I>(&self, id: I) -> Result<Vec<T>, AkitaError>
+    where
+        T: GetTableName + GetFields + FromValue,
+        I: ToValue {
+        self.conn.descendants_of(id)
+    }
+
+    /// Get the checksum of the records.
+    fn table_checksum<T>(&self, wrapper: Wrapper) -> Result<TableChecksum, AkitaError>
+    where
+        T: GetTableName + GetFields {
+        self.conn.table_checksum::<T>(wrapper)
+    }
+
     #[allow(unused_variables)]
     fn save_batch<T>(&self, entities: &[&T]) -> Result<(), AkitaError>
     where
@@ -150,9 +488,33 @@ impl AkitaMapper for AkitaTransaction <'_> {
         self.conn.save_or_update(entity)
     }
 
+    fn upsert<T>(&self, entity: &T) -> Result<u64, AkitaError> where T: GetTableName + GetFields + ToValue {
+        self.conn.upsert(entity)
+    }
+
+    fn save_or_update_returning<T>(&self, entity: &T) -> Result<UpsertResult<T>, AkitaError> where T: GetTableName + GetFields + ToValue + FromValue {
+        self.conn.save_or_update_returning(entity)
+    }
+
     fn exec_iter<S: Into<String>, P: Into<Params>>(&self, sql: S, params: P) -> Result<Rows, AkitaError> {
         self.conn.exec_iter(sql, params)
     }
+
+    fn exec_batch<S: Into<String>, P: Into<Params>>(&self, sql: S, params: Vec<P>) -> Result<u64, AkitaError> {
+        self.conn.exec_batch(sql, params)
+    }
+
+    fn exec_result<S: Into<String>, P: Into<Params>>(&self, sql: S, params: P) -> Result<ExecuteResult, AkitaError> {
+        self.conn.exec_result(sql, params)
+    }
+
+    fn exec_stream<R, S: Into<String>, P: Into<Params>, F>(&self, sql: S, params: P, f: F) -> Result<(), AkitaError> where R: FromValue, F: FnMut(R) -> Result<(), AkitaError> {
+        self.conn.exec_stream(sql, params, f)
+    }
+
+    fn fetch_size(&self) -> usize {
+        self.conn.fetch_size()
+    }
 }
 
 
@@ -209,13 +571,18 @@ impl AkitaEntityManager{
         T: GetTableName + GetFields + ToValue
     {
         let mut conn = self.acquire()?;
-        let columns = T::fields();
+        let columns = fill_fields::<T>(conn.config());
         let sql = build_insert_clause(&conn, entities);
 
         let mut values: Vec<Value> = Vec::with_capacity(entities.len() * columns.len());
+        let mut records = Vec::with_capacity(entities.len());
         for entity in entities.iter() {
+            let mut data = entity.to_value();
+            before_insert::<T>(&mut data)?;
             for col in columns.iter() {
-                let data = entity.to_value();
+                if !col.exist {
+                    continue;
+                }
                 let mut value = data.get_obj_value(&col.name);
                 match &col.fill {
                     None => {}
@@ -229,13 +596,15 @@ impl AkitaEntityManager{
                     }
                 }
                 match value {
-                    Some(value) => values.push(value.clone()),
-                    None => values.push(Value::Nil),
+                    Some(value) if *value != Value::Nil => values.push(value.clone()),
+                    _ => values.push(col.field_type.generate_id().unwrap_or(Value::Nil)),
                 }
             }
+            records.push(data);
         }
         let bvalues: Vec<&Value> = values.iter().collect();
         conn.execute_result(&sql,values.into())?;
+        records.iter().for_each(after_insert::<T>);
         Ok(())
     }
 
@@ -258,11 +627,19 @@ pub fn build_insert_clause<T>(platform: &DatabasePlatform, entities: &[&T]) -> S
     where
         T: GetTableName + GetFields + ToValue,
 {
-    let table = T::table_name();
+    build_insert_clause_into(platform, &T::table_name().complete_name(), entities)
+}
+
+/// build an insert clause of the entities into another table with their columns.
+pub(crate) fn build_insert_clause_into<T>(platform: &DatabasePlatform, table: &str, entities: &[&T]) -> String
+    where
+        T: GetFields + ToValue,
+{
     let columns = T::fields();
+    SensitiveColumns::global().register(&columns);
     let columns_len = columns.len();
     let mut sql = String::new();
-    sql += &format!("INSERT INTO {} ", table.complete_name());
+    sql += &format!("INSERT INTO {} ", table);
     sql += &format!(
         "({})\n",
         columns
@@ -300,6 +677,494 @@ pub fn build_insert_clause<T>(platform: &DatabasePlatform, entities: &[&T]) -> S
     sql
 }
 
+/// upsert the entity and select the final row back within a transaction on the same connection.
+pub(crate) fn upsert_returning<T>(conn: &mut DatabasePlatform, in_transaction: bool, entity: &T) -> Result<UpsertResult<T>, AkitaError>
+    where
+        T: GetTableName + GetFields + ToValue + FromValue,
+{
+    let table = T::table_name();
+    let columns = T::fields();
+    let keys = key_fields::<T>()?;
+    let enumerated_columns = columns
+        .iter().filter(|f| f.exist)
+        .map(|c| format!("`{}`", c.name))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let select_sql = format!("SELECT {} FROM {} WHERE {}", &enumerated_columns, &table.complete_name(), key_clause(conn, &keys, 0));
+    let upsert_sql = build_upsert_clause(conn, &[entity])?;
+    let mut data = entity.to_value();
+    before_insert::<T>(&mut data)?;
+    let values = build_record_values::<T>(conn.config(), std::slice::from_ref(&data));
+    let ids = keys.iter().map(|key| data.get_obj_value(&key.name).cloned().unwrap_or(Value::Nil)).collect::<Vec<_>>();
+    // only a single key is generated by the database, the record is read back by the insert id.
+    let generated = ids.contains(&Value::Nil);
+    if generated && keys.len() > 1 {
+        return Err(AkitaError::MissingIdent(format!("Table({}) record without the values of its composite key to upsert", &table.name)));
+    }
+    #[allow(unreachable_patterns)]
+    let upsert_sql = match &*conn {
+        // MySQL only reports the insert id of an insert: the duplicate of another unique key has it set to its own id.
+        #[cfg(feature = "akita-mysql")]
+        DatabasePlatform::Mysql(_) if generated => format!("{}, `{}` = LAST_INSERT_ID(`{}`)", upsert_sql, &keys[0].name, &keys[0].name),
+        _ => upsert_sql,
+    };
+    let result = with_transaction(conn, in_transaction, |conn| {
+        #[allow(unreachable_patterns)]
+        let inserted = match conn {
+            #[cfg(feature = "akita-mysql")]
+            DatabasePlatform::Mysql(_) => {
+                conn.execute_result(&upsert_sql, values.into())?;
+                // one row affected for an insert, two (or none when unchanged) for an update.
+                conn.affected_rows() == 1
+            }
+            _ => {
+                let existed = !generated && !conn.execute_result(&select_sql, ids.clone().into())?.is_empty();
+                conn.execute_result(&upsert_sql, values.into())?;
+                !existed
+            }
+        };
+        let ids = if generated { vec![Value::Bigint(conn.last_insert_id() as i64)] } else { ids };
+        let rows = conn.execute_result(&select_sql, ids.into())?;
+        let record = match rows.iter().next() {
+            Some(data) => load::<T>(data)?,
+            None => return Err(AkitaError::DataError(format!("Table({}) upserted record not found", &table.name))),
+        };
+        Ok(UpsertResult { record, inserted })
+    })?;
+    after_insert::<T>(&data);
+    Ok(result)
+}
+
+/// insert the entity or update the record with its key, seen as an insert by the listeners.
+pub(crate) fn upsert_with<T>(conn: &mut DatabasePlatform, entity: &T) -> Result<u64, AkitaError>
+    where
+        T: GetTableName + GetFields + ToValue,
+{
+    let sql = build_upsert_clause(conn, &[entity])?;
+    let mut data = entity.to_value();
+    before_insert::<T>(&mut data)?;
+    let values = build_record_values::<T>(conn.config(), std::slice::from_ref(&data));
+    conn.execute_result(&sql, values.into())?;
+    after_insert::<T>(&data);
+    Ok(conn.affected_rows())
+}
+
+/// run the closure in a transaction, or directly when the connection is already in one.
+pub(crate) fn with_transaction<R, F>(conn: &mut DatabasePlatform, in_transaction: bool, f: F) -> Result<R, AkitaError>
+    where
+        F: FnOnce(&mut DatabasePlatform) -> Result<R, AkitaError>,
+{
+    if in_transaction {
+        return f(conn)
+    }
+    conn.start_transaction()?;
+    match f(conn) {
+        Ok(result) => {
+            conn.commit_transaction()?;
+            Ok(result)
+        }
+        Err(err) => {
+            let _ = conn.rollback_transaction();
+            Err(err)
+        }
+    }
+}
+
+/// update the record by id with bound parameters.
+pub(crate) fn update_by_id_with<T>(conn: &mut DatabasePlatform, entity: &T) -> Result<u64, AkitaError>
+    where
+        T: GetTableName + GetFields + ToValue,
+{
+    let table = T::table_name();
+    if table.complete_name().is_empty() {
+        return Err(AkitaError::MissingTable("Find Error, Missing Table Name !".to_string()))
+    }
+    let mut data = entity.to_value();
+    before_update::<T>(&mut data)?;
+    let columns = fill_fields::<T>(conn.config());
+    let col_len = columns.len();
+    let keys = key_fields::<T>()?;
+    let set_fields = columns
+    .iter().filter(|col| col.exist && col.field_type == FieldType::TableField)
+    .enumerate()
+    .map(|(x, col)| {
+        if col.version {
+            return version_increment(&col.name);
+        }
+        #[allow(unreachable_patterns)]
+        match *conn {
+            #[cfg(feature = "akita-mysql")]
+            DatabasePlatform::Mysql(_) => format!("`{}` = ?", &col.name),
+            #[cfg(feature = "akita-sqlite")]
+            DatabasePlatform::Sqlite(_) => format!("`{}` = ${}",&col.name, x + 1),
+            _ => format!("`{}` = ${}", &col.name, x + 1),
+        }
+    })
+    .collect::<Vec<_>>()
+    .join(", ");
+    let mut sql = format!("update {} set {} where {}", &table.name, &set_fields, key_clause(conn, &keys, col_len));
+    let mut values: Vec<Value> = Vec::with_capacity(columns.len());
+    for col in columns.iter() {
+        if !col.exist || col.field_type.ne(&FieldType::TableField) || col.version {
+            continue;
+        }
+        let col_name = &col.name;
+        let mut value = data.get_obj_value(col_name);
+        match &col.fill {
+            None => {}
+            Some(v) => {
+                match v.mode.as_ref() {
+                    "update" | "default" => {
+                        value = v.value.as_ref();
+                    }
+                    _=> {}
+                }
+            }
+        }
+        match value {
+            Some(value) => values.push(value.clone()),
+            None => values.push(Value::Nil),
+        }
+    }
+    let mut id = Vec::with_capacity(keys.len());
+    for key in keys.iter() {
+        match data.get_obj_value(&key.name) {
+            Some(value) => {
+                id.push(value.to_string());
+                values.push(value.clone());
+            }
+            None => {
+                return Err(AkitaError::MissingIdent(format!("Table({}) Missing Ident value...", &table.name)));
+            }
+        }
+    }
+    // the record is only updated if still at the version it was read with.
+    let version = version_column::<T>().and_then(|column| data.get_obj_value(&column).filter(|value| **value != Value::Nil).map(|value| (column, value.clone())));
+    if let Some((column, value)) = &version {
+        #[allow(unreachable_patterns)]
+        match *conn {
+            #[cfg(feature = "akita-mysql")]
+            DatabasePlatform::Mysql(_) => sql.push_str(&format!(" and `{}` = ?", column)),
+            _ => sql.push_str(&format!(" and `{}` = ${}", column, col_len + keys.len() + 1)),
+        }
+        values.push(value.clone());
+    }
+    let _ = conn.execute_result(&sql, values.into())?;
+    let affected = conn.affected_rows();
+    match version {
+        Some((column, value)) if affected == 0 => Err(AkitaError::OptimisticLockFailed(format!("Table({}) record {} is no longer at {} {}", &table.name, id.join(", "), column, value))),
+        _ => {
+            after_update::<T>(&data);
+            Ok(affected)
+        }
+    }
+}
+
+/// update the records by id one statement each, in a single transaction.
+pub(crate) fn update_batch_by_id_with<T>(conn: &mut DatabasePlatform, in_transaction: bool, entities: &[&T]) -> Result<u64, AkitaError>
+    where
+        T: GetTableName + GetFields + ToValue,
+{
+    with_transaction(conn, in_transaction, |conn| {
+        let mut affected = 0;
+        for entity in entities {
+            affected += update_by_id_with(conn, *entity)?;
+        }
+        Ok(affected)
+    })
+}
+
+/// remove the records by ids in chunks, each chunk in its own transaction unless already in one.
+pub(crate) fn remove_by_ids_chunked_with<T, I, F>(conn: &mut DatabasePlatform, in_transaction: bool, ids: Vec<I>, chunk_size: usize, pause: Option<Duration>, mut on_progress: F) -> Result<u64, AkitaError>
+    where
+        I: ToValue,
+        T: GetTableName + GetFields,
+        F: FnMut(&ChunkProgress),
+{
+    let table = T::table_name();
+    if table.complete_name().is_empty() {
+        return Err(AkitaError::MissingTable("Find Error, Missing Table Name !".to_string()))
+    }
+    if chunk_size == 0 {
+        return Err(AkitaError::DataError("chunk size must be greater than zero".to_string()))
+    }
+    let field = single_key_field::<T>()?;
+    let ids = ids.iter().map(|id| id.to_value()).collect::<Vec<_>>();
+    let mut progress = ChunkProgress { chunk: 0, chunks: ids.len().div_ceil(chunk_size), removed: 0 };
+    for chunk in ids.chunks(chunk_size) {
+        if progress.chunk > 0 {
+            if let Some(pause) = pause {
+                std::thread::sleep(pause);
+            }
+        }
+        #[allow(unreachable_patterns)]
+        let placeholders = chunk.iter().enumerate().map(|(i, _)| match conn {
+            #[cfg(feature = "akita-mysql")]
+            DatabasePlatform::Mysql(_) => "?".to_string(),
+            _ => format!("${}", i + 1),
+        }).collect::<Vec<_>>().join(", ");
+        let sql = format!("{} where `{}` in ({})", delete_clause::<T>(&table.complete_name()), &field.name, placeholders);
+        progress.removed += with_transaction(conn, in_transaction, |conn| {
+            conn.execute_drop(&sql, chunk.to_vec().into())?;
+            Ok(conn.affected_rows())
+        })?;
+        progress.chunk += 1;
+        on_progress(&progress);
+    }
+    Ok(progress.removed)
+}
+
+/// execute the statement for each params, the drivers reuse the statement prepared for the first ones.
+pub(crate) fn exec_batch_with(conn: &mut DatabasePlatform, in_transaction: bool, sql: &str, params: Vec<Params>) -> Result<u64, AkitaError> {
+    with_transaction(conn, in_transaction, |conn| {
+        let mut affected = 0;
+        for params in params {
+            conn.execute_drop(sql, params)?;
+            affected += conn.affected_rows();
+        }
+        Ok(affected)
+    })
+}
+
+/// find all the descendants of the record, with a recursive query where supported or level by level otherwise.
+pub(crate) fn descendants_with<T>(conn: &mut DatabasePlatform, id: Value) -> Result<Vec<T>, AkitaError>
+    where
+        T: GetTableName + GetFields + FromValue,
+{
+    let table = T::table_name();
+    if table.complete_name().is_empty() {
+        return Err(AkitaError::MissingTable("Find Error, Missing Table Name !".to_string()))
+    }
+    let columns = T::fields();
+    let key = single_key_field::<T>()?;
+    let parent = columns.iter().find(|field| field.parent_id)
+        .ok_or_else(|| AkitaError::MissingField(format!("Table({}) Missing parent_id field...", &table.name)))?;
+    let enumerated_columns = columns
+        .iter().filter(|f| f.exist)
+        .map(|c| format!("`{}`", c.name))
+        .collect::<Vec<_>>()
+        .join(", ");
+    if supports_recursive_cte(conn)? {
+        let tree_columns = columns
+            .iter().filter(|f| f.exist)
+            .map(|c| format!("t.`{}`", c.name))
+            .collect::<Vec<_>>()
+            .join(", ");
+        #[allow(unreachable_patterns)]
+        let placeholder = match conn {
+            #[cfg(feature = "akita-mysql")]
+            DatabasePlatform::Mysql(_) => "?",
+            _ => "$1",
+        };
+        let sql = format!("WITH RECURSIVE akita_tree AS (SELECT {} FROM {} WHERE `{}` = {} UNION SELECT {} FROM {} t INNER JOIN akita_tree ON t.`{}` = akita_tree.`{}`) SELECT {} FROM akita_tree",
+                          &enumerated_columns, &table.complete_name(), &parent.name, placeholder, &tree_columns, &table.complete_name(), &parent.name, &key.name, &enumerated_columns);
+        let rows = conn.execute_result(&sql, vec![id].into())?;
+        let mut records = Vec::with_capacity(rows.len());
+        for data in rows.iter() {
+            records.push(load::<T>(data)?);
+        }
+        return Ok(records)
+    }
+    let mut records = Vec::new();
+    let mut seen = HashSet::new();
+    let mut level = vec![id];
+    while !level.is_empty() {
+        let mut next = Vec::new();
+        for ids in level.chunks(TREE_BATCH_SIZE) {
+            #[allow(unreachable_patterns)]
+            let placeholders = ids.iter().enumerate().map(|(i, _)| match conn {
+                #[cfg(feature = "akita-mysql")]
+                DatabasePlatform::Mysql(_) => "?".to_string(),
+                _ => format!("${}", i + 1),
+            }).collect::<Vec<_>>().join(", ");
+            let sql = format!("SELECT {} FROM {} WHERE `{}` IN ({})", &enumerated_columns, &table.complete_name(), &parent.name, placeholders);
+            let rows = conn.execute_result(&sql, ids.to_vec().into())?;
+            for data in rows.iter() {
+                let key = data.get_obj_value(&key.name).cloned().unwrap_or(Value::Nil);
+                // a cycle in the parents would otherwise never end.
+                if !seen.insert(key.to_string()) {
+                    continue;
+                }
+                records.push(load::<T>(data)?);
+                next.push(key);
+            }
+        }
+        level = next;
+    }
+    Ok(records)
+}
+
+/// the parent ids queried at once while walking a tree level by level.
+const TREE_BATCH_SIZE: usize = 500;
+
+/// whether the database can run `WITH RECURSIVE` queries, MySQL only supports them since 8.0.
+fn supports_recursive_cte(conn: &mut DatabasePlatform) -> Result<bool, AkitaError> {
+    #[allow(unreachable_patterns)]
+    match conn {
+        #[cfg(feature = "akita-mysql")]
+        DatabasePlatform::Mysql(_) => {
+            let rows = conn.execute_result("SELECT VERSION()", Params::Nil)?;
+            let version = rows.data.first().and_then(|row| row.as_ref(0)).map(|v| v.to_string()).unwrap_or_default();
+            let major = version.split('.').next().and_then(|v| v.parse::<u32>().ok()).unwrap_or_default();
+            Ok(major >= 8)
+        }
+        _ => Ok(true),
+    }
+}
+
+/// compute the checksum of the matched records, in the database where it can hash the rows or here otherwise.
+pub(crate) fn table_checksum_with<T>(conn: &mut DatabasePlatform, wrapper: &mut Wrapper) -> Result<TableChecksum, AkitaError>
+    where
+        T: GetTableName + GetFields,
+{
+    let table = T::table_name();
+    if table.complete_name().is_empty() {
+        return Err(AkitaError::MissingTable("Find Error, Missing Table Name !".to_string()))
+    }
+    let columns = T::fields().into_iter().filter(|f| f.exist).map(|f| f.name).collect::<Vec<_>>();
+    if columns.is_empty() {
+        return Err(AkitaError::MissingField(format!("Table({}) Missing fields...", &table.name)))
+    }
+    exclude_deleted::<T>(wrapper);
+    let where_condition = wrapper.get_sql_segment();
+    let where_condition = if where_condition.trim().is_empty() { String::default() } else { format!("WHERE {}", where_condition) };
+    #[allow(unreachable_patterns)]
+    match conn {
+        #[cfg(feature = "akita-mysql")]
+        DatabasePlatform::Mysql(_) => {
+            // the nulls are marked so that moving a null between two columns changes the row hash.
+            let concat = columns.iter().map(|c| format!("IFNULL(`{}`, CHAR(0))", c)).collect::<Vec<_>>().join(", ");
+            let sql = format!("SELECT COUNT(1), COALESCE(BIT_XOR(CRC32(CONCAT_WS('#', {}))), 0) FROM {} {}", concat, table.complete_name(), where_condition);
+            let rows = conn.execute_result(&sql, Params::Nil)?;
+            let row = rows.data.first();
+            let value = |i: usize| row.and_then(|row| row.data.get(i)).map(|v| v.to_string().parse::<u64>().unwrap_or_default()).unwrap_or_default();
+            Ok(TableChecksum { count: value(0), checksum: value(1) })
+        }
+        _ => {
+            let enumerated_columns = columns.iter().map(|c| format!("`{}`", c)).collect::<Vec<_>>().join(", ");
+            let sql = format!("SELECT {} FROM {} {}", enumerated_columns, table.complete_name(), where_condition);
+            let rows = conn.execute_result(&sql, Params::Nil)?;
+            let checksum = rows.data.iter().fold(0, |checksum, row| {
+                let mut hasher = twox_hash::XxHash64::with_seed(0);
+                for value in row.data.iter() {
+                    match value {
+                        Value::Nil => hasher.write_u8(0),
+                        value => {
+                            hasher.write_u8(1);
+                            hasher.write(value.to_string().as_bytes());
+                        }
+                    }
+                    hasher.write_u8(b'#');
+                }
+                checksum ^ hasher.finish()
+            });
+            Ok(TableChecksum { count: rows.len() as u64, checksum })
+        }
+    }
+}
+
+/// build a select clause with the wrapper, the records flagged by the logic delete field left out.
+pub fn build_select_clause<T>(wrapper: &mut Wrapper) -> Result<String, AkitaError>
+    where
+        T: GetTableName + GetFields,
+{
+    exclude_deleted::<T>(wrapper);
+    build_select_clause_with_deleted::<T>(wrapper)
+}
+
+/// build a select clause, the records flagged by the logic delete field included.
+pub(crate) fn build_select_clause_with_deleted<T>(wrapper: &mut Wrapper) -> Result<String, AkitaError>
+    where
+        T: GetTableName + GetFields,
+{
+    let table = T::table_name();
+    if table.complete_name().is_empty() {
+        return Err(AkitaError::MissingTable("Find Error, Missing Table Name !".to_string()))
+    }
+    SensitiveColumns::global().register(&T::fields());
+    apply_set_operations::<T>(wrapper);
+    let select_fields = wrapper.get_select_sql();
+    let enumerated_columns = if select_fields.eq("*") {
+        T::fields()
+            .iter().filter(|f| f.exist)
+            .map(|c| format!("`{}`", c.name))
+            .collect::<Vec<_>>()
+            .join(", ")
+    } else {
+        select_fields
+    };
+    apply_unions::<T>(wrapper, &enumerated_columns);
+    let with = with_clause(wrapper)?;
+    let where_condition = wrapper.get_sql_segment();
+    let where_condition = if where_condition.trim().is_empty() { String::default() } else { format!("WHERE {}",where_condition) };
+    Ok(format!("{}SELECT {} FROM {} {}", with, &enumerated_columns, &table.complete_name(), where_condition))
+}
+
+/// build an upsert clause, rows conflicting on the table key will update the other columns instead.
+pub fn build_upsert_clause<T>(platform: &DatabasePlatform, entities: &[&T]) -> Result<String, AkitaError>
+    where
+        T: GetTableName + GetFields + ToValue,
+{
+    let columns = T::fields();
+    let update_columns = columns.iter().filter(|col| col.exist && col.field_type == FieldType::TableField).collect::<Vec<_>>();
+    let mut sql = build_insert_clause(platform, entities);
+    #[allow(unreachable_patterns)]
+    match platform {
+        #[cfg(feature = "akita-mysql")]
+        DatabasePlatform::Mysql(_) => {
+            let set_fields = if update_columns.is_empty() {
+                // nothing to update, keep the key as it is so that the duplicate row is ignored.
+                columns.iter().find(|col| col.exist).map(|col| format!("`{}` = `{}`", &col.name, &col.name)).unwrap_or_default()
+            } else {
+                update_columns.iter().map(|col| format!("`{}` = VALUES(`{}`)", &col.name, &col.name)).collect::<Vec<_>>().join(", ")
+            };
+            sql += &format!("\nON DUPLICATE KEY UPDATE {}", set_fields);
+        }
+        _ => {
+            let keys = key_fields::<T>()?.iter().map(|key| format!("`{}`", key.name)).collect::<Vec<_>>().join(", ");
+            if update_columns.is_empty() {
+                sql += &format!("\nON CONFLICT({}) DO NOTHING", &keys);
+            } else {
+                sql += &format!(
+                    "\nON CONFLICT({}) DO UPDATE SET {}",
+                    &keys,
+                    update_columns.iter().map(|col| format!("`{}` = excluded.`{}`", &col.name, &col.name)).collect::<Vec<_>>().join(", ")
+                );
+            }
+        }
+    }
+    Ok(sql)
+}
+
+/// collect the values of the entities for an insert clause, the fill values will be applied.
+pub fn build_insert_values<T>(cfg: &AkitaConfig, entities: &[&T]) -> Vec<Value>
+    where
+        T: GetFields + ToValue,
+{
+    build_record_values::<T>(cfg, &entities.iter().map(|entity| entity.to_value()).collect::<Vec<_>>())
+}
+
+/// the values bound by an insert clause for the columns of the records, as seen by the listeners.
+pub(crate) fn build_record_values<T: GetFields>(cfg: &AkitaConfig, records: &[Value]) -> Vec<Value> {
+    let columns = fill_fields::<T>(cfg);
+    let mut values: Vec<Value> = Vec::with_capacity(records.len() * columns.len());
+    for data in records.iter() {
+        for col in columns.iter().filter(|col| col.exist) {
+            let mut value = data.get_obj_value(&col.name);
+            if let Some(v) = &col.fill {
+                match v.mode.as_ref() {
+                    "insert" | "default" => {
+                        value = v.value.as_ref();
+                    }
+                    _ => {}
+                }
+            }
+            values.push(value.filter(|value| **value != Value::Nil).cloned().or_else(|| col.field_type.generate_id()).unwrap_or(Value::Nil));
+        }
+    }
+    values
+}
+
 /// build an update clause
 pub fn build_update_clause<T>(platform: &DatabasePlatform, _entity: &T, wrapper: &mut Wrapper) -> String
     where
@@ -307,6 +1172,7 @@ pub fn build_update_clause<T>(platform: &DatabasePlatform, _entity: &T, wrapper:
 {
     let table = T::table_name();
     let columns = T::fields();
+    SensitiveColumns::global().register(&columns);
     let set_fields = &mut wrapper.fields_set;
     let mut sql = String::new();
     sql += &format!("update {} ", table.complete_name());
@@ -383,14 +1249,17 @@ impl AkitaMapper for AkitaEntityManager {
         } else {
             select_fields
         };
+        exclude_deleted::<T>(&mut wrapper);
+        apply_unions::<T>(&mut wrapper, &enumerated_columns);
+        let with = with_clause(&mut wrapper)?;
         let where_condition = wrapper.get_sql_segment();
         let where_condition = if where_condition.trim().is_empty() { String::default() } else { format!("WHERE {}",where_condition) };
-        let sql = format!("SELECT {} FROM {} {}", &enumerated_columns, &table.complete_name(),where_condition);
+        let sql = format!("{}SELECT {} FROM {} {}", with, &enumerated_columns, &table.complete_name(),where_condition);
         let mut conn = self.acquire()?;
         let rows = conn.execute_result(&sql, Params::Nil)?;
         let mut entities = vec![];
         for data in rows.iter() {
-            let entity = T::from_value(&data);
+            let entity = load::<T>(data)?;
             entities.push(entity)
         }
         Ok(entities)
@@ -417,19 +1286,22 @@ impl AkitaMapper for AkitaEntityManager {
         } else {
             select_fields
         };
+        exclude_deleted::<T>(&mut wrapper);
+        apply_unions::<T>(&mut wrapper, &enumerated_columns);
+        let with = with_clause(&mut wrapper)?;
         let where_condition = wrapper.get_sql_segment();
         let where_condition = if where_condition.trim().is_empty() { String::default() } else { format!("WHERE {}",where_condition) };
-        let sql = format!("SELECT {} FROM {} {}", &enumerated_columns, &table.complete_name(), where_condition);
+        let sql = format!("{}SELECT {} FROM {} {}", with, &enumerated_columns, &table.complete_name(), where_condition);
         let mut conn = self.acquire()?;
         let rows = conn.execute_result(&sql, Params::Nil)?;
-        Ok(rows.iter().next().map(|data| T::from_value(&data)))
+        rows.iter().next().map(load::<T>).transpose()
     }
 
     /// Get one the table of records by id
     fn select_by_id<T, I>(&self, id: I) -> Result<Option<T>, AkitaError>
     where
         T: GetTableName + GetFields + FromValue,
-        I: ToValue
+        I: ToKey
     {
         let table = T::table_name();
         if table.complete_name().is_empty() {
@@ -443,22 +1315,10 @@ impl AkitaMapper for AkitaEntityManager {
             .collect::<Vec<_>>()
             .join(", ");
         let mut conn = self.acquire()?;
-        if let Some(field) = columns.iter().find(| field| match field.field_type {
-            FieldType::TableId(_) => true,
-            FieldType::TableField => false,
-        }) {
-            let sql = match conn {
-                #[cfg(feature = "akita-mysql")]
-                DatabasePlatform::Mysql(_) => format!("SELECT {} FROM {} WHERE `{}` = ? limit 1", &enumerated_columns, &table.complete_name(), &field.name),
-                #[cfg(feature = "akita-sqlite")]
-                DatabasePlatform::Sqlite(_) => format!("SELECT {} FROM {} WHERE `{}` = ${} limit 1", &enumerated_columns, &table.complete_name(), &field.name, col_len + 1),
-                _ => format!("SELECT {} FROM {} WHERE `{}` = ${} limit 1", &enumerated_columns, &table.complete_name(), &field.name, col_len + 1),
-            };
-            let rows = conn.execute_result(&sql, (id.to_value(),).into())?;
-            Ok(rows.iter().next().map(|data| T::from_value(&data)))
-        } else {
-            Err(AkitaError::MissingIdent(format!("Table({}) Missing Ident...", &table.name)))
-        }
+        let (condition, values) = key_condition::<T, I>(&conn, &id, col_len)?;
+        let sql = format!("SELECT {} FROM {} WHERE {}{} limit 1", &enumerated_columns, &table.complete_name(), condition, not_deleted_sql::<T>());
+        let rows = conn.execute_result(&sql, values.into())?;
+        rows.iter().next().map(load::<T>).transpose()
     }
 
     /// Get table of records with page
@@ -483,18 +1343,21 @@ impl AkitaMapper for AkitaEntityManager {
         } else {
             select_fields
         };
+        exclude_deleted::<T>(&mut wrapper);
+        apply_unions::<T>(&mut wrapper, &enumerated_columns);
+        let with = with_clause(&mut wrapper)?;
         let where_condition = wrapper.get_sql_segment();
         let where_condition = if where_condition.trim().is_empty() { String::default() } else { format!("WHERE {}",where_condition) };
-        let count_sql = format!("select count(1) as count from {} {}", &table.complete_name(), where_condition);
+        let count_sql = PaginationOptions::default().count_sql(&format!("{}SELECT {} FROM {} {}", with, &enumerated_columns, &table.complete_name(), where_condition));
         let count: i64 = self.exec_first(&count_sql, ())?;
         let mut page = IPage::new(page, size ,count as usize, vec![]);
         if page.total > 0 {
-            let sql = format!("SELECT {} FROM {} {} limit {}, {}", &enumerated_columns, &table.complete_name(), where_condition,page.offset(),  page.size);
+            let sql = format!("{}SELECT {} FROM {} {} limit {}, {}", with, &enumerated_columns, &table.complete_name(), where_condition,page.offset(),  page.size);
             let mut conn = self.acquire()?;
             let rows = conn.execute_result(&sql, Params::Nil)?;
             let mut entities = vec![];
             for dao in rows.iter() {
-                let entity = T::from_value(&dao);
+                let entity = load::<T>(dao)?;
                 entities.push(entity)
             }
             page.records = entities;
@@ -511,10 +1374,17 @@ impl AkitaMapper for AkitaEntityManager {
         if table.complete_name().is_empty() {
             return Err(AkitaError::MissingTable("Find Error, Missing Table Name !".to_string()))
         }
+        if wrapper.has_unions() {
+            let sql = format!("SELECT COUNT(1) AS count FROM ({}) TOTAL", build_select_clause::<T>(&mut wrapper)?);
+            return self.exec_first(&sql, ());
+        }
+        exclude_deleted::<T>(&mut wrapper);
+        let with = with_clause(&mut wrapper)?;
         let where_condition = wrapper.get_sql_segment();
         let where_condition = if where_condition.trim().is_empty() { String::default() } else { format!("WHERE {}",where_condition) };
         let sql = format!(
-            "SELECT COUNT(1) AS count FROM {} {}",
+            "{}SELECT COUNT(1) AS count FROM {} {}",
+            with,
             table.complete_name(),
             where_condition
         );
@@ -530,9 +1400,10 @@ impl AkitaMapper for AkitaEntityManager {
         if table.complete_name().is_empty() {
             return Err(AkitaError::MissingTable("Find Error, Missing Table Name !".to_string()))
         }
+        exclude_deleted::<T>(&mut wrapper);
         let where_condition = wrapper.get_sql_segment();
         let where_condition = if where_condition.trim().is_empty() { String::default() } else { format!("WHERE {}",where_condition) };
-        let sql = format!("delete from {} {}", &table.complete_name(), where_condition);
+        let sql = format!("{} {}", delete_clause::<T>(&table.complete_name()), where_condition);
         let mut conn = self.acquire()?;
         let _ = conn.execute_result(&sql, Params::Nil)?;
         Ok(conn.affected_rows())
@@ -541,7 +1412,7 @@ impl AkitaMapper for AkitaEntityManager {
     /// Remove the records by id.
     fn remove_by_id<T, I>(&self, id: I) -> Result<u64, AkitaError>
     where
-        I: ToValue,
+        I: ToKey,
         T: GetTableName + GetFields {
         let table = T::table_name();
         if table.complete_name().is_empty() {
@@ -549,23 +1420,13 @@ impl AkitaMapper for AkitaEntityManager {
         }
         let cols = T::fields();
         let col_len = cols.len();
-        if let Some(field) = cols.iter().find(| field| match field.field_type {
-            FieldType::TableId(_) => true,
-            FieldType::TableField => false,
-        }) {
-            let mut conn = self.acquire()?;
-            let sql = match conn {
-                #[cfg(feature = "akita-mysql")]
-                DatabasePlatform::Mysql(_) => format!("delete from {} where `{}` = ?", &table.name, &field.name),
-                #[cfg(feature = "akita-sqlite")]
-                DatabasePlatform::Sqlite(_) => format!("delete from {} where `{}` = ${}", &table.name, &field.name, col_len + 1),
-                _ => format!("delete from {} where `{}` = ${}", &table.name, &field.name, col_len + 1),
-            };
-            let _ = conn.execute_result(&sql, (id.to_value(),).into())?;
-            Ok(conn.affected_rows())
-        } else {
-            Err(AkitaError::MissingIdent(format!("Table({}) Missing Ident...", &table.name)))
-        }
+        let mut conn = self.acquire()?;
+        let (condition, values) = key_condition::<T, I>(&conn, &id, col_len)?;
+        before_remove::<T>(&values)?;
+        let sql = format!("{} where {}", delete_clause::<T>(&table.name), condition);
+        let _ = conn.execute_result(&sql, values.clone().into())?;
+        after_remove::<T>(&values);
+        Ok(conn.affected_rows())
     }
 
 
@@ -587,10 +1448,10 @@ impl AkitaMapper for AkitaEntityManager {
         }) {
             let sql = match conn {
                 #[cfg(feature = "akita-mysql")]
-                DatabasePlatform::Mysql(_) => format!("delete from {} where `{}` in (?)", &table.name, &field.name),
+                DatabasePlatform::Mysql(_) => format!("{} where `{}` in (?)", delete_clause::<T>(&table.name), &field.name),
                 #[cfg(feature = "akita-sqlite")]
-                DatabasePlatform::Sqlite(_) => format!("delete from {} where `{}` in (${})", &table.name, &field.name, col_len + 1),
-                _ => format!("delete from {} where `{}` = ${}", &table.name, &field.name, col_len + 1),
+                DatabasePlatform::Sqlite(_) => format!("{} where `{}` in (${})", delete_clause::<T>(&table.name), &field.name, col_len + 1),
+                _ => format!("{} where `{}` = ${}", delete_clause::<T>(&table.name), &field.name, col_len + 1),
             };
             let ids = ids.iter().map(|v| v.to_value().to_string()).collect::<Vec<String>>().join(",");
             let _ = conn.execute_result(&sql, (ids,).into())?;
@@ -610,12 +1471,13 @@ impl AkitaMapper for AkitaEntityManager {
             return Err(AkitaError::MissingTable("Find Error, Missing Table Name !".to_string()))
         }
         let mut conn = self.acquire()?;
-        let columns = T::fields();
+        let columns = fill_fields::<T>(conn.config());
         let sql = build_update_clause(&conn, entity, &mut wrapper);
         let update_fields = wrapper.fields_set;
         let mut bvalues: Vec<&Value> = Vec::new();
         if update_fields.is_empty() {
-            let data = entity.to_value();
+            let mut data = entity.to_value();
+            before_update::<T>(&mut data)?;
             let mut values: Vec<Value> = Vec::with_capacity(columns.len());
             for col in columns.iter() {
                 if !col.exist || col.field_type.ne(&FieldType::TableField) {
@@ -640,6 +1502,7 @@ impl AkitaMapper for AkitaEntityManager {
                 }
             }
             conn.execute_result(&sql, values.into())?;
+            after_update::<T>(&data);
         } else {
             conn.execute_result(&sql, Params::Nil)?;
         }
@@ -650,76 +1513,43 @@ impl AkitaMapper for AkitaEntityManager {
     fn update_by_id<T>(&self, entity: &T) -> Result<u64, AkitaError>
     where
         T: GetTableName + GetFields + ToValue {
-        let table = T::table_name();
-        if table.complete_name().is_empty() {
-            return Err(AkitaError::MissingTable("Find Error, Missing Table Name !".to_string()))
-        }
         let mut conn = self.acquire()?;
-        let data = entity.to_value();
-        let columns = T::fields();
-        let col_len = columns.len();
-        if let Some(field) = T::fields().iter().find(| field| match field.field_type {
-            FieldType::TableId(_) => true,
-            FieldType::TableField => false,
-        }) {
-            let set_fields = columns
-            .iter().filter(|col| col.exist && col.field_type == FieldType::TableField)
-            .enumerate()
-            .map(|(x, col)| {
-                #[allow(unreachable_patterns)]
-                match conn {
-                    #[cfg(feature = "akita-mysql")]
-                    DatabasePlatform::Mysql(_) => format!("`{}` = ?", &col.name),
-                    #[cfg(feature = "akita-sqlite")]
-                    DatabasePlatform::Sqlite(_) => format!("`{}` = ${}",&col.name, x + 1),
-                    _ => format!("`{}` = ${}", &col.name, x + 1),
-                }
-            })
-            .collect::<Vec<_>>()
-            .join(", ");
-            let sql = match conn {
-                #[cfg(feature = "akita-mysql")]
-                DatabasePlatform::Mysql(_) => format!("update {} set {} where `{}` = ?", &table.name, &set_fields, &field.name),
-                #[cfg(feature = "akita-sqlite")]
-                DatabasePlatform::Sqlite(_) => format!("update {} set {} where `{}` = ${}", &table.name, &set_fields, &field.name, col_len + 1),
-                _ => format!("update {} set {} where `{}` = ${}", &table.name, &set_fields, &field.name, col_len + 1),
-            };
-            let mut values: Vec<Value> = Vec::with_capacity(columns.len());
-            let id = data.get_obj_value(&field.name);
-            for col in columns.iter() {
-                if !col.exist || col.field_type.ne(&FieldType::TableField) {
-                    continue;
-                }
-                let col_name = &col.name;
-                let mut value = data.get_obj_value(col_name);
-                match &col.fill {
-                    None => {}
-                    Some(v) => {
-                        match v.mode.as_ref() {
-                            "update" | "default" => {
-                                value = v.value.as_ref();
-                            }
-                            _=> {}
-                        }
-                    }
-                }
-                match value {
-                    Some(value) => values.push(value.clone()),
-                    None => values.push(Value::Nil),
-                }
-            }
-            match id {
-                Some(id) => values.push(id.clone()),
-                None => {
-                    return Err(AkitaError::MissingIdent(format!("Table({}) Missing Ident value...", &table.name)));
-                }
-            }
-            let _ = conn.execute_result(&sql, values.into())?;
-            Ok(conn.affected_rows())
-        } else {
-            Err(AkitaError::MissingIdent(format!("Table({}) Missing Ident...", &table.name)))
-        }
+        update_by_id_with(&mut conn, entity)
+    }
+
+    /// Update the records by id, all in one transaction.
+    fn update_batch_by_id<T>(&self, entities: &[&T]) -> Result<u64, AkitaError>
+    where
+        T: GetTableName + GetFields + ToValue {
+        let mut conn = self.acquire()?;
+        update_batch_by_id_with(&mut conn, false, entities)
+    }
 
+    /// Remove the records by ids in chunks, each chunk in its own transaction.
+    fn remove_by_ids_chunked<T, I, F>(&self, ids: Vec<I>, chunk_size: usize, pause: Option<Duration>, on_progress: F) -> Result<u64, AkitaError>
+    where
+        I: ToValue,
+        T: GetTableName + GetFields,
+        F: FnMut(&ChunkProgress) {
+        let mut conn = self.acquire()?;
+        remove_by_ids_chunked_with::<T, I, F>(&mut conn, false, ids, chunk_size, pause, on_progress)
+    }
+
+    /// Get all the descendants of the record.
+    fn descendants_of<T, I>(&self, id: I) -> Result<Vec<T>, AkitaError>
+    where
+        T: GetTableName + GetFields + FromValue,
+        I: ToValue {
+        let mut conn = self.acquire()?;
+        descendants_with(&mut conn, id.to_value())
+    }
+
+    /// Get the checksum of the records.
+    fn table_checksum<T>(&self, mut wrapper: Wrapper) -> Result<TableChecksum, AkitaError>
+    where
+        T: GetTableName + GetFields {
+        let mut conn = self.acquire()?;
+        table_checksum_with::<T>(&mut conn, &mut wrapper)
     }
 
     #[allow(unused_variables)]
@@ -742,12 +1572,17 @@ impl AkitaMapper for AkitaEntityManager {
         T: GetTableName + GetFields + ToValue,
         I: FromValue,
     {
-        let columns = T::fields();
         let mut conn = self.acquire()?;
+        let columns = fill_fields::<T>(conn.config());
         let sql = build_insert_clause(&conn, &[entity]);
-        let data = entity.to_value();
+        let mut data = entity.to_value();
+        before_insert::<T>(&mut data)?;
         let mut values: Vec<Value> = Vec::with_capacity(columns.len());
+        let mut generated_id = None;
         for col in columns.iter() {
+            if !col.exist {
+                continue;
+            }
             let mut value = data.get_obj_value(&col.name);
             match &col.fill {
                 None => {}
@@ -761,12 +1596,22 @@ impl AkitaMapper for AkitaEntityManager {
                 }
             }
             match value {
-                Some(value) => values.push(value.clone()),
-                None => values.push(Value::Nil),
+                Some(value) if *value != Value::Nil => values.push(value.clone()),
+                _ => {
+                    let id = col.field_type.generate_id();
+                    if id.is_some() {
+                        generated_id = id.clone();
+                    }
+                    values.push(id.unwrap_or(Value::Nil));
+                }
             }
         }
         let bvalues: Vec<&Value> = values.iter().collect();
         conn.execute_result(&sql,values.into())?;
+        after_insert::<T>(&data);
+        if let Some(id) = generated_id {
+            return Ok(Some(I::from_value_opt(&id)?));
+        }
         let rows: Rows = match conn {
             #[cfg(feature = "akita-mysql")]
             DatabasePlatform::Mysql(_) => conn.execute_result("SELECT LAST_INSERT_ID();", Params::Nil)?,
@@ -800,11 +1645,53 @@ impl AkitaMapper for AkitaEntityManager {
         }
     }
 
+    /// insert or update on the table key
+    fn upsert<T>(&self, entity: &T) -> Result<u64, AkitaError>
+        where
+            T: GetTableName + GetFields + ToValue {
+        let mut conn = self.acquire()?;
+        upsert_with(&mut conn, entity)
+    }
+
+    fn save_or_update_returning<T>(&self, entity: &T) -> Result<UpsertResult<T>, AkitaError>
+        where
+            T: GetTableName + GetFields + ToValue + FromValue {
+        let mut conn = self.acquire()?;
+        upsert_returning(&mut conn, false, entity)
+    }
+
     fn exec_iter<S: Into<String>, P: Into<Params>>(&self, sql: S, params: P) -> Result<Rows, AkitaError> {
         let mut conn = self.acquire()?;
         let rows = conn.execute_result(&sql.into(), params.into())?;
         Ok(rows)
     }
+
+    fn exec_batch<S: Into<String>, P: Into<Params>>(&self, sql: S, params: Vec<P>) -> Result<u64, AkitaError> {
+        let sql: String = sql.into();
+        let mut conn = self.acquire()?;
+        let affected = exec_batch_with(&mut conn, false, &sql, params.into_iter().map(Into::into).collect())?;
+        Ok(affected)
+    }
+
+    fn exec_result<S: Into<String>, P: Into<Params>>(&self, sql: S, params: P) -> Result<ExecuteResult, AkitaError> {
+        let sql: String = sql.into();
+        let mut conn = self.acquire()?;
+        conn.execute_drop(&sql, params.into())?;
+        ExecuteResult::of(&mut conn)
+    }
+
+    fn exec_stream<R, S: Into<String>, P: Into<Params>, F>(&self, sql: S, params: P, mut f: F) -> Result<(), AkitaError>
+        where
+            R: FromValue,
+            F: FnMut(R) -> Result<(), AkitaError>,
+    {
+        let mut conn = self.acquire()?;
+        conn.execute_stream(&sql.into(), params.into(), &mut |row| f(load::<R>(row.to_value())?))
+    }
+
+    fn fetch_size(&self) -> usize {
+        self.1.fetch_size()
+    }
 }
 
 
@@ -814,7 +1701,7 @@ mod test {
     use akita_core::params;
     // use crate as akita;
 
-    use crate::{self as akita, AkitaConfig, AkitaMapper, BaseMapper, Pool, Wrapper, FromValue, ToValue, AkitaTable};
+    use crate::{self as akita, AkitaConfig, AkitaMapper, BaseMapper, Pool, Wrapper, FromValue, ToValue, AkitaTable, TreeNode};
 
     fn fffff() {
 
@@ -830,6 +1717,25 @@ mod test {
         age: i32,
     }
 
+    #[derive(Debug, AkitaTable, Clone)]
+    #[table(name="t_category")]
+    struct Category {
+        #[table_id]
+        id: i32,
+        #[field(parent_id)]
+        parent_id: Option<i32>,
+        name: String,
+    }
+
+    #[test]
+    fn build_tree() {
+        let category = |id: i32, parent_id: Option<i32>| Category { id, parent_id, name: id.to_string() };
+        let tree = TreeNode::build(vec![category(1, None), category(2, Some(1)), category(3, Some(2)), category(4, Some(9)), category(5, Some(1))]).unwrap();
+        assert_eq!(tree.iter().map(|node| node.record.id).collect::<Vec<_>>(), vec![1, 4]);
+        assert_eq!(tree[0].children.iter().map(|node| node.record.id).collect::<Vec<_>>(), vec![2, 5]);
+        assert_eq!(tree[0].children[0].children[0].record.id, 3);
+    }
+
     #[test]
     fn get_table_info() {
         let s = params! { "test" => 1, "id" => 3, "id"=> 4};
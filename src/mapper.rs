@@ -1,5 +1,19 @@
-use akita_core::{AkitaDataError, from_value, from_value_opt, Rows};
-use crate::{AkitaError, Wrapper, FromValue, ToValue, Params, GetTableName, GetFields};
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+use akita_core::{AkitaDataError, from_value, from_value_opt, Rows, Value};
+use indexmap::IndexMap;
+use crate::cache::{self, CachePolicy};
+use crate::manager::{build_select_clause, build_select_clause_with_deleted};
+use crate::logic_delete::{logic_delete_column, not_deleted_sql, only_deleted, require_logic_delete_column, LOGIC_NOT_DELETED};
+use crate::{AkitaError, Wrapper, FromValue, ToKey, ToValue, Params, GetTableName, GetFields, ISegment};
+use crate::key::{key_fields, key_values, single_key_field};
+use crate::relation::{group_related, load_related, Related};
+use crate::pool::DEFAULT_FETCH_SIZE;
+use crate::pagination::PaginationOptions;
+use crate::database::DatabasePlatform;
+use crate::listener::load;
+use crate::watchdog;
 use serde::{Serialize, Deserialize};
 
 #[derive(Clone, Deserialize, Serialize)]
@@ -8,7 +22,10 @@ pub struct IPage <T>
     pub total: usize,
     pub size: usize,
     pub current: usize,
-    pub records: Vec<T>
+    pub records: Vec<T>,
+    /// the records or the total are partial, the page ran out of its deadline
+    #[serde(default)]
+    pub truncated: bool,
 }
 
 impl <T> IPage <T> 
@@ -19,6 +36,7 @@ where T: Sized {
             size,
             current,
             records,
+            truncated: false,
         }
     }
 
@@ -27,6 +45,131 @@ where T: Sized {
     }
 }
 
+/// The record written by an upsert, and whether it was newly inserted.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct UpsertResult<T> {
+    pub record: T,
+    pub inserted: bool,
+}
+
+/// An aggregate hash of the records matched by a wrapper, cheap to compare between two requests.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TableChecksum {
+    pub count: u64,
+    /// the xor of the row hashes, so it doesn't depend on the row order
+    pub checksum: u64,
+}
+
+impl TableChecksum {
+    /// The checksum as a strong http `ETag` value, quotes included.
+    pub fn etag(&self) -> String {
+        format!("\"{:x}-{:016x}\"", self.count, self.checksum)
+    }
+}
+
+/// A warning raised by the database on a statement, e.g. a value truncated to fit its column.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SqlWarning {
+    /// `Note`, `Warning` or `Error` on MySQL
+    pub level: String,
+    pub code: u32,
+    pub message: String,
+}
+
+impl std::fmt::Display for SqlWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}: {}", &self.level, self.code, &self.message)
+    }
+}
+
+/// The outcome of a statement run with `exec_result`.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct ExecuteResult {
+    pub affected_rows: u64,
+    pub last_insert_id: u64,
+    warnings: Vec<SqlWarning>,
+}
+
+impl ExecuteResult {
+    /// The warnings raised by the statement, always empty on SQLite.
+    pub fn warnings(&self) -> &[SqlWarning] {
+        &self.warnings
+    }
+
+    pub(crate) fn of(conn: &mut DatabasePlatform) -> Result<Self, AkitaError> {
+        // the counters first, fetching the warnings runs a statement.
+        let affected_rows = conn.affected_rows();
+        let last_insert_id = conn.last_insert_id();
+        Ok(ExecuteResult { affected_rows, last_insert_id, warnings: conn.warnings()? })
+    }
+}
+
+/// The progress of a chunked removal, reported after each chunk.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ChunkProgress {
+    /// the chunks done so far
+    pub chunk: usize,
+    pub chunks: usize,
+    /// the records removed so far
+    pub removed: u64,
+}
+
+/// A record with its children, as loaded by the tree queries.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TreeNode<T> {
+    pub record: T,
+    pub children: Vec<TreeNode<T>>,
+}
+
+impl <T> TreeNode<T>
+where T: GetTableName + GetFields + ToValue {
+    /// Nest the records under their parents by the `#[field(parent_id)]` column,
+    /// the records whose parent is not in the list become the roots.
+    pub fn build(records: Vec<T>) -> Result<Vec<TreeNode<T>>, AkitaError> {
+        let table = T::table_name();
+        let fields = T::fields();
+        let key = single_key_field::<T>()?;
+        let parent = fields.iter().find(|field| field.parent_id)
+            .ok_or_else(|| AkitaError::MissingField(format!("Table({}) Missing parent_id field...", &table.name)))?;
+        let mut keys = Vec::with_capacity(records.len());
+        let mut parents = Vec::with_capacity(records.len());
+        let mut children: IndexMap<String, Vec<usize>> = IndexMap::new();
+        for (i, record) in records.iter().enumerate() {
+            let data = record.to_value();
+            keys.push(data.get_obj_value(&key.name).map(|v| v.to_string()).unwrap_or_default());
+            let parent = match data.get_obj_value(&parent.name) {
+                None | Some(Value::Nil) => None,
+                Some(parent) => Some(parent.to_string()),
+            };
+            if let Some(parent) = &parent {
+                children.entry(parent.clone()).or_default().push(i);
+            }
+            parents.push(parent);
+        }
+        let key_set = keys.iter().collect::<HashSet<_>>();
+        let roots = parents.iter().enumerate()
+            .filter(|(_, parent)| parent.as_ref().map(|parent| !key_set.contains(parent)).unwrap_or(true))
+            .map(|(i, _)| i).collect::<Vec<_>>();
+        let mut records = records.into_iter().map(Some).collect::<Vec<_>>();
+        let mut tree = roots.into_iter().filter_map(|i| Self::attach(i, &keys, &children, &mut records)).collect::<Vec<_>>();
+        // the records left are in a cycle, break it at the first one met.
+        for i in 0..records.len() {
+            if let Some(node) = Self::attach(i, &keys, &children, &mut records) {
+                tree.push(node);
+            }
+        }
+        Ok(tree)
+    }
+
+    fn attach(i: usize, keys: &[String], children: &IndexMap<String, Vec<usize>>, records: &mut Vec<Option<T>>) -> Option<TreeNode<T>> {
+        let record = records[i].take()?;
+        let children = children.get(&keys[i]).map(|indexes| {
+            indexes.iter().filter_map(|child| Self::attach(*child, keys, children, records)).collect::<Vec<_>>()
+        }).unwrap_or_default();
+        Some(TreeNode { record, children })
+    }
+}
+
 pub trait BaseMapper{
     type Item;
 
@@ -49,7 +192,7 @@ pub trait BaseMapper{
     fn find_one<M: AkitaMapper>(wrapper: Wrapper, entity_manager: &M) -> Result<Option<Self::Item>, AkitaError> where Self::Item : GetTableName + GetFields + FromValue;
 
     /// Find Data With Table's Ident.
-    fn find_by_id<I: ToValue, M: AkitaMapper>(&self, entity_manager: &M, id: I) -> Result<Option<Self::Item>, AkitaError> where Self::Item : GetTableName + GetFields + FromValue;
+    fn find_by_id<I: ToKey, M: AkitaMapper>(&self, entity_manager: &M, id: I) -> Result<Option<Self::Item>, AkitaError> where Self::Item : GetTableName + GetFields + FromValue;
 
     /// Update Data With Table's Ident.
     fn update_by_id<M: AkitaMapper>(&self, entity_manager: &M) -> Result<u64, AkitaError> where Self::Item : GetFields + GetTableName + ToValue ;
@@ -58,7 +201,7 @@ pub trait BaseMapper{
     fn delete<M: AkitaMapper>(&self, wrapper: Wrapper, entity_manager: &M) -> Result<u64, AkitaError>where Self::Item : GetFields + GetTableName + ToValue ;
 
     /// Delete by ID
-    fn delete_by_id<I: ToValue, M: AkitaMapper>(&self, entity_manager: &M, id: I) -> Result<u64, AkitaError> where Self::Item : GetFields + GetTableName + ToValue ;
+    fn delete_by_id<I: ToKey, M: AkitaMapper>(&self, entity_manager: &M, id: I) -> Result<u64, AkitaError> where Self::Item : GetFields + GetTableName + ToValue ;
 
     /// Get the Table Count.
     fn count<M: AkitaMapper>(&mut self, wrapper: Wrapper, entity_manager: &M) -> Result<usize, AkitaError>;
@@ -71,6 +214,100 @@ pub trait AkitaMapper {
     where
         T: GetTableName + GetFields + FromValue;
 
+    /// Get all the table of records, the ones removed through the `#[field(logic_delete)]` field included.
+    fn list_with_deleted<T>(&self, mut wrapper: Wrapper) -> Result<Vec<T>, AkitaError>
+    where
+        T: GetTableName + GetFields + FromValue,
+    {
+        let sql = build_select_clause_with_deleted::<T>(&mut wrapper)?;
+        self.exec_raw(sql, ())
+    }
+
+    /// Get the records with their `#[has_many]` or `#[belongs_to]` field of `R` loaded,
+    /// all of the related records read by one more query.
+    fn list_with<T, R>(&self, wrapper: Wrapper) -> Result<Vec<T>, AkitaError>
+    where
+        T: GetTableName + GetFields + FromValue + ToValue + Related<R>,
+        R: GetTableName + GetFields + FromValue + ToValue + Clone,
+    {
+        let mut records = self.list::<T>(wrapper)?;
+        load_related(self, &mut records)?;
+        Ok(records)
+    }
+
+    /// Load the `#[has_many]` or `#[belongs_to]` field of `R` of the records, e.g. a second relation of the ones of `list_with`.
+    fn load_with<T, R>(&self, records: &mut [T]) -> Result<(), AkitaError>
+    where
+        T: GetTableName + GetFields + ToValue + Related<R>,
+        R: GetTableName + GetFields + FromValue + ToValue + Clone,
+    {
+        load_related(self, records)
+    }
+
+    /// Get the records of a joined query with their `#[has_many]` field of `C` filled from the same rows,
+    /// the rows grouped by the key of the record, e.g. for
+    /// `Wrapper::new().table("t_user u left join t_order o on o.user_id = u.id").select(vec!["u.id".to_string(), "u.name".to_string(), "o.id as order_id".to_string()])`.
+    /// The columns are read by name, and a row whose key columns of `C` are all null adds no record of `C`.
+    fn list_grouped<P, C, K, F>(&self, mut wrapper: Wrapper, key_fn: F) -> Result<Vec<P>, AkitaError>
+    where
+        P: GetTableName + FromValue + Related<C>,
+        C: GetTableName + GetFields + FromValue,
+        K: Eq + Hash,
+        F: FnMut(&P) -> K,
+    {
+        if wrapper.table.is_none() {
+            wrapper = wrapper.table(P::table_name().complete_name());
+        }
+        let rows = self.exec_iter(wrapper.get_query_sql()?, ())?;
+        group_related::<P, C, K, F>(&rows, key_fn)
+    }
+
+    /// Get the records removed through the `#[field(logic_delete)]` field matched by the wrapper.
+    fn list_deleted<T>(&self, mut wrapper: Wrapper) -> Result<Vec<T>, AkitaError>
+    where
+        T: GetTableName + GetFields + FromValue,
+    {
+        only_deleted::<T>(&mut wrapper)?;
+        self.list_with_deleted(wrapper)
+    }
+
+    /// Count the records removed through the `#[field(logic_delete)]` field matched by the wrapper.
+    fn count_deleted<T>(&self, mut wrapper: Wrapper) -> Result<usize, AkitaError>
+    where
+        T: GetTableName + GetFields,
+    {
+        only_deleted::<T>(&mut wrapper)?;
+        let table = T::table_name();
+        let sql = format!("SELECT COUNT(1) AS count FROM {} WHERE {}", &table.complete_name(), wrapper.get_sql_segment());
+        self.exec_first(sql, ())
+    }
+
+    /// Restore the records removed through the `#[field(logic_delete)]` field matched by the wrapper.
+    fn restore<T>(&self, mut wrapper: Wrapper) -> Result<u64, AkitaError>
+    where
+        T: GetTableName + GetFields,
+    {
+        let column = require_logic_delete_column::<T>()?;
+        only_deleted::<T>(&mut wrapper)?;
+        let sql = format!("update {} set `{}` = {} WHERE {}", &T::table_name().complete_name(), column, LOGIC_NOT_DELETED, wrapper.get_sql_segment());
+        Ok(self.exec_result(sql, ())?.affected_rows)
+    }
+
+    /// Restore the record removed through the `#[field(logic_delete)]` field by id.
+    fn restore_by_id<T, I>(&self, id: I) -> Result<u64, AkitaError>
+    where
+        I: ToKey,
+        T: GetTableName + GetFields,
+    {
+        let table = T::table_name();
+        let column = require_logic_delete_column::<T>()?;
+        let keys = key_fields::<T>()?;
+        let values = key_values::<T, I>(&keys, &id)?;
+        let condition = keys.iter().map(|key| format!("`{}` = ?", &key.name)).collect::<Vec<_>>().join(" and ");
+        let sql = format!("update {} set `{}` = {} where {}", &table.complete_name(), column, LOGIC_NOT_DELETED, condition);
+        Ok(self.exec_result(sql, values)?.affected_rows)
+    }
+
     /// Get one the table of records
     fn select_one<T>(&self, wrapper: Wrapper) -> Result<Option<T>, AkitaError>
     where
@@ -80,18 +317,85 @@ pub trait AkitaMapper {
     fn select_by_id<T, I>(&self, id: I) -> Result<Option<T>, AkitaError>
     where
         T: GetTableName + GetFields + FromValue,
-        I: ToValue;
+        I: ToKey;
 
     /// Get table of records with page
     fn page<T>(&self, page: usize, size: usize, wrapper: Wrapper) -> Result<IPage<T>, AkitaError>
     where
         T: GetTableName + GetFields + FromValue;
 
+    /// Get table of records with page, counted as the options say, e.g. not at all for an infinite scroll.
+    fn page_with<T>(&self, page: usize, size: usize, mut wrapper: Wrapper, options: &PaginationOptions) -> Result<IPage<T>, AkitaError>
+        where
+            T: GetTableName + GetFields + FromValue,
+    {
+        let sql = build_select_clause::<T>(&mut wrapper)?;
+        let mut page = IPage::new(page, size, 0, vec![]);
+        if options.need_total {
+            let count: i64 = self.exec_first(options.count_sql(&sql), ())?;
+            page.total = count as usize;
+            if page.total == 0 {
+                return Ok(page);
+            }
+        }
+        page.records = self.exec_raw(format!("{} limit {}, {}", &sql, page.offset(), page.size), ())?;
+        Ok(page)
+    }
+
+    /// Get table of records with page within the deadline, instead of failing a slow page
+    /// it returns the records read in time flagged as `truncated`, the total is then only a lower bound.
+    /// The time left is the statement timeout of the page and of its count, a statement running past
+    /// the deadline is cancelled the way `AkitaConfig::set_statement_timeout` does.
+    fn page_within<T>(&self, page: usize, size: usize, mut wrapper: Wrapper, deadline: Duration) -> Result<IPage<T>, AkitaError>
+        where
+            T: GetTableName + GetFields + FromValue,
+    {
+        let start = Instant::now();
+        let until = start + deadline;
+        let sql = build_select_clause::<T>(&mut wrapper)?;
+        let mut page = IPage::new(page, size, 0, vec![]);
+        let mut records = Vec::new();
+        let mut truncated = false;
+        let streamed = watchdog::within(until, || self.exec_stream(format!("{} limit {}, {}", &sql, page.offset(), page.size), (), |record: T| {
+            if start.elapsed() < deadline {
+                records.push(record);
+            } else {
+                truncated = true;
+            }
+            Ok(())
+        }));
+        match streamed {
+            Err(AkitaError::QueryTimeout(_)) => truncated = true,
+            streamed => streamed?,
+        }
+        let count = if truncated || start.elapsed() >= deadline {
+            None
+        } else {
+            match watchdog::within(until, || self.exec_first::<i64, _, _>(PaginationOptions::default().count_sql(&sql), ())) {
+                Err(AkitaError::QueryTimeout(_)) => None,
+                count => Some(count? as usize),
+            }
+        };
+        page.total = count.unwrap_or_else(|| {
+            truncated = true;
+            page.offset() + records.len()
+        });
+        page.records = records;
+        page.truncated = truncated;
+        Ok(page)
+    }
+
     /// Get the total count of records
     fn count<T>(&self, wrapper: Wrapper) -> Result<usize, AkitaError>
     where
         T: GetTableName + GetFields;
 
+    /// Get a checksum of the records matched by the wrapper, which changes with any of their columns,
+    /// to answer the list requests with a `304 Not Modified` when it matches the `If-None-Match` header.
+    fn table_checksum<T>(&self, wrapper: Wrapper) -> Result<TableChecksum, AkitaError>
+    where
+        T: GetTableName + GetFields;
+
     /// Remove the records by wrapper.
     fn remove<T>(&self, wrapper: Wrapper) -> Result<u64, AkitaError>
     where
@@ -103,10 +407,18 @@ pub trait AkitaMapper {
             I: ToValue,
             T: GetTableName + GetFields;
 
+    /// Remove the records by ids in chunks of `chunk_size`, each chunk in its own transaction,
+    /// pausing between the chunks so a huge delete doesn't hold the locks or flood the replicas.
+    fn remove_by_ids_chunked<T, I, F>(&self, ids: Vec<I>, chunk_size: usize, pause: Option<Duration>, on_progress: F) -> Result<u64, AkitaError>
+        where
+            I: ToValue,
+            T: GetTableName + GetFields,
+            F: FnMut(&ChunkProgress);
+
     /// Remove the records by id.
     fn remove_by_id<T, I>(&self, id: I) -> Result<u64, AkitaError>
     where
-        I: ToValue,
+        I: ToKey,
         T: GetTableName + GetFields;
     
 
@@ -120,6 +432,11 @@ pub trait AkitaMapper {
     where
         T: GetTableName + GetFields + ToValue;
 
+    /// Update the records by id with bound parameters, all in one transaction.
+    fn update_batch_by_id<T>(&self, entities: &[&T]) -> Result<u64, AkitaError>
+    where
+        T: GetTableName + GetFields + ToValue;
+
     #[allow(unused_variables)]
     fn save_batch<T>(&self, entities: &[&T]) -> Result<(), AkitaError>
     where
@@ -137,6 +454,74 @@ pub trait AkitaMapper {
             T: GetTableName + GetFields + ToValue,
             I: FromValue;
 
+    /// Get the records keyed by the table id, in the order they were returned.
+    fn map_by_id<T, K>(&self, wrapper: Wrapper) -> Result<IndexMap<K, T>, AkitaError>
+        where
+            T: GetTableName + GetFields + FromValue + ToValue,
+            K: FromValue + Eq + Hash,
+    {
+        let field = single_key_field::<T>()?;
+        let records = self.list::<T>(wrapper)?;
+        let mut map = IndexMap::with_capacity(records.len());
+        for record in records {
+            let data = record.to_value();
+            let id = K::from_value_opt(data.get_obj_value(&field.name).unwrap_or(&Value::Nil))?;
+            map.insert(id, record);
+        }
+        Ok(map)
+    }
+
+    /// Get the records grouped by the key, the groups keep the order they were first met.
+    fn group_by<T, K, F>(&self, wrapper: Wrapper, mut key_fn: F) -> Result<IndexMap<K, Vec<T>>, AkitaError>
+        where
+            T: GetTableName + GetFields + FromValue,
+            K: Eq + Hash,
+            F: FnMut(&T) -> K,
+    {
+        let records = self.list::<T>(wrapper)?;
+        let mut map: IndexMap<K, Vec<T>> = IndexMap::new();
+        for record in records {
+            map.entry(key_fn(&record)).or_default().push(record);
+        }
+        Ok(map)
+    }
+
+    /// Get all the descendants of the record through the `#[field(parent_id)]` column,
+    /// with a recursive query where the database supports it or level by level otherwise.
+    fn descendants_of<T, I>(&self, id: I) -> Result<Vec<T>, AkitaError>
+        where
+            T: GetTableName + GetFields + FromValue,
+            I: ToValue;
+
+    /// Get the records nested under their parents.
+    fn tree<T>(&self, wrapper: Wrapper) -> Result<Vec<TreeNode<T>>, AkitaError>
+        where
+            T: GetTableName + GetFields + FromValue + ToValue,
+    {
+        TreeNode::build(self.list::<T>(wrapper)?)
+    }
+
+    /// Get the descendants of the record nested under their parents, the roots are its direct children.
+    fn subtree_of<T, I>(&self, id: I) -> Result<Vec<TreeNode<T>>, AkitaError>
+        where
+            T: GetTableName + GetFields + FromValue + ToValue,
+            I: ToValue,
+    {
+        TreeNode::build(self.descendants_of::<T, I>(id)?)
+    }
+
+    /// Insert the record, or update it when the table key already exists,
+    /// using the native `ON DUPLICATE KEY UPDATE` / `ON CONFLICT DO UPDATE` in a single statement.
+    fn upsert<T>(&self, entity: &T) -> Result<u64, AkitaError>
+        where
+            T: GetTableName + GetFields + ToValue;
+
+    /// Upsert the record and read back the final row in the same transaction,
+    /// telling whether the row was inserted or an existing one was updated.
+    fn save_or_update_returning<T>(&self, entity: &T) -> Result<UpsertResult<T>, AkitaError>
+        where
+            T: GetTableName + GetFields + ToValue + FromValue;
+
     fn query<T, Q>(&mut self, query: Q) -> Result<Vec<T>, AkitaError>
         where
             Q: Into<String>,
@@ -228,6 +613,133 @@ pub trait AkitaMapper {
         params: P,
     ) -> Result<Rows, AkitaError>;
 
+    /// Hand the records to the consumer one by one as they are read from the connection,
+    /// so large result sets never have to be held in memory at once.
+    fn exec_stream<R, S: Into<String>, P: Into<Params>, F>(
+        &self,
+        sql: S,
+        params: P,
+        f: F,
+    ) -> Result<(), AkitaError>
+        where
+            R: FromValue,
+            F: FnMut(R) -> Result<(), AkitaError>;
+
+    /// Hand the records to the consumer in chunks of `fetch_size` as they are read from the connection,
+    /// the last chunk may be smaller.
+    fn exec_chunks<R, S: Into<String>, P: Into<Params>, F>(
+        &self,
+        sql: S,
+        params: P,
+        mut f: F,
+    ) -> Result<(), AkitaError>
+        where
+            R: FromValue,
+            F: FnMut(Vec<R>) -> Result<(), AkitaError>,
+    {
+        let fetch_size = self.fetch_size();
+        let mut chunk = Vec::with_capacity(fetch_size);
+        self.exec_stream(sql, params, |record: R| {
+            chunk.push(record);
+            if chunk.len() >= fetch_size {
+                f(std::mem::replace(&mut chunk, Vec::with_capacity(fetch_size)))?;
+            }
+            Ok(())
+        })?;
+        if !chunk.is_empty() {
+            f(chunk)?;
+        }
+        Ok(())
+    }
+
+    /// The rows handed at once by `exec_chunks`.
+    fn fetch_size(&self) -> usize {
+        DEFAULT_FETCH_SIZE
+    }
+
+    /// Stream the table of records matching the wrapper, see `exec_stream`.
+    fn stream<T, F>(&self, mut wrapper: Wrapper, f: F) -> Result<(), AkitaError>
+        where
+            T: GetTableName + GetFields + FromValue,
+            F: FnMut(T) -> Result<(), AkitaError>,
+    {
+        let sql = build_select_clause::<T>(&mut wrapper)?;
+        self.exec_stream(sql, (), f)
+    }
+
+    /// Walk the whole table ordered by the table key, handing the records to the closure batch by batch.
+    /// Each batch starts after the last key of the previous one instead of an `OFFSET`,
+    /// so the cost stays the same deep into the table. Returns the records scanned.
+    fn scan<T, F>(&self, batch_size: usize, mut f: F) -> Result<u64, AkitaError>
+        where
+            T: GetTableName + GetFields + FromValue,
+            F: FnMut(Vec<T>) -> Result<(), AkitaError>,
+    {
+        let table = T::table_name();
+        if table.complete_name().is_empty() {
+            return Err(AkitaError::MissingTable("Find Error, Missing Table Name !".to_string()))
+        }
+        if batch_size == 0 {
+            return Err(AkitaError::DataError("batch size must be greater than zero".to_string()))
+        }
+        let fields = T::fields();
+        let keys = key_fields::<T>()?;
+        let enumerated_columns = fields
+            .iter().filter(|f| f.exist)
+            .map(|c| format!("`{}`", c.name))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let key_columns = keys.iter().map(|key| format!("`{}`", key.name)).collect::<Vec<_>>().join(", ");
+        let order_by = keys.iter().map(|key| format!("`{}` ASC", key.name)).collect::<Vec<_>>().join(", ");
+        // a composite key is compared as a row value, in the order of its fields.
+        let after = match keys.len() {
+            1 => format!("{} > ?", key_columns),
+            len => format!("({}) > ({})", key_columns, vec!["?"; len].join(", ")),
+        };
+        let not_deleted = logic_delete_column::<T>().map(|column| format!(" WHERE `{}` = {}", column, LOGIC_NOT_DELETED)).unwrap_or_default();
+        let mut last_key: Option<Vec<Value>> = None;
+        let mut scanned = 0;
+        loop {
+            let rows = match last_key.take() {
+                None => self.exec_iter(format!("SELECT {} FROM {}{} ORDER BY {} LIMIT {}", &enumerated_columns, &table.complete_name(), not_deleted, &order_by, batch_size), ())?,
+                Some(last) => self.exec_iter(format!("SELECT {} FROM {} WHERE {}{} ORDER BY {} LIMIT {}", &enumerated_columns, &table.complete_name(), &after, not_deleted_sql::<T>(), &order_by, batch_size), last)?,
+            };
+            let mut batch = Vec::with_capacity(rows.len());
+            for data in rows.iter() {
+                last_key = keys.iter().map(|key| data.get_obj_value(&key.name).cloned()).collect();
+                batch.push(load::<T>(data)?);
+            }
+            let len = batch.len();
+            if len == 0 {
+                break;
+            }
+            scanned += len as u64;
+            f(batch)?;
+            if len < batch_size || last_key.is_none() {
+                break;
+            }
+        }
+        Ok(scanned)
+    }
+
+    /// Reload the record from its row by the table id, to pick up the values
+    /// the database set itself (defaults, triggers, bulk updates).
+    fn refresh<T>(&self, entity: &mut T) -> Result<(), AkitaError>
+        where
+            T: GetTableName + GetFields + FromValue + ToValue,
+    {
+        self.refresh_all(std::slice::from_mut(entity))
+    }
+
+    /// Reload the records from their rows by the table id, with a query for each 500 records.
+    /// Fails when a record has no id or its row is gone.
+    fn refresh_all<T>(&self, entities: &mut [T]) -> Result<(), AkitaError>
+        where
+            T: GetTableName + GetFields + FromValue + ToValue,
+    {
+        refresh_all_with(self, entities)
+    }
+
     #[allow(clippy::redundant_closure)]
     fn exec_raw<R, S: Into<String>, P: Into<Params>>(
         &self,
@@ -238,7 +750,7 @@ pub trait AkitaMapper {
             R: FromValue,
     {
         let rows = self.exec_iter(&sql.into(), params.into())?;
-        Ok(rows.iter().map(|data| R::from_value(&data)).collect::<Vec<R>>())
+        rows.iter().map(load::<R>).collect()
     }
 
     fn exec_first<R, S: Into<String>, P: Into<Params>>(
@@ -272,6 +784,59 @@ pub trait AkitaMapper {
         Ok(())
     }
 
+    /// Execute the statement once for each params, prepared once on a single connection,
+    /// in a transaction unless already in one. Returns the total of the affected rows.
+    fn exec_batch<S: Into<String>, P: Into<Params>>(&self, sql: S, params: Vec<P>) -> Result<u64, AkitaError>;
+
+    /// Same as `exec_drop`, returns the affected rows, the inserted id and the warnings raised by the statement,
+    /// e.g. a value silently truncated. See `AkitaConfig::set_warnings_as_errors` to fail on them instead.
+    fn exec_result<S: Into<String>, P: Into<Params>>(&self, sql: S, params: P) -> Result<ExecuteResult, AkitaError>;
+
+    /// Same as `exec_iter`, the rows are served from the query cache of the datasource while fresh,
+    /// keyed by the statement as the interceptors rewrote it. The selects of a transaction are not cached.
+    fn exec_iter_cached<S: Into<String>, P: Into<Params>>(
+        &self,
+        sql: S,
+        params: P,
+        cache: &CachePolicy,
+    ) -> Result<Rows, AkitaError>
+    {
+        let sql: String = sql.into();
+        cache::cached(&sql, cache, || self.exec_iter(&sql, params))
+    }
+
+    /// Same as `exec_raw`, the rows are served from the query cache while fresh.
+    fn exec_raw_cached<R, S: Into<String>, P: Into<Params>>(
+        &self,
+        sql: S,
+        params: P,
+        cache: &CachePolicy,
+    ) -> Result<Vec<R>, AkitaError>
+        where
+            R: FromValue,
+    {
+        let rows = self.exec_iter_cached(sql, params, cache)?;
+        rows.iter().map(load::<R>).collect()
+    }
+
+    /// Same as `exec_first`, the rows are served from the query cache while fresh.
+    fn exec_first_cached<R, S: Into<String>, P: Into<Params>>(
+        &self,
+        sql: S,
+        params: P,
+        cache: &CachePolicy,
+    ) -> Result<R, AkitaError>
+        where
+            R: FromValue,
+    {
+        let mut result: Vec<R> = self.exec_raw_cached(sql, params, cache)?;
+        match result.len() {
+            0 => Err(AkitaError::DataError("Zero record returned".to_string())),
+            1 => Ok(result.remove(0)),
+            _ => Err(AkitaError::DataError("More than one record returned".to_string())),
+        }
+    }
+
     fn exec_first_opt<R, S: Into<String>, P: Into<Params>>(
         &self,
         sql: S,
@@ -291,4 +856,77 @@ pub trait AkitaMapper {
             Err(e) => Err(e),
         }
     }
-}
\ No newline at end of file
+
+    /// Get the single value of a single row, such as `select count(1) from t_system_user`.
+    fn exec_scalar<R, S: Into<String>, P: Into<Params>>(
+        &self,
+        sql: S,
+        params: P,
+    ) -> Result<R, AkitaError>
+        where
+            R: FromValue,
+    {
+        let sql: String = sql.into();
+        let rows = self.exec_iter(&sql, params)?;
+        match rows.len() {
+            0 => Err(AkitaError::DataError("Zero record returned".to_string())),
+            1 => {
+                let row = &rows.data[0];
+                match row.data.len() {
+                    1 => R::from_value_opt(&row.data[0]).map_err(AkitaError::from),
+                    _ => Err(AkitaError::DataError("Scalar query must return exactly one column".to_string())),
+                }
+            },
+            _ => Err(AkitaError::DataError("More than one record returned".to_string())),
+        }
+    }
+}
+
+/// the number of records reloaded by a query of `refresh_all`.
+const REFRESH_CHUNK_SIZE: usize = 500;
+
+/// reload the records with `SELECT ... WHERE id IN (...)` through the mapper.
+pub(crate) fn refresh_all_with<M, T>(mapper: &M, entities: &mut [T]) -> Result<(), AkitaError>
+    where
+        M: AkitaMapper + ?Sized,
+        T: GetTableName + GetFields + FromValue + ToValue,
+{
+    let table = T::table_name();
+    if table.complete_name().is_empty() {
+        return Err(AkitaError::MissingTable("Find Error, Missing Table Name !".to_string()))
+    }
+    let fields = T::fields();
+    let keys = key_fields::<T>()?;
+    let enumerated_columns = fields
+        .iter().filter(|f| f.exist)
+        .map(|c| format!("`{}`", c.name))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let key_columns = keys.iter().map(|key| format!("`{}`", key.name)).collect::<Vec<_>>().join(", ");
+    // a composite key is matched as a row value, `(a, b) IN ((?, ?), ...)`.
+    let (key_in, placeholder) = match keys.len() {
+        1 => (key_columns, "?".to_string()),
+        len => (format!("({})", key_columns), format!("({})", vec!["?"; len].join(", "))),
+    };
+    let key_of = |data: &Value| keys.iter().map(|key| data.get_obj_value(&key.name).map(|id| id.to_string()).unwrap_or_default()).collect::<Vec<_>>().join(", ");
+    for chunk in entities.chunks_mut(REFRESH_CHUNK_SIZE) {
+        let ids = chunk.iter().map(|entity| {
+            let data = entity.to_value();
+            keys.iter().map(|key| data.get_obj_value(&key.name).filter(|id| **id != Value::Nil).cloned()
+                .ok_or_else(|| AkitaError::MissingIdent(format!("Table({}) record without ident to refresh", &table.name)))).collect::<Result<Vec<_>, _>>()
+        }).collect::<Result<Vec<_>, _>>()?;
+        let placeholders = vec![placeholder.as_str(); ids.len()].join(", ");
+        let rows = mapper.exec_iter(format!("SELECT {} FROM {} WHERE {} IN ({})", &enumerated_columns, &table.complete_name(), &key_in, placeholders), ids.concat())?;
+        let mut loaded = HashMap::with_capacity(rows.len());
+        for data in rows.iter() {
+            loaded.insert(key_of(&data), data);
+        }
+        for (entity, id) in chunk.iter_mut().zip(ids.iter()) {
+            let id = id.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ");
+            let data = loaded.get(&id)
+                .ok_or_else(|| AkitaError::DataError(format!("Table({}) record {} not found", &table.name, id)))?;
+            *entity = load::<T>(data.clone())?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,297 @@
+//!
+//! Masking of the sensitive values in the logs.
+//!
+
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::sync::RwLock;
+use once_cell::sync::Lazy;
+
+use crate::{FieldName, Params, Value};
+
+static SENSITIVE_COLUMNS: Lazy<SensitiveColumns> = Lazy::new(SensitiveColumns::new);
+
+/// What the masked values are logged as.
+pub const MASK: &str = "***";
+
+/// The columns whose values are logged as `***`: the `#[field(sensitive)]` fields of the entities used so far,
+/// and the columns matching the patterns added, like `password` or `*_token`.
+///
+/// The values are masked in the logged statements and params, in the `db.statement` of the tracing spans
+/// and in the statements given to `SlowQueryInterceptor`, they are bound as they are.
+/// A value is known to be of a column when compared to it, as in `password = ?`, or inserted into it.
+pub struct SensitiveColumns {
+    columns: RwLock<HashSet<String>>,
+    patterns: RwLock<Vec<String>>,
+}
+
+impl SensitiveColumns {
+    fn new() -> Self {
+        SensitiveColumns { columns: RwLock::new(HashSet::new()), patterns: RwLock::new(Vec::new()) }
+    }
+
+    /// The columns shared by the whole process.
+    pub fn global() -> &'static SensitiveColumns {
+        &SENSITIVE_COLUMNS
+    }
+
+    /// Mask the columns matching the pattern, whatever their table, `*` standing for any text.
+    pub fn add_pattern<S: Into<String>>(&self, pattern: S) {
+        if let Ok(mut patterns) = self.patterns.write() {
+            patterns.push(pattern.into().to_lowercase());
+        }
+    }
+
+    /// Mask the `#[field(sensitive)]` fields, done when the statements of an entity are built.
+    pub fn register(&self, fields: &[FieldName]) {
+        let sensitive = fields.iter().filter(|field| field.sensitive).map(|field| field.name.to_lowercase()).collect::<Vec<_>>();
+        if sensitive.is_empty() || self.columns.read().map(|columns| sensitive.iter().all(|column| columns.contains(column))).unwrap_or(true) {
+            return;
+        }
+        if let Ok(mut columns) = self.columns.write() {
+            columns.extend(sensitive);
+        }
+    }
+
+    pub fn is_sensitive(&self, column: &str) -> bool {
+        let column = column.trim_matches(['`', '"']).rsplit('.').next().unwrap_or_default().trim_matches(['`', '"']).to_lowercase();
+        self.columns.read().map(|columns| columns.contains(&column)).unwrap_or(false)
+            || self.patterns.read().map(|patterns| patterns.iter().any(|pattern| matches(pattern, &column))).unwrap_or(false)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.columns.read().map(|columns| columns.is_empty()).unwrap_or(true)
+            && self.patterns.read().map(|patterns| patterns.is_empty()).unwrap_or(true)
+    }
+
+    /// The statement with the literal values of the sensitive columns masked.
+    pub fn mask_sql<'a>(&self, sql: &'a str) -> Cow<'a, str> {
+        if self.is_empty() {
+            return Cow::Borrowed(sql);
+        }
+        let literals = self.sensitive_values(sql).into_iter()
+            .filter_map(|(token, _)| match token { Token::Literal(start, end) => Some((start, end)), _ => None })
+            .collect::<Vec<_>>();
+        if literals.is_empty() {
+            return Cow::Borrowed(sql);
+        }
+        let mut masked = sql.to_string();
+        for (start, end) in literals.into_iter().rev() {
+            masked.replace_range(start..end, &format!("'{}'", MASK));
+        }
+        Cow::Owned(masked)
+    }
+
+    /// The params with the ones bound to the sensitive columns masked.
+    pub fn mask_params<'a>(&self, sql: &str, params: &'a Params) -> Cow<'a, Params> {
+        if self.is_empty() || *params == Params::Nil {
+            return Cow::Borrowed(params);
+        }
+        let values = self.sensitive_values(sql);
+        let mut masked = params.clone();
+        match &mut masked {
+            Params::Vector(params) => {
+                for (token, _) in values {
+                    if let Token::Param(index) = token {
+                        if let Some(value) = params.get_mut(index) {
+                            *value = Value::Text(MASK.to_string());
+                        }
+                    }
+                }
+            }
+            Params::Custom(params) => {
+                let names = values.into_iter().filter_map(|(token, _)| match token { Token::Named(name) => Some(name), _ => None }).collect::<Vec<_>>();
+                for (name, value) in params.iter_mut() {
+                    if names.iter().any(|masked| masked == name) || self.is_sensitive(name) {
+                        *value = Value::Text(MASK.to_string());
+                    }
+                }
+            }
+            Params::Nil => {}
+        }
+        if masked == *params { Cow::Borrowed(params) } else { Cow::Owned(masked) }
+    }
+
+    /// the values of the statement given to a sensitive column, with the column.
+    fn sensitive_values(&self, sql: &str) -> Vec<(Token, String)> {
+        values_of_columns(sql).into_iter().filter(|(_, column)| self.is_sensitive(column)).collect()
+    }
+}
+
+/// A token of a statement, with its offsets.
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Word(usize, usize),
+    Op(usize, usize),
+    Punct(char),
+    /// a `?` or a `$1`, with the index of its param
+    Param(usize),
+    Named(String),
+    Literal(usize, usize),
+}
+
+fn tokens(sql: &str) -> Vec<Token> {
+    let chars = sql.char_indices().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut next_param = 0;
+    let mut i = 0;
+    while i < chars.len() {
+        let (start, c) = chars[i];
+        let end_of = |j: usize| chars.get(j).map(|(offset, _)| *offset).unwrap_or(sql.len());
+        let mut j = i + 1;
+        match c {
+            '\'' | '"' => {
+                let mut escaped = false;
+                while j < chars.len() && (escaped || chars[j].1 != c) {
+                    escaped = !escaped && chars[j].1 == '\\';
+                    j += 1;
+                }
+                j += 1;
+                tokens.push(Token::Literal(start, end_of(j)));
+            }
+            '`' => {
+                while j < chars.len() && chars[j].1 != '`' {
+                    j += 1;
+                }
+                j += 1;
+                tokens.push(Token::Word(start, end_of(j)));
+            }
+            '?' => {
+                tokens.push(Token::Param(next_param));
+                next_param += 1;
+            }
+            '$' | ':' if chars.get(j).map(|(_, c)| c.is_alphanumeric() || *c == '_').unwrap_or(false) => {
+                while j < chars.len() && (chars[j].1.is_alphanumeric() || chars[j].1 == '_') {
+                    j += 1;
+                }
+                let name = &sql[chars[i + 1].0..end_of(j)];
+                match (c, name.parse::<usize>()) {
+                    ('$', Ok(index)) => tokens.push(Token::Param(index.saturating_sub(1))),
+                    _ => tokens.push(Token::Named(name.to_string())),
+                }
+            }
+            '=' | '<' | '>' | '!' => {
+                while j < chars.len() && ['=', '<', '>'].contains(&chars[j].1) {
+                    j += 1;
+                }
+                tokens.push(Token::Op(start, end_of(j)));
+            }
+            '(' | ')' | ',' => tokens.push(Token::Punct(c)),
+            c if c.is_alphanumeric() || c == '_' || c == '.' => {
+                while j < chars.len() && (chars[j].1.is_alphanumeric() || ['_', '.', '`'].contains(&chars[j].1)) {
+                    j += 1;
+                }
+                tokens.push(Token::Word(start, end_of(j)));
+            }
+            _ => {}
+        }
+        i = j;
+    }
+    tokens
+}
+
+/// the values given to a column, compared to it or inserted into it.
+fn values_of_columns(sql: &str) -> Vec<(Token, String)> {
+    let tokens = tokens(sql);
+    let word = |token: &Token| match token { Token::Word(start, end) => Some(&sql[*start..*end]), _ => None };
+    let is_value = |token: &Token| match token {
+        Token::Param(_) | Token::Named(_) | Token::Literal(..) => true,
+        Token::Word(..) => word(token).map(|word| word.chars().all(|c| c.is_ascii_digit() || c == '.' || c == '-')).unwrap_or(false),
+        _ => false,
+    };
+    let mut values = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        if !is_value(token) || i < 2 {
+            continue;
+        }
+        let compared = matches!(tokens[i - 1], Token::Op(..)) || word(&tokens[i - 1]).map(|word| word.eq_ignore_ascii_case("like")).unwrap_or(false);
+        if let (true, Some(column)) = (compared, word(&tokens[i - 2])) {
+            values.push((literal(token), column.to_string()));
+        }
+    }
+    // the rows of an insert, by the position of their values.
+    let into = tokens.iter().position(|token| word(token).map(|word| word.eq_ignore_ascii_case("into")).unwrap_or(false));
+    let open = into.and_then(|into| tokens.iter().skip(into).position(|token| *token == Token::Punct('(')).map(|open| open + into));
+    if let Some(open) = open {
+        let close = tokens.iter().skip(open).position(|token| *token == Token::Punct(')')).map(|close| close + open).unwrap_or(tokens.len());
+        let columns = tokens[open + 1..close].iter().filter_map(word).collect::<Vec<_>>();
+        let values_at = tokens.iter().skip(close).position(|token| word(token).map(|word| word.eq_ignore_ascii_case("values")).unwrap_or(false));
+        if let Some(values_at) = values_at.map(|values_at| values_at + close) {
+            let mut depth = 0;
+            let mut column = 0;
+            for token in tokens[values_at + 1..].iter() {
+                match token {
+                    Token::Punct('(') => {
+                        depth += 1;
+                        if depth == 1 {
+                            column = 0;
+                        }
+                    }
+                    Token::Punct(')') => depth -= 1,
+                    Token::Punct(',') if depth == 1 => column += 1,
+                    Token::Punct(',') => {}
+                    _ if depth == 0 => break,
+                    token if is_value(token) => {
+                        if let Some(name) = columns.get(column) {
+                            values.push((literal(token), name.to_string()));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+    values
+}
+
+/// the number values as literals too.
+fn literal(token: &Token) -> Token {
+    match token {
+        Token::Word(start, end) => Token::Literal(*start, *end),
+        token => token.to_owned(),
+    }
+}
+
+/// whether the text matches the pattern, `*` standing for any text.
+fn matches(pattern: &str, text: &str) -> bool {
+    let parts = pattern.split('*').collect::<Vec<_>>();
+    if parts.len() == 1 {
+        return pattern == text;
+    }
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if !text.starts_with(first) || !text[first.len()..].ends_with(last) || text.len() < first.len() + last.len() {
+        return false;
+    }
+    let mut rest = &text[first.len()..text.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{FieldName, Params, Value};
+    use super::SensitiveColumns;
+
+    #[test]
+    fn mask_sensitive_values() {
+        let columns = SensitiveColumns::new();
+        let mut password = FieldName::from("password");
+        password.sensitive = true;
+        columns.register(&[FieldName::from("name"), password]);
+        columns.add_pattern("*_token");
+        assert_eq!(columns.mask_sql("update t_user set `password` = 'secret', name = 'bob' where id = 1"), "update t_user set `password` = '***', name = 'bob' where id = 1");
+        let params = Params::Vector(vec![Value::Text("bob".into()), Value::Text("secret".into()), Value::Int(1)]);
+        assert_eq!(columns.mask_params("insert into t_user (`name`, `password`, age) values (?, ?, ?)", &params).into_owned(),
+                   Params::Vector(vec![Value::Text("bob".into()), Value::Text("***".into()), Value::Int(1)]));
+        assert_eq!(columns.mask_params("select * from t_user where u.name = $2 and reset_token = $1", &params).into_owned(),
+                   Params::Vector(vec![Value::Text("***".into()), Value::Text("secret".into()), Value::Int(1)]));
+        let params = Params::Custom(vec![("p".into(), Value::Text("secret".into())), ("api_token".into(), Value::Text("t".into()))]);
+        assert_eq!(columns.mask_params("select * from t_user where password = :p", &params).into_owned(),
+                   Params::Custom(vec![("p".into(), Value::Text("***".into())), ("api_token".into(), Value::Text("***".into()))]));
+    }
+}
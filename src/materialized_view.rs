@@ -0,0 +1,173 @@
+//!
+//! Materialized views, emulated with tables refreshed from their query.
+//!
+
+use std::time::{Duration, Instant};
+
+use crate::{Akita, AkitaError, AkitaMapper, Params, ToValue};
+use crate::ddl::{check_identifier, now_millis};
+
+/// The table recording the materialized views with the state of their last refresh.
+pub const MATERIALIZED_VIEW_TABLE: &str = "akita_materialized_views";
+
+/// A materialized view with its scheduling metadata, the times in milliseconds since the epoch.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MaterializedView {
+    pub name: String,
+    pub query: String,
+    /// how often the view should be refreshed, by hand only without one
+    pub refresh_every: Option<Duration>,
+    /// the last refresh that succeeded
+    pub last_refreshed_at: Option<i64>,
+    /// the last refresh, failed or not
+    pub last_run_at: Option<i64>,
+    pub last_duration_ms: Option<i64>,
+    /// the rows of the view after the last refresh that succeeded
+    pub last_rows: Option<u64>,
+    /// the error of the last refresh, `None` once one succeeded
+    pub last_error: Option<String>,
+}
+
+impl MaterializedView {
+    /// Whether the view should be refreshed by now, the ones never refreshed are.
+    pub fn is_due(&self) -> bool {
+        self.is_due_at(now_millis())
+    }
+
+    fn is_due_at(&self, now: i64) -> bool {
+        match (self.refresh_every, self.last_refreshed_at) {
+            (None, _) => false,
+            (Some(_), None) => true,
+            (Some(every), Some(last)) => last + every.as_millis() as i64 <= now,
+        }
+    }
+}
+
+impl Akita {
+    /// Create the table of the view filled with the rows of the query, and record it in `akita_materialized_views`.
+    /// Returns the rows of the view.
+    pub fn create_materialized_view(&self, name: &str, query: &str, refresh_every: Option<Duration>) -> Result<u64, AkitaError> {
+        check_identifier("materialized view name", name)?;
+        let akita = self.force_master();
+        create_view_table(&akita)?;
+        akita.exec_result(format!("CREATE TABLE {} AS {}", name, query), Params::Nil)?;
+        let rows: i64 = akita.exec_first(format!("SELECT COUNT(1) FROM {}", name), Params::Nil)?;
+        let now = now_millis();
+        akita.exec_result(format!("INSERT INTO {} (name, query, refresh_every_ms, last_refreshed_at, last_run_at, last_duration_ms, last_rows) VALUES (?, ?, ?, ?, ?, 0, ?)", MATERIALIZED_VIEW_TABLE),
+            Params::Vector(vec![name.to_value(), query.to_value(), refresh_every.map(|every| every.as_millis() as i64).to_value(), now.to_value(), now.to_value(), rows.to_value()]))?;
+        Ok(rows as u64)
+    }
+
+    /// Refresh the view with the rows of its query, returns them. None of the platforms has materialized views,
+    /// the rows are replaced in a transaction, the readers seeing the old ones until it commits.
+    /// `concurrently` runs the query into the `{name}_refresh` table first, the transaction then only copying it:
+    /// the locks are held for the copy instead of the query, at the cost of a second write of the rows.
+    /// The run is recorded in `akita_materialized_views`, its error too before it is returned.
+    pub fn refresh_materialized_view(&self, name: &str, concurrently: bool) -> Result<u64, AkitaError> {
+        let akita = self.force_master();
+        let view = self.materialized_view(name)?.ok_or_else(|| AkitaError::DataError(format!("unknown materialized view: {}", name)))?;
+        let started = Instant::now();
+        let refreshed = refresh(&akita, &view, concurrently);
+        let now = now_millis();
+        let duration = started.elapsed().as_millis() as i64;
+        match &refreshed {
+            Ok(rows) => {
+                akita.exec_result(format!("UPDATE {} SET last_refreshed_at = ?, last_run_at = ?, last_duration_ms = ?, last_rows = ?, last_error = NULL WHERE name = ?", MATERIALIZED_VIEW_TABLE),
+                    Params::Vector(vec![now.to_value(), now.to_value(), duration.to_value(), (*rows as i64).to_value(), name.to_value()]))?;
+            }
+            Err(err) => {
+                akita.exec_result(format!("UPDATE {} SET last_run_at = ?, last_duration_ms = ?, last_error = ? WHERE name = ?", MATERIALIZED_VIEW_TABLE),
+                    Params::Vector(vec![now.to_value(), duration.to_value(), err.to_string().to_value(), name.to_value()]))?;
+            }
+        }
+        refreshed
+    }
+
+    /// The materialized view recorded with the name.
+    pub fn materialized_view(&self, name: &str) -> Result<Option<MaterializedView>, AkitaError> {
+        Ok(self.list_materialized_views("WHERE name = ?", Params::Vector(vec![name.to_value()]))?.pop())
+    }
+
+    /// The materialized views by name.
+    pub fn materialized_views(&self) -> Result<Vec<MaterializedView>, AkitaError> {
+        self.list_materialized_views("", Params::Nil)
+    }
+
+    /// The materialized views due for a refresh, for the jobs refreshing them on schedule.
+    pub fn due_materialized_views(&self) -> Result<Vec<MaterializedView>, AkitaError> {
+        Ok(self.materialized_views()?.into_iter().filter(MaterializedView::is_due).collect())
+    }
+
+    /// Drop the table of the view and its record, returns false when there was no such view.
+    pub fn drop_materialized_view(&self, name: &str) -> Result<bool, AkitaError> {
+        check_identifier("materialized view name", name)?;
+        let akita = self.force_master();
+        if akita.materialized_view(name)?.is_none() {
+            return Ok(false);
+        }
+        akita.exec_result(format!("DROP TABLE IF EXISTS {}", name), Params::Nil)?;
+        akita.exec_result(format!("DELETE FROM {} WHERE name = ?", MATERIALIZED_VIEW_TABLE), Params::Vector(vec![name.to_value()]))?;
+        Ok(true)
+    }
+
+    fn list_materialized_views(&self, filter: &str, params: Params) -> Result<Vec<MaterializedView>, AkitaError> {
+        let akita = self.force_master();
+        create_view_table(&akita)?;
+        akita.exec_iter(format!("SELECT name, query, refresh_every_ms, last_refreshed_at, last_run_at, last_duration_ms, last_rows, last_error FROM {} {} ORDER BY name", MATERIALIZED_VIEW_TABLE, filter), params)?
+            .iter().map(|row| Ok(MaterializedView {
+                name: row.get_obj("name")?,
+                query: row.get_obj("query")?,
+                refresh_every: row.get_obj::<Option<i64>>("refresh_every_ms")?.map(|every| Duration::from_millis(every as u64)),
+                last_refreshed_at: row.get_obj("last_refreshed_at")?,
+                last_run_at: row.get_obj("last_run_at")?,
+                last_duration_ms: row.get_obj("last_duration_ms")?,
+                last_rows: row.get_obj::<Option<i64>>("last_rows")?.map(|rows| rows as u64),
+                last_error: row.get_obj("last_error")?,
+            })).collect()
+    }
+}
+
+/// replace the rows of the view, returns them.
+fn refresh(akita: &Akita, view: &MaterializedView, concurrently: bool) -> Result<u64, AkitaError> {
+    check_identifier("materialized view name", &view.name)?;
+    let source = if concurrently {
+        let staging = format!("{}_refresh", view.name);
+        akita.exec_result(format!("DROP TABLE IF EXISTS {}", staging), Params::Nil)?;
+        akita.exec_result(format!("CREATE TABLE {} AS {}", staging, view.query), Params::Nil)?;
+        format!("SELECT * FROM {}", staging)
+    } else {
+        view.query.to_string()
+    };
+    let transaction = akita.start_transaction()?;
+    // not `TRUNCATE`, MySQL commits it at once.
+    transaction.exec_result(format!("DELETE FROM {}", view.name), Params::Nil)?;
+    let rows = transaction.exec_result(format!("INSERT INTO {} {}", view.name, source), Params::Nil)?.affected_rows;
+    transaction.commit()?;
+    if concurrently {
+        akita.exec_result(format!("DROP TABLE IF EXISTS {}_refresh", view.name), Params::Nil)?;
+    }
+    Ok(rows)
+}
+
+fn create_view_table(akita: &Akita) -> Result<(), AkitaError> {
+    akita.exec_result(format!("CREATE TABLE IF NOT EXISTS {} (name VARCHAR(255) NOT NULL PRIMARY KEY, query TEXT NOT NULL, refresh_every_ms BIGINT, \
+        last_refreshed_at BIGINT, last_run_at BIGINT, last_duration_ms BIGINT, last_rows BIGINT, last_error TEXT)", MATERIALIZED_VIEW_TABLE), Params::Nil).map(|_| ())
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+    use super::MaterializedView;
+
+    #[test]
+    fn due() {
+        let mut view = MaterializedView { name: "mv_sales".to_string(), query: "SELECT 1".to_string(), refresh_every: None, last_refreshed_at: None,
+            last_run_at: None, last_duration_ms: None, last_rows: None, last_error: None };
+        assert!(!view.is_due_at(1_000));
+        view.refresh_every = Some(Duration::from_secs(60));
+        assert!(view.is_due_at(1_000));
+        view.last_refreshed_at = Some(1_000);
+        assert!(!view.is_due_at(60_999));
+        assert!(view.is_due_at(61_000));
+    }
+}
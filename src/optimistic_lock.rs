@@ -0,0 +1,56 @@
+//!
+//! Optimistic locking of the records with a `#[field(version)]` field.
+//!
+
+use crate::{Akita, AkitaError, GetFields};
+
+/// the version column of the entity, if any.
+pub(crate) fn version_column<T: GetFields>() -> Option<String> {
+    T::fields().into_iter().find(|field| field.version && field.exist).map(|field| field.name)
+}
+
+/// the assignment increasing the version, a null one becoming 1.
+pub(crate) fn version_increment(column: &str) -> String {
+    format!("`{}` = COALESCE(`{}`, 0) + 1", column, column)
+}
+
+impl Akita {
+    /// Run the closure again while it fails with `AkitaError::OptimisticLockFailed`, up to `max_retries` times,
+    /// it reads the record again before updating it. Out of a transaction only, the reads of a transaction
+    /// may keep seeing the old version: retry the transaction with `#[transactional(retry = 3)]` instead.
+    pub fn retry_on_conflict<R, F>(&self, max_retries: u32, mut f: F) -> Result<R, AkitaError>
+        where
+            F: FnMut(&Akita) -> Result<R, AkitaError>,
+    {
+        let mut attempts = 0;
+        loop {
+            match f(self) {
+                Err(AkitaError::OptimisticLockFailed(_)) if attempts < max_retries => attempts += 1,
+                result => return result,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{AkitaError, FieldName, GetFields, Retryable};
+    use super::{version_column, version_increment};
+
+    struct Account;
+
+    impl GetFields for Account {
+        fn fields() -> Vec<FieldName> {
+            let mut version = FieldName::from("version");
+            version.version = true;
+            vec![FieldName::from("id"), version]
+        }
+    }
+
+    #[test]
+    fn optimistic_lock() {
+        assert_eq!(version_column::<Account>(), Some("version".to_string()));
+        assert_eq!(version_increment("version"), "`version` = COALESCE(`version`, 0) + 1");
+        assert!(AkitaError::OptimisticLockFailed("t_account".to_string()).is_retryable());
+    }
+}
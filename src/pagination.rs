@@ -0,0 +1,176 @@
+//!
+//! Pagination, with the count of the records optimized.
+//!
+
+use crate::tenant::{placeholders_before, top_level_words, unquoted};
+
+/// The words after which the select list can't be replaced, the count is then run on the statement as a derived table.
+const GROUPING: [&str; 9] = ["distinct", "group", "having", "window", "union", "intersect", "except", "limit", "offset"];
+
+/// The aggregates making a select return a single row.
+const AGGREGATES: [&str; 8] = ["count(", "sum(", "avg(", "min(", "max(", "group_concat(", "string_agg(", "json_arrayagg("];
+
+/// The words ending a `LEFT JOIN` clause.
+const JOIN_END: [&str; 18] = ["left", "right", "inner", "outer", "join", "cross", "natural", "straight_join", "full",
+    "where", "group", "having", "window", "order", "limit", "offset", "for", "lock"];
+
+/// How the pages are read with `AkitaMapper::page_with`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PaginationOptions {
+    /// count the records, the total of the page is left at 0 otherwise
+    pub need_total: bool,
+    /// count without the select list and the `ORDER BY`
+    pub optimize_count: bool,
+    /// count without the `LEFT JOIN` of the tables named nowhere else
+    pub optimize_join: bool,
+}
+
+impl Default for PaginationOptions {
+    fn default() -> Self {
+        PaginationOptions { need_total: true, optimize_count: true, optimize_join: true }
+    }
+}
+
+impl PaginationOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Skip the count, e.g. for an infinite scroll or the pages after the first.
+    pub fn set_need_total(mut self, need_total: bool) -> Self {
+        self.need_total = need_total;
+        self
+    }
+
+    pub fn set_optimize_count(mut self, optimize_count: bool) -> Self {
+        self.optimize_count = optimize_count;
+        self
+    }
+
+    /// A `LEFT JOIN` joining more than one row per row changes the count, disable it when the tables are joined so.
+    /// The columns of the joined tables have to be qualified for them to be seen as used.
+    pub fn set_optimize_join(mut self, optimize_join: bool) -> Self {
+        self.optimize_join = optimize_join;
+        self
+    }
+
+    /// The statement counting the records of the select.
+    ///
+    /// The select list is replaced with `COUNT(*)` and the `ORDER BY` dropped, unless the select is grouped,
+    /// distinct, limited or aggregated, or its select list has params: it is then counted as a derived table.
+    pub fn count_sql(&self, sql: &str) -> String {
+        let sql = sql.trim().trim_end_matches(';').trim_end();
+        if !self.optimize_count {
+            return format!("SELECT COUNT(*) FROM ({}) TOTAL", sql);
+        }
+        let chars = unquoted(sql);
+        let words = top_level_words(sql, &chars);
+        let is_word = |i: usize, word: &str| words.get(i).map(|(_, w)| w.eq_ignore_ascii_case(word)).unwrap_or(false);
+        let limited = words.iter().any(|(_, word)| word.eq_ignore_ascii_case("limit") || word.eq_ignore_ascii_case("offset"));
+        // the order of the records doesn't change their count, unless only some of them are kept.
+        let sql = match (0..words.len()).find(|i| is_word(*i, "order") && is_word(*i + 1, "by")) {
+            Some(order) if !limited => {
+                let end = words[order..].iter().find(|(_, word)| word.eq_ignore_ascii_case("for") || word.eq_ignore_ascii_case("lock"))
+                    .map(|(offset, _)| *offset).unwrap_or(sql.len());
+                let start = words[order].0;
+                if placeholders_before(sql, end) == placeholders_before(sql, start) {
+                    format!("{} {}", sql[..start].trim_end(), &sql[end..]).trim_end().to_string()
+                } else {
+                    sql.to_string()
+                }
+            }
+            _ => sql.to_string(),
+        };
+        let chars = unquoted(&sql);
+        let words = top_level_words(&sql, &chars);
+        let from = words.iter().position(|(_, word)| word.eq_ignore_ascii_case("from"));
+        let grouped = words.iter().any(|(_, word)| GROUPING.iter().any(|grouping| word.eq_ignore_ascii_case(grouping)));
+        let from = match from {
+            Some(from) if is_select(&words) && !grouped => words[from].0,
+            _ => return format!("SELECT COUNT(*) FROM ({}) TOTAL", sql),
+        };
+        let select_list = sql[words[0].1.len()..from].split_whitespace().collect::<Vec<_>>().join("").to_lowercase();
+        if placeholders_before(&sql, from) > 0 || AGGREGATES.iter().any(|aggregate| select_list.contains(aggregate)) {
+            return format!("SELECT COUNT(*) FROM ({}) TOTAL", sql);
+        }
+        let count = format!("SELECT COUNT(*) {}", &sql[from..]);
+        if self.optimize_join { without_left_joins(count) } else { count }
+    }
+}
+
+fn is_select(words: &[(usize, &str)]) -> bool {
+    words.first().map(|(_, word)| word.eq_ignore_ascii_case("select")).unwrap_or(false)
+}
+
+/// the count without the `LEFT JOIN` of the tables the rest of the statement doesn't name,
+/// they can't filter the records out.
+fn without_left_joins(mut sql: String) -> String {
+    let chars = unquoted(&sql);
+    let words = top_level_words(&sql, &chars);
+    let is_word = |i: usize, word: &str| words.get(i).map(|(_, w)| w.eq_ignore_ascii_case(word)).unwrap_or(false);
+    let mut joins = Vec::new();
+    for i in 0..words.len() {
+        if !is_word(i, "left") {
+            continue;
+        }
+        let join = if is_word(i + 1, "join") { i + 1 } else if is_word(i + 1, "outer") && is_word(i + 2, "join") { i + 2 } else { continue };
+        let table = join + 1;
+        let (table_offset, table_word) = match words.get(table) {
+            Some(table) => *table,
+            None => continue,
+        };
+        let derived = sql[words[join].0 + words[join].1.len()..table_offset].contains('(');
+        let name = match words.get(table + 1).map(|(_, word)| word.to_lowercase()) {
+            _ if derived && table_word.eq_ignore_ascii_case("as") => words.get(table + 1).map(|(_, word)| *word),
+            _ if derived => Some(table_word),
+            Some(word) if word == "as" => words.get(table + 2).map(|(_, word)| *word),
+            Some(word) if word != "on" && word != "using" && !JOIN_END.contains(&word.as_str()) => Some(words[table + 1].1),
+            _ => table_word.rsplit('.').next(),
+        };
+        let end = words[join + 1..].iter().find(|(_, word)| JOIN_END.iter().any(|end| word.eq_ignore_ascii_case(end)))
+            .map(|(offset, _)| *offset).unwrap_or(sql.len());
+        if let Some(name) = name {
+            joins.push((words[i].0, end, name.replace('`', "").to_lowercase()));
+        }
+    }
+    // from the last one, a join can be named only in the ones after it.
+    for (start, end, name) in joins.into_iter().rev() {
+        if placeholders_before(&sql, end) != placeholders_before(&sql, start) {
+            continue;
+        }
+        let rest = format!("{} {}", &sql[..start], &sql[end..]);
+        if !names(&rest, &name) {
+            sql = format!("{} {}", sql[..start].trim_end(), &sql[end..]).trim_end().to_string();
+        }
+    }
+    sql
+}
+
+/// whether a column of the table or alias is named out of the quoted strings, as in `name.column`.
+fn names(sql: &str, name: &str) -> bool {
+    let text = unquoted(sql).into_iter().map(|(_, c)| c).filter(|c| *c != '`').collect::<String>().to_lowercase();
+    let qualified = format!("{}.", name);
+    text.match_indices(&qualified).any(|(offset, _)| {
+        text[..offset].chars().last().map(|c| !(c.is_alphanumeric() || c == '_' || c == '$' || c == '.')).unwrap_or(true)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::PaginationOptions;
+
+    #[test]
+    fn optimize_count_sql() {
+        let options = PaginationOptions::new();
+        assert_eq!(options.count_sql("SELECT `id`, `name` FROM t_user WHERE name = ? ORDER BY id DESC"), "SELECT COUNT(*) FROM t_user WHERE name = ?");
+        assert_eq!(options.count_sql("select u.*, r.name from t_user u left join t_role r on r.id = u.role_id left join t_org o on o.id = u.org_id where o.state = 1"),
+                   "SELECT COUNT(*) from t_user u left join t_org o on o.id = u.org_id where o.state = 1");
+        assert_eq!(options.count_sql("select * from t_user u left join (select uid from t_vip) v on v.uid = u.id"), "SELECT COUNT(*) from t_user u");
+        assert_eq!(options.count_sql("select name from t_user group by name order by name"), "SELECT COUNT(*) FROM (select name from t_user group by name) TOTAL");
+        assert_eq!(options.count_sql("select max(age) from t_user"), "SELECT COUNT(*) FROM (select max(age) from t_user) TOTAL");
+        assert_eq!(options.count_sql("select id from t_user order by id limit 10"), "SELECT COUNT(*) FROM (select id from t_user order by id limit 10) TOTAL");
+        assert_eq!(options.count_sql("select if(age > ?, 1, 0) from t_user"), "SELECT COUNT(*) FROM (select if(age > ?, 1, 0) from t_user) TOTAL");
+        assert_eq!(options.clone().set_optimize_join(false).count_sql("select * from t_user u left join t_role r on r.id = u.role_id"),
+                   "SELECT COUNT(*) from t_user u left join t_role r on r.id = u.role_id");
+    }
+}
@@ -0,0 +1,166 @@
+//!
+//! Range partitions of the time series tables.
+//!
+
+use std::marker::PhantomData;
+use akita_core::{Row, TableName};
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+use crate::{Akita, AkitaError, GetTableName, Params, Value};
+use crate::database::DatabasePlatform;
+
+/// A partition of a table, as listed by the database.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct PartitionInfo {
+    pub name: String,
+    /// the partitioning method, `RANGE` or `RANGE COLUMNS`
+    pub method: String,
+    /// the partitioning expression, only the column for `RANGE COLUMNS`
+    pub expression: String,
+    /// the exclusive upper bound, `MAXVALUE` for the catch-all partition
+    pub less_than: String,
+    /// the estimated number of rows
+    pub rows: u64,
+}
+
+/// The range partitions of the table of `T`, got with `Akita::partitions`. Only MySQL is supported.
+///
+/// The inserted rows are routed to their partition by the database,
+/// the partition of a new month has to be there beforehand unless a `MAXVALUE` partition catches them.
+pub struct Partitions<'a, T> {
+    akita: &'a Akita,
+    _marker: PhantomData<T>,
+}
+
+impl Akita {
+    /// Manage the range partitions of the table of `T`.
+    pub fn partitions<T: GetTableName>(&self) -> Partitions<'_, T> {
+        Partitions { akita: self, _marker: PhantomData }
+    }
+}
+
+impl<'a, T: GetTableName> Partitions<'a, T> {
+    /// The partitions in their order, empty when the table is not partitioned.
+    pub fn list(&self) -> Result<Vec<PartitionInfo>, AkitaError> {
+        let mut conn = self.akita.acquire()?;
+        list_partitions(&mut conn, &T::table_name())
+    }
+
+    /// Add the partition `p<yyyymm>` for the rows of the month given as `yyyy-mm`, unless it is there.
+    /// Returns whether it was added.
+    pub fn ensure_month(&self, month: &str) -> Result<bool, AkitaError> {
+        let start = NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d")
+            .map_err(|_| AkitaError::DataError(format!("invalid month: {}, expected yyyy-mm", month)))?;
+        self.ensure_month_of(start)
+    }
+
+    /// Add the partition of the month of the date unless it is there, to call before inserting the rows of a new month.
+    pub fn ensure_month_of<D: Datelike>(&self, date: D) -> Result<bool, AkitaError> {
+        let end = if date.month() == 12 {
+            NaiveDate::from_ymd_opt(date.year() + 1, 1, 1)
+        } else {
+            NaiveDate::from_ymd_opt(date.year(), date.month() + 1, 1)
+        }.ok_or_else(|| AkitaError::DataError(format!("invalid month: {}-{:02}", date.year(), date.month())))?;
+        self.ensure_range(&format!("p{}{:02}", date.year(), date.month()), &end.format("%Y-%m-%d").to_string())
+    }
+
+    /// Add the partition of the rows below the bound unless one has this name, returns whether it was added.
+    /// The bound is a value of the partitioning column, the partitioning expression is applied to it,
+    /// e.g. `TO_DAYS('2025-08-01')` for a table partitioned by `RANGE (TO_DAYS(created_at))`.
+    pub fn ensure_range(&self, name: &str, less_than: &str) -> Result<bool, AkitaError> {
+        check_partition_name(name)?;
+        let table = T::table_name();
+        let mut conn = self.akita.acquire()?;
+        let partitions = list_partitions(&mut conn, &table)?;
+        if partitions.iter().any(|partition| partition.name.eq_ignore_ascii_case(name)) {
+            return Ok(false);
+        }
+        let last = partitions.last()
+            .ok_or_else(|| AkitaError::UnsupportedOperation(format!("Table({}) is not partitioned", &table.name)))?;
+        let partition = format!("PARTITION `{}` VALUES LESS THAN ({})", name, bound_expression(&last.expression, less_than));
+        let sql = if last.less_than.eq_ignore_ascii_case("MAXVALUE") {
+            // the catch-all partition has to stay the last one, the new one is split from it.
+            format!("ALTER TABLE {} REORGANIZE PARTITION `{}` INTO ({}, PARTITION `{}` VALUES LESS THAN (MAXVALUE))", table.complete_name(), &last.name, partition, &last.name)
+        } else {
+            format!("ALTER TABLE {} ADD PARTITION ({})", table.complete_name(), partition)
+        };
+        conn.execute_drop(&sql, Params::Nil)?;
+        Ok(true)
+    }
+
+    /// Drop the partition along with its rows.
+    pub fn drop_partition(&self, name: &str) -> Result<(), AkitaError> {
+        check_partition_name(name)?;
+        let table = T::table_name();
+        let mut conn = self.akita.acquire()?;
+        check_platform(&conn, &table)?;
+        conn.execute_drop(&format!("ALTER TABLE {} DROP PARTITION `{}`", table.complete_name(), name), Params::Nil)?;
+        Ok(())
+    }
+}
+
+fn list_partitions(conn: &mut DatabasePlatform, table: &TableName) -> Result<Vec<PartitionInfo>, AkitaError> {
+    check_platform(conn, table)?;
+    let schema = table.schema.to_owned().map(Value::Text).unwrap_or(Value::Nil);
+    let rows = conn.execute_result("SELECT PARTITION_NAME, PARTITION_METHOD, PARTITION_EXPRESSION, PARTITION_DESCRIPTION, TABLE_ROWS FROM information_schema.PARTITIONS WHERE TABLE_SCHEMA = COALESCE(?, DATABASE()) AND TABLE_NAME = ? ORDER BY PARTITION_ORDINAL_POSITION",
+                                   vec![schema, Value::Text(table.name.to_owned())].into())?;
+    // a table without partitions has a single row without name.
+    Ok(rows.data.iter().filter(|row| !text(row, 0).is_empty()).map(|row| PartitionInfo {
+        name: text(row, 0),
+        method: text(row, 1),
+        expression: text(row, 2),
+        less_than: text(row, 3),
+        rows: text(row, 4).parse().unwrap_or_default(),
+    }).collect())
+}
+
+#[allow(unused_variables)]
+fn check_platform(conn: &DatabasePlatform, table: &TableName) -> Result<(), AkitaError> {
+    #[allow(unreachable_patterns)]
+    match conn {
+        #[cfg(feature = "akita-mysql")]
+        DatabasePlatform::Mysql(_) => Ok(()),
+        _ => Err(AkitaError::UnsupportedOperation(format!("Table({}) partitions are only managed on MySQL", &table.name))),
+    }
+}
+
+fn text(row: &Row, index: usize) -> String {
+    match row.data.get(index) {
+        None | Some(Value::Nil) => String::default(),
+        Some(Value::Blob(v)) => String::from_utf8_lossy(v).to_string(),
+        Some(v) => v.to_string(),
+    }
+}
+
+/// partition names are sent as is, only plain identifiers are allowed.
+fn check_partition_name(name: &str) -> Result<(), AkitaError> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(AkitaError::InvalidSQL(format!("invalid partition name: {}", name)));
+    }
+    Ok(())
+}
+
+/// the partitioning expression with its column replaced by the quoted bound.
+fn bound_expression(expression: &str, less_than: &str) -> String {
+    let literal = format!("'{}'", less_than.replace('\'', "''"));
+    let column = expression.find('`').and_then(|start| expression[start + 1..].find('`').map(|end| (start, start + end + 2)));
+    match column {
+        Some((start, end)) => format!("{}{}{}", &expression[..start], literal, &expression[end..]),
+        None => literal,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{bound_expression, check_partition_name};
+
+    #[test]
+    fn partition_bounds() {
+        assert_eq!(bound_expression("to_days(`created_at`)", "2025-08-01"), "to_days('2025-08-01')");
+        assert_eq!(bound_expression("`created_at`", "2025-08-01"), "'2025-08-01'");
+        assert_eq!(bound_expression("", "it's"), "'it''s'");
+        assert!(check_partition_name("p202507").is_ok());
+        assert!(check_partition_name("p1`; DROP TABLE t").is_err());
+    }
+}
@@ -6,25 +6,43 @@ use mysql::{Conn, Error, Opts, OptsBuilder, Row, prelude::Queryable};
 use r2d2::{ManageConnection, Pool};
 
 use std::result::Result;
+use std::collections::HashMap;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, atomic::{AtomicUsize, Ordering}};
+use std::time::{Duration, Instant};
 use akita_core::Array;
 
-use crate::{AkitaConfig, Params, self as akita};
+use crate::{AkitaConfig, OperationType, Params, SqlWarning, TransactionSynchronization, cache, self as akita};
 
 cfg_if! {if #[cfg(feature = "akita-auth")]{
     use crate::auth::{GrantUserPrivilege, Role, UserInfo, DataBaseUser};
 }}
 use crate::database::Database;
-use crate::pool::LogLevel;
+use crate::interceptor::StatementContext;
+use crate::pool::{FailoverPolicy, LogLevel, PoolEvents, PoolMetrics};
+use crate::watchdog::{self, Deadline, Watchdog};
 use serde_json::Map;
 use crate::{ToValue, Value, FromValue, Rows, SqlType, cfg_if, AkitaError, ColumnDef, FieldName, ColumnSpecification, DatabaseName, TableDef, TableName, SchemaContent, comm};
 type R2d2Pool = Pool<MysqlConnectionManager>;
 
+/// how long a killed query has to stop before its connection is killed.
+const KILL_GRACE: Duration = Duration::from_secs(5);
+
+/// the code of the deprecated syntax warnings.
+const ER_WARN_DEPRECATED_SYNTAX: u32 = 1287;
+
 #[derive(Debug)]
-pub struct MysqlDatabase(r2d2::PooledConnection<MysqlConnectionManager>, AkitaConfig);
+/// The connection, its config and the id of the transaction in progress.
+pub struct MysqlDatabase(r2d2::PooledConnection<MysqlConnectionManager>, AkitaConfig, Option<u64>);
 
 impl MysqlDatabase {
     pub fn new(pool: r2d2::PooledConnection<MysqlConnectionManager>, cfg: AkitaConfig) -> Self {
-        MysqlDatabase(pool, cfg)
+        MysqlDatabase(pool, cfg, None)
+    }
+
+    /// The driver connection underneath.
+    pub fn raw(&mut self) -> &mut Conn {
+        &mut self.0.conn
     }
 
     pub fn log(&self, _fmt: String) {
@@ -53,22 +71,48 @@ impl MysqlDatabase {
     }
 }
 
-/// MYSQL数据操作
-impl Database for MysqlDatabase {
-    fn start_transaction(&mut self) -> Result<(), AkitaError> {
-        self.execute_result("BEGIN", Params::Nil).map(|_| ()).map_err(AkitaError::from)
+impl MysqlDatabase {
+    /// where the statements run, for the interceptors and the spans.
+    fn context(&self) -> StatementContext {
+        StatementContext {
+            system: "mysql",
+            peer: Some(self.1.ip_or_hostname()),
+            datasource: self.1.datasource_key(),
+            transaction_id: self.2,
+            connection_id: Some(self.0.connection_id() as u64),
+        }
     }
 
-    fn commit_transaction(&mut self) -> Result<(), AkitaError> {
-        self.execute_result("COMMIT", Params::Nil).map(|_| ()).map_err(AkitaError::from)
+    /// the configured statement timeout, shortened to the deadline of the caller.
+    fn statement_timeout(&self) -> Option<Duration> {
+        watchdog::statement_timeout(self.1.statement_timeout())
     }
 
-    fn rollback_transaction(&mut self) -> Result<(), AkitaError> {
-        self.execute_result("ROLLBACK", Params::Nil).map(|_| ()).map_err(AkitaError::from)
+    /// cancel the statement once over the statement timeout, then kill its connection,
+    /// on the host the connection reached.
+    fn deadline(&self) -> Option<Deadline> {
+        let timeout = self.statement_timeout()?;
+        let connection_id = self.0.connection_id();
+        let opts = match &self.0.host {
+            Some((name, port)) => Opts::from(OptsBuilder::from(&self.1).ip_or_hostname(Some(name.to_string())).tcp_port(*port)),
+            None => Opts::from(OptsBuilder::from(&self.1)),
+        };
+        let query = opts.clone();
+        Some(Watchdog::global().arm(timeout, move || kill(query, &format!("KILL QUERY {}", connection_id)))
+            .then(timeout + KILL_GRACE, move || kill(opts, &format!("KILL {}", connection_id))))
     }
-    
-    fn execute_result(&mut self, sql: &str, param: Params) -> Result<Rows, AkitaError> {
-        self.log(format!("Prepare SQL: {} params: {:?}", &sql, param));
+
+    /// run the statement, failing with `QueryTimeout` once cancelled.
+    fn timed<R, F: FnOnce(&mut Self) -> Result<R, AkitaError>>(&mut self, f: F) -> Result<R, AkitaError> {
+        match (self.deadline(), self.statement_timeout()) {
+            (Some(deadline), Some(timeout)) => deadline.check(f(self), timeout),
+            _ => f(self),
+        }
+    }
+
+    fn run_result(&mut self, sql: &str, param: Params) -> Result<Rows, AkitaError> {
+        let param = self.uuids(param);
+        self.log(format!("Prepare SQL: {} params: {:?}", self.1.log_sql(sql), self.1.log_params(sql, &param)));
         fn collect<T: Protocol>(mut rows: mysql::QueryResult<T>) -> Result<Rows, AkitaError> {
             let column_types: Vec<_> = rows.columns().as_ref().iter().map(|c| c.column_type()).collect();
             let _fields = rows
@@ -144,8 +188,9 @@ impl Database for MysqlDatabase {
         }
     }
     
-    fn execute_drop(&mut self, sql: &str, param: Params) -> Result<(), AkitaError> {
-        self.log(format!("Prepare SQL: {} params: {:?}", &sql, param));
+    fn run_drop(&mut self, sql: &str, param: Params) -> Result<(), AkitaError> {
+        let param = self.uuids(param);
+        self.log(format!("Prepare SQL: {} params: {:?}", self.1.log_sql(sql), self.1.log_params(sql, &param)));
         match param {
             Params::Nil => {
                 self
@@ -192,6 +237,148 @@ impl Database for MysqlDatabase {
         }
     }
 
+    fn run_stream(&mut self, sql: &str, param: Params, consumer: &mut dyn FnMut(crate::Row) -> Result<(), AkitaError>) -> Result<(), AkitaError> {
+        let param = self.uuids(param);
+        self.log(format!("Prepare SQL: {} params: {:?}", self.1.log_sql(sql), self.1.log_params(sql, &param)));
+        let (sql, param) = match param {
+            Params::Nil => (sql.to_owned(), vec![]),
+            Params::Vector(param) => (sql.to_owned(), param),
+            Params::Custom(param) => {
+                let mut format_sql = sql.to_owned();
+                let len = format_sql.len();
+                let mut values = param.into_iter().map(|param| {
+                    let key = format!(":{}", param.0);
+                    let index = format_sql.find(&key).unwrap_or(len);
+                    format_sql = format_sql.replace(&key, "?");
+                    (index, param.1)
+                }).collect::<Vec<_>>();
+                values.sort_by_key(|v| v.0);
+                (format_sql, values.into_iter().map(|v| v.1).collect::<Vec<_>>())
+            },
+        };
+        let stmt = self
+            .0
+            .prep(&sql)
+            .map_err(|e| AkitaError::ExcuteSqlError(e.to_string(), sql.to_string()))?;
+        let params: mysql::Params = param
+            .iter()
+            .map(MySQLValue)
+            .map(|v| mysql::prelude::ToValue::to_value(&v))
+            .collect::<Vec<_>>()
+            .into();
+        let mut rows = self.0.exec_iter(stmt, &params).map_err(|e| AkitaError::ExcuteSqlError(e.to_string(), sql.to_string()))?;
+        let column_types: Vec<_> = rows.columns().as_ref().iter().map(|c| c.column_type()).collect();
+        for r in rows.by_ref() {
+            consumer(into_record(r.map_err(AkitaError::from)?, &column_types)?)?;
+        }
+        Ok(())
+    }
+
+    /// the uuids as their text unless they are bound as bytes, see `AkitaConfig::set_uuid_binary`.
+    fn uuids(&self, param: Params) -> Params {
+        if self.1.uuid_binary() {
+            return param;
+        }
+        param.map_values(|value| match value {
+            Value::Uuid(v) => Value::Text(v.to_string()),
+            value => value,
+        })
+    }
+
+    /// fail on the warnings of the statement when they are escalated, the notes are left out,
+    /// and so is the deprecation of `VALUES(col)` by MySQL 8.0.20: the upserts keep it for the row alias
+    /// replacing it is neither known to MariaDB nor to MySQL before 8.0.19.
+    fn check_warnings(&mut self, sql: &str) -> Result<(), AkitaError> {
+        if !self.1.warnings_as_errors() || self.0.warnings() == 0 {
+            return Ok(());
+        }
+        let warnings = self.warnings()?.into_iter()
+            .filter(|warning| !warning.level.eq_ignore_ascii_case("Note") && !is_values_deprecation(warning))
+            .map(|warning| warning.to_string())
+            .collect::<Vec<_>>();
+        if warnings.is_empty() {
+            return Ok(());
+        }
+        Err(AkitaError::ExcuteSqlError(warnings.join("; "), sql.to_string()))
+    }
+}
+
+/// whether the warning is the deprecation of `VALUES(col)` in `ON DUPLICATE KEY UPDATE`.
+fn is_values_deprecation(warning: &SqlWarning) -> bool {
+    warning.code == ER_WARN_DEPRECATED_SYNTAX && warning.message.contains("VALUES function")
+}
+
+/// MYSQL数据操作
+impl Database for MysqlDatabase {
+    fn start_transaction(&mut self) -> Result<(), AkitaError> {
+        self.execute_result("BEGIN", Params::Nil)?;
+        self.2 = Some(self.1.interceptors().transaction_started());
+        Ok(())
+    }
+
+    fn commit_transaction(&mut self) -> Result<(), AkitaError> {
+        self.execute_result("COMMIT", Params::Nil)?;
+        let transaction_id = self.2.take();
+        self.1.interceptors().transaction_committed(transaction_id);
+        TransactionSynchronization::global().committed(transaction_id);
+        Ok(())
+    }
+
+    fn rollback_transaction(&mut self) -> Result<(), AkitaError> {
+        let result = self.execute_result("ROLLBACK", Params::Nil).map(|_| ());
+        let transaction_id = self.2.take();
+        self.1.interceptors().transaction_rolled_back(transaction_id);
+        TransactionSynchronization::global().rolled_back(transaction_id);
+        result
+    }
+
+    fn savepoint(&mut self, name: &str) -> Result<(), AkitaError> {
+        self.execute_result(&format!("SAVEPOINT {}", name), Params::Nil).map(|_| ())
+    }
+
+    fn release_savepoint(&mut self, name: &str) -> Result<(), AkitaError> {
+        self.execute_result(&format!("RELEASE SAVEPOINT {}", name), Params::Nil).map(|_| ())
+    }
+
+    fn rollback_to_savepoint(&mut self, name: &str) -> Result<(), AkitaError> {
+        self.execute_result(&format!("ROLLBACK TO SAVEPOINT {}", name), Params::Nil).map(|_| ())
+    }
+    
+    fn execute_result(&mut self, sql: &str, param: Params) -> Result<Rows, AkitaError> {
+        // the rows are mapped on this thread right after.
+        akita::set_strict_mapping(self.1.strict_mapping());
+        self.1.statement_policy().check(sql)?;
+        let interceptors = self.1.interceptors().clone();
+        let cached = cache::CachedRead::of(&self.1, self.2, sql);
+        let rows = interceptors.intercept_rows(sql, param, self.context(), |sql, param| cache::read_through(cached.as_ref(), sql, param, |param| {
+            let rows = self.timed(|db| db.run_result(sql, param))?;
+            self.check_warnings(sql)?;
+            let affected_rows = if OperationType::of(sql) == OperationType::Select { rows.data.len() as u64 } else { self.0.affected_rows() };
+            Ok((rows, Some(affected_rows)))
+        }));
+        cache::invalidate(&self.1, self.2, sql);
+        rows
+    }
+
+    fn execute_drop(&mut self, sql: &str, param: Params) -> Result<(), AkitaError> {
+        self.1.statement_policy().check(sql)?;
+        let interceptors = self.1.interceptors().clone();
+        let dropped = interceptors.intercept_counted(sql, param, self.context(), |sql, param| {
+            self.timed(|db| db.run_drop(sql, param))?;
+            self.check_warnings(sql)?;
+            Ok(((), Some(self.0.affected_rows())))
+        });
+        cache::invalidate(&self.1, self.2, sql);
+        dropped
+    }
+
+    fn execute_stream(&mut self, sql: &str, param: Params, consumer: &mut dyn FnMut(crate::Row) -> Result<(), AkitaError>) -> Result<(), AkitaError> {
+        akita::set_strict_mapping(self.1.strict_mapping());
+        self.1.statement_policy().check(sql)?;
+        let interceptors = self.1.interceptors().clone();
+        interceptors.intercept_counted(sql, param, self.context(), |sql, param| self.timed(|db| db.run_stream(sql, param, consumer)).map(|_| ((), None)))
+    }
+
     fn get_table(&mut self, table_name: &TableName) -> Result<Option<TableDef>, AkitaError> {
         #[derive(Debug, FromValue)]
         struct TableSpec {
@@ -431,6 +618,22 @@ impl Database for MysqlDatabase {
         self.0.last_insert_id()
     }
 
+    fn warnings(&mut self) -> Result<Vec<SqlWarning>, AkitaError> {
+        if self.0.warnings() == 0 {
+            return Ok(Vec::new());
+        }
+        let warnings: Vec<(String, u32, String)> = self.0.query("SHOW WARNINGS").map_err(|e| AkitaError::ExcuteSqlError(e.to_string(), "SHOW WARNINGS".to_string()))?;
+        Ok(warnings.into_iter().map(|(level, code, message)| SqlWarning { level, code, message }).collect())
+    }
+
+    fn transaction_id(&self) -> Option<u64> {
+        self.2
+    }
+
+    fn config(&self) -> &AkitaConfig {
+        &self.1
+    }
+
     fn get_database_name(&mut self) -> Result<Option<DatabaseName>, AkitaError> {
         let sql = "SELECT database() AS name";
         let mut database_names: Vec<Option<DatabaseName>> =
@@ -685,6 +888,9 @@ impl mysql::prelude::ToValue for MySQLValue<'_> {
             Value::Char(ref v) => v.to_string().into(),
             Value::Text(ref v) => v.into(),
             Value::Uuid(ref v) => v.as_bytes().into(),
+            Value::Inet(ref v) => v.to_string().into(),
+            Value::Cidr(ref v) => v.to_string().into(),
+            Value::MacAddr(ref v) => v.to_string().into(),
             Value::Date(ref v) => v.into(),
             Value::Timestamp(ref v) => v.naive_utc().into(),
             Value::DateTime(ref v) => v.into(),
@@ -854,11 +1060,37 @@ pub fn from_long_row<T: FromRowExt + Default>(row: Row) -> T {
     FromRowExt::from_long_row(row)
 }
 
+/// A connection of the pool, with the host it reached when several are configured.
+#[derive(Debug)]
+pub struct MysqlConnection {
+    conn: Conn,
+    /// the host and port failed over to, none for the configured one
+    host: Option<(String, u16)>,
+}
+
+impl Deref for MysqlConnection {
+    type Target = Conn;
+
+    fn deref(&self) -> &Conn {
+        &self.conn
+    }
+}
+
+impl DerefMut for MysqlConnection {
+    fn deref_mut(&mut self) -> &mut Conn {
+        &mut self.conn
+    }
+}
+
 #[allow(unused)]
 #[derive(Clone, Debug)]
 pub struct MysqlConnectionManager {
     params: Opts,
     cfg: AkitaConfig,
+    /// the host the next round-robin connection starts from
+    next_host: Arc<AtomicUsize>,
+    /// when the connections were last checked, by connection id
+    validated: Arc<Mutex<HashMap<u32, Instant>>>,
 }
 
 impl MysqlConnectionManager {
@@ -866,23 +1098,91 @@ impl MysqlConnectionManager {
         MysqlConnectionManager {
             params: Opts::from(params),
             cfg,
+            next_host: Arc::new(AtomicUsize::new(0)),
+            validated: Arc::new(Mutex::new(HashMap::new())),
         }
     }
+
+    /// connect to the first host up, in the order of the failover policy.
+    /// The host names are resolved again on each attempt, so a promoted standby is reached without restart.
+    fn connect_failover(&self, hosts: &[String]) -> Result<MysqlConnection, Error> {
+        let start = match self.cfg.failover_policy() {
+            FailoverPolicy::PrimaryFirst => 0,
+            FailoverPolicy::RoundRobin => self.next_host.fetch_add(1, Ordering::Relaxed) % hosts.len(),
+        };
+        let mut last_error = None;
+        for index in 0..hosts.len() {
+            let host = &hosts[(start + index) % hosts.len()];
+            let (name, port) = match host.rsplit_once(':').and_then(|(name, port)| port.parse::<u16>().ok().map(|port| (name, port))) {
+                Some((name, port)) => (name.to_string(), port),
+                None => (host.to_string(), self.params.get_tcp_port()),
+            };
+            let opts = OptsBuilder::from_opts(self.params.to_owned()).ip_or_hostname(Some(name.to_string())).tcp_port(port);
+            match Conn::new(opts) {
+                Ok(conn) => return Ok(MysqlConnection { conn, host: Some((name, port)) }),
+                Err(err) => last_error = Some(err),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| Error::DriverError(mysql::DriverError::CouldNotConnect(None))))
+    }
+}
+
+/// run the kill on a connection of its own, the one of the statement being busy.
+fn kill(opts: Opts, statement: &str) {
+    if let Err(_err) = Conn::new(opts).and_then(|mut conn| conn.query_drop(statement)) {
+        #[cfg(feature = "akita-logging")]
+        log::warn!("[Akita]: {} failed: {}", statement, _err);
+        #[cfg(feature = "akita-tracing")]
+        tracing::warn!("[Akita]: {} failed: {}", statement, _err);
+    }
 }
 
 impl r2d2::ManageConnection for MysqlConnectionManager {
-    type Connection = Conn;
+    type Connection = MysqlConnection;
     type Error = Error;
 
-    fn connect(&self) -> Result<Conn, Error> {
-        Conn::new(self.params.to_owned())
+    fn connect(&self) -> Result<MysqlConnection, Error> {
+        let hosts = self.cfg.hosts();
+        let mut conn = if hosts.len() > 1 {
+            self.connect_failover(hosts)?
+        } else {
+            MysqlConnection { conn: Conn::new(self.params.to_owned())?, host: None }
+        };
+        // kept in the statement cache of the connection.
+        for sql in self.cfg.warmup_statements() {
+            conn.prep(sql)?;
+        }
+        Ok(conn)
     }
 
-    fn is_valid(&self, conn: &mut Conn) -> Result<(), Error> {
-        conn.query_drop("SELECT version()")
+    fn is_valid(&self, conn: &mut MysqlConnection) -> Result<(), Error> {
+        let pool = self.cfg.pool_config();
+        let connection_id = conn.connection_id();
+        if let Some(interval) = pool.validation_interval() {
+            let checked = self.validated.lock().ok().and_then(|validated| validated.get(&connection_id).copied());
+            if checked.is_some_and(|checked| checked.elapsed() < interval) {
+                return Ok(());
+            }
+        }
+        let result = conn.query_drop(pool.validation_query().unwrap_or("SELECT 1"));
+        if let Ok(mut validated) = self.validated.lock() {
+            match (&result, pool.validation_interval()) {
+                (Ok(_), Some(interval)) => {
+                    // forget the connections gone since.
+                    if validated.len() > self.cfg.max_size() as usize * 2 {
+                        validated.retain(|_, checked| checked.elapsed() < interval);
+                    }
+                    validated.insert(connection_id, Instant::now());
+                }
+                _ => {
+                    validated.remove(&connection_id);
+                }
+            }
+        }
+        result
     }
 
-    fn has_broken(&self, conn: &mut Conn) -> bool {
+    fn has_broken(&self, conn: &mut MysqlConnection) -> bool {
         self.is_valid(conn).is_err()
     }
 }
@@ -891,10 +1191,14 @@ impl r2d2::ManageConnection for MysqlConnectionManager {
 /// 创建连接池
 /// cfg 配置信息
 /// 
-pub fn init_pool(cfg: &AkitaConfig) -> Result<R2d2Pool, AkitaError> {
-    test_connection(cfg)?;
+pub fn init_pool(cfg: &AkitaConfig, metrics: &Arc<PoolMetrics>) -> Result<R2d2Pool, AkitaError> {
     let manager = MysqlConnectionManager::new(cfg.into(), cfg.to_owned());
-    let pool = Pool::builder().connection_timeout(cfg.connection_timeout()).min_idle(cfg.min_idle()).max_size(cfg.max_size()).build(manager)?;
+    let builder = Pool::builder().connection_timeout(cfg.connection_timeout()).test_on_check_out(cfg.pool_config().test_on_borrow()).event_handler(Box::new(PoolEvents(metrics.clone()))).min_idle(cfg.min_idle()).max_lifetime(cfg.max_lifetime()).idle_timeout(cfg.idle_timeout()).max_size(cfg.max_size());
+    if !cfg.warmup() {
+        return Ok(builder.build_unchecked(manager));
+    }
+    test_connection(cfg)?;
+    let pool = builder.build(manager)?;
     Ok(pool)
 }
 
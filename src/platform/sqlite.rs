@@ -8,23 +8,32 @@ use uuid::Uuid;
 use std::fmt;
 use std::path::{Path, PathBuf};
 use std::result::Result;
+use std::sync::Arc;
 
 
 cfg_if! {if #[cfg(feature = "akita-auth")]{
     use crate::auth::{GrantUserPrivilege, Role, UserInfo, DataBaseUser};
 }}
 
-use crate::{AkitaConfig, Params, ToValue};
+use crate::{AkitaConfig, OperationType, Params, SqlWarning, ToValue, TransactionSynchronization, cache};
 use crate::database::Database;
-use crate::pool::LogLevel;
+use crate::interceptor::StatementContext;
+use crate::watchdog::{self, Watchdog};
+use crate::pool::{DEFAULT_STMT_CACHE_SIZE, LogLevel, PoolEvents, PoolMetrics};
 use crate::{self as akita, comm::{extract_datatype_with_capacity, maybe_trim_parenthesis}, Rows, Value, SqlType, cfg_if, Capacity, ColumnConstraint, ForeignKey, Key, Literal, TableKey, AkitaError, ColumnDef, FieldName, ColumnSpecification, DatabaseName, TableDef, TableName, SchemaContent};
 type R2d2Pool = Pool<SqliteConnectionManager>;
 
-pub struct SqliteDatabase(r2d2::PooledConnection<SqliteConnectionManager>, AkitaConfig);
+/// The connection, its config and the id of the transaction in progress.
+pub struct SqliteDatabase(r2d2::PooledConnection<SqliteConnectionManager>, AkitaConfig, Option<u64>);
 
 impl SqliteDatabase {
     pub fn new(pool: r2d2::PooledConnection<SqliteConnectionManager>, cfg: AkitaConfig) -> Self {
-        SqliteDatabase(pool, cfg)
+        SqliteDatabase(pool, cfg, None)
+    }
+
+    /// The driver connection underneath.
+    pub fn raw(&mut self) -> &mut rusqlite::Connection {
+        &mut self.0
     }
 
     pub fn log(&self, fmt: String) {
@@ -53,24 +62,39 @@ impl SqliteDatabase {
     }
 }
 
-/// SQLite数据操作
-#[allow(unused)]
-impl Database for SqliteDatabase {
-    fn start_transaction(&mut self) -> Result<(), AkitaError> {
-        self.execute_result("BEGIN TRANSACTION", Params::Nil).map(|_| ()).map_err(AkitaError::from)
+impl SqliteDatabase {
+    /// where the statements run, for the interceptors and the spans.
+    fn context(&self) -> StatementContext {
+        StatementContext { system: "sqlite", peer: None, datasource: self.1.datasource_key(), transaction_id: self.2, connection_id: None }
     }
 
-    fn commit_transaction(&mut self) -> Result<(), AkitaError> {
-        self.execute_result("COMMIT TRANSACTION", Params::Nil).map(|_| ()).map_err(AkitaError::from)
+    /// the uuids as their bytes when they are bound so, see `AkitaConfig::set_uuid_binary`.
+    fn uuids(&self, params: Params) -> Params {
+        if !self.1.uuid_binary() {
+            return params;
+        }
+        params.map_values(|value| match value {
+            Value::Uuid(v) => Value::Blob(v.as_bytes().to_vec()),
+            value => value,
+        })
     }
 
-    fn rollback_transaction(&mut self) -> Result<(), AkitaError> {
-        self.execute_result("ROLLBACK TRANSACTION", Params::Nil).map(|_| ()).map_err(AkitaError::from)
+    /// run the statement, interrupted once over the statement timeout.
+    fn timed<R, F: FnOnce(&mut Self) -> Result<R, AkitaError>>(&mut self, f: F) -> Result<R, AkitaError> {
+        match watchdog::statement_timeout(self.1.statement_timeout()) {
+            Some(timeout) => {
+                let handle = self.0.get_interrupt_handle();
+                let deadline = Watchdog::global().arm(timeout, move || handle.interrupt());
+                deadline.check(f(self), timeout)
+            }
+            None => f(self),
+        }
     }
-    
-    fn execute_result(&mut self, sql: &str, params: Params) -> Result<Rows, AkitaError> {
-        self.log(format!("Prepare SQL: {} params: {:?}", &sql, params));
-        let stmt = self.0.prepare(&sql);
+
+    fn run_result(&mut self, sql: &str, params: Params) -> Result<Rows, AkitaError> {
+        let params = self.uuids(params);
+        self.log(format!("Prepare SQL: {} params: {:?}", self.1.log_sql(sql), self.1.log_params(sql, &params)));
+        let stmt = self.0.prepare_cached(&sql);
         let column_names = if let Ok(ref stmt) = stmt {
             stmt.column_names()
         } else {
@@ -136,9 +160,10 @@ impl Database for SqliteDatabase {
         }
     }
 
-    fn execute_drop(&mut self, sql: &str, params: Params) -> Result<(), AkitaError> {
-        self.log(format!("Prepare SQL: {} params: {:?}", &sql, params));
-        let stmt = self.0.prepare(&sql);
+    fn run_drop(&mut self, sql: &str, params: Params) -> Result<(), AkitaError> {
+        let params = self.uuids(params);
+        self.log(format!("Prepare SQL: {} params: {:?}", self.1.log_sql(sql), self.1.log_params(sql, &params)));
+        let stmt = self.0.prepare_cached(&sql);
         match stmt {
             Ok(mut stmt) => {
                 let sql_values = match params {
@@ -172,6 +197,118 @@ impl Database for SqliteDatabase {
         }
     }
 
+    fn run_stream(&mut self, sql: &str, params: Params, consumer: &mut dyn FnMut(crate::Row) -> Result<(), AkitaError>) -> Result<(), AkitaError> {
+        let params = self.uuids(params);
+        self.log(format!("Prepare SQL: {} params: {:?}", self.1.log_sql(sql), self.1.log_params(sql, &params)));
+        let mut stmt = self.0.prepare_cached(sql).map_err(AkitaError::from)?;
+        let column_names: Vec<String> = stmt.column_names().iter().map(ToString::to_string).collect();
+        let column_count = stmt.column_count();
+        let sql_values = match params {
+            Params::Nil => vec![],
+            Params::Vector(param) => param.iter().map(to_sq_value).collect::<Vec<_>>(),
+            Params::Custom(param) => {
+                let len = sql.len();
+                let mut values = param.iter().map(|param| {
+                    let key = format!(":{}", param.0);
+                    (sql.find(&key).unwrap_or(len), &param.1)
+                }).collect::<Vec<_>>();
+                values.sort_by_key(|v| v.0);
+                values.iter().map(|v| to_sq_value(v.1)).collect::<Vec<_>>()
+            },
+        };
+        let mut rows = stmt.query(sql_values).map_err(AkitaError::from)?;
+        while let Some(row) = rows.next()? {
+            let mut record: Vec<Value> = Vec::with_capacity(column_count);
+            for i in 0..column_count {
+                if let Ok(raw) = row.get(i) {
+                    record.push(match raw {
+                        rusqlite::types::Value::Blob(v) => Value::Blob(v),
+                        rusqlite::types::Value::Real(v) => Value::Double(v),
+                        rusqlite::types::Value::Integer(v) => Value::Bigint(v),
+                        rusqlite::types::Value::Text(v) => Value::Text(v),
+                        rusqlite::types::Value::Null => Value::Nil,
+                    });
+                }
+            }
+            consumer(crate::Row {
+                columns: column_names.clone(),
+                data: record,
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// SQLite数据操作
+#[allow(unused)]
+impl Database for SqliteDatabase {
+    fn start_transaction(&mut self) -> Result<(), AkitaError> {
+        self.execute_result("BEGIN TRANSACTION", Params::Nil)?;
+        self.2 = Some(self.1.interceptors().transaction_started());
+        Ok(())
+    }
+
+    fn commit_transaction(&mut self) -> Result<(), AkitaError> {
+        self.execute_result("COMMIT TRANSACTION", Params::Nil)?;
+        let transaction_id = self.2.take();
+        self.1.interceptors().transaction_committed(transaction_id);
+        TransactionSynchronization::global().committed(transaction_id);
+        Ok(())
+    }
+
+    fn rollback_transaction(&mut self) -> Result<(), AkitaError> {
+        let result = self.execute_result("ROLLBACK TRANSACTION", Params::Nil).map(|_| ());
+        let transaction_id = self.2.take();
+        self.1.interceptors().transaction_rolled_back(transaction_id);
+        TransactionSynchronization::global().rolled_back(transaction_id);
+        result
+    }
+
+    fn savepoint(&mut self, name: &str) -> Result<(), AkitaError> {
+        self.execute_result(&format!("SAVEPOINT {}", name), Params::Nil).map(|_| ())
+    }
+
+    fn release_savepoint(&mut self, name: &str) -> Result<(), AkitaError> {
+        self.execute_result(&format!("RELEASE SAVEPOINT {}", name), Params::Nil).map(|_| ())
+    }
+
+    fn rollback_to_savepoint(&mut self, name: &str) -> Result<(), AkitaError> {
+        self.execute_result(&format!("ROLLBACK TO SAVEPOINT {}", name), Params::Nil).map(|_| ())
+    }
+    
+    fn execute_result(&mut self, sql: &str, params: Params) -> Result<Rows, AkitaError> {
+        // the rows are mapped on this thread right after.
+        akita::set_strict_mapping(self.1.strict_mapping());
+        self.1.statement_policy().check(sql)?;
+        let interceptors = self.1.interceptors().clone();
+        let cached = cache::CachedRead::of(&self.1, self.2, sql);
+        let rows = interceptors.intercept_rows(sql, params, self.context(), |sql, params| cache::read_through(cached.as_ref(), sql, params, |params| {
+            let rows = self.timed(|db| db.run_result(sql, params))?;
+            let affected_rows = if OperationType::of(sql) == OperationType::Select { rows.data.len() as u64 } else { self.affected_rows() };
+            Ok((rows, Some(affected_rows)))
+        }));
+        cache::invalidate(&self.1, self.2, sql);
+        rows
+    }
+
+    fn execute_drop(&mut self, sql: &str, params: Params) -> Result<(), AkitaError> {
+        self.1.statement_policy().check(sql)?;
+        let interceptors = self.1.interceptors().clone();
+        let dropped = interceptors.intercept_counted(sql, params, self.context(), |sql, params| {
+            self.timed(|db| db.run_drop(sql, params))?;
+            Ok(((), Some(self.affected_rows())))
+        });
+        cache::invalidate(&self.1, self.2, sql);
+        dropped
+    }
+
+    fn execute_stream(&mut self, sql: &str, params: Params, consumer: &mut dyn FnMut(crate::Row) -> Result<(), AkitaError>) -> Result<(), AkitaError> {
+        akita::set_strict_mapping(self.1.strict_mapping());
+        self.1.statement_policy().check(sql)?;
+        let interceptors = self.1.interceptors().clone();
+        interceptors.intercept_counted(sql, params, self.context(), |sql, params| self.timed(|db| db.run_stream(sql, params, consumer)).map(|_| ((), None)))
+    }
+
     fn get_table(&mut self, table_name: &TableName) -> Result<Option<TableDef>, AkitaError> {
         #[derive(Debug)]
         struct ColumnSimple {
@@ -472,11 +609,24 @@ impl Database for SqliteDatabase {
     }
 
     fn affected_rows(&self) -> u64 {
-        todo!()
+        self.0.query_row("SELECT changes()", rusqlite::NO_PARAMS, |row| row.get::<_, i64>(0)).map(|v| v as u64).unwrap_or_default()
     }
 
     fn last_insert_id(&self) -> u64 {
-        todo!()
+        self.0.last_insert_rowid() as u64
+    }
+
+    /// SQLite doesn't raise warnings, it stores the values as they are.
+    fn warnings(&mut self) -> Result<Vec<SqlWarning>, AkitaError> {
+        Ok(Vec::new())
+    }
+
+    fn transaction_id(&self) -> Option<u64> {
+        self.2
+    }
+
+    fn config(&self) -> &AkitaConfig {
+        &self.1
     }
 
     fn create_database(&mut self, _database: &str) -> Result<(), AkitaError> {
@@ -681,6 +831,9 @@ fn to_sq_value(val: &Value) -> rusqlite::types::Value {
         Value::Char(v) => rusqlite::types::Value::Text(format!("{}", v)),
         Value::Json(ref v) => rusqlite::types::Value::Text(v.to_string()),
         Value::Uuid(ref v) => rusqlite::types::Value::Text(v.to_string()),
+        Value::Inet(ref v) => rusqlite::types::Value::Text(v.to_string()),
+        Value::Cidr(ref v) => rusqlite::types::Value::Text(v.to_string()),
+        Value::MacAddr(ref v) => rusqlite::types::Value::Text(v.to_string()),
         Value::Date(ref v) => rusqlite::types::Value::Text(v.to_string()),
         Value::DateTime(ref v) => rusqlite::types::Value::Text(v.to_string()),
         Value::Nil => rusqlite::types::Value::Null,
@@ -701,6 +854,7 @@ pub struct SqliteConnectionManager {
     source: Source,
     flags: OpenFlags,
     init: Option<Box<InitFn>>,
+    validation_query: Option<String>,
 }
 
 impl fmt::Debug for SqliteConnectionManager {
@@ -709,6 +863,7 @@ impl fmt::Debug for SqliteConnectionManager {
         let _ = builder.field("source", &self.source);
         let _ = builder.field("flags", &self.source);
         let _ = builder.field("init", &self.init.as_ref().map(|_| "InitFn"));
+        let _ = builder.field("validation_query", &self.validation_query);
         builder.finish()
     }
 }
@@ -722,6 +877,7 @@ impl SqliteConnectionManager {
             source: Source::File(path.as_ref().to_path_buf()),
             flags: OpenFlags::default(),
             init: None,
+            validation_query: None,
         }
     }
 
@@ -731,6 +887,7 @@ impl SqliteConnectionManager {
             source: Source::Memory,
             flags: OpenFlags::default(),
             init: None,
+            validation_query: None,
         }
     }
 
@@ -763,6 +920,12 @@ impl SqliteConnectionManager {
         let init: Option<Box<InitFn>> = Some(Box::new(init));
         Self { init, ..self }
     }
+
+    /// Converts `SqliteConnectionManager` into one that checks the connections
+    /// with the query.
+    pub fn with_validation_query(self, validation_query: Option<String>) -> Self {
+        Self { validation_query, ..self }
+    }
 }
 
 impl r2d2::ManageConnection for SqliteConnectionManager {
@@ -782,7 +945,7 @@ impl r2d2::ManageConnection for SqliteConnectionManager {
     }
 
     fn is_valid(&self, conn: &mut Connection) -> Result<(), Error> {
-        conn.execute_batch("").map_err(Into::into)
+        conn.execute_batch(self.validation_query.as_deref().unwrap_or("SELECT 1")).map_err(Into::into)
     }
 
     fn has_broken(&self, _: &mut Connection) -> bool {
@@ -794,11 +957,23 @@ impl r2d2::ManageConnection for SqliteConnectionManager {
 /// 创建连接池
 /// cfg 配置信息
 /// 
-pub fn init_pool(cfg: &AkitaConfig) -> Result<R2d2Pool, AkitaError> {
+pub fn init_pool(cfg: &AkitaConfig, metrics: &Arc<PoolMetrics>) -> Result<R2d2Pool, AkitaError> {
     let database_url = &cfg.url().to_owned();
+    let statements = cfg.warmup_statements().to_vec();
+    let manager = SqliteConnectionManager::file(database_url).with_validation_query(cfg.pool_config().validation_query().map(ToString::to_string))
+        .with_init(move |conn| {
+            conn.set_prepared_statement_cache_capacity(statements.len().max(DEFAULT_STMT_CACHE_SIZE));
+            for sql in statements.iter() {
+                conn.prepare_cached(sql)?;
+            }
+            Ok(())
+        });
+    let builder = Pool::builder().connection_timeout(cfg.to_owned().connection_timeout()).test_on_check_out(cfg.pool_config().test_on_borrow()).event_handler(Box::new(PoolEvents(metrics.clone()))).min_idle(cfg.min_idle()).max_lifetime(cfg.max_lifetime()).idle_timeout(cfg.idle_timeout()).max_size(cfg.max_size());
+    if !cfg.warmup() {
+        return Ok(builder.build_unchecked(manager));
+    }
     test_connection(&database_url)?;
-    let manager = SqliteConnectionManager::file(database_url);
-    let pool = Pool::builder().connection_timeout(cfg.to_owned().connection_timeout()).min_idle(cfg.min_idle()).max_size(cfg.max_size()).build(manager)?;
+    let pool = builder.build(manager)?;
     Ok(pool)
 }
 
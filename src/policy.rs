@@ -0,0 +1,98 @@
+//!
+//! The statements a datasource is allowed to run, e.g. no writes on the replicas.
+//!
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{AkitaError, OperationType};
+use crate::cache::{read_tables, write_tables};
+
+/// The kinds of statements allowed or denied on a datasource and on its tables,
+/// checked before the interceptors with `AkitaError::StatementDenied` for the ones refused.
+/// Everything is allowed by default.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StatementPolicy {
+    /// the kinds allowed, all of them when none
+    allowed: Option<HashSet<OperationType>>,
+    denied: HashSet<OperationType>,
+    /// the kinds denied on each table, by lowercase name
+    tables: HashMap<String, HashSet<OperationType>>,
+}
+
+impl StatementPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only the queries and the statements of the session, e.g. for the replicas.
+    pub fn read_only() -> Self {
+        Self::new().allow(vec![OperationType::Select, OperationType::Other])
+    }
+
+    /// Refuse the kinds of statements not listed.
+    pub fn allow(mut self, operations: Vec<OperationType>) -> Self {
+        self.allowed.get_or_insert_with(HashSet::new).extend(operations);
+        self
+    }
+
+    pub fn deny(mut self, operations: Vec<OperationType>) -> Self {
+        self.denied.extend(operations);
+        self
+    }
+
+    /// Refuse the kinds of statements on the table, e.g. `OperationType::Delete` on `ledger_entries`.
+    /// The table matches with or without its schema.
+    pub fn deny_on_table<S: Into<String>>(mut self, table: S, operations: Vec<OperationType>) -> Self {
+        self.tables.entry(table.into().to_lowercase()).or_default().extend(operations);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.allowed.is_none() && self.denied.is_empty() && self.tables.is_empty()
+    }
+
+    /// Fail on the statement the policy refuses.
+    pub fn check(&self, sql: &str) -> Result<(), AkitaError> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        let operation = OperationType::of(sql);
+        if self.denied.contains(&operation) || matches!(&self.allowed, Some(allowed) if !allowed.contains(&operation)) {
+            return Err(AkitaError::StatementDenied(format!("{:?} statements are not allowed: {}", operation, sql)));
+        }
+        if self.tables.is_empty() {
+            return Ok(());
+        }
+        let tables = if operation == OperationType::Select { read_tables(sql) } else { write_tables(sql) };
+        for table in tables {
+            let table = table.to_lowercase();
+            let name = table.rsplit('.').next().unwrap_or_default();
+            let denied = self.tables.get(&table).or_else(|| self.tables.get(name));
+            if matches!(denied, Some(denied) if denied.contains(&operation)) {
+                return Err(AkitaError::StatementDenied(format!("{:?} statements are not allowed on the table {}: {}", operation, table, sql)));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{AkitaError, OperationType};
+    use super::StatementPolicy;
+
+    #[test]
+    fn check_statements() {
+        let replica = StatementPolicy::read_only();
+        assert!(replica.check("select * from t_user").is_ok());
+        assert!(replica.check("SAVEPOINT sp_1").is_ok());
+        assert!(matches!(replica.check("update t_user set name = ?"), Err(AkitaError::StatementDenied(_))));
+        let reporting = StatementPolicy::new().deny(vec![OperationType::Ddl]);
+        assert!(reporting.check("DROP TABLE t_user").is_err());
+        assert!(reporting.check("insert into t_user(name) values (?)").is_ok());
+        let ledger = StatementPolicy::new().deny_on_table("ledger_entries", vec![OperationType::Delete]);
+        assert!(ledger.check("delete from `db`.`ledger_entries` where id = ?").is_err());
+        assert!(ledger.check("delete from t_user where id = ?").is_ok());
+        assert!(ledger.check("select * from ledger_entries").is_ok());
+    }
+}
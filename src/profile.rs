@@ -0,0 +1,92 @@
+//!
+//! The statistics of the columns of an entity, from a sample of its records.
+//!
+
+use crate::{Akita, AkitaError, AkitaMapper, GetFields, GetTableName, Params, Platform, Value};
+use crate::ddl::is_sqlite;
+use crate::logic_delete::{logic_delete_column, LOGIC_NOT_DELETED};
+
+/// The values kept by `ColumnProfile::top_values`.
+const TOP_VALUES: usize = 5;
+
+/// The statistics of a column over the sampled records.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnProfile {
+    pub column: String,
+    /// the records sampled, at most the sample size
+    pub sampled: u64,
+    pub nulls: u64,
+    /// `Value::Nil` when all the sampled values are null
+    pub min: Value,
+    pub max: Value,
+    /// the distinct values of the sample, the ones of the table when it was sampled whole
+    pub distinct: u64,
+    /// the most frequent values that are not null with their count, the most frequent first
+    pub top_values: Vec<(Value, u64)>,
+}
+
+impl ColumnProfile {
+    /// the share of null values in the sample, `0` for an empty one.
+    pub fn null_ratio(&self) -> f64 {
+        if self.sampled == 0 { 0.0 } else { self.nulls as f64 / self.sampled as f64 }
+    }
+}
+
+impl Akita {
+    /// Profile the columns of the entity, all of them when none is given, over a random sample of its records,
+    /// the logically deleted ones left out. Each column is profiled over its own sample, its values grouped in one query.
+    pub fn profile<T: GetTableName + GetFields>(&self, columns: &[&str], sample_size: usize) -> Result<Vec<ColumnProfile>, AkitaError> {
+        let table = T::table_name().complete_name();
+        if table.is_empty() {
+            return Err(AkitaError::MissingTable("Find Error, Missing Table Name !".to_string()))
+        }
+        if sample_size == 0 {
+            return Err(AkitaError::DataError("sample size must be greater than zero".to_string()))
+        }
+        let fields = T::fields().into_iter().filter(|field| field.exist).map(|field| field.name).collect::<Vec<_>>();
+        let columns = if columns.is_empty() { fields.clone() } else { columns.iter().map(|column| column.to_string()).collect() };
+        if let Some(column) = columns.iter().find(|column| !fields.contains(column)) {
+            return Err(AkitaError::MissingField(format!("Table({}) has no column {}", &table, column)))
+        }
+        let not_deleted = logic_delete_column::<T>().map(|column| format!(" WHERE `{}` = {}", column, LOGIC_NOT_DELETED)).unwrap_or_default();
+        let platform = self.config().platform();
+        let mut profiles = Vec::with_capacity(columns.len());
+        for column in columns {
+            // the values of the sample with their count, ordered by value, the null first.
+            let sample = sample_sql(&platform, &table, &column, &not_deleted, sample_size);
+            let groups = self.exec_iter(format!("SELECT `{0}` AS profiled_value, COUNT(*) AS profiled_count FROM ({1}) s GROUP BY `{0}` ORDER BY `{0}`", column, sample), Params::Nil)?
+                .iter().map(|row| Ok((row.get_obj::<Value>("profiled_value")?, row.get_obj::<i64>("profiled_count")? as u64)))
+                .collect::<Result<Vec<_>, AkitaError>>()?;
+            let sampled = groups.iter().map(|(_, count)| count).sum();
+            let nulls = groups.iter().filter(|(value, _)| *value == Value::Nil).map(|(_, count)| count).sum();
+            let mut values = groups.into_iter().filter(|(value, _)| *value != Value::Nil).collect::<Vec<_>>();
+            let min = values.first().map(|(value, _)| value.clone()).unwrap_or(Value::Nil);
+            let max = values.last().map(|(value, _)| value.clone()).unwrap_or(Value::Nil);
+            let distinct = values.len() as u64;
+            values.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+            values.truncate(TOP_VALUES);
+            profiles.push(ColumnProfile { column, sampled, nulls, min, max, distinct, top_values: values });
+        }
+        Ok(profiles)
+    }
+}
+
+/// the column of a random sample of the records.
+fn sample_sql(platform: &Platform, table: &str, column: &str, filter: &str, sample_size: usize) -> String {
+    let random = if is_sqlite(platform) { "RANDOM()" } else { "RAND()" };
+    format!("SELECT `{}` FROM {}{} ORDER BY {} LIMIT {}", column, table, filter, random, sample_size)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Platform, Value};
+    use super::{sample_sql, ColumnProfile};
+
+    #[test]
+    fn sample() {
+        assert_eq!(sample_sql(&Platform::Unsupported("mysql".to_string()), "t_user", "email", " WHERE `deleted` = 0", 1000),
+            "SELECT `email` FROM t_user WHERE `deleted` = 0 ORDER BY RAND() LIMIT 1000");
+        let profile = ColumnProfile { column: "email".to_string(), sampled: 4, nulls: 1, min: Value::Nil, max: Value::Nil, distinct: 3, top_values: vec![] };
+        assert_eq!(profile.null_ratio(), 0.25);
+    }
+}
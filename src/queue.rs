@@ -0,0 +1,220 @@
+//!
+//! A job queue in a table, consumed with `SKIP LOCKED`.
+//!
+
+use std::marker::PhantomData;
+use std::time::Duration;
+use once_cell::sync::OnceCell;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{Akita, AkitaError, AkitaMapper, Params, ToValue};
+use crate::ddl::{check_identifier, is_sqlite, now_millis};
+
+/// A message taken from the queue, hidden from the other consumers until it is acked, nacked or its visibility times out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueueMessage<T> {
+    pub id: i64,
+    pub payload: T,
+    /// the deliveries of the message, this one included
+    pub attempts: u32,
+    /// the delivery of the message, a consumer whose visibility timed out can no longer ack it
+    receipt: String,
+}
+
+/// A message that failed all its attempts, kept in the `{table}_dead` table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeadLetter<T> {
+    pub id: i64,
+    pub payload: T,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+}
+
+/// The json messages of `T` in a table, e.g. the jobs of the workers of an application, on the primary.
+///
+/// `dequeue_batch` claims the visible messages with `FOR UPDATE SKIP LOCKED` on MySQL, the concurrent consumers
+/// taking the others, with a single `UPDATE` on SQLite. A claimed message is visible again after the visibility
+/// timeout unless acked, and moved to the `{table}_dead` table once delivered `max_attempts` times.
+/// The tables are created on first use.
+pub struct DbQueue<T> {
+    akita: Akita,
+    table: String,
+    visibility_timeout: Duration,
+    max_attempts: u32,
+    created: OnceCell<()>,
+    _payload: PhantomData<fn() -> T>,
+}
+
+impl<T: Serialize + DeserializeOwned> DbQueue<T> {
+    pub fn new<S: Into<String>>(akita: &Akita, table: S) -> Self {
+        DbQueue { akita: akita.force_master(), table: table.into(), visibility_timeout: Duration::from_secs(30), max_attempts: 5, created: OnceCell::new(), _payload: PhantomData }
+    }
+
+    /// How long a dequeued message stays hidden, 30 seconds by default.
+    pub fn set_visibility_timeout(mut self, visibility_timeout: Duration) -> Self {
+        self.visibility_timeout = visibility_timeout;
+        self
+    }
+
+    /// The deliveries of a message before it is dead lettered, 5 by default.
+    pub fn set_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+
+    /// the table of the dead letters.
+    pub fn dead_table(&self) -> String {
+        format!("{}_dead", self.table)
+    }
+
+    /// Add the message to the queue, returns its id.
+    pub fn enqueue(&self, payload: &T) -> Result<i64, AkitaError> {
+        self.enqueue_delayed(payload, Duration::ZERO)
+    }
+
+    /// Add the message to the queue, visible after the delay.
+    pub fn enqueue_delayed(&self, payload: &T, delay: Duration) -> Result<i64, AkitaError> {
+        self.create()?;
+        let now = now_millis();
+        let result = self.akita.exec_result(format!("INSERT INTO {} (payload, attempts, visible_at, created_at) VALUES (?, 0, ?, ?)", self.table),
+            Params::Vector(vec![to_json(payload)?.to_value(), (now + delay.as_millis() as i64).to_value(), now.to_value()]))?;
+        Ok(result.last_insert_id as i64)
+    }
+
+    /// Take up to `n` visible messages, the oldest first. The ones already delivered `max_attempts` times are dead lettered instead.
+    pub fn dequeue_batch(&self, n: usize) -> Result<Vec<QueueMessage<T>>, AkitaError> {
+        self.create()?;
+        if n == 0 {
+            return Ok(Vec::new());
+        }
+        let now = now_millis();
+        let receipt = uuid::Uuid::new_v4().to_string();
+        let visible_at = now + self.visibility_timeout.as_millis() as i64;
+        // the claimed rows are read in the claiming transaction, on the primary whatever the routing of the reads.
+        let transaction = self.akita.start_transaction()?;
+        if is_sqlite(&self.akita.config().platform()) {
+            // a single statement is atomic, SQLite has no row locks to skip.
+            transaction.exec_result(format!("UPDATE {0} SET visible_at = ?, attempts = attempts + 1, receipt = ? WHERE id IN ({1})", self.table, visible_sql(&self.table, n, false)),
+                Params::Vector(vec![visible_at.to_value(), receipt.to_value(), now.to_value()]))?;
+        } else {
+            let ids = transaction.exec_iter(visible_sql(&self.table, n, true), Params::Vector(vec![now.to_value()]))?
+                .iter().map(|row| row.get_obj::<i64>("id")).collect::<Result<Vec<_>, _>>()?;
+            if !ids.is_empty() {
+                transaction.exec_result(format!("UPDATE {} SET visible_at = ?, attempts = attempts + 1, receipt = ? WHERE id IN ({})", self.table, ids.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(", ")),
+                    Params::Vector(vec![visible_at.to_value(), receipt.to_value()]))?;
+            }
+        }
+        let rows = transaction.exec_iter(format!("SELECT id, payload, attempts FROM {} WHERE receipt = ? ORDER BY id", self.table), Params::Vector(vec![receipt.to_value()]))?;
+        transaction.commit()?;
+        let mut messages = Vec::new();
+        for row in rows.iter() {
+            let (id, attempts) = (row.get_obj::<i64>("id")?, row.get_obj::<i64>("attempts")? as u32);
+            if attempts > self.max_attempts {
+                // the claim is not a delivery.
+                self.dead_letter(id, &receipt, None, 1)?;
+                continue;
+            }
+            messages.push(QueueMessage { id, payload: from_json(&row.get_obj::<String>("payload")?)?, attempts, receipt: receipt.to_string() });
+        }
+        Ok(messages)
+    }
+
+    /// Remove the handled message, returns false when it was delivered again after its visibility timed out.
+    pub fn ack(&self, message: &QueueMessage<T>) -> Result<bool, AkitaError> {
+        let result = self.akita.exec_result(format!("DELETE FROM {} WHERE id = ? AND receipt = ?", self.table),
+            Params::Vector(vec![message.id.to_value(), message.receipt.to_value()]))?;
+        Ok(result.affected_rows > 0)
+    }
+
+    /// Give the failed message back to the queue, visible at once, or dead letter it after its last attempt.
+    /// Returns false when it was delivered again after its visibility timed out.
+    pub fn nack(&self, message: &QueueMessage<T>, error: &str) -> Result<bool, AkitaError> {
+        if message.attempts >= self.max_attempts {
+            return self.dead_letter(message.id, &message.receipt, Some(error), 0);
+        }
+        let result = self.akita.exec_result(format!("UPDATE {} SET visible_at = ?, receipt = NULL, last_error = ? WHERE id = ? AND receipt = ?", self.table),
+            Params::Vector(vec![now_millis().to_value(), error.to_value(), message.id.to_value(), message.receipt.to_value()]))?;
+        Ok(result.affected_rows > 0)
+    }
+
+    /// The dead letters, the oldest first.
+    pub fn dead_letters(&self, limit: usize) -> Result<Vec<DeadLetter<T>>, AkitaError> {
+        self.create()?;
+        self.akita.exec_iter(format!("SELECT id, payload, attempts, last_error FROM {} ORDER BY id LIMIT {}", self.dead_table(), limit), Params::Nil)?
+            .iter().map(|row| Ok(DeadLetter {
+                id: row.get_obj("id")?,
+                payload: from_json(&row.get_obj::<String>("payload")?)?,
+                attempts: row.get_obj::<i64>("attempts")? as u32,
+                last_error: row.get_obj("last_error")?,
+            })).collect()
+    }
+
+    /// Put the dead letter back in the queue for `max_attempts` more deliveries, returns false when there is no such letter.
+    pub fn requeue_dead(&self, id: i64) -> Result<bool, AkitaError> {
+        self.create()?;
+        let transaction = self.akita.start_transaction()?;
+        let now = now_millis();
+        let moved = transaction.exec_result(format!("INSERT INTO {} (id, payload, attempts, visible_at, last_error, created_at) SELECT id, payload, 0, ?, last_error, ? FROM {} WHERE id = ?", self.table, self.dead_table()),
+            Params::Vector(vec![now.to_value(), now.to_value(), id.to_value()]))?.affected_rows > 0;
+        transaction.exec_result(format!("DELETE FROM {} WHERE id = ?", self.dead_table()), Params::Vector(vec![id.to_value()]))?;
+        transaction.commit()?;
+        Ok(moved)
+    }
+
+    /// move the message still held with the receipt to the dead letters, less the attempts of the claim.
+    fn dead_letter(&self, id: i64, receipt: &str, error: Option<&str>, claimed: i64) -> Result<bool, AkitaError> {
+        let transaction = self.akita.start_transaction()?;
+        let moved = transaction.exec_result(format!("INSERT INTO {} (id, payload, attempts, last_error, failed_at) SELECT id, payload, attempts - ?, COALESCE(?, last_error), ? FROM {} WHERE id = ? AND receipt = ?", self.dead_table(), self.table),
+            Params::Vector(vec![claimed.to_value(), error.to_value(), now_millis().to_value(), id.to_value(), receipt.to_value()]))?.affected_rows > 0;
+        transaction.exec_result(format!("DELETE FROM {} WHERE id = ? AND receipt = ?", self.table), Params::Vector(vec![id.to_value(), receipt.to_value()]))?;
+        transaction.commit()?;
+        Ok(moved)
+    }
+
+    fn create(&self) -> Result<(), AkitaError> {
+        self.created.get_or_try_init(|| {
+            check_identifier("queue table name", &self.table)?;
+            let id = if is_sqlite(&self.akita.config().platform()) { "INTEGER PRIMARY KEY AUTOINCREMENT" } else { "BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY" };
+            self.akita.exec_result(format!("CREATE TABLE IF NOT EXISTS {} (id {}, payload TEXT NOT NULL, attempts INT NOT NULL DEFAULT 0, visible_at BIGINT NOT NULL, \
+                receipt VARCHAR(36), last_error TEXT, created_at BIGINT NOT NULL)", self.table, id), Params::Nil)?;
+            self.akita.exec_result(format!("CREATE TABLE IF NOT EXISTS {} (id BIGINT NOT NULL PRIMARY KEY, payload TEXT NOT NULL, attempts INT NOT NULL, \
+                last_error TEXT, failed_at BIGINT NOT NULL)", self.dead_table()), Params::Nil)?;
+            Ok(())
+        }).map(|_| ())
+    }
+}
+
+impl Akita {
+    /// The queue of the messages of `T` in the table, see `DbQueue`.
+    pub fn queue<T: Serialize + DeserializeOwned>(&self, table: impl Into<String>) -> DbQueue<T> {
+        DbQueue::new(self, table)
+    }
+}
+
+/// the ids of the first visible messages, the ones locked by the other consumers skipped when locking.
+fn visible_sql(table: &str, n: usize, lock: bool) -> String {
+    format!("SELECT id FROM {} WHERE visible_at <= ? ORDER BY id LIMIT {}{}", table, n, if lock { " FOR UPDATE SKIP LOCKED" } else { "" })
+}
+
+fn to_json<T: Serialize>(payload: &T) -> Result<String, AkitaError> {
+    serde_json::to_string(payload).map_err(|e| AkitaError::DataError(e.to_string()))
+}
+
+fn from_json<T: DeserializeOwned>(payload: &str) -> Result<T, AkitaError> {
+    serde_json::from_str(payload).map_err(|e| AkitaError::DataError(e.to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::visible_sql;
+
+    #[test]
+    fn claim() {
+        assert_eq!(visible_sql("jobs", 10, true), "SELECT id FROM jobs WHERE visible_at <= ? ORDER BY id LIMIT 10 FOR UPDATE SKIP LOCKED");
+        assert_eq!(visible_sql("jobs", 10, false), "SELECT id FROM jobs WHERE visible_at <= ? ORDER BY id LIMIT 10");
+    }
+}
@@ -0,0 +1,198 @@
+//!
+//! Relations between the entities, loaded eagerly with one `IN` query per batch of keys.
+//!
+
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+use akita_core::Rows;
+
+use crate::{AkitaError, AkitaMapper, FromValue, GetFields, GetTableName, ToValue, Value, Wrapper};
+use crate::key::key_fields;
+use crate::manager::build_select_clause;
+
+/// the keys bound by each query of the related records.
+const RELATION_BATCH_SIZE: usize = 500;
+
+/// How the records of an entity refer to the records of another.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Relation {
+    /// the related records hold the key of the record in their `foreign_key` column.
+    HasMany { foreign_key: &'static str },
+    /// the record holds the key of the related one in its `foreign_key` column.
+    BelongsTo { foreign_key: &'static str },
+}
+
+/// An entity with a field of records of `R`, implemented by `#[derive(AkitaTable)]` for the
+/// `#[has_many(foreign_key = "user_id")] orders: Vec<Order>` and `#[belongs_to(foreign_key = "user_id")] user: Option<User>` fields.
+pub trait Related<R> {
+    fn relation() -> Relation;
+
+    /// Set the field with the records related to this one.
+    fn attach(&mut self, related: Vec<R>);
+}
+
+/// the key column of the entity, relations on a composite key are not supported.
+fn relation_key<T: GetTableName + GetFields>() -> Result<String, AkitaError> {
+    let mut keys = key_fields::<T>()?;
+    if keys.len() > 1 {
+        return Err(AkitaError::UnsupportedOperation(format!("Table({}) has a composite key, it can't be related", &T::table_name().name)));
+    }
+    Ok(keys.remove(0).name)
+}
+
+/// attach the records of `R` related to each of the records.
+pub(crate) fn load_related<M, T, R>(mapper: &M, records: &mut [T]) -> Result<(), AkitaError>
+    where
+        M: AkitaMapper + ?Sized,
+        T: GetTableName + GetFields + ToValue + Related<R>,
+        R: GetTableName + GetFields + FromValue + ToValue + Clone,
+{
+    let (local, remote) = match T::relation() {
+        Relation::HasMany { foreign_key } => (relation_key::<T>()?, foreign_key.to_string()),
+        Relation::BelongsTo { foreign_key } => (foreign_key.to_string(), relation_key::<R>()?),
+    };
+    let locals = records.iter().map(|record| match record.to_value().get_obj_value(&local) {
+        None | Some(Value::Nil) => None,
+        Some(value) => Some(value.clone()),
+    }).collect::<Vec<_>>();
+    let mut values = Vec::new();
+    let mut seen = HashSet::new();
+    for value in locals.iter().flatten() {
+        if seen.insert(value.to_string()) {
+            values.push(value.clone());
+        }
+    }
+    let mut related: HashMap<String, Vec<R>> = HashMap::new();
+    for batch in values.chunks(RELATION_BATCH_SIZE) {
+        let placeholders = batch.iter().map(|_| "?").collect::<Vec<_>>().join(", ");
+        let sql = build_select_clause::<R>(&mut Wrapper::new().apply(format!("`{}` in ({})", &remote, placeholders)))?;
+        for record in mapper.exec_raw::<R, _, _>(sql, batch.to_vec())? {
+            let key = record.to_value().get_obj_value(&remote).map(|value| value.to_string()).unwrap_or_default();
+            related.entry(key).or_default().push(record);
+        }
+    }
+    for (record, local) in records.iter_mut().zip(locals) {
+        let related = local.and_then(|local| related.get(&local.to_string()).cloned()).unwrap_or_default();
+        record.attach(related);
+    }
+    Ok(())
+}
+
+/// the parents of the rows of a join, each of the rows with the same key read once,
+/// with the related records read from the rest of the columns of the rows.
+pub(crate) fn group_related<P, C, K, F>(rows: &Rows, mut key_fn: F) -> Result<Vec<P>, AkitaError>
+    where
+        P: FromValue + Related<C>,
+        C: GetTableName + GetFields + FromValue,
+        K: Eq + Hash,
+        F: FnMut(&P) -> K,
+{
+    // a record of the rows a left join matched nothing to has all of these null.
+    let columns = key_fields::<C>().unwrap_or_else(|_| C::fields().into_iter().filter(|field| field.exist).collect());
+    // the rows not read as a parent are kept together, the first one gives the error below.
+    let groups = rows.group_by_key(|row| P::from_value_opt(row).ok().map(|parent| key_fn(&parent)));
+    let mut records = Vec::with_capacity(groups.len());
+    for (_, rows) in groups {
+        let mut record = P::from_value_opt(&rows[0])?;
+        let mut related = Vec::new();
+        for row in rows.iter().filter(|row| columns.iter().any(|column| !matches!(row.get_obj_value(&column.name), None | Some(Value::Nil)))) {
+            related.push(C::from_value_opt(row)?);
+        }
+        record.attach(related);
+        records.push(record);
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod test {
+    use akita_core::{Row, Rows};
+
+    use crate::{AkitaDataError, AkitaError, FieldName, FieldType, FromValue, GetFields, GetTableName, TableName, Value};
+    use super::{group_related, relation_key, Related, Relation};
+
+    struct Member;
+
+    impl GetTableName for Member {
+        fn table_name() -> TableName {
+            TableName::from("t_member")
+        }
+    }
+
+    impl GetFields for Member {
+        fn fields() -> Vec<FieldName> {
+            let key = |name: &str| {
+                let mut key = FieldName::from(name);
+                key.field_type = FieldType::TableId("none".to_string());
+                key
+            };
+            vec![key("org_id"), key("login")]
+        }
+    }
+
+    #[test]
+    fn composite_key_not_related() {
+        assert!(matches!(relation_key::<Member>(), Err(AkitaError::UnsupportedOperation(_))));
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct User {
+        id: i32,
+        orders: Vec<Order>,
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Order {
+        order_id: i32,
+    }
+
+    impl FromValue for User {
+        fn from_value_opt(v: &Value) -> Result<Self, AkitaDataError> {
+            Ok(User { id: v.get_obj("id")?, orders: Vec::new() })
+        }
+    }
+
+    impl Related<Order> for User {
+        fn relation() -> Relation {
+            Relation::HasMany { foreign_key: "user_id" }
+        }
+
+        fn attach(&mut self, related: Vec<Order>) {
+            self.orders = related;
+        }
+    }
+
+    impl GetTableName for Order {
+        fn table_name() -> TableName {
+            TableName::from("t_order")
+        }
+    }
+
+    impl GetFields for Order {
+        fn fields() -> Vec<FieldName> {
+            let mut key = FieldName::from("order_id");
+            key.field_type = FieldType::TableId("none".to_string());
+            vec![key]
+        }
+    }
+
+    impl FromValue for Order {
+        fn from_value_opt(v: &Value) -> Result<Self, AkitaDataError> {
+            Ok(Order { order_id: v.get_obj("order_id")? })
+        }
+    }
+
+    #[test]
+    fn group_joined_rows() {
+        let mut rows = Rows::new();
+        for (id, order_id) in [(1, Value::Int(10)), (2, Value::Nil), (1, Value::Int(11))] {
+            rows.push(Row { columns: vec!["id".to_string(), "order_id".to_string()], data: vec![Value::Int(id), order_id] });
+        }
+        let users = group_related::<User, Order, _, _>(&rows, |user| user.id).unwrap();
+        assert_eq!(users, vec![
+            User { id: 1, orders: vec![Order { order_id: 10 }, Order { order_id: 11 }] },
+            User { id: 2, orders: vec![] },
+        ]);
+    }
+}
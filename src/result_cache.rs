@@ -0,0 +1,259 @@
+//!
+//! Second-level cache of the query results.
+//!
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::{CachePolicy, Params, Rows};
+use crate::cache::{invalidate_backend, read_tables, write_tables};
+use crate::interceptor::{ExecutedStatement, Interceptor, OperationType};
+
+/// Where `CacheInterceptor` and the `#[sql(cache = ...)]` queries keep the rows, e.g. in memory with `QueryCache`
+/// or `LruCacheBackend`, or in Redis. See `AkitaConfig::set_query_cache` to share one between them.
+pub trait CacheBackend: Send + Sync {
+    /// The rows cached for the key if still fresh.
+    fn get(&self, key: &str) -> Option<Rows>;
+
+    /// Cache the rows read from the tables for the ttl.
+    fn put(&self, key: String, tables: Vec<String>, rows: Rows, ttl: Duration);
+
+    /// Drop the rows read from the table, its name possibly qualified by its schema in the reads.
+    fn invalidate_table(&self, table: &str);
+
+    /// Drop the rows of the tables written by the sql, a select writes nothing.
+    fn invalidate_sql(&self, sql: &str) {
+        write_tables(sql).iter().for_each(|table| self.invalidate_table(table));
+    }
+
+    fn clear(&self);
+}
+
+impl fmt::Debug for dyn CacheBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CacheBackend")
+    }
+}
+
+struct LruEntry {
+    rows: Rows,
+    tables: Vec<String>,
+    expire_at: Instant,
+    used: u64,
+}
+
+#[derive(Default)]
+struct LruState {
+    entries: HashMap<String, LruEntry>,
+    /// the keys by their last use
+    order: BTreeMap<u64, String>,
+    tick: u64,
+}
+
+/// Keeps the rows in memory, dropping the expired then the least recently used ones past its capacity.
+pub struct LruCacheBackend {
+    capacity: usize,
+    state: Mutex<LruState>,
+}
+
+impl LruCacheBackend {
+    pub fn new(capacity: usize) -> Self {
+        LruCacheBackend { capacity: capacity.max(1), state: Mutex::new(LruState::default()) }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The number of entries, the expired ones included until they are read or evicted.
+    pub fn len(&self) -> usize {
+        self.state.lock().map(|state| state.entries.len()).unwrap_or_default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl CacheBackend for LruCacheBackend {
+    fn get(&self, key: &str) -> Option<Rows> {
+        let mut state = self.state.lock().ok()?;
+        let state = &mut *state;
+        state.tick += 1;
+        let tick = state.tick;
+        let entry = state.entries.get_mut(key)?;
+        state.order.remove(&entry.used);
+        if entry.expire_at <= Instant::now() {
+            state.entries.remove(key);
+            return None;
+        }
+        entry.used = tick;
+        state.order.insert(tick, key.to_string());
+        Some(entry.rows.clone())
+    }
+
+    fn put(&self, key: String, tables: Vec<String>, rows: Rows, ttl: Duration) {
+        if let Ok(mut state) = self.state.lock() {
+            state.tick += 1;
+            let used = state.tick;
+            if let Some(entry) = state.entries.remove(&key) {
+                state.order.remove(&entry.used);
+            }
+            if state.entries.len() >= self.capacity {
+                let now = Instant::now();
+                let state = &mut *state;
+                let order = &mut state.order;
+                state.entries.retain(|_, entry| {
+                    let fresh = entry.expire_at > now;
+                    if !fresh {
+                        order.remove(&entry.used);
+                    }
+                    fresh
+                });
+            }
+            while state.entries.len() >= self.capacity {
+                let oldest = match state.order.keys().next() {
+                    Some(oldest) => *oldest,
+                    None => break,
+                };
+                if let Some(oldest) = state.order.remove(&oldest) {
+                    state.entries.remove(&oldest);
+                }
+            }
+            state.order.insert(used, key.clone());
+            state.entries.insert(key, LruEntry { rows, tables, expire_at: Instant::now() + ttl, used });
+        }
+    }
+
+    fn invalidate_table(&self, table: &str) {
+        if let Ok(mut state) = self.state.lock() {
+            let state = &mut *state;
+            let qualified = format!(".{}", table.to_lowercase());
+            let order = &mut state.order;
+            state.entries.retain(|_, entry| {
+                let read = entry.tables.iter().any(|name| name.eq_ignore_ascii_case(table) || name.to_lowercase().ends_with(&qualified));
+                if read {
+                    order.remove(&entry.used);
+                }
+                !read
+            });
+        }
+    }
+
+    fn clear(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.entries.clear();
+            state.order.clear();
+        }
+    }
+}
+
+/// Caches the rows of the selects run out of a transaction, keyed by their datasource, sql and params, for the ttl of its policy.
+/// The rows read from a table are dropped on each write to it, and again once the transaction of the write commits.
+/// Add it with `AkitaConfig::add_interceptor`, after the interceptors rewriting the statements.
+///
+/// The writes made out of akita, or by the other processes sharing an in-memory backend's database, are not seen.
+pub struct CacheInterceptor {
+    backend: Arc<dyn CacheBackend>,
+    policy: CachePolicy,
+    ignore_tables: Vec<String>,
+}
+
+impl CacheInterceptor {
+    pub fn new<B: CacheBackend + 'static>(backend: B, ttl: Duration) -> Self {
+        Self::with_backend(Arc::new(backend), ttl)
+    }
+
+    /// A cache on a backend shared with the application, which can clear it.
+    pub fn with_backend(backend: Arc<dyn CacheBackend>, ttl: Duration) -> Self {
+        CacheInterceptor { backend, policy: CachePolicy::new(ttl), ignore_tables: Vec::new() }
+    }
+
+    pub fn set_policy(mut self, policy: CachePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Never cache the reads of these tables, e.g. the ones written by other applications.
+    pub fn set_ignore_tables<S: Into<String>>(mut self, tables: Vec<S>) -> Self {
+        self.ignore_tables = tables.into_iter().map(|table| table.into().to_lowercase()).collect();
+        self
+    }
+
+    pub fn backend(&self) -> &Arc<dyn CacheBackend> {
+        &self.backend
+    }
+
+    fn cacheable(&self, tables: &[String]) -> bool {
+        !tables.is_empty() && !tables.iter().any(|table| {
+            let table = table.to_lowercase();
+            self.ignore_tables.iter().any(|ignored| table == *ignored || table.ends_with(&format!(".{}", ignored)))
+        })
+    }
+}
+
+impl Interceptor for CacheInterceptor {
+    fn after_execute(&self, statement: &ExecutedStatement) {
+        if statement.operation() == OperationType::Select {
+            return;
+        }
+        invalidate_backend(self.backend.clone(), statement.transaction_id, statement.sql);
+    }
+
+    fn cached_rows(&self, datasource: &str, sql: &str, params: &Params) -> Option<Rows> {
+        self.backend.get(&self.policy.datasource_key(datasource, sql, params))
+    }
+
+    fn after_rows(&self, datasource: &str, sql: &str, params: &Params, rows: &Rows) {
+        let tables = read_tables(sql);
+        if self.cacheable(&tables) {
+            self.backend.put(self.policy.datasource_key(datasource, sql, params), tables, rows.clone(), self.policy.ttl);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::time::Duration;
+    use crate::{Interceptors, Params, Row, Rows, Value};
+    use crate::interceptor::StatementContext;
+    use super::{CacheBackend, CacheInterceptor, LruCacheBackend};
+
+    fn context() -> StatementContext {
+        StatementContext { system: "sqlite", peer: None, datasource: "sqlite://primary".to_string(), transaction_id: None, connection_id: None }
+    }
+
+    fn rows(value: i32) -> Rows {
+        Rows { data: vec![Row { columns: vec!["id".to_string()], data: vec![Value::Int(value)] }], count: None }
+    }
+
+    #[test]
+    fn cache_query_results() {
+        let backend = Arc::new(LruCacheBackend::new(2));
+        let mut interceptors = Interceptors::default();
+        interceptors.push(Arc::new(CacheInterceptor::with_backend(backend.clone(), Duration::from_secs(60)).set_ignore_tables(vec!["t_log"])));
+        let mut runs = 0;
+        for _ in 0..2 {
+            let read = interceptors.intercept_rows("SELECT id FROM t_user WHERE id = ?", Params::Vector(vec![Value::Int(1)]), context(), |_, _| {
+                runs += 1;
+                Ok((rows(runs), None))
+            }).unwrap();
+            assert_eq!(read, rows(1));
+        }
+        let orders = StatementContext { datasource: "sqlite://orders".to_string(), ..context() };
+        let read = interceptors.intercept_rows("SELECT id FROM t_user WHERE id = ?", Params::Vector(vec![Value::Int(1)]), orders, |_, _| Ok((rows(2), None))).unwrap();
+        assert_eq!(read, rows(2));
+        interceptors.intercept_rows("SELECT id FROM t_log", Params::Nil, context(), |_, _| Ok((rows(0), None))).unwrap();
+        assert_eq!(backend.len(), 2);
+        interceptors.intercept_counted("UPDATE `t_user` SET name = ?", Params::Nil, context(), |_, _| Ok(((), Some(1)))).unwrap();
+        assert!(backend.is_empty());
+        backend.put("a".to_string(), vec![], rows(1), Duration::from_secs(60));
+        backend.put("b".to_string(), vec![], rows(2), Duration::from_secs(60));
+        backend.get("a");
+        backend.put("c".to_string(), vec![], rows(3), Duration::from_secs(60));
+        assert_eq!((backend.get("a"), backend.get("b"), backend.get("c")), (Some(rows(1)), None, Some(rows(3))));
+    }
+}
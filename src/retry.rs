@@ -0,0 +1,115 @@
+//!
+//! Retries of the statements failing on transient errors.
+//!
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::cache::{read_tables, write_tables};
+use crate::interceptor::{ExecutedStatement, Interceptor, OperationType};
+
+/// Runs the statements failing on a lost connection, a deadlock or a serialization failure again,
+/// waiting a jittered exponential backoff in between. Add it with `AkitaConfig::add_interceptor`.
+///
+/// The retries can be set per operation and per table, the table ones come first.
+/// The statements failing in a transaction are left to the transaction retries, see `#[transactional(retry = 3)]`.
+#[derive(Clone, Debug)]
+pub struct RetryInterceptor {
+    max_retries: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+    operations: HashMap<OperationType, u32>,
+    tables: HashMap<String, u32>,
+}
+
+impl RetryInterceptor {
+    /// Retry up to `max_retries` times, waiting from 50ms up to 2s.
+    pub fn new(max_retries: u32) -> Self {
+        RetryInterceptor {
+            max_retries,
+            base_delay: Duration::from_millis(50),
+            max_delay: Duration::from_secs(2),
+            operations: HashMap::new(),
+            tables: HashMap::new(),
+        }
+    }
+
+    /// The first delay, doubled on each retry up to the max one.
+    pub fn set_backoff(mut self, base_delay: Duration, max_delay: Duration) -> Self {
+        self.base_delay = base_delay;
+        self.max_delay = max_delay.max(base_delay);
+        self
+    }
+
+    /// The retries of an operation, zero not to retry it, e.g. the inserts without an idempotency key.
+    pub fn set_operation_retries(mut self, operation: OperationType, max_retries: u32) -> Self {
+        self.operations.insert(operation, max_retries);
+        self
+    }
+
+    /// The retries of the statements on the table.
+    pub fn set_table_retries<S: Into<String>>(mut self, table: S, max_retries: u32) -> Self {
+        self.tables.insert(table.into().to_lowercase(), max_retries);
+        self
+    }
+
+    fn max_retries(&self, statement: &ExecutedStatement) -> u32 {
+        let table = write_tables(statement.sql).into_iter().chain(read_tables(statement.sql))
+            .find_map(|table| {
+                let table = table.to_lowercase();
+                // `schema.table` matches the retries of the table too.
+                let name = table.rsplit('.').next().unwrap_or_default().to_string();
+                self.tables.get(&table).or_else(|| self.tables.get(&name)).copied()
+            });
+        table.or_else(|| self.operations.get(&statement.operation()).copied()).unwrap_or(self.max_retries)
+    }
+
+    /// the backoff of the attempt, between half and all of the doubled delay.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self.base_delay.saturating_mul(1 << attempt.saturating_sub(1).min(16)).min(self.max_delay);
+        let jitter = (uuid::Uuid::new_v4().as_u128() % 1000) as u32;
+        delay / 2 + delay / 2 * jitter / 1000
+    }
+}
+
+impl Interceptor for RetryInterceptor {
+    fn retry_after(&self, statement: &ExecutedStatement, attempt: u32) -> Option<Duration> {
+        let transient = statement.error.map(|error| error.is_transient()).unwrap_or_default();
+        if transient && attempt <= self.max_retries(statement) {
+            Some(self.backoff(attempt))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::time::Duration;
+    use crate::{AkitaError, Interceptors, OperationType, Params};
+    use super::RetryInterceptor;
+
+    #[test]
+    fn retry_transient_errors() {
+        let retry = RetryInterceptor::new(2).set_backoff(Duration::from_millis(1), Duration::from_millis(4))
+            .set_operation_retries(OperationType::Insert, 0)
+            .set_table_retries("t_account", 4);
+        let mut interceptors = Interceptors::default();
+        interceptors.push(Arc::new(retry));
+        let run = |sql: &str, transaction_id: Option<u64>| {
+            let runs = AtomicU32::new(0);
+            let result: Result<(), AkitaError> = interceptors.intercept(sql, Params::Nil, transaction_id, |sql, _| {
+                runs.fetch_add(1, Ordering::Relaxed);
+                Err(AkitaError::MySQLError(format!("Deadlock found when trying to get lock: {}", sql)))
+            });
+            assert!(result.is_err());
+            runs.load(Ordering::Relaxed)
+        };
+        assert_eq!(run("UPDATE t_user SET name = ?", None), 3);
+        assert_eq!(run("INSERT INTO t_user VALUES (?)", None), 1);
+        assert_eq!(run("UPDATE akita.t_account SET balance = ?", None), 5);
+        assert_eq!(run("UPDATE t_user SET name = ?", Some(1)), 1);
+    }
+}
@@ -0,0 +1,298 @@
+//!
+//! Snapshots of the schema read from the database, and the drift between two of them.
+//!
+
+use std::fmt;
+use serde::{Deserialize, Serialize};
+
+use crate::{Akita, AkitaError, AkitaMapper, IndexDef, Params, TableName, ToValue};
+use crate::ddl::is_sqlite;
+
+/// A column as declared in the database.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ColumnSnapshot {
+    pub name: String,
+    /// the type as the database reports it, lowercase, e.g. `varchar(64)`
+    pub column_type: String,
+    pub nullable: bool,
+    /// the sql of the default value
+    pub default: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TableSnapshot {
+    pub name: String,
+    /// the columns in the order of the table
+    pub columns: Vec<ColumnSnapshot>,
+    /// the indexes by name
+    pub indexes: Vec<IndexDef>,
+}
+
+/// The tables of the database with their columns and indexes, by name.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SchemaSnapshot {
+    pub tables: Vec<TableSnapshot>,
+}
+
+/// A difference between two snapshots, from the old one to the new one.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum SchemaChange {
+    TableAdded { table: String },
+    TableRemoved { table: String },
+    ColumnAdded { table: String, column: ColumnSnapshot },
+    ColumnRemoved { table: String, column: String },
+    /// the type, the nullability or the default of the column changed
+    ColumnChanged { table: String, old: ColumnSnapshot, new: ColumnSnapshot },
+    IndexAdded { table: String, index: IndexDef },
+    IndexRemoved { table: String, index: String },
+    IndexChanged { table: String, old: IndexDef, new: IndexDef },
+}
+
+impl SchemaSnapshot {
+    pub fn to_json(&self) -> Result<String, AkitaError> {
+        serde_json::to_string_pretty(self).map_err(|e| AkitaError::DataError(e.to_string()))
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, AkitaError> {
+        serde_json::from_str(json).map_err(|e| AkitaError::DataError(e.to_string()))
+    }
+
+    pub fn table(&self, name: &str) -> Option<&TableSnapshot> {
+        self.tables.iter().find(|table| table.name.eq_ignore_ascii_case(name))
+    }
+
+    /// The changes from this snapshot to the new one, by table name, then the columns removed, added and changed,
+    /// then the indexes removed, added and changed.
+    pub fn diff(&self, new: &SchemaSnapshot) -> Vec<SchemaChange> {
+        let mut names = self.tables.iter().chain(new.tables.iter()).map(|table| table.name.to_string()).collect::<Vec<_>>();
+        names.sort();
+        names.dedup();
+        let mut changes = Vec::new();
+        for name in names {
+            let table = name.to_string();
+            let (old, new) = match (self.table(&name), new.table(&name)) {
+                (Some(old), Some(new)) => (old, new),
+                (None, _) => {
+                    changes.push(SchemaChange::TableAdded { table });
+                    continue;
+                }
+                (_, None) => {
+                    changes.push(SchemaChange::TableRemoved { table });
+                    continue;
+                }
+            };
+            let column = |table: &TableSnapshot, name: &str| table.columns.iter().find(|column| column.name.eq_ignore_ascii_case(name)).cloned();
+            changes.extend(old.columns.iter().filter(|old| column(new, &old.name).is_none())
+                .map(|old| SchemaChange::ColumnRemoved { table: table.to_string(), column: old.name.to_string() }));
+            changes.extend(new.columns.iter().filter(|new| column(old, &new.name).is_none())
+                .map(|new| SchemaChange::ColumnAdded { table: table.to_string(), column: new.clone() }));
+            changes.extend(new.columns.iter().filter_map(|new| column(old, &new.name).filter(|old| old != new).map(|old| (old, new.clone())))
+                .map(|(old, new)| SchemaChange::ColumnChanged { table: table.to_string(), old, new }));
+            let index = |table: &TableSnapshot, name: &str| table.indexes.iter().find(|index| index.name.eq_ignore_ascii_case(name)).cloned();
+            changes.extend(old.indexes.iter().filter(|old| index(new, &old.name).is_none())
+                .map(|old| SchemaChange::IndexRemoved { table: table.to_string(), index: old.name.to_string() }));
+            changes.extend(new.indexes.iter().filter(|new| index(old, &new.name).is_none())
+                .map(|new| SchemaChange::IndexAdded { table: table.to_string(), index: new.clone() }));
+            changes.extend(new.indexes.iter().filter_map(|new| index(old, &new.name).filter(|old| old != new).map(|old| (old, new.clone())))
+                .map(|(old, new)| SchemaChange::IndexChanged { table: table.to_string(), old, new }));
+        }
+        changes
+    }
+}
+
+impl fmt::Display for ColumnSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.column_type, if self.nullable { "" } else { " not null" })?;
+        if let Some(default) = &self.default {
+            write!(f, " default {}", default)?;
+        }
+        Ok(())
+    }
+}
+
+/// A line of the drift report, e.g. `~ column t_user.email: varchar(64) -> varchar(128) not null`.
+impl fmt::Display for SchemaChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SchemaChange::TableAdded { table } => write!(f, "+ table {}", table),
+            SchemaChange::TableRemoved { table } => write!(f, "- table {}", table),
+            SchemaChange::ColumnAdded { table, column } => write!(f, "+ column {}.{}: {}", table, column.name, column),
+            SchemaChange::ColumnRemoved { table, column } => write!(f, "- column {}.{}", table, column),
+            SchemaChange::ColumnChanged { table, old, new } => write!(f, "~ column {}.{}: {} -> {}", table, new.name, old, new),
+            SchemaChange::IndexAdded { table, index } => write!(f, "+ index {}.{}: {}", table, index.name, index),
+            SchemaChange::IndexRemoved { table, index } => write!(f, "- index {}.{}", table, index),
+            SchemaChange::IndexChanged { table, old, new } => write!(f, "~ index {}.{}: {} -> {}", table, new.name, old, new),
+        }
+    }
+}
+
+impl Akita {
+    /// The tables of the database of the akita with their columns and indexes, read on the primary.
+    /// Stored with `to_json`, e.g. next to the migrations, for `schema_drift`.
+    pub fn schema_snapshot(&self) -> Result<SchemaSnapshot, AkitaError> {
+        let akita = self.force_master();
+        let sql = if is_sqlite(&akita.config().platform()) {
+            "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name"
+        } else {
+            "SELECT TABLE_NAME AS name FROM information_schema.tables WHERE TABLE_SCHEMA = DATABASE() AND TABLE_TYPE = 'BASE TABLE' ORDER BY TABLE_NAME"
+        };
+        let mut tables = Vec::new();
+        for row in akita.exec_iter(sql, Params::Nil)?.iter() {
+            let name = row.get_obj::<String>("name")?;
+            tables.extend(read_table(&akita, &TableName::from(name.as_str()))?);
+        }
+        tables.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(SchemaSnapshot { tables })
+    }
+
+    /// The changes of the schema since the snapshot, e.g. for the CI to fail on the changes no migration explains.
+    pub fn schema_drift(&self, old: &SchemaSnapshot) -> Result<Vec<SchemaChange>, AkitaError> {
+        Ok(old.diff(&self.schema_snapshot()?))
+    }
+
+    /// The indexes of the table by name, the primary key included as `PRIMARY`, read on the primary.
+    pub fn indexes(&self, table: &str) -> Result<Vec<IndexDef>, AkitaError> {
+        read_table(&self.force_master(), &TableName::from(table))?.map(|table| table.indexes)
+            .ok_or_else(|| AkitaError::MissingTable(format!("Table({}) does not exist", table)))
+    }
+
+    /// Whether an index of the table starts with the columns in this order, the one a query on them can use.
+    pub fn has_index(&self, table: &str, columns: &[&str]) -> Result<bool, AkitaError> {
+        Ok(self.indexes(table)?.iter().any(|index| covers(index, columns)))
+    }
+}
+
+/// Assert that an index of the table starts with the columns, see `Akita::has_index`,
+/// e.g. in the tests of the queries relying on it:
+///
+/// ```ignore
+/// assert_index_exists!(akita, "t_user", ["tenant_id", "email"]);
+/// ```
+#[macro_export]
+macro_rules! assert_index_exists {
+    ($akita:expr, $table:expr, [$($column:expr),+ $(,)?]) => {{
+        let columns: &[&str] = &[$($column),+];
+        match $akita.indexes($table) {
+            Ok(indexes) => assert!($crate::covers_any(&indexes, columns), "no index of {} on ({}), the indexes are: {}",
+                $table, columns.join(", "), indexes.iter().map(|index| format!("{} {}", index.name, index)).collect::<Vec<_>>().join(", ")),
+            Err(err) => panic!("the indexes of {} could not be read: {}", $table, err),
+        }
+    }};
+}
+
+#[doc(hidden)]
+pub fn covers_any(indexes: &[IndexDef], columns: &[&str]) -> bool {
+    indexes.iter().any(|index| covers(index, columns))
+}
+
+/// whether the index starts with the columns.
+fn covers(index: &IndexDef, columns: &[&str]) -> bool {
+    !columns.is_empty() && index.columns.len() >= columns.len()
+        && index.columns.iter().zip(columns.iter()).all(|(indexed, column)| indexed.eq_ignore_ascii_case(column))
+}
+
+/// the columns and the indexes of the table in the database, none when there is no such table.
+pub(crate) fn read_table(akita: &Akita, table: &TableName) -> Result<Option<TableSnapshot>, AkitaError> {
+    let mut columns = Vec::new();
+    let mut indexes: Vec<IndexDef> = Vec::new();
+    if is_sqlite(&akita.config().platform()) {
+        let mut keys = Vec::new();
+        for row in akita.exec_iter(format!("PRAGMA table_info({})", table.complete_name()), Params::Nil)?.iter() {
+            let pk = row.get_obj::<i64>("pk")?;
+            if pk > 0 {
+                keys.push((pk, row.get_obj::<String>("name")?));
+            }
+            columns.push(ColumnSnapshot {
+                name: row.get_obj("name")?,
+                column_type: row.get_obj::<String>("type")?.to_lowercase(),
+                nullable: row.get_obj::<i64>("notnull")? == 0,
+                default: row.get_obj("dflt_value")?,
+            });
+        }
+        let mut primary_indexed = false;
+        for row in akita.exec_iter(format!("PRAGMA index_list({})", table.complete_name()), Params::Nil)?.iter() {
+            let name = row.get_obj::<String>("name")?;
+            let info = akita.exec_iter(format!("PRAGMA index_info(`{}`)", name.replace('`', "")), Params::Nil)?;
+            let columns = info.iter().map(|column| column.get_obj::<Option<String>>("name").map(Option::unwrap_or_default)).collect::<Result<Vec<_>, _>>()?;
+            indexes.push(IndexDef { name, columns, unique: row.get_obj::<i64>("unique")? != 0 });
+            primary_indexed |= row.get_obj::<Option<String>>("origin")?.as_deref() == Some("pk");
+        }
+        // the `INTEGER PRIMARY KEY` is the rowid, listed as no index, named as on MySQL.
+        if !primary_indexed && !keys.is_empty() {
+            keys.sort();
+            indexes.push(IndexDef { name: "PRIMARY".to_string(), columns: keys.into_iter().map(|(_, name)| name).collect(), unique: true });
+        }
+    } else {
+        let params = || Params::Vector(vec![table.schema.to_value(), table.name.to_value()]);
+        let sql = "SELECT COLUMN_NAME AS name, COLUMN_TYPE AS column_type, IS_NULLABLE AS nullable, COLUMN_DEFAULT AS column_default \
+            FROM information_schema.columns WHERE TABLE_SCHEMA = COALESCE(?, DATABASE()) AND TABLE_NAME = ? ORDER BY ORDINAL_POSITION";
+        for row in akita.exec_iter(sql, params())?.iter() {
+            columns.push(ColumnSnapshot {
+                name: row.get_obj("name")?,
+                column_type: row.get_obj::<String>("column_type")?.to_lowercase(),
+                nullable: row.get_obj::<String>("nullable")?.eq_ignore_ascii_case("yes"),
+                default: row.get_obj("column_default")?,
+            });
+        }
+        let sql = "SELECT INDEX_NAME AS name, COLUMN_NAME AS column_name, NON_UNIQUE AS non_unique \
+            FROM information_schema.statistics WHERE TABLE_SCHEMA = COALESCE(?, DATABASE()) AND TABLE_NAME = ? ORDER BY INDEX_NAME, SEQ_IN_INDEX";
+        for row in akita.exec_iter(sql, params())?.iter() {
+            let name = row.get_obj::<String>("name")?;
+            let column = row.get_obj::<Option<String>>("column_name")?.unwrap_or_default();
+            match indexes.iter_mut().find(|index| index.name == name) {
+                Some(index) => index.columns.push(column),
+                None => indexes.push(IndexDef { name, columns: vec![column], unique: row.get_obj::<i64>("non_unique")? == 0 }),
+            }
+        }
+    }
+    if columns.is_empty() {
+        return Ok(None);
+    }
+    indexes.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(Some(TableSnapshot { name: table.complete_name(), columns, indexes }))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IndexDef;
+    use super::{covers_any, ColumnSnapshot, SchemaChange, SchemaSnapshot, TableSnapshot};
+
+    fn column(name: &str, column_type: &str, nullable: bool) -> ColumnSnapshot {
+        ColumnSnapshot { name: name.to_string(), column_type: column_type.to_string(), nullable, default: None }
+    }
+
+    #[test]
+    fn drift() {
+        let index = |name: &str, columns: &[&str], unique: bool| IndexDef { name: name.to_string(), columns: columns.iter().map(|c| c.to_string()).collect(), unique };
+        let old = SchemaSnapshot { tables: vec![
+            TableSnapshot { name: "t_user".to_string(), columns: vec![column("id", "bigint", false), column("email", "varchar(64)", true), column("nick", "text", true)], indexes: vec![index("idx_email", &["email"], false)] },
+            TableSnapshot { name: "t_old".to_string(), columns: vec![column("id", "int", false)], indexes: vec![] },
+        ] };
+        let new = SchemaSnapshot { tables: vec![
+            TableSnapshot { name: "t_user".to_string(), columns: vec![column("id", "bigint", false), column("email", "varchar(128)", false), column("tenant_id", "int", true)], indexes: vec![index("idx_email", &["tenant_id", "email"], true)] },
+            TableSnapshot { name: "t_new".to_string(), columns: vec![column("id", "int", false)], indexes: vec![] },
+        ] };
+        let old = SchemaSnapshot::from_json(&old.to_json().unwrap()).unwrap();
+        let changes = old.diff(&new);
+        assert_eq!(changes.iter().map(|change| change.to_string()).collect::<Vec<_>>(), vec![
+            "+ table t_new",
+            "- table t_old",
+            "- column t_user.nick",
+            "+ column t_user.tenant_id: int",
+            "~ column t_user.email: varchar(64) -> varchar(128) not null",
+            "~ index t_user.idx_email: (email) -> unique (tenant_id, email)",
+        ]);
+        assert!(matches!(&changes[0], SchemaChange::TableAdded { table } if table == "t_new"));
+        assert!(new.diff(&new).is_empty());
+    }
+
+    #[test]
+    fn index_prefix() {
+        let indexes = vec![IndexDef { name: "idx_tenant_email".to_string(), columns: vec!["tenant_id".to_string(), "email".to_string()], unique: true }];
+        assert!(covers_any(&indexes, &["tenant_id"]));
+        assert!(covers_any(&indexes, &["TENANT_ID", "email"]));
+        assert!(!covers_any(&indexes, &["email"]));
+        assert!(!covers_any(&indexes, &["tenant_id", "email", "id"]));
+        assert!(!covers_any(&indexes, &[]));
+    }
+}
@@ -0,0 +1,119 @@
+//!
+//! The tables of the entities created and completed at startup.
+//!
+
+use std::sync::Mutex;
+use indexmap::IndexMap;
+use once_cell::sync::Lazy;
+
+use crate::{Akita, AkitaError, AkitaMapper, FieldName, GetFields, GetTableName, IndexDef, Params, Platform, TableDdl, TableName};
+use crate::ddl::{column, create_index};
+use crate::script::split_script;
+use crate::schema::read_table;
+
+static SCHEMA_SYNC: Lazy<SchemaSync> = Lazy::new(SchemaSync::new);
+
+/// What the schema synchronization changes, set with `AkitaConfig::set_schema_sync`.
+/// The columns and the indexes are never dropped nor altered, that is left to the migrations.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum SchemaSyncMode {
+    /// the schema is left as it is
+    #[default]
+    Off,
+    /// the missing tables are created
+    Create,
+    /// the missing tables are created, and the missing columns and indexes added to the existing ones
+    Update,
+}
+
+/// an entity of the synchronization.
+struct SyncedTable {
+    table_name: fn() -> TableName,
+    fields: fn() -> Vec<FieldName>,
+    datasource: fn() -> Option<&'static str>,
+    indexes: fn() -> Vec<IndexDef>,
+    ddl: fn(&Platform) -> Result<String, AkitaError>,
+}
+
+/// The entities whose tables are synchronized with the database by `Akita::sync_schema`,
+/// run by `Akita::new` in the mode of the config.
+pub struct SchemaSync {
+    tables: Mutex<IndexMap<&'static str, SyncedTable>>,
+}
+
+impl SchemaSync {
+    pub fn new() -> Self {
+        Self { tables: Mutex::new(IndexMap::new()) }
+    }
+
+    /// The entities shared by the whole process, synchronized at startup.
+    pub fn global() -> &'static SchemaSync {
+        &SCHEMA_SYNC
+    }
+
+    /// Synchronize the table of the entity, on its datasource. The tables are created in registration order.
+    pub fn register<T: GetTableName + GetFields + 'static>(&self) {
+        if let Ok(mut tables) = self.tables.lock() {
+            tables.insert(std::any::type_name::<T>(), SyncedTable {
+                table_name: T::table_name,
+                fields: T::fields,
+                datasource: T::datasource,
+                indexes: T::indexes,
+                ddl: <T as TableDdl>::ddl,
+            });
+        }
+    }
+
+    /// The names of the tables registered, in registration order.
+    pub fn table_names(&self) -> Vec<String> {
+        self.tables.lock().map(|tables| tables.values().map(|table| (table.table_name)().complete_name()).collect()).unwrap_or_default()
+    }
+}
+
+impl Default for SchemaSync {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Akita {
+    /// Bring the tables of the entities of the global `SchemaSync` to the mode, on the primary.
+    /// Returns the statements run, nothing when the schema was already synchronized.
+    pub fn sync_schema(&self, mode: SchemaSyncMode) -> Result<Vec<String>, AkitaError> {
+        self.sync_schema_with(SchemaSync::global(), mode)
+    }
+
+    /// Same as `sync_schema`, with the entities of the sync.
+    pub fn sync_schema_with(&self, sync: &SchemaSync, mode: SchemaSyncMode) -> Result<Vec<String>, AkitaError> {
+        if mode == SchemaSyncMode::Off {
+            return Ok(Vec::new());
+        }
+        let tables = sync.tables.lock().map_err(|_| AkitaError::DataError("the schema sync is poisoned".to_string()))?;
+        let mut statements = Vec::new();
+        for table in tables.values() {
+            let akita = match (table.datasource)() {
+                Some(name) => self.use_ds(name)?.force_master(),
+                None => self.force_master(),
+            };
+            let platform = akita.config().platform();
+            let table_name = (table.table_name)();
+            let sqls = match read_table(&akita, &table_name)? {
+                None => split_script(&(table.ddl)(&platform)?).into_iter().map(|statement| statement.sql).collect(),
+                Some(_) if mode == SchemaSyncMode::Create => Vec::new(),
+                Some(live) => (table.fields)().into_iter()
+                    .filter(|field| field.exist && !live.columns.iter().any(|column| column.name.eq_ignore_ascii_case(&field.name)))
+                    .map(|field| format!("ALTER TABLE {} ADD COLUMN {}", table_name.complete_name(), column(&platform, &field, false)))
+                    .chain((table.indexes)().iter()
+                        .filter(|index| !live.indexes.iter().any(|live| live.name.eq_ignore_ascii_case(&index.name)))
+                        .map(|index| create_index(&platform, &table_name.complete_name(), index)))
+                    .collect(),
+            };
+            for sql in sqls {
+                akita.exec_result(&sql, Params::Nil)
+                    .map_err(|err| AkitaError::ExcuteSqlError(format!("schema sync of {} failed: {}", table_name.complete_name(), err), sql.to_string()))?;
+                statements.push(sql);
+            }
+        }
+        Ok(statements)
+    }
+}
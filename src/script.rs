@@ -0,0 +1,145 @@
+//!
+//! Sql scripts.
+//!
+
+use crate::{Akita, AkitaError, Params};
+
+/// A statement of a script, with the line it starts on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptStatement {
+    pub line: usize,
+    pub sql: String,
+}
+
+/// Split the script into its statements, on `;` or the delimiter set by a `DELIMITER` line.
+/// The delimiters inside strings, quoted identifiers and comments are skipped,
+/// the comments are dropped except the `/*! ... */` ones read by MySQL.
+pub fn split_script(script: &str) -> Vec<ScriptStatement> {
+    let chars = script.chars().collect::<Vec<_>>();
+    let mut statements = Vec::new();
+    let mut delimiter = vec![';'];
+    let mut current = String::new();
+    let mut start_line = 1;
+    let mut line = 1;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        // the `DELIMITER $$` command takes a line of its own.
+        if i == 0 || chars[i - 1] == '\n' {
+            let end = chars[i..].iter().position(|c| *c == '\n').map(|p| i + p).unwrap_or(chars.len());
+            let text = chars[i..end].iter().collect::<String>();
+            let text = text.trim();
+            if text.len() > 10 && text.get(..10).is_some_and(|command| command.eq_ignore_ascii_case("delimiter ")) {
+                push_statement(&mut statements, &mut current, start_line);
+                delimiter = text[10..].trim().chars().collect();
+                i = end;
+                continue;
+            }
+        }
+        if current.trim().is_empty() {
+            start_line = line;
+        }
+        match c {
+            '\'' | '"' | '`' => {
+                current.push(c);
+                i += 1;
+                while i < chars.len() {
+                    let q = chars[i];
+                    current.push(q);
+                    if q == '\n' {
+                        line += 1;
+                    }
+                    i += 1;
+                    if q == '\\' && c != '`' && i < chars.len() {
+                        current.push(chars[i]);
+                        i += 1;
+                    } else if q == c {
+                        break;
+                    }
+                }
+                continue;
+            }
+            '-' if chars.get(i + 1) == Some(&'-') => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+                continue;
+            }
+            '#' => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+                continue;
+            }
+            '/' if chars.get(i + 1) == Some(&'*') => {
+                let keep = chars.get(i + 2) == Some(&'!');
+                let end = chars[i + 2..].windows(2).position(|w| w == ['*', '/']).map(|p| i + 2 + p + 2).unwrap_or(chars.len());
+                let comment = chars[i..end].iter().collect::<String>();
+                line += comment.matches('\n').count();
+                if keep {
+                    current.push_str(&comment);
+                } else {
+                    current.push(' ');
+                }
+                i = end;
+                continue;
+            }
+            _ => {}
+        }
+        if chars[i..].starts_with(&delimiter) {
+            push_statement(&mut statements, &mut current, start_line);
+            i += delimiter.len();
+            continue;
+        }
+        if c == '\n' {
+            line += 1;
+        }
+        current.push(c);
+        i += 1;
+    }
+    push_statement(&mut statements, &mut current, start_line);
+    statements
+}
+
+fn push_statement(statements: &mut Vec<ScriptStatement>, current: &mut String, line: usize) {
+    let sql = current.trim();
+    if !sql.is_empty() {
+        statements.push(ScriptStatement { line, sql: sql.to_string() });
+    }
+    current.clear();
+}
+
+impl Akita {
+    /// Run the statements of the script in order, stopping at the first failing one,
+    /// the error tells which statement failed and on which line. Returns the number of statements run.
+    pub fn run_script(&self, script: &str) -> Result<usize, AkitaError> {
+        let statements = split_script(script);
+        let mut conn = self.acquire()?;
+        for (index, statement) in statements.iter().enumerate() {
+            conn.execute_drop(&statement.sql, Params::Nil).map_err(|err| {
+                AkitaError::ExcuteSqlError(format!("statement {} at line {} failed: {}", index + 1, statement.line, err), statement.sql.to_string())
+            })?;
+        }
+        Ok(statements.len())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::split_script;
+
+    #[test]
+    fn split_statements() {
+        let script = "-- seed\nINSERT INTO t VALUES ('a;b', \"c\\\";\");\n# comment;\nUPDATE t SET x = 1; /* block; */ DELETE FROM t\n\nDELIMITER $$\nCREATE PROCEDURE p() BEGIN SELECT 1; SELECT 2; END$$\nDELIMITER ;\nSELECT /*!40101 1 */ 2;";
+        let statements = split_script(script);
+        let sqls = statements.iter().map(|s| s.sql.as_str()).collect::<Vec<_>>();
+        assert_eq!(sqls, vec![
+            "INSERT INTO t VALUES ('a;b', \"c\\\";\")",
+            "UPDATE t SET x = 1",
+            "DELETE FROM t",
+            "CREATE PROCEDURE p() BEGIN SELECT 1; SELECT 2; END",
+            "SELECT /*!40101 1 */ 2",
+        ]);
+        assert_eq!(statements.iter().map(|s| s.line).collect::<Vec<_>>(), vec![2, 4, 4, 7, 9]);
+    }
+}
@@ -0,0 +1,101 @@
+//!
+//! Database seeders.
+//!
+
+use std::sync::{Arc, Mutex};
+use indexmap::IndexMap;
+use once_cell::sync::Lazy;
+
+use crate::{Akita, AkitaError, AkitaMapper, Params, ToValue};
+
+/// The table recording the executed seeders.
+pub const SEEDER_TABLE: &str = "akita_seeders";
+
+static SEEDERS: Lazy<SeederRegistry> = Lazy::new(SeederRegistry::new);
+
+/// Fills the database with the records an environment needs, run once by `Akita::seed`.
+pub trait Seeder: Send + Sync {
+    /// the unique name of the seeder, recorded once executed
+    fn name(&self) -> &str;
+
+    fn run(&self, akita: &Akita) -> Result<(), AkitaError>;
+}
+
+/// The seeders known by name.
+pub struct SeederRegistry {
+    seeders: Mutex<IndexMap<String, Arc<dyn Seeder>>>,
+}
+
+impl SeederRegistry {
+    pub fn new() -> Self {
+        Self { seeders: Mutex::new(IndexMap::new()) }
+    }
+
+    /// The registry shared by the whole process, used by `Akita::seed`.
+    pub fn global() -> &'static SeederRegistry {
+        &SEEDERS
+    }
+
+    /// Register the seeder, replacing the one with the same name.
+    pub fn register<S: Seeder + 'static>(&self, seeder: S) {
+        if let Ok(mut seeders) = self.seeders.lock() {
+            seeders.insert(seeder.name().to_string(), Arc::new(seeder));
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Seeder>> {
+        self.seeders.lock().ok()?.get(name).cloned()
+    }
+
+    /// The names of the registered seeders, in registration order.
+    pub fn names(&self) -> Vec<String> {
+        self.seeders.lock().map(|seeders| seeders.keys().cloned().collect()).unwrap_or_default()
+    }
+}
+
+impl Default for SeederRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Akita {
+    /// Run the seeders of the global registry in the given order, skipping the ones already executed.
+    /// Each seeder runs in a transaction along with its record in `akita_seeders`.
+    /// Returns the names of the seeders executed this time.
+    pub fn seed<I, S>(&self, names: I) -> Result<Vec<String>, AkitaError>
+        where
+            I: IntoIterator<Item = S>,
+            S: AsRef<str>,
+    {
+        self.seed_with(SeederRegistry::global(), names)
+    }
+
+    /// Same as `seed`, with the seeders of the registry.
+    pub fn seed_with<I, S>(&self, registry: &SeederRegistry, names: I) -> Result<Vec<String>, AkitaError>
+        where
+            I: IntoIterator<Item = S>,
+            S: AsRef<str>,
+    {
+        let seeders = names.into_iter()
+            .map(|name| registry.get(name.as_ref()).ok_or_else(|| AkitaError::DataError(format!("unknown seeder: {}", name.as_ref()))))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.exec_drop(format!("CREATE TABLE IF NOT EXISTS {} (name VARCHAR(255) NOT NULL PRIMARY KEY, executed_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP)", SEEDER_TABLE), Params::Nil)?;
+        let mut executed = Vec::new();
+        for seeder in seeders {
+            let name = seeder.name().to_string();
+            let params = Params::Vector(vec![name.to_value()]);
+            let count: i64 = self.exec_first(format!("SELECT COUNT(1) FROM {} WHERE name = ?", SEEDER_TABLE), params.clone())?;
+            if count > 0 {
+                continue;
+            }
+            let transaction = self.start_transaction()?;
+            seeder.run(transaction.akita())
+                .map_err(|err| AkitaError::DataError(format!("seeder {} failed: {}", name, err)))?;
+            transaction.exec_iter(format!("INSERT INTO {} (name) VALUES (?)", SEEDER_TABLE), params)?;
+            transaction.commit()?;
+            executed.push(name);
+        }
+        Ok(executed)
+    }
+}
@@ -292,6 +292,16 @@ impl ToSegment for String
     }
 }
 
+impl ToSegment for akita_core::CiString
+{
+    fn to_segment(&self) -> Segment {
+        if self.is_empty() {
+            return Segment::Nil
+        }
+        Segment::Extenssion(format!("'{}'", self.replace(SINGLE_QUOTE, EMPTY)))
+    }
+}
+
 impl ToSegment for NaiveDateTime
 {
     fn to_segment(&self) -> Segment {
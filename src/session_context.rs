@@ -0,0 +1,70 @@
+//!
+//! Session variables of a transaction, for the row-level security enforced by the database.
+//!
+
+use crate::{Akita, AkitaError, AkitaTransaction, Params, ToValue};
+use crate::database::DatabasePlatform;
+use crate::ddl::check_identifier;
+
+/// The temporary table keeping the session variables on SQLite, with the columns `name` and `value`.
+pub const SESSION_TABLE: &str = "akita_session";
+
+impl Akita {
+    /// Set the session variable on the connection of the transaction, e.g. `app.current_user_id`,
+    /// for the views and triggers filtering the rows by it. It is cleared once the outermost transaction ends,
+    /// before the connection goes back to the pool.
+    ///
+    /// MySQL reads it as the user variable ``@`app.current_user_id` ``,
+    /// SQLite with `(SELECT value FROM akita_session WHERE name = 'app.current_user_id')`.
+    pub fn set_session<S: Into<String>, V: ToValue>(&self, name: S, value: V) -> Result<(), AkitaError> {
+        let name = name.into();
+        check_identifier("session variable name", &name)?;
+        let variables = match self.session_variables() {
+            Some(variables) if self.in_transaction() => variables,
+            _ => return Err(AkitaError::UnsupportedOperation(format!("the session variable {} can only be set within a transaction", name))),
+        };
+        let mut conn = self.acquire()?;
+        #[allow(unreachable_patterns)]
+        match &*conn {
+            #[cfg(feature = "akita-mysql")]
+            DatabasePlatform::Mysql(_) => conn.execute_drop(&format!("SET @`{}` = ?", name), Params::Vector(vec![value.to_value()]))?,
+            _ => {
+                conn.execute_drop(&format!("CREATE TEMP TABLE IF NOT EXISTS {} (name TEXT PRIMARY KEY, value)", SESSION_TABLE), Params::Nil)?;
+                conn.execute_drop(&format!("INSERT OR REPLACE INTO temp.{} (name, value) VALUES (?, ?)", SESSION_TABLE), Params::Vector(vec![name.to_value(), value.to_value()]))?;
+            }
+        }
+        if let Ok(mut variables) = variables.lock() {
+            if !variables.contains(&name) {
+                variables.push(name);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl AkitaTransaction<'_> {
+    /// Set the session variable until the outermost transaction ends, see `Akita::set_session`.
+    pub fn set_session<S: Into<String>, V: ToValue>(&self, name: S, value: V) -> Result<(), AkitaError> {
+        self.conn.set_session(name, value)
+    }
+}
+
+/// clear the session variables set on the connection of the ended transaction.
+pub(crate) fn reset_session_with(akita: &Akita, conn: &mut DatabasePlatform) -> Result<(), AkitaError> {
+    let names = match akita.session_variables().and_then(|variables| variables.lock().ok().map(|mut names| std::mem::take(&mut *names))) {
+        Some(names) if !names.is_empty() => names,
+        _ => return Ok(()),
+    };
+    #[allow(unreachable_patterns)]
+    match conn {
+        #[cfg(feature = "akita-mysql")]
+        DatabasePlatform::Mysql(_) => {
+            let variables = names.iter().map(|name| format!("@`{}` = NULL", name)).collect::<Vec<_>>().join(", ");
+            conn.execute_drop(&format!("SET {}", variables), Params::Nil)
+        }
+        _ => {
+            let placeholders = names.iter().map(|_| "?").collect::<Vec<_>>().join(", ");
+            conn.execute_drop(&format!("DELETE FROM temp.{} WHERE name IN ({})", SESSION_TABLE, placeholders), Params::Vector(names.iter().map(|name| name.to_value()).collect()))
+        }
+    }
+}
@@ -0,0 +1,127 @@
+//!
+//! `INTERSECT` and `EXCEPT` of the wrappers, run as `IN` and `NOT IN` subqueries, and their `UNION`.
+//!
+
+use crate::{GetFields, GetTableName, ISegment, Wrapper};
+use crate::key::key_fields;
+use crate::logic_delete::exclude_deleted;
+use crate::segment::MergeSegments;
+use crate::wrapper::SetOperation;
+
+/// turn the set operations of the wrapper into conditions on the records of the entity.
+///
+/// The records are compared by their key, or by the selected columns with `Wrapper::select`.
+/// The subqueries run on any MySQL version, `INTERSECT` and `EXCEPT` only come with 8.0.31.
+pub(crate) fn apply_set_operations<T: GetTableName + GetFields>(wrapper: &mut Wrapper) {
+    if wrapper.set_operations.iter().all(|(operation, _)| is_union(operation)) {
+        return;
+    }
+    // the unions are applied to the query once its conditions are complete, see `apply_unions`.
+    let (unions, operations): (Vec<_>, Vec<_>) = std::mem::take(&mut wrapper.set_operations).into_iter().partition(|(operation, _)| is_union(operation));
+    wrapper.set_operations = unions;
+    let columns = match &wrapper.sql_select {
+        Some(select) if select.trim() != "*" => select.to_owned(),
+        _ => compared_columns::<T>(),
+    };
+    let table = T::table_name().complete_name();
+    for (operation, mut other) in operations {
+        exclude_deleted::<T>(&mut other);
+        let keyword = match operation {
+            SetOperation::Intersect => "IN",
+            SetOperation::Except => "NOT IN",
+            SetOperation::Union | SetOperation::UnionAll => continue,
+        };
+        let condition = format!("({}) {} (SELECT {} FROM {} WHERE {})", &columns, keyword, &columns, &table, other.get_sql_segment().trim());
+        *wrapper = std::mem::replace(wrapper, Wrapper::new()).and_apply_nested(condition);
+    }
+}
+
+/// append the unions of the wrapper to its conditions, before its order and limit which then apply to the union,
+/// the order of the other wrappers left out.
+///
+/// The other wrappers select the columns of this one from the table of the entity, the records not deleted only,
+/// or from their own table as they are.
+pub(crate) fn apply_unions<T: GetTableName + GetFields>(wrapper: &mut Wrapper, columns: &str) {
+    if !wrapper.has_unions() {
+        return;
+    }
+    let mut unions = String::new();
+    for (operation, mut other) in std::mem::take(&mut wrapper.set_operations) {
+        let keyword = match operation {
+            SetOperation::Union => "UNION",
+            SetOperation::UnionAll => "UNION ALL",
+            SetOperation::Intersect | SetOperation::Except => continue,
+        };
+        other.last_sql = None;
+        other.expression.order_by = MergeSegments::default().order_by;
+        let table = match other.table.to_owned() {
+            Some(table) => table,
+            None => {
+                exclude_deleted::<T>(&mut other);
+                T::table_name().complete_name()
+            }
+        };
+        let other_columns = other.sql_select.to_owned().unwrap_or_else(|| columns.to_string());
+        apply_unions::<T>(&mut other, &other_columns);
+        unions.push_str(&format!(" {} SELECT {} FROM {} WHERE {}", keyword, other_columns, table, other.get_sql_segment().trim()));
+    }
+    let mut order_by = std::mem::replace(&mut wrapper.expression.order_by, MergeSegments::default().order_by);
+    wrapper.last_sql = format!("{}{} {}", unions, order_by.get_sql_segment(), wrapper.last_sql.take().unwrap_or_default()).into();
+}
+
+fn is_union(operation: &SetOperation) -> bool {
+    matches!(operation, SetOperation::Union | SetOperation::UnionAll)
+}
+
+/// the key columns, all the columns without a key.
+fn compared_columns<T: GetTableName + GetFields>() -> String {
+    let columns = key_fields::<T>().unwrap_or_else(|_| T::fields().into_iter().filter(|field| field.exist).collect());
+    columns.iter().map(|field| format!("`{}`", field.name)).collect::<Vec<_>>().join(", ")
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{FieldName, FieldType, GetFields, GetTableName, ISegment, TableName, Wrapper};
+    use crate::logic_delete::exclude_deleted;
+    use super::{apply_set_operations, apply_unions};
+
+    struct Order;
+
+    impl GetTableName for Order {
+        fn table_name() -> TableName {
+            TableName::from("t_order")
+        }
+    }
+
+    impl GetFields for Order {
+        fn fields() -> Vec<FieldName> {
+            let mut id = FieldName::from("id");
+            id.field_type = FieldType::TableId("none".to_string());
+            let mut deleted = FieldName::from("deleted");
+            deleted.logic_delete = true;
+            vec![id, FieldName::from("state"), deleted]
+        }
+    }
+
+    #[test]
+    fn set_operations() {
+        let mut wrapper = Wrapper::new().eq("state", 1).intersect(Wrapper::new().gt("amount", 10)).except(Wrapper::new().eq("user_id", 3));
+        exclude_deleted::<Order>(&mut wrapper);
+        assert_eq!(wrapper.get_sql_segment().split_whitespace().collect::<Vec<_>>().join(" "), "( ( ( (state = 1) and (`id`) IN (SELECT `id` FROM t_order WHERE ( (amount > 10) and `deleted` = 0))) \
+            and (`id`) NOT IN (SELECT `id` FROM t_order WHERE ( (user_id = 3) and `deleted` = 0))) and `deleted` = 0)");
+        let mut wrapper = Wrapper::new().select(vec!["state".to_string()]).except(Wrapper::new());
+        apply_set_operations::<Order>(&mut wrapper);
+        assert_eq!(wrapper.get_sql_segment().trim(), "((state) NOT IN (SELECT state FROM t_order WHERE (`deleted` = 0)))");
+    }
+
+    #[test]
+    fn unions() {
+        let mut wrapper = Wrapper::new().eq("state", 1).union(Wrapper::new().gt("amount", 10).order_by(true, vec!["amount"]))
+            .union_all(Wrapper::new().table("t_order_archive").eq("state", 1)).order_by(true, vec!["id"]).last("limit 10");
+        exclude_deleted::<Order>(&mut wrapper);
+        apply_unions::<Order>(&mut wrapper, "`id`, `state`");
+        assert_eq!(wrapper.get_sql_segment().split_whitespace().collect::<Vec<_>>().join(" "), "( (state = 1) and `deleted` = 0) \
+            UNION SELECT `id`, `state` FROM t_order WHERE ( (amount > 10) and `deleted` = 0) \
+            UNION ALL SELECT `id`, `state` FROM t_order_archive WHERE (state = 1) order by id asc limit 10");
+    }
+}
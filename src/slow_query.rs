@@ -0,0 +1,210 @@
+//!
+//! Logging of the slow statements.
+//!
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::{Params, SensitiveColumns, SqlFormat};
+use crate::audit::sql_digest;
+use crate::interceptor::{ExecutedStatement, Interceptor};
+
+type SlowQueryCallback = Box<dyn Fn(&ExecutedStatement) + Send + Sync>;
+
+/// the fingerprints counted at most, the slow statements of new ones are only logged past it.
+const MAX_FINGERPRINTS: usize = 1000;
+
+/// The slow statements of a fingerprint, see `SlowQueryInterceptor::stats`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SlowQueryStats {
+    /// the digest of the fingerprint, to refer to it
+    pub id: String,
+    /// the statement with its literal values as `?`
+    pub fingerprint: String,
+    pub count: u64,
+    pub total: Duration,
+    pub max: Duration,
+    /// the last slow statement of the fingerprint with its params, as it ran
+    pub sql: String,
+    pub params: Params,
+}
+
+/// Logs the statements taking longer than the threshold at WARN, with their params,
+/// affected rows and connection id. Add it with `AkitaConfig::add_interceptor`.
+///
+/// The callback gets the slow statements too, e.g. to push them to an APM.
+pub struct SlowQueryInterceptor {
+    threshold: Duration,
+    callback: Option<SlowQueryCallback>,
+    format: Option<SqlFormat>,
+    stats: Mutex<HashMap<String, SlowQueryStats>>,
+}
+
+impl SlowQueryInterceptor {
+    pub fn new(threshold: Duration) -> Self {
+        SlowQueryInterceptor { threshold, callback: None, format: None, stats: Mutex::new(HashMap::new()) }
+    }
+
+    /// Called with each slow statement, after it is logged.
+    pub fn set_callback<F>(mut self, callback: F) -> Self
+        where
+            F: Fn(&ExecutedStatement) + Send + Sync + 'static,
+    {
+        self.callback = Some(Box::new(callback));
+        self
+    }
+
+    /// Lay the long statements out in the log, the callback still gets them as they ran.
+    pub fn set_format(mut self, format: SqlFormat) -> Self {
+        self.format = format.into();
+        self
+    }
+
+    pub fn threshold(&self) -> Duration {
+        self.threshold
+    }
+
+    /// The slow statements counted by fingerprint, the slowest in total first.
+    /// Keep an `Arc` of the interceptor added to read them.
+    pub fn stats(&self) -> Vec<SlowQueryStats> {
+        let mut stats = self.stats.lock().map(|stats| stats.values().cloned().collect::<Vec<_>>()).unwrap_or_default();
+        stats.sort_by_key(|stats| std::cmp::Reverse(stats.total));
+        stats
+    }
+
+    /// The slow statements of the fingerprint with the id.
+    pub fn stats_of(&self, id: &str) -> Option<SlowQueryStats> {
+        self.stats.lock().ok().and_then(|stats| stats.get(id).cloned())
+    }
+
+    pub fn reset_stats(&self) {
+        if let Ok(mut stats) = self.stats.lock() {
+            stats.clear();
+        }
+    }
+
+    fn count(&self, statement: &ExecutedStatement) {
+        let fingerprint = fingerprint(statement.sql);
+        let id = sql_digest(&fingerprint);
+        if let Ok(mut stats) = self.stats.lock() {
+            if !stats.contains_key(&id) && stats.len() >= MAX_FINGERPRINTS {
+                return;
+            }
+            let stats = stats.entry(id.clone()).or_insert_with(|| SlowQueryStats {
+                id, fingerprint, count: 0, total: Duration::ZERO, max: Duration::ZERO, sql: String::new(), params: Params::Nil,
+            });
+            stats.count += 1;
+            stats.total += statement.elapsed;
+            stats.max = stats.max.max(statement.elapsed);
+            stats.sql = statement.sql.to_string();
+            stats.params = statement.params.clone();
+        }
+    }
+}
+
+/// The statement with its literal strings and numbers as `?` and its whitespace collapsed,
+/// the same for the runs of a statement with other values.
+pub fn fingerprint(sql: &str) -> String {
+    let mut fingerprint = String::with_capacity(sql.len());
+    let mut chars = sql.chars().peekable();
+    let mut previous = ' ';
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' | '"' => {
+                while let Some(next) = chars.next() {
+                    if next == '\\' {
+                        chars.next();
+                    } else if next == c {
+                        if chars.peek() == Some(&c) {
+                            chars.next();
+                        } else {
+                            break;
+                        }
+                    }
+                }
+                fingerprint.push('?');
+            }
+            '$' if chars.peek().is_some_and(|next| next.is_ascii_digit()) => {
+                while chars.peek().is_some_and(|next| next.is_ascii_digit()) {
+                    chars.next();
+                }
+                fingerprint.push('?');
+            }
+            c if c.is_ascii_digit() && !(previous.is_alphanumeric() || previous == '_' || previous == '`') => {
+                while chars.peek().is_some_and(|next| next.is_ascii_digit() || *next == '.') {
+                    chars.next();
+                }
+                fingerprint.push('?');
+            }
+            c if c.is_whitespace() => {
+                if !fingerprint.is_empty() && !fingerprint.ends_with(' ') {
+                    fingerprint.push(' ');
+                }
+            }
+            c => fingerprint.extend(c.to_lowercase()),
+        }
+        previous = c;
+    }
+    fingerprint.trim_end().to_string()
+}
+
+impl Interceptor for SlowQueryInterceptor {
+    fn after_execute(&self, statement: &ExecutedStatement) {
+        if statement.elapsed < self.threshold {
+            return;
+        }
+        self.count(statement);
+        let _sql = SensitiveColumns::global().mask_sql(statement.sql);
+        let _sql = self.format.as_ref().map(|format| format.format(&_sql)).unwrap_or_else(|| _sql.to_string());
+        let _message = format!("Slow SQL ({:?}): {} params: {:?} affected rows: {:?} connection: {:?}{}",
+                               statement.elapsed, _sql, SensitiveColumns::global().mask_params(statement.sql, statement.params), statement.affected_rows, statement.connection_id,
+                               statement.error.map(|error| format!(" error: {}", error)).unwrap_or_default());
+        #[cfg(feature = "akita-logging")]
+        log::warn!("[Akita]: {}", &_message);
+        #[cfg(feature = "akita-tracing")]
+        tracing::warn!("[Akita]: {}", &_message);
+        if let Some(callback) = &self.callback {
+            callback(statement);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use crate::{Interceptors, Params};
+    use crate::interceptor::StatementContext;
+    use super::{fingerprint, SlowQueryInterceptor};
+
+    #[test]
+    fn report_slow_statements() {
+        let slow = Arc::new(Mutex::new(Vec::new()));
+        let reported = slow.clone();
+        let mut interceptors = Interceptors::default();
+        interceptors.push(Arc::new(SlowQueryInterceptor::new(Duration::from_millis(10))
+            .set_callback(move |statement| reported.lock().unwrap().push((statement.sql.to_string(), statement.affected_rows, statement.connection_id)))));
+        interceptors.intercept_counted("UPDATE t_user SET name = ?", Params::Nil, StatementContext { system: "mysql", peer: None, datasource: String::new(), transaction_id: None, connection_id: Some(7) }, |_, _| {
+            std::thread::sleep(Duration::from_millis(15));
+            Ok(((), Some(3)))
+        }).unwrap();
+        interceptors.intercept("SELECT 1", Params::Nil, None, |_, _| Ok(())).unwrap();
+        assert_eq!(*slow.lock().unwrap(), vec![("UPDATE t_user SET name = ?".to_string(), Some(3), Some(7))]);
+    }
+
+    #[test]
+    fn fingerprints() {
+        assert_eq!(fingerprint("SELECT * FROM t_order_2025  WHERE id = 12 AND name = 'it''s'\n AND price > 1.5"), "select * from t_order_2025 where id = ? and name = ? and price > ?");
+        assert_eq!(fingerprint("select * from t_user where id = $1"), fingerprint("SELECT * FROM t_user WHERE id = ?"));
+        let slow = Arc::new(SlowQueryInterceptor::new(Duration::ZERO));
+        let mut interceptors = Interceptors::default();
+        interceptors.push(slow.clone());
+        for id in [1, 2] {
+            interceptors.intercept(&format!("SELECT * FROM t_user WHERE id = {}", id), Params::Nil, None, |_, _| Ok(())).unwrap();
+        }
+        let stats = slow.stats();
+        assert_eq!((stats.len(), stats[0].count, stats[0].sql.as_str()), (1, 2, "SELECT * FROM t_user WHERE id = 2"));
+        assert_eq!(slow.stats_of(&stats[0].id).map(|stats| stats.fingerprint), Some("select * from t_user where id = ?".to_string()));
+    }
+}
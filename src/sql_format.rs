@@ -0,0 +1,178 @@
+//!
+//! Formatting of the logged statements.
+//!
+
+use crate::tenant::{closing_paren, top_level_words, unquoted};
+
+/// The keywords written in upper case.
+const KEYWORDS: [&str; 52] = ["select", "from", "where", "and", "or", "not", "in", "is", "null", "like", "between", "exists",
+    "join", "left", "right", "inner", "outer", "cross", "full", "natural", "on", "using", "as", "distinct", "all",
+    "group", "order", "by", "having", "limit", "offset", "union", "insert", "replace", "into", "values", "update", "set",
+    "delete", "duplicate", "key", "returning", "with", "case", "when", "then", "else", "end", "asc", "desc", "for", "ignore"];
+
+/// The keywords starting a clause on its own line.
+const CLAUSES: [&str; 17] = ["from", "where", "left", "right", "inner", "cross", "full", "natural", "join",
+    "group", "order", "having", "limit", "union", "set", "values", "returning"];
+
+/// How the statements are written to the logs, set with `AkitaConfig::set_log_format` or `SlowQueryInterceptor::set_format`.
+///
+/// The long statements are laid out with a line per clause, and the rows of a `VALUES` list past the first ones
+/// are replaced with their count, so a batch insert of thousands of rows stays readable.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SqlFormat {
+    /// the statements shorter than this stay on one line
+    pub min_length: usize,
+    pub uppercase_keywords: bool,
+    /// the rows of a `VALUES` list written out
+    pub max_rows: usize,
+}
+
+impl Default for SqlFormat {
+    fn default() -> Self {
+        SqlFormat { min_length: 120, uppercase_keywords: true, max_rows: 3 }
+    }
+}
+
+impl SqlFormat {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_min_length(mut self, min_length: usize) -> Self {
+        self.min_length = min_length;
+        self
+    }
+
+    pub fn set_uppercase_keywords(mut self, uppercase_keywords: bool) -> Self {
+        self.uppercase_keywords = uppercase_keywords;
+        self
+    }
+
+    pub fn set_max_rows(mut self, max_rows: usize) -> Self {
+        self.max_rows = max_rows.max(1);
+        self
+    }
+
+    /// The statement as it is logged, its quoted strings and identifiers left as they are.
+    pub fn format(&self, sql: &str) -> String {
+        let sql = self.collapse_rows(&collapse_whitespace(sql));
+        let layout = sql.len() >= self.min_length;
+        if !layout && !self.uppercase_keywords {
+            return sql;
+        }
+        let mut out = String::with_capacity(sql.len() + 32);
+        let mut quote: Option<char> = None;
+        let mut escaped = false;
+        let mut depth = 0usize;
+        // the start of the current word in the output, and the previous word
+        let mut word: Option<usize> = None;
+        let mut previous = String::new();
+        for c in sql.chars().chain(Some(' ')) {
+            match quote {
+                Some(_) if escaped => escaped = false,
+                Some(q) if c == '\\' && q != '`' => escaped = true,
+                Some(q) if c == q => quote = None,
+                Some(_) => {}
+                None if c.is_alphanumeric() || c == '_' || c == '$' || c == '.' => {
+                    word.get_or_insert(out.len());
+                }
+                None => {
+                    if let Some(start) = word.take() {
+                        let lower = out[start..].to_lowercase();
+                        if self.uppercase_keywords && KEYWORDS.contains(&lower.as_str()) {
+                            let upper = out[start..].to_uppercase();
+                            out.replace_range(start.., &upper);
+                        }
+                        let breaks = layout && CLAUSES.contains(&lower.as_str())
+                            && !(lower == "join" && ["left", "right", "inner", "outer", "cross", "full", "natural"].contains(&previous.as_str()))
+                            && !(lower == "values" && c == '(');
+                        if breaks && start > 0 && out[..start].ends_with(' ') {
+                            out.replace_range(start - 1..start, &format!("\n{}", "  ".repeat(depth)));
+                        }
+                        previous = lower;
+                    }
+                    match c {
+                        '\'' | '"' | '`' => quote = Some(c),
+                        '(' => depth += 1,
+                        ')' => depth = depth.saturating_sub(1),
+                        _ => {}
+                    }
+                }
+            }
+            out.push(c);
+        }
+        out.pop();
+        out
+    }
+
+    /// the rows of the `VALUES` list past `max_rows` replaced with their count.
+    fn collapse_rows(&self, sql: &str) -> String {
+        let chars = unquoted(sql);
+        let words = top_level_words(sql, &chars);
+        let values = match words.iter().find(|(_, word)| word.eq_ignore_ascii_case("values")) {
+            Some((offset, word)) => offset + word.len(),
+            None => return sql.to_string(),
+        };
+        let rows_end = words.iter().find(|(offset, _)| *offset > values).map(|(offset, _)| *offset).unwrap_or(sql.len());
+        let mut rows = Vec::new();
+        let mut i = chars.iter().position(|(offset, _)| *offset >= values).unwrap_or(chars.len());
+        while let Some(open) = chars[i..].iter().position(|(_, c)| *c == '(').map(|open| open + i) {
+            if chars[open].0 > rows_end {
+                break;
+            }
+            match closing_paren(&chars, open) {
+                Some(close) => {
+                    rows.push(chars[close].0 + 1);
+                    i = close + 1;
+                }
+                None => break,
+            }
+        }
+        if rows.len() <= self.max_rows {
+            return sql.to_string();
+        }
+        let kept = rows[self.max_rows - 1];
+        let last = rows[rows.len() - 1];
+        format!("{}, /* {} more rows */{}", &sql[..kept], rows.len() - self.max_rows, &sql[last..])
+    }
+}
+
+/// the runs of whitespace out of the quoted strings as a single space.
+fn collapse_whitespace(sql: &str) -> String {
+    let mut out = String::with_capacity(sql.len());
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    for c in sql.trim().chars() {
+        match quote {
+            Some(_) if escaped => escaped = false,
+            Some(q) if c == '\\' && q != '`' => escaped = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c.is_whitespace() => {
+                if !out.ends_with(' ') {
+                    out.push(' ');
+                }
+                continue;
+            }
+            None if c == '\'' || c == '"' || c == '`' => quote = Some(c),
+            None => {}
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::SqlFormat;
+
+    #[test]
+    fn format_sql() {
+        let format = SqlFormat::new().set_min_length(40);
+        assert_eq!(format.format("select id from t_user where id = 1"), "SELECT id FROM t_user WHERE id = 1");
+        assert_eq!(format.format("select u.id, o.no from t_user u\n   left join t_order o on o.uid = u.id where u.name = 'select  from' and o.id in (select id from t_a) order by u.id"),
+                   "SELECT u.id, o.no\nFROM t_user u\nLEFT JOIN t_order o ON o.uid = u.id\nWHERE u.name = 'select  from' AND o.id IN (SELECT id\n  FROM t_a)\nORDER BY u.id");
+        assert_eq!(format.format("insert into t_log (a, b) values (1, 'x'), (2, ')'), (3, 'z'), (4, 'w'), (5, 'v') on duplicate key update b = values(b)"),
+                   "INSERT INTO t_log (a, b)\nVALUES (1, 'x'), (2, ')'), (3, 'z'), /* 2 more rows */ ON DUPLICATE KEY UPDATE b = VALUES(b)");
+    }
+}
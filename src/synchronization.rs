@@ -0,0 +1,88 @@
+//!
+//! Work deferred until the transaction commits.
+//!
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use once_cell::sync::Lazy;
+
+use crate::{Akita, AkitaError};
+
+static SYNCHRONIZATION: Lazy<TransactionSynchronization> = Lazy::new(|| TransactionSynchronization {
+    callbacks: Mutex::new(HashMap::new()),
+});
+
+type Callback = Box<dyn FnOnce() + Send>;
+
+/// The callbacks waiting for their transaction to commit, by transaction id.
+/// The ids are the ones given to the interceptors.
+pub struct TransactionSynchronization {
+    callbacks: Mutex<HashMap<u64, Vec<Callback>>>,
+}
+
+#[allow(unused)]
+impl TransactionSynchronization {
+    pub fn global() -> &'static TransactionSynchronization {
+        &SYNCHRONIZATION
+    }
+
+    /// Run the callback once the transaction is committed, it is dropped if the transaction is rolled back.
+    pub fn after_commit<F: FnOnce() + Send + 'static>(&self, transaction_id: u64, f: F) {
+        if let Ok(mut callbacks) = self.callbacks.lock() {
+            callbacks.entry(transaction_id).or_default().push(Box::new(f));
+        }
+    }
+
+    /// The number of callbacks waiting for the transaction.
+    pub fn pending(&self, transaction_id: u64) -> usize {
+        self.callbacks.lock().map(|callbacks| callbacks.get(&transaction_id).map(Vec::len).unwrap_or_default()).unwrap_or_default()
+    }
+
+    /// run the callbacks of the committed transaction in registration order.
+    pub(crate) fn committed(&self, transaction_id: Option<u64>) {
+        for callback in self.take(transaction_id) {
+            callback();
+        }
+    }
+
+    pub(crate) fn rolled_back(&self, transaction_id: Option<u64>) {
+        self.take(transaction_id);
+    }
+
+    fn take(&self, transaction_id: Option<u64>) -> Vec<Callback> {
+        transaction_id.and_then(|transaction_id| self.callbacks.lock().ok()?.remove(&transaction_id)).unwrap_or_default()
+    }
+}
+
+impl Akita {
+    /// Run the callback once the transaction of this akita commits, or right away out of a transaction.
+    /// Within a nested transaction it waits for the outermost one.
+    pub fn after_commit<F: FnOnce() + Send + 'static>(&self, f: F) -> Result<(), AkitaError> {
+        match self.acquire()?.transaction_id() {
+            Some(transaction_id) => TransactionSynchronization::global().after_commit(transaction_id, f),
+            None => f(),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+    use super::TransactionSynchronization;
+
+    #[test]
+    fn run_after_commit_only() {
+        let synchronization = TransactionSynchronization::global();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        for (transaction_id, name) in [(u64::MAX, "first"), (u64::MAX, "second"), (u64::MAX - 1, "rolled back")] {
+            let events = events.clone();
+            synchronization.after_commit(transaction_id, move || events.lock().unwrap().push(name));
+        }
+        assert_eq!(synchronization.pending(u64::MAX), 2);
+        synchronization.rolled_back(Some(u64::MAX - 1));
+        synchronization.committed(Some(u64::MAX));
+        assert_eq!(*events.lock().unwrap(), vec!["first", "second"]);
+        assert_eq!(synchronization.pending(u64::MAX), 0);
+    }
+}
@@ -0,0 +1,153 @@
+//!
+//! Dynamic table names, e.g. a table per month.
+//!
+
+use std::collections::HashMap;
+
+use crate::{AkitaError, Params};
+use crate::interceptor::Interceptor;
+
+type TableNameHandler = Box<dyn Fn(&str) -> String + Send + Sync>;
+
+/// Replaces the logical tables of the statements with the physical ones given by their handler,
+/// e.g. `t_order` with `t_order_202501`, for the wrapper calls as well as the raw sql.
+/// Add it with `AkitaConfig::add_interceptor`.
+///
+/// The table is replaced wherever it is named out of the quoted strings, `db.t_order` and `t_order.id` included.
+/// A column named as a registered table would be replaced too.
+#[derive(Default)]
+pub struct DynamicTableNameInterceptor {
+    /// the logical tables as given, with their handler, by their lowercase name
+    handlers: HashMap<String, (String, TableNameHandler)>,
+}
+
+impl DynamicTableNameInterceptor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The handler gives the physical table of the logical one on each statement, e.g. from the current month
+    /// or the request context, the logical table itself to leave it.
+    pub fn add_table<S, F>(mut self, table: S, handler: F) -> Self
+        where
+            S: Into<String>,
+            F: Fn(&str) -> String + Send + Sync + 'static,
+    {
+        let table = table.into();
+        self.handlers.insert(table.to_lowercase(), (table, Box::new(handler)));
+        self
+    }
+
+    /// the sql with the registered tables replaced, none when there are none.
+    fn rewrite(&self, sql: &str) -> Option<String> {
+        let mut resolved: HashMap<String, String> = HashMap::new();
+        let mut result = String::with_capacity(sql.len());
+        let mut replaced = false;
+        let mut last = 0;
+        for (start, end) in identifiers(sql) {
+            let name = sql[start..end].to_lowercase();
+            let (table, handler) = match self.handlers.get(&name) {
+                Some(handler) => handler,
+                None => continue,
+            };
+            let physical = resolved.entry(name).or_insert_with(|| handler(table));
+            if physical.as_str() == &sql[start..end] {
+                continue;
+            }
+            result.push_str(&sql[last..start]);
+            result.push_str(physical);
+            last = end;
+            replaced = true;
+        }
+        if !replaced {
+            return None;
+        }
+        result.push_str(&sql[last..]);
+        Some(result)
+    }
+}
+
+impl Interceptor for DynamicTableNameInterceptor {
+    fn before_execute(&self, sql: &mut String, _params: &mut Params) -> Result<(), AkitaError> {
+        if let Some(rewritten) = self.rewrite(sql) {
+            *sql = rewritten;
+        }
+        Ok(())
+    }
+}
+
+/// the ranges of the identifiers of the sql out of the quoted strings, without their backquotes.
+/// The named params like `:name` are left out.
+fn identifiers(sql: &str) -> Vec<(usize, usize)> {
+    let mut identifiers = Vec::new();
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    let mut start: Option<usize> = None;
+    let mut param = false;
+    let mut previous = ' ';
+    for (offset, c) in sql.char_indices() {
+        let word_char = c.is_alphanumeric() || c == '_' || c == '$';
+        match quote {
+            Some(_) if escaped => escaped = false,
+            Some('`') if c == '`' => {
+                if let Some(start) = start.take() {
+                    identifiers.push((start, offset));
+                }
+                quote = None;
+            }
+            Some('`') => {
+                start.get_or_insert(offset);
+            }
+            Some(_) if c == '\\' => escaped = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if word_char => {
+                if start.is_none() && !param {
+                    if previous == ':' || previous == '@' {
+                        param = true;
+                    } else {
+                        start = Some(offset);
+                    }
+                }
+            }
+            None => {
+                if let Some(start) = start.take() {
+                    identifiers.push((start, offset));
+                }
+                param = false;
+                if c == '\'' || c == '"' || c == '`' {
+                    quote = Some(c);
+                }
+            }
+        }
+        previous = c;
+    }
+    if let (Some(start), None) = (start, quote) {
+        identifiers.push((start, sql.len()));
+    }
+    identifiers
+}
+
+#[cfg(test)]
+mod test {
+    use crate::Params;
+    use crate::interceptor::Interceptor;
+    use super::DynamicTableNameInterceptor;
+
+    fn rewrite(sql: &str) -> String {
+        let tables = DynamicTableNameInterceptor::new()
+            .add_table("t_order", |table| format!("{}_202501", table))
+            .add_table("t_log", |table| table.to_string());
+        let mut sql = sql.to_string();
+        tables.before_execute(&mut sql, &mut Params::Nil).unwrap();
+        sql
+    }
+
+    #[test]
+    fn replace_table_names() {
+        assert_eq!(rewrite("select * from t_order where t_order.id = ? and name = 't_order'"), "select * from t_order_202501 where t_order_202501.id = ? and name = 't_order'");
+        assert_eq!(rewrite("UPDATE `T_ORDER` SET state = :t_order WHERE id = ?"), "UPDATE `t_order_202501` SET state = :t_order WHERE id = ?");
+        assert_eq!(rewrite("insert into db.t_order(id) values (?)"), "insert into db.t_order_202501(id) values (?)");
+        assert_eq!(rewrite("select * from t_order_item i join t_log l on l.id = i.id"), "select * from t_order_item i join t_log l on l.id = i.id");
+    }
+}
@@ -0,0 +1,94 @@
+//!
+//! Temporary tables living on one connection.
+//!
+
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{Akita, AkitaError, AkitaMapper, FromValue, GetFields, GetTableName, Params, ToValue};
+use crate::manager::{build_insert_clause_into, build_insert_values};
+
+static TEMP_TABLE_ID: AtomicU64 = AtomicU64::new(1);
+
+/// A temporary table with the columns of `T`, created by `Akita::with_temp_table`.
+/// It is only visible on the connection pinned for it, and dropped along with it.
+pub struct TempTable<T> {
+    akita: Akita,
+    name: String,
+    _marker: PhantomData<T>,
+}
+
+impl Akita {
+    /// Create a temporary table with the columns of the table of `T` and run the closure with it,
+    /// to bulk load the records and join them with the other tables through `TempTable::akita`.
+    /// The connection stays pinned until the closure returns, then the table is dropped, even on an error.
+    pub fn with_temp_table<T, R, F>(&self, f: F) -> Result<R, AkitaError>
+        where
+            T: GetTableName + GetFields,
+            F: FnOnce(&TempTable<T>) -> Result<R, AkitaError>,
+    {
+        if let Some(akita) = self.entity_ds::<T>()? {
+            return akita.with_temp_table::<T, R, F>(f);
+        }
+        let table = T::table_name();
+        if table.complete_name().is_empty() {
+            return Err(AkitaError::MissingTable("Find Error, Missing Table Name !".to_string()))
+        }
+        let temp = TempTable {
+            akita: self.pin()?,
+            name: format!("tmp_{}_{}", &table.name, TEMP_TABLE_ID.fetch_add(1, Ordering::Relaxed)),
+            _marker: PhantomData,
+        };
+        let columns = T::fields().iter().filter(|f| f.exist).map(|c| format!("`{}`", c.name)).collect::<Vec<_>>().join(", ");
+        // the columns keep the types of the table, without its rows, keys or indexes.
+        temp.akita.exec_drop(format!("CREATE TEMPORARY TABLE {} AS SELECT {} FROM {} WHERE 1 = 0", &temp.name, columns, table.complete_name()), Params::Nil)?;
+        f(&temp)
+    }
+}
+
+impl<T> TempTable<T> {
+    /// The name of the table, to use in the queries joining it.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The akita pinned to the connection of the table, the only one seeing it.
+    pub fn akita(&self) -> &Akita {
+        &self.akita
+    }
+}
+
+impl<T: GetFields + ToValue + FromValue> TempTable<T> {
+    /// Insert the records in the table.
+    pub fn save_batch(&self, entities: &[&T]) -> Result<(), AkitaError> {
+        if entities.is_empty() {
+            return Ok(());
+        }
+        let mut conn = self.akita.acquire()?;
+        let sql = build_insert_clause_into(&conn, &self.name, entities);
+        let values = build_insert_values(conn.config(), entities);
+        conn.execute_drop(&sql, values.into())?;
+        Ok(())
+    }
+
+    /// All the records of the table.
+    pub fn list(&self) -> Result<Vec<T>, AkitaError> {
+        let columns = T::fields().iter().filter(|f| f.exist).map(|c| format!("`{}`", c.name)).collect::<Vec<_>>().join(", ");
+        self.akita.exec_raw(format!("SELECT {} FROM {}", columns, &self.name), Params::Nil)
+    }
+}
+
+impl<T> Drop for TempTable<T> {
+    /// Will drop the table before the connection goes back to the pool.
+    fn drop(&mut self) {
+        if let Ok(mut conn) = self.akita.acquire() {
+            #[allow(unreachable_patterns)]
+            let sql = match *conn {
+                #[cfg(feature = "akita-mysql")]
+                crate::database::DatabasePlatform::Mysql(_) => format!("DROP TEMPORARY TABLE IF EXISTS {}", &self.name),
+                _ => format!("DROP TABLE IF EXISTS temp.{}", &self.name),
+            };
+            let _ = conn.execute_drop(&sql, Params::Nil);
+        }
+    }
+}
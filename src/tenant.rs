@@ -0,0 +1,401 @@
+//!
+//! Tenant line, isolating the rows of the tenants sharing the tables.
+//!
+
+use std::collections::HashSet;
+
+use crate::{AkitaError, Params, Value};
+use crate::interceptor::Interceptor;
+
+type TenantProvider = Box<dyn Fn() -> Option<Value> + Send + Sync>;
+
+/// the offset to insert at, the text before the tenant param, whether to bind it, and the text after.
+type Edit = (usize, String, bool, String);
+
+/// The words ending a `WHERE` clause.
+const CLAUSES: [&str; 8] = ["group", "having", "window", "order", "limit", "offset", "for", "lock"];
+
+/// Adds `tenant_id = ?` to the `WHERE` clause of the selects, updates and deletes, and the `tenant_id` column
+/// to the inserts, bound to the tenant of the current call. Add it with `AkitaConfig::add_interceptor`.
+///
+/// The main table of each select is filtered, the ones of the `WITH` queries and of the derived tables
+/// in `FROM (...)` included, not the joined tables nor the subqueries of the conditions.
+/// The inserts naming the tenant column and the `INSERT ... SELECT` are left as they are.
+/// A statement it can not rewrite, e.g. a union of parenthesized selects, fails with `AkitaError::InvalidSQL`.
+pub struct TenantLineInterceptor {
+    column: String,
+    tenant: TenantProvider,
+    ignore_tables: HashSet<String>,
+}
+
+impl TenantLineInterceptor {
+    /// The tenant is given by the resolver on each statement, e.g. read from the request context,
+    /// none to run the statement unchanged, e.g. for the platform admins.
+    pub fn new<F>(tenant: F) -> Self
+        where
+            F: Fn() -> Option<Value> + Send + Sync + 'static,
+    {
+        TenantLineInterceptor {
+            column: "tenant_id".to_string(),
+            tenant: Box::new(tenant),
+            ignore_tables: HashSet::new(),
+        }
+    }
+
+    /// The column holding the tenant, `tenant_id` by default.
+    pub fn set_column<S: Into<String>>(mut self, column: S) -> Self {
+        self.column = column.into();
+        self
+    }
+
+    /// The tables shared by all the tenants, e.g. the dictionaries, their statements are left as they are.
+    pub fn set_ignore_tables<S: Into<String>>(mut self, tables: Vec<S>) -> Self {
+        self.ignore_tables = tables.into_iter().map(|table| table.into().to_lowercase()).collect();
+        self
+    }
+
+    fn is_ignored(&self, table: &str) -> bool {
+        let table = table.replace('`', "").to_lowercase();
+        let name = table.rsplit('.').next().unwrap_or_default();
+        self.ignore_tables.contains(&table) || self.ignore_tables.contains(name)
+    }
+
+    /// the places to add the tenant to, with whether they take the tenant param.
+    fn edits(&self, sql: &str) -> Result<Vec<Edit>, AkitaError> {
+        let chars = unquoted(sql);
+        let end = sql.trim_end().trim_end_matches(';').trim_end().len();
+        self.statement(sql, &chars, end, &HashSet::new())
+    }
+
+    /// the edits of the statement made of the chars up to `end`, the whole sql or one in parentheses,
+    /// `ctes` being the names of the `WITH` queries it can read.
+    fn statement(&self, sql: &str, chars: &[(usize, char)], end: usize, ctes: &HashSet<String>) -> Result<Vec<Edit>, AkitaError> {
+        let words = top_level_words(sql, chars);
+        let statement = words.first().map(|(_, word)| word.to_lowercase()).unwrap_or_default();
+        match statement.as_str() {
+            "with" => self.with(sql, chars, &words, end, ctes),
+            "select" => {
+                // each select of a union is filtered.
+                let mut edits = Vec::new();
+                let mut start = 0;
+                for i in 0..=words.len() {
+                    if i == words.len() || ["union", "intersect", "except"].iter().any(|operator| words[i].1.eq_ignore_ascii_case(operator)) {
+                        let segment_end = words.get(i).map(|(offset, _)| *offset).unwrap_or(end);
+                        let segment = &words[start..i];
+                        if let Some(from) = segment.iter().position(|(_, word)| word.eq_ignore_ascii_case("from")) {
+                            let from = &segment[from..];
+                            match next_char(chars, from[0].0 + from[0].1.len()) {
+                                // a derived table, its own select is filtered.
+                                Some(open) if chars[open].1 == '(' => {
+                                    let close = closing_paren(chars, open).ok_or_else(|| not_rewritable(sql))?;
+                                    edits.extend(self.statement(sql, &chars[open + 1..close], chars[close].0, ctes)?);
+                                }
+                                _ => edits.extend(self.filter(sql, from, segment_end, ctes)),
+                            }
+                        }
+                        start = i + 1;
+                    }
+                }
+                Ok(edits)
+            }
+            "update" | "delete" => {
+                let table = if statement == "update" { 0 } else { words.iter().position(|(_, word)| word.eq_ignore_ascii_case("from")).unwrap_or(words.len()) };
+                Ok(words.get(table..).map(|words| self.filter(sql, words, end, ctes)).unwrap_or_default())
+            }
+            "insert" | "replace" => Ok(self.insert(sql, chars, &words)),
+            // the selects in parentheses, e.g. `(SELECT ...) UNION (SELECT ...)`, are not parsed.
+            _ if chars.iter().find(|(_, c)| !c.is_whitespace()).map(|(_, c)| *c == '(').unwrap_or(false) => Err(not_rewritable(sql)),
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    /// the edits of the `WITH` queries and of the statement reading them, their names are not tables to filter.
+    fn with(&self, sql: &str, chars: &[(usize, char)], words: &[(usize, &str)], end: usize, ctes: &HashSet<String>) -> Result<Vec<Edit>, AkitaError> {
+        let main = words.iter().position(|(_, word)| ["select", "insert", "replace", "update", "delete"].iter().any(|statement| word.eq_ignore_ascii_case(statement)))
+            .ok_or_else(|| not_rewritable(sql))?;
+        let mut ctes = ctes.clone();
+        let mut bodies = Vec::new();
+        for i in 1..main {
+            if !words[i].1.eq_ignore_ascii_case("as") {
+                continue;
+            }
+            ctes.insert(words[i - 1].1.replace('`', "").to_lowercase());
+            let open = next_char(chars, words[i].0 + words[i].1.len()).filter(|open| chars[*open].1 == '(').ok_or_else(|| not_rewritable(sql))?;
+            bodies.push((open, closing_paren(chars, open).ok_or_else(|| not_rewritable(sql))?));
+        }
+        if bodies.is_empty() {
+            return Err(not_rewritable(sql));
+        }
+        // the names are all known first, a recursive query reads its own.
+        let mut edits = Vec::new();
+        for (open, close) in bodies {
+            edits.extend(self.statement(sql, &chars[open + 1..close], chars[close].0, &ctes)?);
+        }
+        let start = chars.iter().position(|(offset, _)| *offset == words[main].0).ok_or_else(|| not_rewritable(sql))?;
+        edits.extend(self.statement(sql, &chars[start..], end, &ctes)?);
+        Ok(edits)
+    }
+
+    /// the condition on the table following the first word, added to its `WHERE` clause.
+    fn filter(&self, sql: &str, words: &[(usize, &str)], end: usize, ctes: &HashSet<String>) -> Vec<Edit> {
+        let (table_end, table) = match words.get(1) {
+            // a derived table, its alias is not a table.
+            Some((offset, word)) if !sql[words[0].0 + words[0].1.len()..*offset].contains('(') => (offset + word.len(), *word),
+            _ => return Vec::new(),
+        };
+        if self.is_ignored(table) || ctes.contains(&table.replace('`', "").to_lowercase()) {
+            return Vec::new();
+        }
+        let alias = match words.get(2).map(|(_, word)| word.to_lowercase()) {
+            Some(word) if word == "as" => words.get(3).map(|(_, word)| *word),
+            Some(word) if !is_keyword(&word) && sql[table_end..words[2].0].trim().is_empty() => Some(words[2].1),
+            _ => None,
+        };
+        let condition = format!("{}.{} = ", alias.unwrap_or(table), &self.column);
+        let clause = |from: usize| words[from..].iter().find(|(_, word)| CLAUSES.iter().any(|clause| word.eq_ignore_ascii_case(clause)))
+            .map(|(offset, _)| *offset).unwrap_or(end);
+        match words.iter().position(|(_, word)| word.eq_ignore_ascii_case("where")) {
+            Some(i) => {
+                let offset = words[i].0 + words[i].1.len();
+                // the conditions there are kept together, an `or` among them can't escape the tenant.
+                let close = sql[..clause(i)].trim_end().len();
+                let start = offset + sql[offset..].len() - sql[offset..].trim_start().len();
+                vec![(start, condition, true, " AND (".to_string()), (close, String::new(), false, ")".to_string())]
+            }
+            None => {
+                let offset = sql[..clause(1)].trim_end().len();
+                vec![(offset, format!(" WHERE {}", condition), true, String::new())]
+            }
+        }
+    }
+
+    /// the tenant column added to the column list, and the tenant to each row of values.
+    fn insert(&self, sql: &str, chars: &[(usize, char)], words: &[(usize, &str)]) -> Vec<Edit> {
+        let into = words.iter().position(|(_, word)| word.eq_ignore_ascii_case("into")).unwrap_or(0);
+        let (table_offset, table) = match words.get(into + 1) {
+            Some(table) => *table,
+            None => return Vec::new(),
+        };
+        let values = words.iter().position(|(_, word)| word.eq_ignore_ascii_case("values") || word.eq_ignore_ascii_case("value"));
+        let columns = chars.iter().position(|(offset, c)| *offset > table_offset && *c == '(');
+        let (values, columns) = match (values, columns) {
+            (Some(values), Some(columns)) if chars[columns].0 < words[values].0 && !self.is_ignored(table) => (values, columns),
+            _ => return Vec::new(),
+        };
+        let close = match closing_paren(chars, columns) {
+            Some(close) => close,
+            None => return Vec::new(),
+        };
+        let named = sql[chars[columns].0 + 1..chars[close].0].split(',')
+            .any(|column| column.trim().trim_matches(['`', '"']).eq_ignore_ascii_case(&self.column));
+        if named {
+            return Vec::new();
+        }
+        let mut edits = vec![(chars[close].0, format!(", {}", &self.column), false, String::new())];
+        // the rows up to an `ON DUPLICATE KEY UPDATE` or a `RETURNING`.
+        let rows_end = words.get(values + 1).map(|(offset, _)| *offset).unwrap_or(sql.len());
+        let mut i = chars.iter().position(|(offset, _)| *offset > words[values].0).unwrap_or(chars.len());
+        while let Some(open) = chars[i..].iter().position(|(_, c)| *c == '(').map(|open| open + i) {
+            if chars[open].0 > rows_end {
+                break;
+            }
+            match closing_paren(chars, open) {
+                Some(close) => {
+                    edits.push((chars[close].0, ", ".to_string(), true, String::new()));
+                    i = close + 1;
+                }
+                None => break,
+            }
+        }
+        edits
+    }
+}
+
+impl Interceptor for TenantLineInterceptor {
+    fn before_execute(&self, sql: &mut String, params: &mut Params) -> Result<(), AkitaError> {
+        let edits = self.edits(sql);
+        if matches!(&edits, Ok(edits) if edits.is_empty()) {
+            return Ok(());
+        }
+        let tenant = match (self.tenant)() {
+            Some(tenant) => tenant,
+            None => return Ok(()),
+        };
+        // never run unfiltered a statement read by a tenant.
+        let edits = edits?;
+        // applied from the end, the offsets and the params before stay right.
+        let mut edits = edits.into_iter().map(|edit| (placeholders_before(sql, edit.0), edit)).collect::<Vec<_>>();
+        edits.sort_by_key(|(_, edit)| std::cmp::Reverse(edit.0));
+        let mut custom = 0;
+        for (placeholders, (offset, mut text, bind, after)) in edits {
+            if bind {
+                match params {
+                    Params::Custom(params) => {
+                        custom += 1;
+                        let name = format!("akita_tenant_{}_", custom);
+                        text.push_str(&format!(":{}", &name));
+                        params.push((name, tenant.clone()));
+                    }
+                    Params::Vector(params) => {
+                        text.push('?');
+                        params.insert(placeholders.min(params.len()), tenant.clone());
+                    }
+                    Params::Nil => {
+                        text.push('?');
+                        *params = Params::Vector(vec![tenant.clone()]);
+                    }
+                }
+            }
+            text.push_str(&after);
+            sql.insert_str(offset, &text);
+        }
+        Ok(())
+    }
+}
+
+fn not_rewritable(sql: &str) -> AkitaError {
+    AkitaError::InvalidSQL(format!("the tenant line can not be added to: {}", sql))
+}
+
+/// the index in the chars of the first one past the offset out of the whitespaces.
+fn next_char(chars: &[(usize, char)], offset: usize) -> Option<usize> {
+    chars.iter().position(|(at, c)| *at >= offset && !c.is_whitespace())
+}
+
+fn is_keyword(word: &str) -> bool {
+    ["where", "set", "join", "inner", "left", "right", "cross", "natural", "straight_join", "on", "using", "union"].contains(&word)
+        || CLAUSES.contains(&word)
+}
+
+/// the chars of the sql out of the quoted strings and identifiers, with their offset.
+pub(crate) fn unquoted(sql: &str) -> Vec<(usize, char)> {
+    let mut chars = Vec::new();
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    for (offset, c) in sql.char_indices() {
+        match quote {
+            Some(_) if escaped => escaped = false,
+            Some(_) if c == '\\' => escaped = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '\'' || c == '"' => quote = Some(c),
+            None => chars.push((offset, c)),
+        }
+    }
+    chars
+}
+
+/// the words of the sql out of the parentheses, with their offset.
+pub(crate) fn top_level_words<'a>(sql: &'a str, chars: &[(usize, char)]) -> Vec<(usize, &'a str)> {
+    let mut words = Vec::new();
+    let mut depth = 0usize;
+    let mut start: Option<usize> = None;
+    let mut last = 0;
+    for (offset, c) in chars {
+        let word_char = c.is_alphanumeric() || *c == '_' || *c == '.' || *c == '`' || *c == '$';
+        // a quoted string in between ends the word too.
+        if word_char && depth == 0 && (start.is_none() || *offset == last + 1) {
+            start.get_or_insert(*offset);
+            last = *offset + c.len_utf8() - 1;
+            continue;
+        }
+        if let Some(start) = start.take() {
+            words.push((start, &sql[start..=last]));
+        }
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            _ if word_char && depth == 0 => {
+                start = Some(*offset);
+                last = *offset + c.len_utf8() - 1;
+            }
+            _ => {}
+        }
+    }
+    if let Some(start) = start {
+        words.push((start, &sql[start..=last]));
+    }
+    words
+}
+
+/// the index in the chars of the parenthesis closing the one at `open`.
+pub(crate) fn closing_paren(chars: &[(usize, char)], open: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (i, (_, c)) in chars.iter().enumerate().skip(open) {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// the `?` and the `$1` of SQLite before the offset.
+pub(crate) fn placeholders_before(sql: &str, offset: usize) -> usize {
+    let chars = unquoted(sql);
+    chars.iter().zip(chars.iter().skip(1).map(|(_, c)| *c).chain(Some(' ')))
+        .filter(|((at, c), next)| *at < offset && (*c == '?' || *c == '$' && next.is_ascii_digit()))
+        .count()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Params, Value};
+    use crate::interceptor::Interceptor;
+    use super::TenantLineInterceptor;
+
+    fn rewrite(sql: &str, params: Vec<Value>) -> (String, Vec<Value>) {
+        let tenant = TenantLineInterceptor::new(|| Some(Value::Bigint(7))).set_ignore_tables(vec!["t_dict"]);
+        let mut sql = sql.to_string();
+        let mut params = Params::Vector(params);
+        tenant.before_execute(&mut sql, &mut params).unwrap();
+        match params {
+            Params::Vector(params) => (sql, params),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn add_tenant_line() {
+        assert_eq!(rewrite("select * from t_user u where u.name = ? or u.id = ? order by id limit 1", vec![Value::Text("a".into()), Value::Int(1)]),
+                   ("select * from t_user u where u.tenant_id = ? AND (u.name = ? or u.id = ?) order by id limit 1".to_string(), vec![Value::Bigint(7), Value::Text("a".into()), Value::Int(1)]));
+        assert_eq!(rewrite("SELECT count(1) FROM `t_user` LEFT JOIN t_order o ON o.uid = t_user.id GROUP BY name", vec![]).0,
+                   "SELECT count(1) FROM `t_user` LEFT JOIN t_order o ON o.uid = t_user.id WHERE `t_user`.tenant_id = ? GROUP BY name");
+        assert_eq!(rewrite("UPDATE t_user SET name = 'a where b' WHERE id = ?", vec![Value::Int(1)]),
+                   ("UPDATE t_user SET name = 'a where b' WHERE t_user.tenant_id = ? AND (id = ?)".to_string(), vec![Value::Bigint(7), Value::Int(1)]));
+        assert_eq!(rewrite("delete from t_user;", vec![]).0, "delete from t_user WHERE t_user.tenant_id = ?;");
+        assert_eq!(rewrite("insert into t_user (name, age) values (?, ?), ('b', ?)", vec![Value::Int(1), Value::Int(2), Value::Int(3)]),
+                   ("insert into t_user (name, age, tenant_id) values (?, ?, ?), ('b', ?, ?)".to_string(), vec![Value::Int(1), Value::Int(2), Value::Bigint(7), Value::Int(3), Value::Bigint(7)]));
+        assert_eq!(rewrite("INSERT INTO t_user (`id`, `name`)\nVALUES\n\t($1, $2)", vec![Value::Nil, Value::Int(1)]).1, vec![Value::Nil, Value::Int(1), Value::Bigint(7)]);
+        assert_eq!(rewrite("insert into t_user (name, tenant_id) values (?, ?)", vec![]).0, "insert into t_user (name, tenant_id) values (?, ?)");
+        assert_eq!(rewrite("select * from t_dict where code = ?", vec![]).0, "select * from t_dict where code = ?");
+        assert_eq!(rewrite("select id from t_a union select id from t_b", vec![]).0, "select id from t_a WHERE t_a.tenant_id = ? union select id from t_b WHERE t_b.tenant_id = ?");
+    }
+
+    #[test]
+    fn tenant_line_in_ctes_and_derived_tables() {
+        assert_eq!(rewrite("WITH RECURSIVE akita_tree AS (SELECT `id` FROM t_cat WHERE `pid` = ? UNION SELECT t.`id` FROM t_cat t INNER JOIN akita_tree ON t.`pid` = akita_tree.`id`) SELECT `id` FROM akita_tree", vec![Value::Int(1)]),
+                   ("WITH RECURSIVE akita_tree AS (SELECT `id` FROM t_cat WHERE t_cat.tenant_id = ? AND (`pid` = ?) UNION SELECT t.`id` FROM t_cat t INNER JOIN akita_tree ON t.`pid` = akita_tree.`id` WHERE t.tenant_id = ?) SELECT `id` FROM akita_tree".to_string(),
+                    vec![Value::Bigint(7), Value::Int(1), Value::Bigint(7)]));
+        assert_eq!(rewrite("WITH paid (uid) AS (select uid from t_order where paid = 1) select * from t_user u where u.id in (select uid from paid) order by id", vec![]).0,
+                   "WITH paid (uid) AS (select uid from t_order where t_order.tenant_id = ? AND (paid = 1)) select * from t_user u where u.tenant_id = ? AND (u.id in (select uid from paid)) order by id");
+        assert_eq!(rewrite("SELECT COUNT(1) AS count FROM (SELECT id FROM t_a WHERE id > ? UNION SELECT id FROM t_b) TOTAL", vec![Value::Int(3)]),
+                   ("SELECT COUNT(1) AS count FROM (SELECT id FROM t_a WHERE t_a.tenant_id = ? AND (id > ?) UNION SELECT id FROM t_b WHERE t_b.tenant_id = ?) TOTAL".to_string(),
+                    vec![Value::Bigint(7), Value::Int(3), Value::Bigint(7)]));
+        assert_eq!(rewrite("select count(*) from (select * from t_user order by id) TOTAL", vec![]).0,
+                   "select count(*) from (select * from t_user WHERE t_user.tenant_id = ? order by id) TOTAL");
+        let tenant = TenantLineInterceptor::new(|| Some(Value::Bigint(7)));
+        let union = "(select id from t_a) union (select id from t_b)";
+        assert!(tenant.before_execute(&mut union.to_string(), &mut Params::Nil).is_err());
+        assert!(tenant.before_execute(&mut "with x as select 1".to_string(), &mut Params::Nil).is_err());
+        let mut sql = union.to_string();
+        assert!(TenantLineInterceptor::new(|| None).before_execute(&mut sql, &mut Params::Nil).is_ok());
+        assert_eq!(sql, union);
+    }
+}
@@ -0,0 +1,189 @@
+//!
+//! Cancellation of the statements running past their timeout.
+//!
+
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use once_cell::sync::Lazy;
+
+use crate::AkitaError;
+
+static WATCHDOG: Lazy<Watchdog> = Lazy::new(Watchdog::start);
+
+thread_local! {
+    /// the deadline of the statements run by the thread, see `within`.
+    static DEADLINE: Cell<Option<Instant>> = const { Cell::new(None) };
+}
+
+type Cancel = Box<dyn FnOnce() + Send>;
+
+#[derive(Default)]
+struct Timers {
+    /// the cancellations by deadline, with the flag of their statement
+    timers: BTreeMap<(Instant, u64), (Arc<AtomicBool>, Cancel)>,
+    next_id: u64,
+}
+
+/// The thread running the cancellations of the statements past their deadline.
+pub(crate) struct Watchdog {
+    timers: Arc<(Mutex<Timers>, Condvar)>,
+}
+
+#[allow(unused)]
+impl Watchdog {
+    fn start() -> Self {
+        let timers = Arc::new((Mutex::new(Timers::default()), Condvar::new()));
+        let shared = timers.clone();
+        std::thread::Builder::new().name("akita-watchdog".to_string()).spawn(move || run(&shared)).ok();
+        Watchdog { timers }
+    }
+
+    pub(crate) fn global() -> &'static Watchdog {
+        &WATCHDOG
+    }
+
+    /// Run the cancellation once the timeout is over, unless the deadline is dropped before.
+    pub(crate) fn arm<F: FnOnce() + Send + 'static>(&'static self, timeout: Duration, cancel: F) -> Deadline {
+        Deadline { watchdog: self, fired: Arc::new(AtomicBool::new(false)), keys: Vec::new() }.then(timeout, cancel)
+    }
+
+    fn schedule(&self, at: Instant, fired: Arc<AtomicBool>, cancel: Cancel) -> (Instant, u64) {
+        let (timers, condvar) = &*self.timers;
+        let mut timers = timers.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        timers.next_id += 1;
+        let key = (at, timers.next_id);
+        timers.timers.insert(key, (fired, cancel));
+        condvar.notify_one();
+        key
+    }
+
+    fn disarm(&self, keys: &[(Instant, u64)]) {
+        let (timers, _) = &*self.timers;
+        let mut timers = timers.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        keys.iter().for_each(|key| {
+            timers.timers.remove(key);
+        });
+    }
+}
+
+fn run(timers: &(Mutex<Timers>, Condvar)) {
+    let (timers, condvar) = timers;
+    let mut guard = timers.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    loop {
+        let now = Instant::now();
+        let next = guard.timers.keys().next().copied();
+        match next {
+            None => guard = condvar.wait(guard).unwrap_or_else(|poisoned| poisoned.into_inner()),
+            Some((at, _)) if at > now => guard = condvar.wait_timeout(guard, at - now).unwrap_or_else(|poisoned| poisoned.into_inner()).0,
+            Some(key) => {
+                if let Some((fired, cancel)) = guard.timers.remove(&key) {
+                    fired.store(true, Ordering::SeqCst);
+                    // the cancellation talks to the server, the other statements are armed meanwhile.
+                    drop(guard);
+                    cancel();
+                    guard = timers.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+                }
+            }
+        }
+    }
+}
+
+/// Run the statements of `f` with the time left until the deadline as their statement timeout,
+/// unless the configured one is shorter. The deadlines nest, the earliest one wins.
+pub(crate) fn within<R, F: FnOnce() -> R>(deadline: Instant, f: F) -> R {
+    let outer = DEADLINE.with(|current| current.replace(Some(current.get().map_or(deadline, |outer| outer.min(deadline)))));
+    let result = f();
+    DEADLINE.with(|current| current.set(outer));
+    result
+}
+
+/// The statement timeout of the next statement: the configured one, shortened to the deadline of `within`.
+#[allow(unused)]
+pub(crate) fn statement_timeout(configured: Option<Duration>) -> Option<Duration> {
+    match DEADLINE.with(Cell::get) {
+        // an expired deadline still arms, the statement is cancelled at once.
+        Some(deadline) => {
+            let left = deadline.saturating_duration_since(Instant::now()).max(Duration::from_millis(1));
+            Some(configured.map_or(left, |configured| configured.min(left)))
+        }
+        None => configured,
+    }
+}
+
+/// The cancellations of a running statement, disarmed when dropped.
+pub(crate) struct Deadline {
+    watchdog: &'static Watchdog,
+    fired: Arc<AtomicBool>,
+    keys: Vec<(Instant, u64)>,
+}
+
+#[allow(unused)]
+impl Deadline {
+    /// Escalate with another cancellation once this timeout is over too.
+    pub(crate) fn then<F: FnOnce() + Send + 'static>(mut self, timeout: Duration, cancel: F) -> Self {
+        let key = self.watchdog.schedule(Instant::now() + timeout, self.fired.clone(), Box::new(cancel));
+        self.keys.push(key);
+        self
+    }
+
+    /// Whether a cancellation ran.
+    pub(crate) fn fired(&self) -> bool {
+        self.fired.load(Ordering::SeqCst)
+    }
+
+    /// the failure of the statement as a timeout once it was cancelled.
+    pub(crate) fn check<R>(self, result: Result<R, AkitaError>, timeout: Duration) -> Result<R, AkitaError> {
+        match result {
+            Err(err) if self.fired() => Err(AkitaError::QueryTimeout(format!("cancelled after {:?}: {}", timeout, err))),
+            result => result,
+        }
+    }
+}
+
+impl Drop for Deadline {
+    fn drop(&mut self) {
+        self.watchdog.disarm(&self.keys);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+    use crate::AkitaError;
+    use super::{statement_timeout, within, Watchdog};
+
+    #[test]
+    fn cancel_past_deadline() {
+        let cancelled = Arc::new(AtomicUsize::new(0));
+        let (query, connection) = (cancelled.clone(), cancelled.clone());
+        let deadline = Watchdog::global().arm(Duration::from_millis(10), move || { query.fetch_add(1, Ordering::SeqCst); })
+            .then(Duration::from_secs(60), move || { connection.fetch_add(10, Ordering::SeqCst); });
+        std::thread::sleep(Duration::from_millis(100));
+        let result: Result<(), _> = deadline.check(Err(AkitaError::MySQLError("Query execution was interrupted".to_string())), Duration::from_millis(10));
+        assert!(matches!(result, Err(AkitaError::QueryTimeout(_))));
+        let disarmed = cancelled.clone();
+        let deadline = Watchdog::global().arm(Duration::from_millis(10), move || { disarmed.fetch_add(100, Ordering::SeqCst); });
+        drop(deadline);
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(cancelled.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn deadline_within() {
+        use std::time::Instant;
+        assert_eq!(statement_timeout(None), None);
+        let deadline = Instant::now() + Duration::from_secs(10);
+        within(deadline, || {
+            assert!(statement_timeout(None).unwrap() <= Duration::from_secs(10));
+            assert_eq!(statement_timeout(Some(Duration::from_secs(1))), Some(Duration::from_secs(1)));
+            within(deadline + Duration::from_secs(60), || assert!(statement_timeout(Some(Duration::from_secs(60))).unwrap() <= Duration::from_secs(10)));
+            within(Instant::now(), || assert_eq!(statement_timeout(None), Some(Duration::from_millis(1))));
+        });
+        assert_eq!(statement_timeout(Some(Duration::from_secs(60))), Some(Duration::from_secs(60)));
+    }
+}
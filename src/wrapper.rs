@@ -16,9 +16,11 @@
 //! ```
 //!
 //!
-use crate::{segment::{MergeSegments, Segment, SqlKeyword, SqlLike, ToSegment, ISegment}, comm::*, AkitaError};
+use std::fmt;
 
-#[derive(Debug, Clone, PartialEq)]
+use crate::{segment::{MergeSegments, Segment, SegmentList, SqlKeyword, SqlLike, ToSegment, ISegment}, comm::*, AkitaError, Cidr, Column, ColumnValue, Flag, Platform};
+
+#[derive(Clone, PartialEq)]
 pub struct Wrapper{
     /// 表名
     pub table: Option<String>,
@@ -37,6 +39,30 @@ pub struct Wrapper{
     /// SQL结束语句
     pub last_sql: Option<String>,
     pub expression: MergeSegments,
+    /// the wrappers intersected with, excepted from or united with the records of this one
+    pub set_operations: Vec<(SetOperation, Wrapper)>,
+    /// the common table expressions of the query, as `WITH`
+    pub ctes: Vec<CommonTableExpression>,
+}
+
+/// How the records matched by another wrapper are combined with the ones of a wrapper.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SetOperation {
+    Intersect,
+    Except,
+    Union,
+    UnionAll,
+}
+
+/// A query named for the conditions of a wrapper, see `Wrapper::with_cte`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CommonTableExpression {
+    pub name: String,
+    /// the names of its columns, the ones of its select without any
+    pub columns: Vec<String>,
+    pub recursive: bool,
+    /// the select of the query, from its `table`
+    pub query: Wrapper,
 }
 
 impl ISegment for Wrapper {
@@ -59,7 +85,7 @@ impl ISegment for Wrapper {
 impl Wrapper{
 
     pub fn new() -> Self {
-        Self { table: None, sql_set: Vec::new(), expression: MergeSegments::default(), param_name_seq: 0, sql_first: None, last_sql: None, sql_comment: None, sql_select: None, fields_set: Vec::new() }
+        Self { table: None, sql_set: Vec::new(), expression: MergeSegments::default(), param_name_seq: 0, sql_first: None, last_sql: None, sql_comment: None, sql_select: None, fields_set: Vec::new(), set_operations: Vec::new(), ctes: Vec::new() }
     }
 
     pub fn set<S: Into<String>, U: ToSegment>(self, column: S, val: U) -> Self {
@@ -147,12 +173,37 @@ impl Wrapper{
     pub fn ge<S: Into<String>, U: ToSegment>(self, column: S, val: U) -> Self { self.add_condition(true, Segment::ColumnField(column.into()), SqlKeyword::GE, val.into()) }
     pub fn lt<S: Into<String>, U: ToSegment>(self, column: S, val: U) -> Self { self.add_condition(true, Segment::ColumnField(column.into()), SqlKeyword::LT, val.into()) }
     pub fn le<S: Into<String>, U: ToSegment>(self, column: S, val: U) -> Self { self.add_condition(true, Segment::ColumnField(column.into()), SqlKeyword::LE, val.into()) }
+    /// The conditions on a `Column<T>` of the derive, only building with values of the type of the column.
+    pub fn eq_t<T, V: ColumnValue<T>>(self, column: Column<T>, val: V) -> Self { self.eq(column, val) }
+    pub fn ne_t<T, V: ColumnValue<T>>(self, column: Column<T>, val: V) -> Self { self.ne(column, val) }
+    pub fn gt_t<T, V: ColumnValue<T>>(self, column: Column<T>, val: V) -> Self { self.gt(column, val) }
+    pub fn ge_t<T, V: ColumnValue<T>>(self, column: Column<T>, val: V) -> Self { self.ge(column, val) }
+    pub fn lt_t<T, V: ColumnValue<T>>(self, column: Column<T>, val: V) -> Self { self.lt(column, val) }
+    pub fn le_t<T, V: ColumnValue<T>>(self, column: Column<T>, val: V) -> Self { self.le(column, val) }
+    pub fn between_t<T, V: ColumnValue<T>>(self, column: Column<T>, val1: V, val2: V) -> Self { self.between(column, val1, val2) }
+    pub fn inside_t<T, V: ColumnValue<T> + Clone>(self, column: Column<T>, vals: Vec<V>) -> Self { self.inside(column, vals) }
     pub fn eq_condition<S: Into<String>, U: ToSegment>(self, condition: bool, column: S, val: U) -> Self { self.add_condition(condition, Segment::ColumnField(column.into()), SqlKeyword::EQ, val.into()) }
     pub fn ne_condition<S: Into<String>, U: ToSegment>(self, condition: bool, column: S, val: U) -> Self { self.add_condition(condition, Segment::ColumnField(column.into()), SqlKeyword::NE, val.into()) }
     pub fn gt_condition<S: Into<String>, U: ToSegment>(self, condition: bool, column: S, val: U) -> Self { self.add_condition(condition, Segment::ColumnField(column.into()), SqlKeyword::GT, val.into()) }
     pub fn ge_condition<S: Into<String>, U: ToSegment>(self, condition: bool, column: S, val: U) -> Self { self.add_condition(condition, Segment::ColumnField(column.into()), SqlKeyword::GE, val.into()) }
     pub fn lt_condition<S: Into<String>, U: ToSegment>(self, condition: bool, column: S, val: U) -> Self { self.add_condition(condition, Segment::ColumnField(column.into()), SqlKeyword::LT, val.into()) }
     pub fn le_condition<S: Into<String>, U: ToSegment>(self, condition: bool, column: S, val: U) -> Self { self.add_condition(condition, Segment::ColumnField(column.into()), SqlKeyword::LE, val.into()) }
+    pub fn has_flag<S: Into<String>, F: Flag>(self, column: S, flag: F) -> Self { self.has_bits_condition(true, column, flag.bit()) }
+    pub fn has_flag_condition<S: Into<String>, F: Flag>(self, condition: bool, column: S, flag: F) -> Self { self.has_bits_condition(condition, column, flag.bit()) }
+    pub fn not_has_flag<S: Into<String>, F: Flag>(self, column: S, flag: F) -> Self { self.not_has_flag_condition(true, column, flag) }
+    pub fn not_has_flag_condition<S: Into<String>, F: Flag>(self, condition: bool, column: S, flag: F) -> Self { let bit = flag.bit(); self.add_condition(condition, Segment::ColumnField(format!("({} & {})", column.into(), bit)), SqlKeyword::EQ, 0u64.into()) }
+    /// all the bits of the mask are set, e.g. `(perms & 5) = 5`, on an integer or a MySQL `SET` column.
+    pub fn has_bits<S: Into<String>>(self, column: S, bits: u64) -> Self { self.has_bits_condition(true, column, bits) }
+    pub fn has_bits_condition<S: Into<String>>(self, condition: bool, column: S, bits: u64) -> Self { self.add_condition(condition, Segment::ColumnField(format!("({} & {})", column.into(), bits)), SqlKeyword::EQ, bits.into()) }
+    /// the address in the column is in the network, e.g. `10.1.0.0/16`, with the `INET6_ATON` of MySQL on the addresses stored as text.
+    /// The addresses of the other family are left out, their bytes compare with the network's regardless.
+    pub fn ip_in_subnet<S: Into<String>>(self, column: S, subnet: Cidr) -> Self { self.ip_in_subnet_condition(true, column, subnet) }
+    pub fn ip_in_subnet_condition<S: Into<String>>(self, condition: bool, column: S, subnet: Cidr) -> Self {
+        let column = format!("INET6_ATON({})", column.into());
+        let len = if subnet.addr().is_ipv4() { 4 } else { 16 };
+        self.do_it(condition, vec![Segment::ColumnField(format!("LENGTH({})", &column)), SqlKeyword::EQ.into(), Segment::ColumnField(len.to_string()), SqlKeyword::AND.into(),
+            Segment::ColumnField(column), SqlKeyword::BETWEEN.into(), Segment::ColumnField(format!("INET6_ATON('{}')", subnet.addr())), SqlKeyword::AND.into(), Segment::ColumnField(format!("INET6_ATON('{}')", subnet.last()))])
+    }
     pub fn first<S: Into<String>>(self, sql: S) -> Self { self.first_condition(true, sql) }
     pub fn last<S: Into<String>>(self, sql: S) -> Self { self.last_condition(true, sql) }
     pub fn first_condition<S: Into<String>>(mut self, condition: bool, sql: S) -> Self { if condition { self.sql_first = format!("{}{}", sql.into(), SPACE ).into(); } self }
@@ -204,6 +255,88 @@ impl Wrapper{
         self.or_inner().add_nested_condition(true, f)
     }
 
+    /// the conditions so far nested, and the column equal to the value, which then holds whatever their `or`.
+    pub(crate) fn and_eq_nested<S: Into<String>, U: ToSegment>(mut self, column: S, val: U) -> Self {
+        if self.expression.normal.segments.is_empty() {
+            return self.eq(column, val);
+        }
+        let mut nested = Wrapper::new();
+        nested.expression.normal = std::mem::replace(&mut self.expression.normal, MergeSegments::default().normal);
+        self.and(|_| nested).eq(column, val)
+    }
+
+    /// the conditions so far nested, and the sql appended to them.
+    pub(crate) fn and_apply_nested<S: Into<String>>(mut self, apply_sql: S) -> Self {
+        if self.expression.normal.segments.is_empty() {
+            return self.apply(apply_sql);
+        }
+        let mut nested = Wrapper::new();
+        nested.expression.normal = std::mem::replace(&mut self.expression.normal, MergeSegments::default().normal);
+        self.and(|_| nested).apply(apply_sql)
+    }
+
+    /// the `select 1` of the table and the conditions of the wrapper, for an `exists`.
+    fn exists_sql(mut self) -> String {
+        let condition = self.expression.get_sql_segment();
+        let mut sql = format!("select 1 from {}", self.table.unwrap_or_default());
+        if !condition.is_empty() {
+            sql.push_str(" where ");
+            sql.push_str(&condition);
+        }
+        sql
+    }
+
+    /// Keep the records also matched by the other wrapper, as `INTERSECT`.
+    pub fn intersect(mut self, other: Wrapper) -> Self {
+        self.set_operations.push((SetOperation::Intersect, other));
+        self
+    }
+
+    /// Leave out the records matched by the other wrapper, as `EXCEPT`.
+    pub fn except(mut self, other: Wrapper) -> Self {
+        self.set_operations.push((SetOperation::Except, other));
+        self
+    }
+
+    /// Add the records matched by the other wrapper, as `UNION`, the duplicates left out.
+    /// The other wrapper selects the same columns, from its own table with `table`. The order and limit
+    /// of this wrapper apply to the union, the ones of the other are ignored. Only the queries run it, not the updates and removes.
+    pub fn union(mut self, other: Wrapper) -> Self {
+        self.set_operations.push((SetOperation::Union, other));
+        self
+    }
+
+    /// Same as `union`, the duplicates kept, as `UNION ALL`.
+    pub fn union_all(mut self, other: Wrapper) -> Self {
+        self.set_operations.push((SetOperation::UnionAll, other));
+        self
+    }
+
+    /// Name the query built by the closure for the conditions of this one, as `WITH name AS (...)`. The query selects
+    /// from its `table`, the columns of its `select`, e.g. the users who ordered lately with
+    /// `.with_cte("recent_orders", |w| w.table("t_order").select(vec!["user_id".to_string()]).gt("created_at", since)).apply("id in (select user_id from recent_orders)")`.
+    /// Only the queries run it, not the updates and removes.
+    pub fn with_cte<S: Into<String>, F: FnOnce(Self) -> Self>(self, name: S, f: F) -> Self {
+        self.add_cte(name.into(), Vec::new(), false, f)
+    }
+
+    /// Same as `with_cte`, the query referring to itself, as `WITH RECURSIVE name (columns) AS (...)`: the rows of its select,
+    /// then the ones of its `union_all` joined with the rows found so far, e.g. an org tree from its root with
+    /// `|w| w.table("t_org").select(cols).eq("id", 1).union_all(Wrapper::new().table("t_org o, org_tree t").select(o_cols).eq_column("o.parent_id", "t.id"))`.
+    pub fn with_recursive_cte<S: Into<String>, F: FnOnce(Self) -> Self>(self, name: S, columns: Vec<String>, f: F) -> Self {
+        self.add_cte(name.into(), columns, true, f)
+    }
+
+    fn add_cte<F: FnOnce(Self) -> Self>(mut self, name: String, columns: Vec<String>, recursive: bool, f: F) -> Self {
+        self.ctes.push(CommonTableExpression { name, columns, recursive, query: f(Self::new()) });
+        self
+    }
+
+    /// Whether the wrapper has a `union` or `union_all`, its query then a union.
+    pub fn has_unions(&self) -> bool {
+        self.set_operations.iter().any(|(operation, _)| matches!(operation, SetOperation::Union | SetOperation::UnionAll))
+    }
+
     fn add_nested_condition<F: FnOnce(Self) -> Self>(self, condition: bool, f: F) -> Self {
         if condition {
             let instance = f(Self::new());
@@ -232,6 +365,14 @@ impl Wrapper{
     pub fn not_exists_condition<S: Into<String>>(self, condition: bool, not_exists_sql: S) -> Self  { self.not_condition(condition).exists_condition(condition, not_exists_sql) }
     pub fn exists<S: Into<String>>(self, exists_sql: S) -> Self { self.do_it(true, vec![SqlKeyword::EXISTS.into(), Segment::Extenssion(format!("({})", exists_sql.into()))]) }
     pub fn exists_condition<S: Into<String>>(self, condition: bool, exists_sql: S) -> Self { self.do_it(condition, vec![SqlKeyword::EXISTS.into(), Segment::Extenssion(format!("({})", exists_sql.into()))]) }
+    /// `exists (select 1 ..)` of the wrapper built by the closure, correlated with `eq_column`,
+    /// e.g. `exists_with(|w| w.table("t_order o").eq_column("o.user_id", "t_user.id").gt("o.amount", 100))`.
+    pub fn exists_with<F: FnOnce(Self) -> Self>(self, f: F) -> Self { self.exists_condition(true, f(Self::new()).exists_sql()) }
+    pub fn exists_with_condition<F: FnOnce(Self) -> Self>(self, condition: bool, f: F) -> Self { if condition { self.exists_with(f) } else { self } }
+    pub fn not_exists_with<F: FnOnce(Self) -> Self>(self, f: F) -> Self { self.not().exists_with(f) }
+    pub fn not_exists_with_condition<F: FnOnce(Self) -> Self>(self, condition: bool, f: F) -> Self { if condition { self.not_exists_with(f) } else { self } }
+    /// The column equal to another one, e.g. of the outer query in a subquery.
+    pub fn eq_column<S: Into<String>, C: Into<String>>(self, column: S, other: C) -> Self { self.add_condition(true, Segment::ColumnField(column.into()), SqlKeyword::EQ, Segment::ColumnField(other.into())) }
     pub fn in_sql<S: Into<String>, U: Into<String>>(self, column: S, in_val: U) -> Self { self.do_it(true, vec![column.into().into() ,SqlKeyword::IN.into(), Segment::Extenssion(format!("({})", in_val.into()))]) }
     pub fn in_sql_condition<S: Into<String>, U: Into<String>>(self, condition: bool, column: S, in_val: U) -> Self { self.do_it(condition, vec![column.into().into() ,SqlKeyword::IN.into(), Segment::Extenssion(format!("({})", in_val.into()))]) }
     pub fn group_by<S: Into<String> + Clone>(self, columns: Vec<S>) -> Self { let cols: Vec<String> = columns.iter().map(|col|col.to_owned().into()).collect::<Vec<String>>();if columns.is_empty() { self } else { self.do_it(true, vec![SqlKeyword::GROUP_BY.into(), Segment::ColumnField(cols.join(COMMA))]) } }
@@ -247,6 +388,174 @@ impl Wrapper{
 }
 
 
+impl Wrapper {
+    /// The statement of the wrapper laid out on several lines, for reading it while debugging.
+    /// It starts with a comment saying it is not to be run: the values are inlined as they are rendered,
+    /// with their line breaks escaped, and the table is the one of the wrapper or `<table>`.
+    pub fn pretty(&self, platform: &Platform) -> String {
+        let table = self.table.as_ref().map(|table| quote_identifier(platform, table)).unwrap_or_else(|| "<table>".to_string());
+        let mut lines = vec!["-- akita debug output, values inlined, not executable".to_string()];
+        lines.extend(self.sql_comment.iter().map(|comment| format!("-- {}", escape(comment))));
+        if self.sql_set.is_empty() {
+            lines.push(format!("SELECT {}", self.sql_select.as_deref().unwrap_or("*")));
+            lines.push(format!("FROM {}", table));
+        } else {
+            lines.push(format!("UPDATE {}", table));
+            lines.push(format!("SET {}", self.sql_set.iter().map(|set| escape(set)).collect::<Vec<_>>().join(",\n    ")));
+        }
+        if let Some(first) = &self.sql_first {
+            lines.push(escape(first.trim()));
+        }
+        if let Some(condition) = self.condition() {
+            let mut text = String::new();
+            condition.pretty(0, &mut text);
+            lines.push(format!("WHERE {}", text));
+        }
+        let clauses = [("GROUP BY", &self.expression.group_by, COMMA), ("HAVING", &self.expression.having, SPACE), ("ORDER BY", &self.expression.order_by, SPACE)];
+        for (keyword, list, separator) in clauses.iter() {
+            if let Some(clause) = clause(list, separator) {
+                lines.push(format!("{} {}", keyword, escape(&clause)));
+            }
+        }
+        if let Some(last) = &self.last_sql {
+            lines.push(escape(last.trim()));
+        }
+        lines.join("\n")
+    }
+
+    /// the conditions of the `WHERE`, as a tree of the `and` and the `or`.
+    fn condition(&self) -> Option<Condition> {
+        Condition::of(&self.expression.normal.segments)
+    }
+}
+
+impl fmt::Debug for Wrapper {
+    /// The clauses of the wrapper with its conditions as a tree, `{:#?}` to lay them out.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Wrapper")
+            .field("table", &self.table)
+            .field("select", &self.sql_select)
+            .field("set", &self.sql_set)
+            .field("first", &self.sql_first)
+            .field("condition", &self.condition())
+            .field("group_by", &clause(&self.expression.group_by, COMMA))
+            .field("having", &clause(&self.expression.having, SPACE))
+            .field("order_by", &clause(&self.expression.order_by, SPACE))
+            .field("last", &self.last_sql)
+            .field("comment", &self.sql_comment)
+            .field("set_operations", &self.set_operations)
+            .field("ctes", &self.ctes)
+            .finish()
+    }
+}
+
+/// A condition of a wrapper, the `and` binding tighter than the `or` as in sql.
+enum Condition {
+    Term(String),
+    And(Vec<Condition>),
+    Or(Vec<Condition>),
+}
+
+impl Condition {
+    fn of(segments: &[Segment]) -> Option<Condition> {
+        let mut ors = Vec::new();
+        let mut ands = Vec::new();
+        let mut term = Vec::new();
+        for segment in segments {
+            match segment {
+                Segment::Keyword(SqlKeyword::AND) => ands.extend(Condition::term(&mut term)),
+                Segment::Keyword(SqlKeyword::OR) => {
+                    ands.extend(Condition::term(&mut term));
+                    ors.extend(Condition::group(std::mem::take(&mut ands), Condition::And));
+                }
+                segment => term.push(segment.to_owned()),
+            }
+        }
+        ands.extend(Condition::term(&mut term));
+        ors.extend(Condition::group(ands, Condition::And));
+        Condition::group(ors, Condition::Or)
+    }
+
+    fn term(segments: &mut Vec<Segment>) -> Option<Condition> {
+        let segments = std::mem::take(segments);
+        match segments.as_slice() {
+            [Segment::Wrapper(wrapper)] => wrapper.condition(),
+            _ => {
+                let text = segments.into_iter().map(|mut segment| segment.get_sql_segment().trim().to_string())
+                    .filter(|text| !text.is_empty()).collect::<Vec<_>>().join(SPACE);
+                if text.is_empty() { None } else { Some(Condition::Term(text)) }
+            }
+        }
+    }
+
+    fn group(mut conditions: Vec<Condition>, group: fn(Vec<Condition>) -> Condition) -> Option<Condition> {
+        match conditions.len() {
+            0 => None,
+            1 => conditions.pop(),
+            _ => Some(group(conditions)),
+        }
+    }
+
+    fn pretty(&self, depth: usize, text: &mut String) {
+        let (conditions, keyword) = match self {
+            Condition::Term(term) => return text.push_str(&escape(term)),
+            Condition::And(conditions) => (conditions, "AND"),
+            Condition::Or(conditions) => (conditions, "OR"),
+        };
+        let indent = "    ".repeat(depth);
+        for (i, condition) in conditions.iter().enumerate() {
+            if i > 0 {
+                text.push_str(&format!("\n{}  {} ", indent, keyword));
+            }
+            match condition {
+                Condition::Term(_) => condition.pretty(depth, text),
+                _ => {
+                    text.push_str(&format!("(\n{}    ", indent));
+                    condition.pretty(depth + 1, text);
+                    text.push_str(&format!("\n{})", indent));
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Condition::Term(term) => fmt::Debug::fmt(term, f),
+            Condition::And(conditions) => {
+                f.write_str("And ")?;
+                f.debug_list().entries(conditions).finish()
+            }
+            Condition::Or(conditions) => {
+                f.write_str("Or ")?;
+                f.debug_list().entries(conditions).finish()
+            }
+        }
+    }
+}
+
+/// the sql of a group by, having or order by list, without its keyword.
+fn clause(list: &SegmentList, separator: &str) -> Option<String> {
+    let text = list.segments.iter().cloned().map(|mut segment| segment.get_sql_segment().trim().to_string())
+        .filter(|text| !text.is_empty()).collect::<Vec<_>>().join(separator);
+    if text.is_empty() { None } else { Some(text) }
+}
+
+fn quote_identifier(platform: &Platform, identifier: &str) -> String {
+    #[allow(unreachable_patterns)]
+    match platform {
+        #[cfg(feature = "akita-mysql")]
+        Platform::Mysql => format!("`{}`", identifier.replace('`', "")),
+        _ => format!("\"{}\"", identifier.replace('"', "")),
+    }
+}
+
+/// the line breaks of a value escaped, they can't break the layout nor end the comment.
+fn escape(text: &str) -> String {
+    text.replace('\r', "\\r").replace('\n', "\\n")
+}
+
 #[test]
 #[allow(unused)]
 fn basic_test() {
@@ -255,4 +564,46 @@ fn basic_test() {
     let mut wrapper = Wrapper::new().set_sql("a='b'").eq("a", "bn").last("limit 1");
         //.not_in("vecs", vec!["a","f","g"]);
     println!("{}", wrapper.get_set_sql().unwrap_or_default());
-}
\ No newline at end of file
+}
+#[test]
+fn flag_test() {
+    #[derive(Clone, Copy, PartialEq, Eq, Hash)]
+    enum Perm { Read, Write, Admin }
+    impl Flag for Perm {
+        const FLAGS: &'static [Self] = &[Perm::Read, Perm::Write, Perm::Admin];
+        fn name(&self) -> &'static str { match self { Perm::Read => "read", Perm::Write => "write", Perm::Admin => "admin" } }
+    }
+    let mut wrapper = Wrapper::new().has_flag("perms", Perm::Admin).not_has_flag("perms", Perm::Write).has_bits("perms", 3);
+    assert_eq!(wrapper.get_sql_segment().trim(), "((perms & 4) = 4 and (perms & 2) = 0 and (perms & 3) = 3)");
+}
+
+#[test]
+fn subnet_test() {
+    let subnet: Cidr = "10.1.2.3/16".parse().unwrap();
+    assert!(subnet.contains(&"10.1.200.1".parse().unwrap()));
+    assert!(!subnet.contains(&"10.2.0.1".parse().unwrap()));
+    let mut wrapper = Wrapper::new().ip_in_subnet("client_ip", subnet);
+    assert_eq!(wrapper.get_sql_segment().trim(), "(LENGTH(INET6_ATON(client_ip)) = 4 and INET6_ATON(client_ip) between INET6_ATON('10.1.0.0') and INET6_ATON('10.1.255.255'))");
+    let subnet: Cidr = "fd00::/8".parse().unwrap();
+    let mut wrapper = Wrapper::new().ip_in_subnet("client_ip", subnet);
+    assert_eq!(wrapper.get_sql_segment().trim(), "(LENGTH(INET6_ATON(client_ip)) = 16 and INET6_ATON(client_ip) between INET6_ATON('fd00::') and INET6_ATON('fdff:ffff:ffff:ffff:ffff:ffff:ffff:ffff'))");
+}
+
+#[test]
+fn exists_test() {
+    let mut wrapper = Wrapper::new().eq("u.status", 1)
+        .exists_with(|w| w.table("t_order o").eq_column("o.user_id", "u.id").gt("o.amount", 100))
+        .not_exists_with(|w| w.table("t_ban b").eq_column("b.user_id", "u.id"))
+        .exists("select 1 from t_vip v where v.user_id = u.id");
+    assert_eq!(wrapper.get_sql_segment().trim(), "(u.status = 1 and exists (select 1 from t_order o where (o.user_id = u.id and o.amount > 100)) \
+        and not exists (select 1 from t_ban b where (b.user_id = u.id)) and exists (select 1 from t_vip v where v.user_id = u.id))");
+}
+
+#[test]
+fn pretty_test() {
+    let wrapper = Wrapper::new().table("t_user").eq("name", "Jack\n").gt("age", 18)
+        .and(|wrapper| wrapper.eq("level", 1).or_direct().eq("vip", true)).desc_by(vec!["id"]);
+    let platform = Platform::Unsupported("none".to_string());
+    assert_eq!(wrapper.pretty(&platform), "-- akita debug output, values inlined, not executable\nSELECT *\nFROM \"t_user\"\nWHERE name = 'Jack\\n'\n  AND age > 18\n  AND (\n    level = 1\n      OR vip = 1\n)\nORDER BY id desc");
+    assert_eq!(format!("{:?}", wrapper.condition()), r#"Some(And ["name = 'Jack\n'", "age > 18", Or ["level = 1", "vip = 1"]])"#);
+}
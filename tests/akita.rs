@@ -146,4 +146,31 @@ fn main() {
         transaction.commit()
         // transaction.unwrap()?;
     }).unwrap();
-}
\ No newline at end of file
+}
+#[derive(AkitaTable, Clone, Default)]
+#[table(name = "t_temp_order")]
+pub struct TempOrder {
+    #[table_id]
+    pub id: i32,
+    pub name: String,
+}
+
+#[test]
+#[cfg(feature = "akita-sqlite")]
+fn rollback_within_temp_table() {
+    let path = std::env::temp_dir().join(format!("akita_temp_{}.sqlite3", std::process::id()));
+    let akita = Akita::new(AkitaConfig::new(format!("sqlite://{}", path.display()))).unwrap();
+    akita.exec_drop("CREATE TABLE IF NOT EXISTS t_temp_order (id INTEGER PRIMARY KEY, name TEXT NOT NULL)", Params::Nil).unwrap();
+    let order = TempOrder { id: 1, name: "a".to_string() };
+    let listed = akita.with_temp_table::<TempOrder, _, _>(|tmp| {
+        // the connection of the table is pinned but in no transaction, the options are still allowed.
+        let transaction = tmp.akita().start_transaction_with(TransactionOptions::new().set_isolation(IsolationLevel::Serializable))?;
+        tmp.save_batch(&[&order])?;
+        transaction.akita().exec_drop(format!("INSERT INTO t_temp_order SELECT id, name FROM {}", tmp.name()), Params::Nil)?;
+        transaction.rollback()?;
+        tmp.list()
+    }).unwrap();
+    assert!(listed.is_empty());
+    assert!(akita.list::<TempOrder>(Wrapper::new()).unwrap().is_empty());
+    let _ = std::fs::remove_file(&path);
+}